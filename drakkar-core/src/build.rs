@@ -0,0 +1,2673 @@
+//! Sources, objects and the commands that build them: collecting sources,
+//! mapping them to object paths under temp_dir, deciding what is stale,
+//! and the compile, link and archive command lines.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use crate::codegen;
+use crate::config::{
+    BuildProfile, CompileCommands, Executor, ProjectConfig, RebuildPolicy, StripMode, TargetConfig,
+    TargetType, ToolCommand, TEXT_CONFIG, TOML_CONFIG,
+};
+use crate::error::BuildError;
+use crate::flags::{self, Conflict};
+use crate::depfile::{parse_depfile, parse_show_includes, write_depfile};
+use crate::glob;
+use crate::hash::Sha256;
+use crate::ice;
+use crate::sandbox;
+use crate::shell;
+use crate::state::BuildState;
+use crate::template::{render, render_dir, TemplateVars};
+use crate::toolchain::Toolchain;
+
+/// The language a source is compiled as, from its extension.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Language {
+    /// C: `.c`, and `.C` unless `uppercase_c_is_cpp` is set.
+    C,
+    /// C++: `.cpp`, `.cc`, `.cxx` and the other C++ extensions.
+    Cpp,
+}
+
+/// A source file found under a source or vendor dir.
+#[derive(Debug, Clone)]
+pub struct SourceFile {
+    /// Where it is, as the config spells its directory.
+    pub path: PathBuf,
+    /// Its path below the source dir it was found in.
+    pub rel_path: PathBuf,
+    /// What it is compiled as.
+    pub language: Language,
+}
+
+/// A source and the files compiling it produces.
+#[derive(Debug, Clone)]
+pub struct ObjectFile {
+    /// The source it is compiled from.
+    pub src: SourceFile,
+    /// The object file, under temp_dir.
+    pub obj_path: PathBuf,
+    /// The `-MMD` depfile next to it.
+    pub dep_path: PathBuf,
+}
+
+// ─────────────────────────────────────────────
+// Directory creation
+// ─────────────────────────────────────────────
+
+/// Create output_dir, temp_dir and the directories the objects go in.
+pub fn prepare_build_dirs(
+    config: &ProjectConfig,
+    objects: &[ObjectFile],
+) -> Result<(), BuildError> {
+    std::fs::create_dir_all(&config.output_dir).map_err(|e| {
+        BuildError::IoError(format!(
+            "Cannot create output_dir {:?}: {}",
+            config.output_dir, e
+        ))
+    })?;
+    std::fs::create_dir_all(&config.temp_dir).map_err(|e| {
+        BuildError::IoError(format!(
+            "Cannot create temp_dir {:?}: {}",
+            config.temp_dir, e
+        ))
+    })?;
+
+    for obj in objects {
+        if let Some(parent) = obj.obj_path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                BuildError::IoError(format!(
+                    "Cannot create directory {:?}: {}",
+                    parent, e
+                ))
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+// Cleaning
+// ─────────────────────────────────────────────
+
+/// What [`clean_dir`] removed, and what it left behind on purpose.
+#[derive(Debug, Default, PartialEq)]
+pub struct CleanStats {
+    /// Files removed.
+    pub files: usize,
+    /// Their total size.
+    pub bytes: u64,
+    /// Files `keep` accepted, left in place.
+    pub kept: usize,
+}
+
+/// Refuse to clean `dir`, the project's `key` (temp_dir or output_dir),
+/// when emptying it would take more than build output with it: it is the
+/// project directory or one above it, or it holds a source, include, vendor
+/// or tests dir (the project's or a target's), the config or a `.git`.
+/// Paths are relative to the project directory, which is the current one.
+pub fn check_clean_dir(dir: &Path, key: &str, config: &ProjectConfig) -> Result<(), BuildError> {
+    let dir = match dir.canonicalize() {
+        Ok(dir) => dir,
+        // Nothing there to lose
+        Err(_) => return Ok(()),
+    };
+    let refuse = |what: String| {
+        Err(BuildError::ConfigError(format!(
+            "refusing to clean {} = {:?}: it {}; point {} at a directory of its own",
+            key, dir, what, key
+        )))
+    };
+    let root = std::env::current_dir()?.canonicalize()?;
+    if root.starts_with(&dir) {
+        return refuse("contains the project directory".to_string());
+    }
+    let project_dirs = config
+        .source_dirs
+        .iter()
+        .map(|d| ("source_dir", d))
+        .chain(config.include_dirs.iter().map(|d| ("include_dirs", d)))
+        .chain(config.vendor_dirs.iter().map(|d| ("vendor_dirs", d)))
+        .chain(std::iter::once(("tests_dir", &config.tests_dir)))
+        .chain(config.targets.iter().flat_map(|t| {
+            t.source_dirs
+                .iter()
+                .map(|d| ("source_dir", d))
+                .chain(t.include_dirs.iter().map(|d| ("include_dirs", d)))
+                .chain(t.vendor_dirs.iter().map(|d| ("vendor_dirs", d)))
+        }));
+    for (what, project_dir) in project_dirs {
+        if project_dir.canonicalize().is_ok_and(|d| d.starts_with(&dir)) {
+            return refuse(format!("contains {} {:?}", what, project_dir));
+        }
+    }
+    for name in [".git", "config.txt", "drakkar.toml"] {
+        if dir.join(name).exists() {
+            return refuse(format!("contains {}", name));
+        }
+    }
+    Ok(())
+}
+
+/// Remove everything inside `dir` except files `keep` accepts. `dir`
+/// itself stays, as do subdirectories still holding kept files.
+/// A missing `dir` is already clean.
+pub fn clean_dir(dir: &Path, keep: &dyn Fn(&Path) -> bool) -> Result<CleanStats, BuildError> {
+    let mut stats = CleanStats::default();
+    if dir.exists() {
+        clean_recursive(dir, keep, &mut stats)?;
+    }
+    Ok(stats)
+}
+
+fn clean_recursive(
+    dir: &Path,
+    keep: &dyn Fn(&Path) -> bool,
+    stats: &mut CleanStats,
+) -> Result<(), BuildError> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", dir, e)))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let meta = std::fs::symlink_metadata(&path)
+            .map_err(|e| BuildError::IoError(format!("Cannot stat {:?}: {}", path, e)))?;
+        if meta.is_dir() {
+            clean_recursive(&path, keep, stats)?;
+            // Fails harmlessly when kept files remain inside
+            let _ = std::fs::remove_dir(&path);
+        } else if keep(&path) {
+            stats.kept += 1;
+        } else {
+            std::fs::remove_file(&path)
+                .map_err(|e| BuildError::IoError(format!("Cannot remove {:?}: {}", path, e)))?;
+            stats.files += 1;
+            stats.bytes += meta.len();
+        }
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+// Source collection
+// ─────────────────────────────────────────────
+
+/// Result of scanning `source_dir`.
+#[derive(Debug, Default)]
+pub struct SourceScan {
+    /// The sources found.
+    pub sources: Vec<SourceFile>,
+    /// Subdirectories skipped because they hold their own `config.txt` or
+    /// `drakkar.toml`.
+    pub nested_projects: Vec<PathBuf>,
+}
+
+/// Recursively collect all C/C++ source files under each `source_dir` root,
+/// then the ones the generators write. Directories with their own config
+/// file are separate projects and are not compiled into this one; names
+/// matching `ignore_patterns` and paths matching `exclude` are skipped,
+/// and with `sources` set only the files matching it are kept.
+pub fn collect_sources(config: &ProjectConfig) -> Result<SourceScan, BuildError> {
+    let mut scan = SourceScan::default();
+    for root in config.source_dirs.iter().chain(&config.vendor_dirs) {
+        collect_sources_inner(config, root, root, &mut scan)?;
+    }
+    scan.sources.extend(codegen::sources(config));
+    Ok(scan)
+}
+
+fn collect_sources_inner(
+    config: &ProjectConfig,
+    root: &Path,
+    dir: &Path,
+    scan: &mut SourceScan,
+) -> Result<(), BuildError> {
+    let entries = std::fs::read_dir(dir).map_err(|e| {
+        BuildError::IoError(format!("Cannot read directory {:?}: {}", dir, e))
+    })?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| BuildError::IoError(e.to_string()))?;
+        let path = entry.path();
+        let file_name = entry.file_name();
+        let name = file_name.to_string_lossy();
+
+        if glob::matches_any(&config.ignore_patterns, &name)
+            || glob::matches_any_path(&config.exclude_patterns, &path)
+        {
+            continue;
+        }
+
+        // Skip hidden directories and common build/tool dirs
+        if path.is_dir() {
+            if name.starts_with('.') || name == "target" || name == "out" {
+                continue;
+            }
+            if path.join(TEXT_CONFIG).is_file() || path.join(TOML_CONFIG).is_file() {
+                scan.nested_projects.push(path);
+                continue;
+            }
+            // Collected as a root of its own
+            if path != root && config.vendor_dirs.contains(&path) {
+                continue;
+            }
+            collect_sources_inner(config, root, &path, scan)?;
+        } else if path.is_file() {
+            let selected = config.source_patterns.is_empty()
+                || glob::matches_any_path(&config.source_patterns, &path);
+            if !selected {
+                continue;
+            }
+            if let Some(language) = language_for(&path, config) {
+                let rel_path = path
+                    .strip_prefix(root)
+                    .map_err(|_| {
+                        BuildError::IoError(format!(
+                            "Cannot strip prefix {:?} from {:?}",
+                            root, path
+                        ))
+                    })?
+                    .to_path_buf();
+
+                scan.sources.push(SourceFile {
+                    path: path.clone(),
+                    rel_path,
+                    language,
+                });
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Language of a source file by extension, or None if it isn't compiled on
+/// its own (headers, `.inl` implementation includes, everything else).
+pub fn language_for(path: &Path, config: &ProjectConfig) -> Option<Language> {
+    let ext = path.extension()?.to_string_lossy();
+    // Historically C++ on Unix; kept as C unless the project opts in
+    if ext == "C" {
+        return Some(if config.uppercase_c_is_cpp {
+            Language::Cpp
+        } else {
+            Language::C
+        });
+    }
+    match ext.to_lowercase().as_str() {
+        "c" => Some(Language::C),
+        "cpp" | "cc" | "cxx" | "c++" | "cppm" | "ixx" => Some(Language::Cpp),
+        _ => None,
+    }
+}
+
+/// `-x` value for sources whose extension the compiler driver would
+/// otherwise misread: `.C` compiled as C, and C++ extensions gcc doesn't
+/// know (module units, upper-case variants).
+fn language_override(src: &SourceFile) -> Option<&'static str> {
+    let ext = src.path.extension()?.to_string_lossy();
+    match src.language {
+        Language::C if ext != "c" => Some("c"),
+        Language::Cpp if !["cc", "cp", "cxx", "cpp", "CPP", "c++", "C"].contains(&&*ext) => {
+            Some("c++")
+        }
+        _ => None,
+    }
+}
+
+// ─────────────────────────────────────────────
+// Executable planning
+// ─────────────────────────────────────────────
+
+/// One executable to link: an output name and the objects that go into it.
+#[derive(Debug, Clone)]
+pub struct Executable {
+    /// The output name, `app_name` for the main executable.
+    pub name: String,
+    /// The objects linked into it.
+    pub objects: Vec<ObjectFile>,
+}
+
+/// Where a source file ends up when `bin_discovery` is on.
+#[derive(Debug, PartialEq)]
+enum EntryRole {
+    /// `main.<ext>` at the source root: the `app_name` executable.
+    Primary,
+    /// `*_main.<ext>`, `bin/<name>.<ext>` or anything under `bin/<name>/`.
+    Bin(String),
+    /// Linked into every executable.
+    Common,
+}
+
+/// Whether a source is an entry point (`main.*`, `*_main.*`, `bin/`)
+/// rather than code shared by every executable.
+pub fn is_entry_point(rel_path: &Path) -> bool {
+    entry_role(rel_path) != EntryRole::Common
+}
+
+fn entry_role(rel_path: &Path) -> EntryRole {
+    let stem = rel_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let components: Vec<String> = rel_path
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+
+    if components.len() >= 2 && components[0] == "bin" {
+        return if components.len() == 2 {
+            EntryRole::Bin(stem)
+        } else {
+            EntryRole::Bin(components[1].clone())
+        };
+    }
+    if let Some(name) = stem.strip_suffix("_main") {
+        if !name.is_empty() {
+            return EntryRole::Bin(name.to_string());
+        }
+    }
+    if components.len() == 1 && stem == "main" {
+        return EntryRole::Primary;
+    }
+    EntryRole::Common
+}
+
+/// The entry point that makes `src` part of its executable: the source
+/// itself, or the `bin/<name>/` directory it is in.
+fn entry_source(src: &SourceFile) -> PathBuf {
+    let depth = src.rel_path.components().count();
+    if depth > 2 && src.rel_path.starts_with("bin") {
+        let root = src.path.ancestors().nth(depth).unwrap_or(Path::new(""));
+        let name: PathBuf = src.rel_path.components().take(2).collect();
+        return root.join(name);
+    }
+    src.path.clone()
+}
+
+/// Group objects into executables.
+///
+/// Without `bin_discovery` everything links into `app_name`. With it, each
+/// discovered entry point becomes its own executable sharing the common
+/// objects (like cargo's `src/bin/`); `app_name` is only produced when a
+/// root-level `main.<ext>` exists. Two entry points that would link to
+/// the same output (`main.cpp` and `bin/<app_name>.cpp`, `bin/x.cpp` and
+/// `x_main.cpp`) are an error.
+pub fn plan_executables(
+    objects: Vec<ObjectFile>,
+    config: &ProjectConfig,
+) -> Result<Vec<Executable>, BuildError> {
+    if !config.bin_discovery {
+        return Ok(vec![Executable {
+            name: config.app_name.clone(),
+            objects,
+        }]);
+    }
+
+    let mut common = Vec::new();
+    let mut primary = Vec::new();
+    let mut bins: Vec<(String, Vec<ObjectFile>)> = Vec::new();
+    let mut entries: Vec<(String, PathBuf)> = Vec::new();
+
+    for obj in objects {
+        let role = entry_role(&obj.src.rel_path);
+        let name = match &role {
+            EntryRole::Common => {
+                common.push(obj);
+                continue;
+            }
+            EntryRole::Primary => config.app_name.clone(),
+            EntryRole::Bin(name) => name.clone(),
+        };
+        let entry = entry_source(&obj.src);
+        match entries.iter().find(|(n, _)| *n == name) {
+            Some((_, first)) if *first != entry => {
+                return Err(BuildError::ConfigError(format!(
+                    "{} and {} both build the executable {}; rename one of them",
+                    first.display(),
+                    entry.display(),
+                    executable_path(config, &name).display()
+                )));
+            }
+            Some(_) => {}
+            None => entries.push((name.clone(), entry)),
+        }
+        if role == EntryRole::Primary {
+            primary.push(obj);
+        } else {
+            match bins.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, objs)) => objs.push(obj),
+                None => bins.push((name, vec![obj])),
+            }
+        }
+    }
+    bins.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut exes = Vec::new();
+    if !primary.is_empty() {
+        primary.extend(common.iter().cloned());
+        exes.push(Executable {
+            name: config.app_name.clone(),
+            objects: primary,
+        });
+    }
+    for (name, mut objs) in bins {
+        objs.extend(common.iter().cloned());
+        exes.push(Executable { name, objects: objs });
+    }
+    Ok(exes)
+}
+
+/// Output path of an executable, adding `.exe` when building for Windows.
+pub fn executable_path(config: &ProjectConfig, name: &str) -> PathBuf {
+    if config.target_os() == "windows" {
+        config.output_dir.join(format!("{}.exe", name))
+    } else {
+        config.output_dir.join(name)
+    }
+}
+
+/// Output path of a `static_lib` target's archive.
+pub fn static_lib_path(config: &ProjectConfig, name: &str) -> PathBuf {
+    if config.toolchain == Toolchain::Msvc {
+        config.output_dir.join(format!("{}.lib", name))
+    } else {
+        config.output_dir.join(format!("lib{}.a", name))
+    }
+}
+
+/// Output path of a `plugin` target: `<name>.so` (`.dll` on Windows) in
+/// `plugins_dir` under output_dir.
+pub fn plugin_path(config: &ProjectConfig, name: &str) -> PathBuf {
+    let ext = if config.target_os() == "windows" { "dll" } else { "so" };
+    config
+        .output_dir
+        .join(&config.plugins_dir)
+        .join(format!("{}.{}", name, ext))
+}
+
+// ─────────────────────────────────────────────
+// Named targets
+// ─────────────────────────────────────────────
+
+fn find_target<'a>(config: &'a ProjectConfig, name: &str) -> Result<&'a TargetConfig, BuildError> {
+    config.targets.iter().find(|t| t.name == name).ok_or_else(|| {
+        let names: Vec<&str> = config.targets.iter().map(|t| t.name.as_str()).collect();
+        BuildError::ConfigError(format!(
+            "no target named '{}' (available: {})",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+/// The targets to build for `selected` (every target when None), each one
+/// after the library targets it depends on.
+pub fn resolve_targets<'a>(
+    config: &'a ProjectConfig,
+    selected: Option<&str>,
+) -> Result<Vec<&'a TargetConfig>, BuildError> {
+    let roots = match selected {
+        Some(name) => vec![find_target(config, name)?],
+        None => config.targets.iter().collect(),
+    };
+    let mut order = Vec::new();
+    for target in roots {
+        visit_target(config, target, &mut Vec::new(), &mut order)?;
+    }
+    Ok(order)
+}
+
+fn visit_target<'a>(
+    config: &'a ProjectConfig,
+    target: &'a TargetConfig,
+    stack: &mut Vec<&'a str>,
+    order: &mut Vec<&'a TargetConfig>,
+) -> Result<(), BuildError> {
+    if order.iter().any(|t| t.name == target.name) {
+        return Ok(());
+    }
+    if stack.contains(&target.name.as_str()) {
+        return Err(BuildError::ConfigError(format!(
+            "target dependency cycle: {} -> {}",
+            stack.join(" -> "),
+            target.name
+        )));
+    }
+    stack.push(&target.name);
+    for dep in &target.deps {
+        let dep_target = find_target(config, dep)?;
+        if dep_target.target_type == TargetType::Executable {
+            return Err(BuildError::ConfigError(format!(
+                "target '{}' depends on '{}', which is an executable",
+                target.name, dep
+            )));
+        }
+        visit_target(config, dep_target, stack, order)?;
+    }
+    stack.pop();
+    order.push(target);
+    Ok(())
+}
+
+/// The config `target` is built with: the project settings plus the
+/// target's own, objects under `temp_dir/<name>/`, and the archives of
+/// every library it depends on, directly or not. Plugins, and libraries
+/// that end up in one, are compiled position-independent.
+pub fn target_config(
+    config: &ProjectConfig,
+    target: &TargetConfig,
+) -> Result<ProjectConfig, BuildError> {
+    if target.source_dirs.is_empty() {
+        return Err(BuildError::ConfigError(format!(
+            "target '{}' has no source_dir",
+            target.name
+        )));
+    }
+    // Dependents before their dependencies, as the linker wants them
+    let mut libs = resolve_targets(config, Some(&target.name))?;
+    libs.pop();
+    libs.reverse();
+    libs.retain(|t| t.target_type == TargetType::StaticLib);
+
+    if !config.vendor_dirs.is_empty() {
+        return Err(BuildError::ConfigError(
+            "vendor_dirs applies to projects without targets; set it in the [target.<name>] \
+             whose sources use them"
+                .to_string(),
+        ));
+    }
+    let mut cfg = config.clone();
+    cfg.app_name = target.output_name().to_string();
+    cfg.source_dirs = target.source_dirs.clone();
+    cfg.vendor_dirs = target.vendor_dirs.clone();
+    cfg.temp_dir = config.temp_dir.join(&target.name);
+    cfg.c_flags.extend(target.c_flags.iter().cloned());
+    cfg.cxx_flags.extend(target.cxx_flags.iter().cloned());
+    cfg.ld_flags.extend(target.ld_flags.iter().cloned());
+    cfg.include_dirs.extend(target.include_dirs.iter().cloned());
+    cfg.link_libs.extend(target.link_libs.iter().cloned());
+    cfg.generators.retain(|rule| match &rule.target {
+        Some(name) => *name == target.name,
+        None => true,
+    });
+    for lib in &libs {
+        cfg.include_dirs.extend(lib.include_dirs.iter().cloned());
+        cfg.link_libs.extend(lib.link_libs.iter().cloned());
+    }
+    match target.target_type {
+        TargetType::StaticLib => cfg.extra_objects.clear(),
+        // Ahead of the prebuilt inputs, which include project dependencies
+        TargetType::Executable | TargetType::Plugin => {
+            let own = libs.iter().map(|lib| static_lib_path(config, lib.output_name()));
+            cfg.extra_objects.splice(0..0, own);
+        }
+    }
+    if target.target_type == TargetType::Plugin {
+        cfg.ld_flags.extend(plugin_link_flags(config));
+        cfg.exports = target.exports.clone();
+    }
+    if needs_pic(config, target)? && config.target_os() != "windows" {
+        cfg.c_flags.push("-fPIC".to_string());
+        cfg.cxx_flags.push("-fPIC".to_string());
+    }
+    cfg.bin_discovery = false;
+    cfg.targets = Vec::new();
+    // The caller writes one database covering every target
+    cfg.compile_commands = CompileCommands::Off;
+    Ok(cfg)
+}
+
+/// Whether `target` is a plugin or a library linked into one.
+fn needs_pic(config: &ProjectConfig, target: &TargetConfig) -> Result<bool, BuildError> {
+    for plugin in config.targets.iter().filter(|t| t.target_type == TargetType::Plugin) {
+        if resolve_targets(config, Some(&plugin.name))?
+            .iter()
+            .any(|t| t.name == target.name)
+        {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Link flags for a loadable module. No `-soname` is passed, so the module
+/// has no SONAME. On macOS it is a bundle whose undefined symbols resolve
+/// against the host executable when loaded.
+fn plugin_link_flags(config: &ProjectConfig) -> Vec<String> {
+    let flags: &[&str] = if config.toolchain == Toolchain::Msvc {
+        &["/DLL"]
+    } else if config.target_os() == "macos" {
+        &["-bundle", "-undefined", "dynamic_lookup"]
+    } else {
+        &["-shared"]
+    };
+    flags.iter().map(|f| f.to_string()).collect()
+}
+
+// ─────────────────────────────────────────────
+// Object path computation
+// ─────────────────────────────────────────────
+
+/// Name of drakkar's own directory in temp_dir. Hidden directories are
+/// never collected as sources, so no object is mirrored into it.
+pub const RESERVED_DIR: &str = ".drakkar";
+
+/// Where files that aren't mirrored from the project's sources go: the test
+/// programs, the vendored sources' objects and the requirement probes.
+pub fn reserved_dir(config: &ProjectConfig) -> PathBuf {
+    config.temp_dir.join(RESERVED_DIR)
+}
+
+/// Compute the object and dependency file paths for a source file.
+/// Uses mirrored directory structure: temp_dir/<rel_path>.o
+pub fn object_path_for(src: &SourceFile, config: &ProjectConfig) -> ObjectFile {
+    let mirrored = match source_root_label(src, config) {
+        Some(label) => label.join(&src.rel_path),
+        None => src.rel_path.clone(),
+    };
+
+    let obj_path = config
+        .temp_dir
+        .join(mirrored.with_extension(config.toolchain.object_extension()));
+    let dep_path = config.temp_dir.join(mirrored.with_extension("d"));
+
+    ObjectFile {
+        src: src.clone(),
+        obj_path,
+        dep_path,
+    }
+}
+
+/// With several source roots, objects are mirrored under a per-root
+/// subdirectory so `src/util.cpp` and `tools/util.cpp` don't collide.
+/// A single root keeps the flat layout (`src/a/b.cpp` → `target/a/b.o`).
+fn source_root_label(src: &SourceFile, config: &ProjectConfig) -> Option<PathBuf> {
+    let in_root =
+        |root: &&PathBuf| src.path.strip_prefix(root).ok() == Some(src.rel_path.as_path());
+    // Vendored sources go under `.drakkar/vendor/`, where none of the
+    // project's (`src/vendor/a.c` included) can land
+    if let Some(root) = config.vendor_dirs.iter().find(in_root) {
+        return Some(Path::new(RESERVED_DIR).join("vendor").join(root_label(root)));
+    }
+    if config.source_dirs.len() < 2 {
+        return None;
+    }
+    let root = config.source_dirs.iter().find(in_root)?;
+    Some(root_label(root))
+}
+
+fn root_label(root: &Path) -> PathBuf {
+    // `../shared` must not escape temp_dir
+    root.components()
+        .filter_map(|c| match c {
+            std::path::Component::Normal(s) => Some(s.to_os_string()),
+            std::path::Component::ParentDir => Some("__".into()),
+            _ => None,
+        })
+        .collect()
+}
+
+// ─────────────────────────────────────────────
+// Incremental build check
+// ─────────────────────────────────────────────
+
+/// Why an object has to be (re)compiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RebuildReason {
+    /// No object file yet (first build or newly added source).
+    NewFile,
+    /// The source is newer than its object, or its content changed.
+    SourceChanged,
+    /// A header in the depfile is newer, or its content changed.
+    HeaderChanged,
+    /// Compiler, flags or profile differ from the recorded command line,
+    /// or a `tracked_env` variable changed.
+    FlagsChanged,
+    /// The depfile is missing or unreadable, so staleness can't be proven.
+    DepsUnknown,
+    /// `incremental = "false"`.
+    IncrementalDisabled,
+}
+
+impl RebuildReason {
+    /// A short phrase for the rebuild summary: `header changed`.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            RebuildReason::NewFile => "new file",
+            RebuildReason::SourceChanged => "source changed",
+            RebuildReason::HeaderChanged => "header changed",
+            RebuildReason::FlagsChanged => "flags changed",
+            RebuildReason::DepsUnknown => "dependencies unknown",
+            RebuildReason::IncrementalDisabled => "incremental disabled",
+        }
+    }
+}
+
+/// Hash of the full compile command and the `tracked_env` variables,
+/// recorded to detect flag changes.
+pub fn compile_flags_hash(compiler: &str, args: &[String], config: &ProjectConfig) -> String {
+    let mut h = Sha256::new();
+    h.update_field(compiler);
+    for arg in args {
+        h.update_field(arg);
+    }
+    for name in &config.tracked_env {
+        // Unset hashes apart from set-but-empty
+        let value = std::env::var_os(name).map(|v| format!("={}", v.to_string_lossy()));
+        h.update_field(name);
+        h.update_field(value.as_deref().unwrap_or_default());
+    }
+    h.finish_hex()
+}
+
+/// Decide whether `obj` must be recompiled, returning the reason if so.
+/// `flags_hash` is the hash of the command line it would be built with.
+pub fn rebuild_reason(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    state: &BuildState,
+    flags_hash: &str,
+) -> Option<RebuildReason> {
+    // Force rebuild if incremental is disabled
+    if !config.incremental {
+        return Some(RebuildReason::IncrementalDisabled);
+    }
+
+    // Rebuild if .o doesn't exist
+    let obj_meta = match std::fs::metadata(&obj.obj_path) {
+        Ok(m) => m,
+        Err(_) => return Some(RebuildReason::NewFile),
+    };
+
+    let obj_mtime = match obj_meta.modified() {
+        Ok(t) => t,
+        Err(_) => return Some(RebuildReason::DepsUnknown),
+    };
+
+    // Rebuild if .d doesn't exist
+    if !obj.dep_path.exists() {
+        return Some(RebuildReason::DepsUnknown);
+    }
+
+    // Parse .d file to get all dependencies
+    let deps = match parse_depfile(&obj.dep_path) {
+        Ok(d) => d.prerequisites,
+        Err(_) => return Some(RebuildReason::DepsUnknown), // Can't parse = rebuild
+    };
+
+    let prev = state.get(&obj.obj_path);
+
+    // Under the hash policy, compare against the content the object was
+    // built from; objects without recorded hashes fall back to mtimes.
+    let recorded = prev
+        .filter(|_| config.rebuild_policy == RebuildPolicy::Hash)
+        .and_then(|p| p.source_hash.as_ref().zip(p.headers_hash.as_ref()));
+    if let Some((source_hash, headers_hash)) = recorded {
+        if file_hash(&obj.src.path).as_ref() != Some(source_hash) {
+            return Some(RebuildReason::SourceChanged);
+        }
+        if headers_content_hash(&obj.src.path, &deps).as_ref() != Some(headers_hash) {
+            return Some(RebuildReason::HeaderChanged);
+        }
+    } else {
+        // Source first: a touched .cpp is reported as such even if headers changed too
+        if is_newer_than(&obj.src.path, obj_mtime) {
+            return Some(RebuildReason::SourceChanged);
+        }
+
+        // Check if any other dependency is newer than the .o
+        for dep in &deps {
+            if dep != &obj.src.path && is_newer_than(dep, obj_mtime) {
+                return Some(RebuildReason::HeaderChanged);
+            }
+        }
+    }
+
+    // Objects built before flags were recorded have no entry; don't
+    // rebuild them just for that.
+    if let Some(prev) = prev {
+        if prev.flags_hash != flags_hash {
+            return Some(RebuildReason::FlagsChanged);
+        }
+    }
+
+    None
+}
+
+/// `(source, headers)` content hashes of a freshly compiled `obj`, as
+/// recorded in the state under `rebuild_policy = "hash"`. None when the
+/// depfile or one of its entries can't be read.
+pub fn content_hashes(obj: &ObjectFile) -> Option<(String, String)> {
+    let deps = parse_depfile(&obj.dep_path).ok()?.prerequisites;
+    Some((file_hash(&obj.src.path)?, headers_content_hash(&obj.src.path, &deps)?))
+}
+
+/// SHA-256 of a file's contents; None when it can't be read.
+pub fn file_hash(path: &Path) -> Option<String> {
+    let mut h = Sha256::new();
+    h.update(&std::fs::read(path).ok()?);
+    Some(h.finish_hex())
+}
+
+/// Paths and contents of every depfile entry except the source; a deleted
+/// header yields None, which never matches a recorded hash.
+pub fn headers_content_hash(source: &Path, deps: &[PathBuf]) -> Option<String> {
+    let mut h = Sha256::new();
+    for dep in deps.iter().filter(|d| d.as_path() != source) {
+        h.update_field(&dep.to_string_lossy());
+        h.update_field(&file_hash(dep)?);
+    }
+    Some(h.finish_hex())
+}
+
+/// Counts of up-to-date vs rebuilt objects, for the end-of-build summary.
+#[derive(Debug, Default, Clone)]
+pub struct RebuildStats {
+    /// Objects that didn't need compiling.
+    pub up_to_date: usize,
+    /// How many were rebuilt for each reason, in first-seen order.
+    pub reasons: Vec<(RebuildReason, usize)>,
+}
+
+impl RebuildStats {
+    /// Count one object, up to date when `reason` is None.
+    pub fn record(&mut self, reason: Option<RebuildReason>) {
+        match reason {
+            None => self.up_to_date += 1,
+            Some(r) => match self.reasons.iter_mut().find(|(k, _)| *k == r) {
+                Some((_, n)) => *n += 1,
+                None => self.reasons.push((r, 1)),
+            },
+        }
+    }
+
+    /// Objects rebuilt for any reason.
+    pub fn rebuilt(&self) -> usize {
+        self.reasons.iter().map(|(_, n)| n).sum()
+    }
+
+    /// `3 up-to-date, 2 rebuilt (1 new file, 1 header changed)`
+    pub fn summary(&self) -> String {
+        let mut s = format!("{} up-to-date, {} rebuilt", self.up_to_date, self.rebuilt());
+        if !self.reasons.is_empty() {
+            let parts: Vec<String> = self
+                .reasons
+                .iter()
+                .map(|(r, n)| format!("{} {}", n, r.describe()))
+                .collect();
+            s.push_str(&format!(" ({})", parts.join(", ")));
+        }
+        s
+    }
+}
+
+fn is_newer_than(path: &Path, reference: SystemTime) -> bool {
+    match std::fs::metadata(path) {
+        Ok(m) => match m.modified() {
+            Ok(t) => t > reference,
+            Err(_) => false,
+        },
+        // If dep file doesn't exist (e.g., header was deleted), force rebuild
+        Err(_) => true,
+    }
+}
+
+// ─────────────────────────────────────────────
+// Compilation
+// ─────────────────────────────────────────────
+
+/// Build the compiler argument list for a source file. A setting given
+/// twice (`-O0` from the profile, `-O2` after `--`) is passed once, the
+/// last value winning.
+pub fn build_compile_args(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> (String, Vec<String>) {
+    let (program, mut args) = all_compile_args(obj, config, profile, extra_flags);
+    flags::dedupe(&mut args, config.toolchain == Toolchain::Msvc);
+    (program, args)
+}
+
+/// Flags in `obj`'s compile command that a later flag overrides.
+pub fn compile_flag_conflicts(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> Vec<Conflict> {
+    let (_, mut args) = all_compile_args(obj, config, profile, extra_flags);
+    flags::dedupe(&mut args, config.toolchain == Toolchain::Msvc)
+}
+
+/// The compile command as assembled, before repeated settings are dropped.
+fn all_compile_args(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> (String, Vec<String>) {
+    let vendored = config.is_vendored(&obj.src.path);
+    let (tool, base_flags, standard) = match (&obj.src.language, vendored) {
+        (Language::C, false) => {
+            (config.c_compiler(), config.c_flags.clone(), config.c_standard.as_ref())
+        }
+        (Language::Cpp, false) => {
+            (config.cxx_compiler(), config.cxx_flags.clone(), config.cxx_standard.as_ref())
+        }
+        (Language::C, true) => (
+            config.c_compiler(),
+            config.vendor_c_flags.clone(),
+            config.vendor_c_standard.as_ref().or(config.c_standard.as_ref()),
+        ),
+        (Language::Cpp, true) => (
+            config.cxx_compiler(),
+            config.vendor_cxx_flags.clone(),
+            config.vendor_cxx_standard.as_ref().or(config.cxx_standard.as_ref()),
+        ),
+    };
+    let toolchain = config.toolchain;
+
+    // Wrapper arguments (`ccache g++`) come first
+    let mut args: Vec<String> = tool.args;
+    if toolchain == Toolchain::Msvc {
+        args.push("/nologo".to_string());
+    }
+    args.push("-c".to_string());
+
+    // Input source
+    if let Some(lang) = language_override(&obj.src) {
+        args.extend(toolchain.language_flags(lang));
+    }
+    args.push(obj.src.path.to_string_lossy().into_owned());
+
+    // Output object
+    args.extend(toolchain.output_flags(&obj.obj_path.to_string_lossy()));
+
+    // Toolchain target
+    args.extend(config.target_flags());
+
+    // Base language flags; third-party code isn't ours to fix
+    args.extend(base_flags);
+    if vendored {
+        args.push(toolchain.no_warnings_flag());
+    }
+
+    // Standard
+    if let Some(std) = standard {
+        args.extend(toolchain.std_flag(std));
+    }
+
+    // Profile-specific flags
+    args.extend(toolchain.profile_flags(profile));
+    args.extend(config.lto_flags(profile).0);
+
+    // openmp / threads
+    args.extend(config.feature_flags().0);
+    args.push(match profile {
+        BuildProfile::Debug => "-DDEBUG".to_string(),
+        BuildProfile::Release => "-DNDEBUG".to_string(),
+    });
+
+    // Include dirs
+    for inc in &config.include_dirs {
+        args.push(format!("-I{}", inc.display()));
+    }
+    for dir in config.vendor_dirs.iter().chain(&config.system_include_dirs) {
+        args.extend(toolchain.system_include_flags(&dir.to_string_lossy()));
+    }
+    for rule in &config.generators {
+        args.push(format!("-I{}", codegen::out_dir(config, rule).display()));
+    }
+
+    // Dependency generation
+    args.extend(toolchain.dep_flags(&obj.dep_path.to_string_lossy()));
+
+    // Extra CLI flags
+    args.extend_from_slice(extra_flags);
+
+    (tool.program, args)
+}
+
+/// Compile a single source file to an object file.
+/// Returns the compiler's diagnostics (warnings), empty if it was silent.
+pub fn compile_source_to_object(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    verbose: bool,
+    active_children: &crate::worker::ActiveChildren,
+) -> Result<String, BuildError> {
+    let (compiler, args) = build_compile_args(obj, config, profile, extra_flags);
+    let result = run_compiler(obj, &compiler, &args, config, verbose, active_children);
+    ice::on_failure(&result, obj, config, &compiler, &args);
+    result
+}
+
+/// Where the pipelined build keeps a source's preprocessed output.
+/// The extension tells the compiler not to preprocess it again.
+pub fn preprocessed_path(obj: &ObjectFile) -> PathBuf {
+    match obj.src.language {
+        Language::C => obj.obj_path.with_extension("i"),
+        Language::Cpp => obj.obj_path.with_extension("ii"),
+    }
+}
+
+/// Where a source's compiler warnings from its last successful compile are
+/// cached, so `--replay-warnings` can show them while the object is up to date.
+pub fn warnings_path(obj: &ObjectFile) -> PathBuf {
+    obj.obj_path.with_extension("warn")
+}
+
+/// Split a full compile command into its preprocess (`-E`) and codegen
+/// stages. Dependency generation stays in the first stage; the second
+/// compiles the preprocessed file with the remaining flags.
+pub fn pipeline_stage_args(obj: &ObjectFile, args: &[String]) -> (Vec<String>, Vec<String>) {
+    let src = obj.src.path.to_string_lossy();
+    let obj_out = obj.obj_path.to_string_lossy();
+    let pp = preprocessed_path(obj).to_string_lossy().into_owned();
+
+    let preprocess = args
+        .iter()
+        .map(|a| match a.as_str() {
+            "-c" => "-E".to_string(),
+            a if a == obj_out => pp.clone(),
+            _ => a.clone(),
+        })
+        .collect();
+
+    let mut codegen = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(a) = iter.next() {
+        match a.as_str() {
+            "-MMD" | "-MP" => {}
+            // The preprocessed file's extension already names its language
+            "-MF" | "-x" => {
+                iter.next();
+            }
+            a if a == src => codegen.push(pp.clone()),
+            _ => codegen.push(a.clone()),
+        }
+    }
+
+    (preprocess, codegen)
+}
+
+/// First pipeline stage: preprocess into [`preprocessed_path`] (writing
+/// the depfile as usual). Returns the SHA-256 of the output and the
+/// preprocessor's diagnostics.
+pub fn preprocess_source(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    verbose: bool,
+    active_children: &crate::worker::ActiveChildren,
+) -> Result<(String, String), BuildError> {
+    let (compiler, args) = build_compile_args(obj, config, profile, extra_flags);
+    let (preprocess, _) = pipeline_stage_args(obj, &args);
+    let diagnostics = run_compiler(obj, &compiler, &preprocess, config, verbose, active_children)?;
+
+    let pp = preprocessed_path(obj);
+    let content = std::fs::read(&pp).map_err(|e| {
+        BuildError::IoError(format!("Cannot read preprocessed output {:?}: {}", pp, e))
+    })?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+    Ok((hasher.finish_hex(), diagnostics))
+}
+
+/// Second pipeline stage: compile the preprocessed file to the object.
+/// The intermediate file is removed afterwards unless `preserve_temp` is set.
+pub fn compile_preprocessed(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    verbose: bool,
+    active_children: &crate::worker::ActiveChildren,
+) -> Result<String, BuildError> {
+    let (compiler, args) = build_compile_args(obj, config, profile, extra_flags);
+    let (_, codegen) = pipeline_stage_args(obj, &args);
+    let result = run_compiler(obj, &compiler, &codegen, config, verbose, active_children);
+    ice::on_failure(&result, obj, config, &compiler, &args);
+    if !config.keeps_temp() {
+        let _ = std::fs::remove_file(preprocessed_path(obj));
+    }
+    result
+}
+
+/// Skip the second pipeline stage because its input, the preprocessed
+/// file, came out the same as for the existing object with the same
+/// flags: the object is marked current instead of being regenerated.
+pub fn keep_object(obj: &ObjectFile, config: &ProjectConfig) -> Result<(), BuildError> {
+    std::fs::File::options()
+        .write(true)
+        .open(&obj.obj_path)
+        .and_then(|f| f.set_modified(SystemTime::now()))
+        .map_err(|e| BuildError::IoError(format!("Cannot touch {:?}: {}", obj.obj_path, e)))?;
+    if !config.keeps_temp() {
+        let _ = std::fs::remove_file(preprocessed_path(obj));
+    }
+    Ok(())
+}
+
+fn run_compiler(
+    obj: &ObjectFile,
+    compiler: &str,
+    args: &[String],
+    config: &ProjectConfig,
+    verbose: bool,
+    active_children: &crate::worker::ActiveChildren,
+) -> Result<String, BuildError> {
+    if crate::platform::is_cancelled() {
+        return Err(BuildError::Cancelled);
+    }
+
+    // Batch job, then sandbox, then the compiler: `srun … bwrap … g++ …`
+    let mut launcher: Vec<String> = Vec::new();
+    let prefixes = [config.executor_command(), sandbox::command(config, compiler)];
+    for prefix in prefixes.into_iter().flatten() {
+        launcher.push(prefix.program);
+        launcher.extend(prefix.args);
+    }
+    launcher.push(compiler.to_string());
+    if verbose || config.explain_commands {
+        let cmd_str = shell::command_line(&launcher[0], &[&launcher[1..], args].concat());
+        let mut line = format!("  \x1b[2m$ {}\x1b[0m", cmd_str);
+        if config.explain_commands {
+            line.push_str(&crate::explain::notes(args));
+        }
+        crate::ui::line(&line);
+    }
+
+    let mut cmd = std::process::Command::new(&launcher[0]);
+    cmd.args(&launcher[1..]).args(args);
+    // Keep bsub's "Job <n> is submitted" chatter out of the diagnostics
+    if config.executor == Executor::Lsf {
+        cmd.env("BSUB_QUIET", "1");
+    }
+
+    // Variant B: set process group for killpg support
+    if config.use_process_groups {
+        crate::platform::set_process_group(&mut cmd);
+    }
+
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+
+    let child = cmd.spawn().map_err(|e| {
+        BuildError::IoError(format!("Failed to spawn compiler '{}': {}", compiler, e))
+    })?;
+
+    // Register child for cleanup on Ctrl+C
+    let child_id = child.id();
+    active_children.add(child_id);
+
+    let output = child.wait_with_output().map_err(|e| {
+        BuildError::IoError(format!("Failed to wait for compiler: {}", e))
+    })?;
+
+    active_children.remove(child_id);
+
+    if crate::platform::is_cancelled() {
+        return Err(BuildError::Cancelled);
+    }
+
+    let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    if config.toolchain == Toolchain::Msvc {
+        // cl reports diagnostics on stdout, mixed with /showIncludes notes
+        let system_dirs: Vec<PathBuf> = std::env::var_os("INCLUDE")
+            .map(|dirs| std::env::split_paths(&dirs).collect())
+            .unwrap_or_default();
+        let root = std::env::current_dir().unwrap_or_default();
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let (headers, diagnostics) = parse_show_includes(&stdout, &obj.src.path, &root, &system_dirs);
+        stderr.insert_str(0, &diagnostics);
+        if output.status.success() {
+            let mut deps = vec![obj.src.path.clone()];
+            deps.extend(headers);
+            write_depfile(&obj.dep_path, &obj.obj_path, &deps)?;
+        }
+    }
+    if output.status.success() {
+        Ok(stderr)
+    } else {
+        Err(BuildError::CompileError {
+            src: obj.src.path.clone(),
+            stderr,
+            code: output.status.code(),
+        })
+    }
+}
+
+// ─────────────────────────────────────────────
+// Linking
+// ─────────────────────────────────────────────
+
+/// Link all object files into the final executable.
+/// Wrap the static archives among `libs` in a `--start-group` /
+/// `--end-group` pair so GNU ld rescans them until no new symbols resolve,
+/// whatever order they were listed in and even if they depend on each other.
+/// The group takes the place of the first archive; other entries keep their
+/// order. macOS ld64 already rescans archives and has no group flags.
+fn group_static_archives(libs: Vec<String>, os: &str) -> Vec<String> {
+    let is_archive = |lib: &str| !lib.starts_with('-') && lib.ends_with(".a");
+    if os == "macos" || libs.iter().filter(|l| is_archive(l)).count() < 2 {
+        return libs;
+    }
+
+    let (archives, others): (Vec<_>, Vec<_>) = libs.iter().partition(|l| is_archive(l));
+    let first = libs.iter().position(|l| is_archive(l)).unwrap_or(0);
+    let mut out: Vec<String> = others[..first].iter().map(|l| l.to_string()).collect();
+    out.push("-Wl,--start-group".to_string());
+    out.extend(archives.into_iter().cloned());
+    out.push("-Wl,--end-group".to_string());
+    out.extend(others[first..].iter().map(|l| l.to_string()));
+    out
+}
+
+/// The file the linker actually writes: on Windows `.exe` is added when
+/// `out_exe` has no extension.
+fn linked_exe_path(out_exe: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let mut p = out_exe.to_path_buf();
+        if p.extension().is_none() {
+            p.set_extension("exe");
+        }
+        p
+    }
+    #[cfg(not(windows))]
+    {
+        out_exe.to_path_buf()
+    }
+}
+
+/// The export allow-list file passed to the linker for `exports`.
+pub fn export_list_path(config: &ProjectConfig, out_exe: &Path) -> PathBuf {
+    let name = out_exe
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    config.temp_dir.join(format!("{}.exports", name))
+}
+
+/// Contents of the export list: a version script for GNU ld / lld, a
+/// symbol list for ld64 (which wants the C-level underscore).
+fn export_list(config: &ProjectConfig) -> String {
+    if config.target_os() == "macos" {
+        config.exports.iter().map(|s| format!("_{}\n", s)).collect()
+    } else {
+        let globals: String = config.exports.iter().map(|s| format!("    {};\n", s)).collect();
+        format!("{{\n  global:\n{}  local: *;\n}};\n", globals)
+    }
+}
+
+/// Map file written next to the objects when `link_map` is set.
+pub fn link_map_path(config: &ProjectConfig, out_exe: &Path) -> PathBuf {
+    let name = out_exe
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    config.temp_dir.join(format!("{}.map", name))
+}
+
+/// Build the full link command for `objects` (plus `extra_objects`).
+/// Returns (linker, args).
+pub fn build_link_args(
+    objects: &[ObjectFile],
+    out_exe: &Path,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> (String, Vec<String>) {
+    if config.toolchain == Toolchain::Msvc {
+        return msvc_link_args(objects, out_exe, config, profile, extra_flags);
+    }
+    let ToolCommand {
+        program: linker,
+        mut args,
+    } = config.cxx_compiler();
+    args.extend(config.target_flags());
+
+    // Object files, then prebuilt inputs
+    for obj in objects {
+        args.push(obj.obj_path.to_string_lossy().into_owned());
+    }
+    for extra in &config.extra_objects {
+        args.push(extra.to_string_lossy().into_owned());
+    }
+
+    // Output executable
+    args.push("-o".to_string());
+    args.push(linked_exe_path(out_exe).to_string_lossy().into_owned());
+
+    // Linker flags
+    args.extend(config.ld_flags.clone());
+    // Windows DLLs export only what is marked dllexport already
+    if !config.exports.is_empty() && config.target_os() != "windows" {
+        let list = export_list_path(config, out_exe).display().to_string();
+        args.push(if config.target_os() == "macos" {
+            format!("-Wl,-exported_symbols_list,{}", list)
+        } else {
+            format!("-Wl,--version-script={}", list)
+        });
+    }
+    // GNU ld / lld map with a cross reference table; ld64 has neither
+    if config.link_map && config.target_os() != "macos" {
+        args.push(format!("-Wl,-Map={}", link_map_path(config, out_exe).display()));
+        args.push("-Wl,--cref".to_string());
+    }
+
+    // Link libraries
+    args.extend(group_static_archives(
+        config.resolved_link_libs(),
+        config.target_os(),
+    ));
+
+    // openmp / threads
+    args.extend(config.feature_flags().1);
+
+    // Profile-specific
+    let settings = config.profile(profile);
+    args.extend(config.lto_flags(profile).1);
+    args.extend(settings.ld_flags.iter().cloned());
+    if settings.strip == StripMode::Full {
+        args.push("-s".to_string()); // strip symbols
+    }
+
+    // Extra CLI flags
+    args.extend_from_slice(extra_flags);
+
+    (linker, args)
+}
+
+/// `link.exe` spelling of [`build_link_args`]. `-lfoo` and `-Lpath` from
+/// `link_libs` become `foo.lib` and `/LIBPATH:path`; other entries pass
+/// through. A PDB is written unless the profile strips fully.
+fn msvc_link_args(
+    objects: &[ObjectFile],
+    out_exe: &Path,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> (String, Vec<String>) {
+    let mut args = vec!["/nologo".to_string()];
+    for obj in objects {
+        args.push(obj.obj_path.to_string_lossy().into_owned());
+    }
+    for extra in &config.extra_objects {
+        args.push(extra.to_string_lossy().into_owned());
+    }
+    args.push(format!("/OUT:{}", linked_exe_path(out_exe).display()));
+    args.extend(config.ld_flags.clone());
+    if config.link_map {
+        args.push(format!("/MAP:{}", link_map_path(config, out_exe).display()));
+    }
+    for lib in config.resolved_link_libs() {
+        args.push(if let Some(name) = lib.strip_prefix("-l") {
+            format!("{}.lib", name)
+        } else if let Some(dir) = lib.strip_prefix("-L") {
+            format!("/LIBPATH:{}", dir)
+        } else {
+            lib
+        });
+    }
+    args.extend(config.feature_flags().1);
+    let settings = config.profile(profile);
+    args.extend(config.lto_flags(profile).1);
+    args.extend(settings.ld_flags.iter().cloned());
+    if settings.strip != StripMode::Full {
+        args.push("/DEBUG".to_string());
+    }
+    args.extend_from_slice(extra_flags);
+    ("link".to_string(), args)
+}
+
+/// Decide whether `out_exe` must be relinked: it is missing, an object or
+/// `extra_objects` input is newer, or the link command changed
+/// (`link_hash`, as recorded in the state under the executable's path).
+pub fn relink_needed(
+    objects: &[ObjectFile],
+    out_exe: &Path,
+    config: &ProjectConfig,
+    state: &BuildState,
+    link_hash: &str,
+) -> bool {
+    if !config.incremental {
+        return true;
+    }
+    let exe_path = linked_exe_path(out_exe);
+    let exe_mtime = match std::fs::metadata(&exe_path).and_then(|m| m.modified()) {
+        Ok(t) => t,
+        Err(_) => return true,
+    };
+    let inputs_newer = objects
+        .iter()
+        .map(|o| o.obj_path.as_path())
+        .chain(config.extra_objects.iter().map(PathBuf::as_path))
+        .any(|input| is_newer_than(input, exe_mtime));
+    let command_changed = state.get(out_exe).map(|prev| prev.flags_hash.as_str()) != Some(link_hash);
+    inputs_newer || command_changed
+}
+
+/// Link `objects` into `out_exe` with the project's linker and flags.
+pub fn link_objects(
+    objects: &[ObjectFile],
+    out_exe: &Path,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    verbose: bool,
+) -> Result<(), BuildError> {
+    if objects.is_empty() {
+        return Err(BuildError::LinkError {
+            stderr: "No object files to link".to_string(),
+            code: None,
+        });
+    }
+
+    let (linker, args) = build_link_args(objects, out_exe, config, profile, extra_flags);
+    let exe_path = linked_exe_path(out_exe);
+
+    if !config.exports.is_empty() {
+        let list = export_list_path(config, out_exe);
+        std::fs::write(&list, export_list(config))
+            .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", list, e)))?;
+    }
+    if let Some(parent) = exe_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+        })?;
+    }
+
+    if verbose || config.explain_commands {
+        let mut cmd_str = format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&linker, &args));
+        if config.explain_commands {
+            cmd_str.push_str(&crate::explain::notes(&args));
+        }
+        crate::ui::line(&cmd_str);
+    }
+
+    let mut cmd = std::process::Command::new(&linker);
+    cmd.args(&args);
+    cmd.stdout(std::process::Stdio::piped());
+    cmd.stderr(std::process::Stdio::piped());
+
+    let output = cmd.spawn().map_err(|e| {
+        BuildError::IoError(format!("Failed to spawn linker '{}': {}", linker, e))
+    })?.wait_with_output().map_err(|e| {
+        BuildError::IoError(format!("Failed to wait for linker: {}", e))
+    })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        return Err(BuildError::LinkError {
+            stderr,
+            code: output.status.code(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Build the archiver command that packs `objects` into `out_lib`.
+/// Returns (archiver, args).
+pub fn build_archive_args(
+    objects: &[ObjectFile],
+    out_lib: &Path,
+    config: &ProjectConfig,
+) -> (String, Vec<String>) {
+    if config.toolchain == Toolchain::Msvc {
+        let mut args = vec!["/nologo".to_string(), format!("/OUT:{}", out_lib.display())];
+        args.extend(objects.iter().map(|o| o.obj_path.to_string_lossy().into_owned()));
+        return ("lib".to_string(), args);
+    }
+    let mut args = vec!["rcs".to_string(), out_lib.to_string_lossy().into_owned()];
+    args.extend(objects.iter().map(|o| o.obj_path.to_string_lossy().into_owned()));
+    (config.ar_path.clone(), args)
+}
+
+/// Pack `objects` into the static library `out_lib`, replacing it.
+pub fn archive_objects(
+    objects: &[ObjectFile],
+    out_lib: &Path,
+    config: &ProjectConfig,
+    verbose: bool,
+) -> Result<(), BuildError> {
+    // `ar r` only adds and replaces members; start over so removed sources go away
+    if out_lib.exists() {
+        std::fs::remove_file(out_lib).map_err(|e| {
+            BuildError::IoError(format!("Cannot remove {:?}: {}", out_lib, e))
+        })?;
+    }
+
+    let (archiver, args) = build_archive_args(objects, out_lib, config);
+    if verbose || config.explain_commands {
+        let mut cmd_str = format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&archiver, &args));
+        if config.explain_commands {
+            cmd_str.push_str(&crate::explain::notes(&args));
+        }
+        crate::ui::line(&cmd_str);
+    }
+    let output = std::process::Command::new(&archiver)
+        .args(&args)
+        .output()
+        .map_err(|e| {
+            BuildError::IoError(format!("Failed to spawn archiver '{}': {}", archiver, e))
+        })?;
+    if !output.status.success() {
+        return Err(BuildError::LinkError {
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+            code: output.status.code(),
+        });
+    }
+    Ok(())
+}
+
+// ─────────────────────────────────────────────
+// Post-link pipeline
+// ─────────────────────────────────────────────
+
+/// One step run in place on a freshly linked binary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum PostLinkStep {
+    /// `strip = "separate"`
+    SplitDebug,
+    /// `compress_binary`
+    Compress,
+    /// One `post_link` command.
+    Command(ToolCommand),
+    /// `sign_identity`, always last so nothing changes the signed binary.
+    Sign,
+}
+
+/// Post-link steps of a profile, in order: debug info split, packing,
+/// `post_link`, then signing of release binaries. Plugins are never packed.
+pub fn post_link_steps(
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    target_type: TargetType,
+) -> Vec<PostLinkStep> {
+    let settings = config.profile(profile);
+    let mut steps = Vec::new();
+    // link.exe already keeps debug info in a separate PDB
+    if settings.strip == StripMode::Separate && config.toolchain != Toolchain::Msvc {
+        steps.push(PostLinkStep::SplitDebug);
+    }
+    if settings.compress && target_type != TargetType::Plugin {
+        steps.push(PostLinkStep::Compress);
+    }
+    steps.extend(settings.post_link.iter().cloned().map(PostLinkStep::Command));
+    if config.sign_identity.is_some() && *profile == BuildProfile::Release {
+        steps.push(PostLinkStep::Sign);
+    }
+    steps
+}
+
+/// Where a binary with post-link steps is linked to. The steps then run
+/// on a copy in output_dir, so changing them does not need a relink.
+pub fn linked_path(config: &ProjectConfig, out_exe: &Path) -> PathBuf {
+    let name = out_exe.file_name().unwrap_or(out_exe.as_os_str());
+    config.temp_dir.join("linked").join(name)
+}
+
+/// Hash of the steps and of the binary they start from: the pipeline only
+/// needs to run again when one of them changed.
+pub fn post_link_hash(
+    steps: &[PostLinkStep],
+    config: &ProjectConfig,
+    linked: &Path,
+) -> Result<String, BuildError> {
+    let content = std::fs::read(linked)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", linked, e)))?;
+    let mut h = Sha256::new();
+    for step in steps {
+        match step {
+            PostLinkStep::SplitDebug => h.update_field(&format!("split-debug {}", config.objcopy_path)),
+            PostLinkStep::Compress => h.update_field(&format!("compress {}", config.upx_path)),
+            PostLinkStep::Command(cmd) => h.update_field(&cmd.to_string()),
+            PostLinkStep::Sign => {
+                let signer = config.sign_command.as_ref().map(ToString::to_string);
+                h.update_field(&format!("sign {:?} {:?}", signer, config.sign_identity));
+            }
+        }
+    }
+    h.update(&content);
+    Ok(h.finish_hex())
+}
+
+/// Copy the linked binary over `out_exe`, where the steps modify it.
+pub fn install_linked(linked: &Path, out_exe: &Path) -> Result<(), BuildError> {
+    if let Some(parent) = out_exe.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+        })?;
+    }
+    std::fs::copy(linked, out_exe)
+        .map(|_| ())
+        .map_err(|e| BuildError::IoError(format!("Cannot copy {:?} to {:?}: {}", linked, out_exe, e)))
+}
+
+/// Run a `post_link` command on `exe`: in place of every `{exe}` in its
+/// arguments, or appended when there is none.
+pub fn run_post_link_command(cmd: &ToolCommand, exe: &Path, verbose: bool) -> Result<(), BuildError> {
+    let exe_str = exe.to_string_lossy();
+    let mut args: Vec<String> = cmd.args.iter().map(|a| a.replace("{exe}", &exe_str)).collect();
+    if !cmd.args.iter().any(|a| a.contains("{exe}")) {
+        args.push(exe_str.into_owned());
+    }
+    if verbose {
+        crate::ui::line(&format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&cmd.program, &args)));
+    }
+    let output = std::process::Command::new(&cmd.program)
+        .args(&args)
+        .output()
+        .map_err(|e| {
+            BuildError::IoError(format!("Failed to spawn post_link step '{}': {}", cmd, e))
+        })?;
+    if !output.status.success() {
+        let mut stderr = format!("post_link step '{}' failed\n", cmd);
+        stderr.push_str(&String::from_utf8_lossy(&output.stderr));
+        return Err(BuildError::LinkError {
+            stderr,
+            code: output.status.code(),
+        });
+    }
+    Ok(())
+}
+
+/// The signing tool: `sign_command`, else codesign on macOS and signtool
+/// on Windows.
+pub fn signer(config: &ProjectConfig) -> Result<ToolCommand, BuildError> {
+    if let Some(cmd) = &config.sign_command {
+        return Ok(cmd.clone());
+    }
+    match config.target_os() {
+        "macos" => Ok(ToolCommand::new("codesign")),
+        "windows" => Ok(ToolCommand::new("signtool")),
+        os => Err(BuildError::ConfigError(format!(
+            "sign_identity is set, but there is no default signing tool for '{}'; set sign_command",
+            os
+        ))),
+    }
+}
+
+/// Sign and verify command lines for `exe`. codesign and signtool get
+/// their usual arguments around the configured ones; any other tool gets
+/// its own arguments with `{identity}` and `{exe}` filled in (the binary is
+/// appended when `{exe}` is absent) and is trusted to fail on its own.
+fn sign_commands(
+    signer: &ToolCommand,
+    identity: &str,
+    exe: &Path,
+) -> (Vec<String>, Option<Vec<String>>) {
+    let exe_str = exe.to_string_lossy().into_owned();
+    let tool = Path::new(&signer.program)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let strs = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+    match tool.as_str() {
+        "codesign" => {
+            let mut sign = strs(&["--force", "--sign", identity]);
+            sign.extend(signer.args.iter().cloned());
+            sign.push(exe_str.clone());
+            (sign, Some(strs(&["--verify", "--strict", "--verbose=2", &exe_str])))
+        }
+        "signtool" => {
+            let mut sign = strs(&["sign", "/fd", "SHA256", "/n", identity]);
+            sign.extend(signer.args.iter().cloned());
+            sign.push(exe_str.clone());
+            (sign, Some(strs(&["verify", "/pa", &exe_str])))
+        }
+        _ => {
+            let mut sign: Vec<String> = signer
+                .args
+                .iter()
+                .map(|a| a.replace("{identity}", identity).replace("{exe}", &exe_str))
+                .collect();
+            if !signer.args.iter().any(|a| a.contains("{exe}")) {
+                sign.push(exe_str);
+            }
+            (sign, None)
+        }
+    }
+}
+
+/// Sign `exe` with `sign_identity`, then check that the signature verifies.
+pub fn sign_binary(exe: &Path, config: &ProjectConfig, verbose: bool) -> Result<(), BuildError> {
+    let signer = signer(config)?;
+    let identity = config.sign_identity.as_deref().unwrap_or_default();
+    let (sign, verify) = sign_commands(&signer, identity, exe);
+    let steps = std::iter::once(("sign", sign)).chain(verify.map(|v| ("verify", v)));
+    for (what, args) in steps {
+        if verbose {
+            crate::ui::line(&format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&signer.program, &args)));
+        }
+        let output = std::process::Command::new(&signer.program)
+            .args(&args)
+            .output()
+            .map_err(|e| {
+                BuildError::IoError(format!("Failed to spawn signing tool '{}': {}", signer.program, e))
+            })?;
+        if !output.status.success() {
+            let mut stderr = format!("{} {} failed for {}\n", signer.program, what, exe.display());
+            stderr.push_str(&String::from_utf8_lossy(&output.stderr));
+            stderr.push_str(&String::from_utf8_lossy(&output.stdout));
+            return Err(BuildError::LinkError {
+                stderr,
+                code: output.status.code(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Move debug info from `exe` into `exe.debug` and leave a
+/// `.gnu_debuglink` behind so debuggers and symbolicators find it.
+pub fn split_debug_info(exe: &Path, config: &ProjectConfig, verbose: bool) -> Result<(), BuildError> {
+    let mut debug_name = exe.as_os_str().to_owned();
+    debug_name.push(".debug");
+    let debug_file = PathBuf::from(debug_name);
+
+    let exe_str = exe.to_string_lossy().into_owned();
+    let debug_str = debug_file.to_string_lossy().into_owned();
+    let steps: [Vec<String>; 2] = [
+        vec!["--only-keep-debug".to_string(), exe_str.clone(), debug_str.clone()],
+        vec![
+            "--strip-debug".to_string(),
+            "--strip-unneeded".to_string(),
+            format!("--add-gnu-debuglink={}", debug_str),
+            exe_str,
+        ],
+    ];
+
+    for args in &steps {
+        if verbose {
+            crate::ui::line(&format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&config.objcopy_path, args)));
+        }
+        let output = std::process::Command::new(&config.objcopy_path)
+            .args(args)
+            .output()
+            .map_err(|e| {
+                BuildError::IoError(format!(
+                    "Failed to spawn objcopy '{}' for strip = \"separate\": {}",
+                    config.objcopy_path, e
+                ))
+            })?;
+        if !output.status.success() {
+            return Err(BuildError::LinkError {
+                stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+                code: output.status.code(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Pack `exe` in place with `upx_path`. Returns its size before and after.
+pub fn compress_binary(
+    exe: &Path,
+    config: &ProjectConfig,
+    verbose: bool,
+) -> Result<(u64, u64), BuildError> {
+    let size = |p: &Path| {
+        std::fs::metadata(p)
+            .map(|m| m.len())
+            .map_err(|e| BuildError::IoError(format!("Cannot stat {:?}: {}", p, e)))
+    };
+    let before = size(exe)?;
+
+    let upx = &config.upx_path;
+    let mut args = upx.args.clone();
+    args.push("-q".to_string());
+    args.push(exe.to_string_lossy().into_owned());
+    if verbose {
+        crate::ui::line(&format!("  \x1b[2m$ {}\x1b[0m", shell::command_line(&upx.program, &args)));
+    }
+    let output = std::process::Command::new(&upx.program)
+        .args(&args)
+        .output()
+        .map_err(|e| {
+            BuildError::IoError(format!(
+                "Failed to spawn upx '{}' for compress_binary: {}",
+                upx.program, e
+            ))
+        })?;
+    if !output.status.success() {
+        // upx reports its errors on stdout
+        let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        stderr.push_str(&String::from_utf8_lossy(&output.stdout));
+        return Err(BuildError::LinkError {
+            stderr,
+            code: output.status.code(),
+        });
+    }
+
+    Ok((before, size(exe)?))
+}
+
+// ─────────────────────────────────────────────
+// Project creation skeleton
+// ─────────────────────────────────────────────
+
+const DEFAULT_CONFIG_TEMPLATE: &str = r#"# drakkar config — project: {{name}}
+config_version = "{{config_version}}"
+app_name = "{{name}}"
+source_dir = "src/"
+output_dir = "out/"
+temp_dir = "target/"
+
+# File/directory names skipped when collecting sources (* and ? wildcards)
+ignore_patterns = "*~ #*# .#* *.swp *.orig *.rej"
+
+# Compiler flags
+c_flags = "-Wall -Wextra -std=c11"
+cxx_flags = "-Wall -Wextra -std=c++17"
+ld_flags = ""
+include_dirs = ""
+link_libs = ""
+
+# Standards
+c_standard = "c11"
+cxx_standard = "c++17"
+
+# Per-profile link settings (strip: none, full, separate)
+debug.ld_flags = ""
+release.ld_flags = ""
+release.strip = "full"
+
+# Compiler commands (defaults: gcc, g++); may include a wrapper, e.g. "ccache g++"
+gcc_path = "gcc"
+gpp_path = "g++"
+
+# Build options
+parallel_jobs = "4"
+incremental = "true"
+preserve_temp = "true"
+use_process_groups = "false"
+"#;
+
+const DEFAULT_README_TEMPLATE: &str = r#"# {{name}}
+
+A C/C++ project built with [drakkar](https://github.com/yourorg/drakkar).
+
+## Building
+
+```sh
+drakkar build           # debug build
+drakkar build release   # release build
+drakkar run             # build & run
+```
+
+## Project structure
+
+```
+src/        — source files (.c, .cpp, .cc, .cxx)
+out/        — compiled binaries
+target/     — object files and dependency files (.o, .d)
+config.txt  — build configuration
+config.local.txt — optional, uncommitted per-machine overrides
+```
+"#;
+
+const DEFAULT_GITIGNORE_TEMPLATE: &str = r#"out/
+target/
+config.local.txt
+drakkar.local.toml
+compile_commands.json
+channels/
+deps/
+"#;
+
+const DEFAULT_MAIN_TEMPLATE: &str = r#"#include <iostream>
+
+int main() {
+    std::cout << "Hello from drakkar!" << std::endl;
+    return 0;
+}
+"#;
+
+/// Create a new project directory.
+///
+/// Without `template_dir` the built-in skeleton is written; otherwise the
+/// template directory is copied with `{{var}}` substitution in file names
+/// and contents. `name` and `config_version` are always defined and can be
+/// overridden through `vars`.
+pub fn create_project(
+    name: &str,
+    template_dir: Option<&Path>,
+    vars: &[(String, String)],
+) -> Result<(), BuildError> {
+    let root = PathBuf::from(name);
+
+    if root.exists() {
+        return Err(BuildError::IoError(format!(
+            "Directory '{}' already exists",
+            name
+        )));
+    }
+
+    let mut template_vars = TemplateVars::new();
+    template_vars.insert("name".to_string(), name.to_string());
+    template_vars.insert(
+        "config_version".to_string(),
+        crate::migrate::CURRENT_CONFIG_VERSION.to_string(),
+    );
+    for (key, value) in vars {
+        template_vars.insert(key.clone(), value.clone());
+    }
+
+    if let Some(dir) = template_dir {
+        let result = render_dir(dir, &root, &template_vars);
+        if result.is_err() {
+            let _ = std::fs::remove_dir_all(&root);
+        }
+        return result;
+    }
+
+    std::fs::create_dir_all(root.join("src"))?;
+    std::fs::create_dir_all(root.join("out"))?;
+    std::fs::create_dir_all(root.join("target"))?;
+
+    std::fs::write(
+        root.join("config.txt"),
+        render(DEFAULT_CONFIG_TEMPLATE, &template_vars)?,
+    )?;
+    std::fs::write(
+        root.join("README.md"),
+        render(DEFAULT_README_TEMPLATE, &template_vars)?,
+    )?;
+    std::fs::write(
+        root.join(".gitignore"),
+        render(DEFAULT_GITIGNORE_TEMPLATE, &template_vars)?,
+    )?;
+    // Write a sample main.cpp
+    std::fs::write(
+        root.join("src").join("main.cpp"),
+        render(DEFAULT_MAIN_TEMPLATE, &template_vars)?,
+    )?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_collect_sources_skips_hidden() {
+        let dir = std::env::temp_dir().join("drakkar_test_collect");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join(".git")).unwrap();
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/main.cpp"), "").unwrap();
+        fs::write(dir.join(".git/config"), "").unwrap();
+
+        let cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            ..ProjectConfig::default()
+        };
+        let sources = collect_sources(&cfg).unwrap().sources;
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].rel_path, PathBuf::from("main.cpp"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collect_sources_skips_ignored() {
+        let dir = std::env::temp_dir().join("drakkar_test_collect_ignored");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/scratch")).unwrap();
+        fs::write(dir.join("src/main.cpp"), "").unwrap();
+        fs::write(dir.join("src/.#main.cpp"), "").unwrap();
+        fs::write(dir.join("src/scratch/try.cpp"), "").unwrap();
+
+        let mut cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            ..ProjectConfig::default()
+        };
+        assert_eq!(collect_sources(&cfg).unwrap().sources.len(), 2);
+
+        cfg.ignore_patterns.push("scratch".to_string());
+        let sources = collect_sources(&cfg).unwrap().sources;
+        assert_eq!(sources.len(), 1);
+        assert_eq!(sources[0].rel_path, PathBuf::from("main.cpp"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collect_sources_patterns() {
+        let dir = std::env::temp_dir().join("drakkar_test_collect_patterns");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/net")).unwrap();
+        fs::create_dir_all(dir.join("src/experimental")).unwrap();
+        for file in ["main.cpp", "legacy.c", "net/http.cpp", "experimental/try.cpp"] {
+            fs::write(dir.join("src").join(file), "").unwrap();
+        }
+
+        let root = dir.display();
+        let cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            source_patterns: vec![format!("{}/src/**/*.cpp", root)],
+            exclude_patterns: vec![format!("{}/src/experimental/**", root)],
+            ..ProjectConfig::default()
+        };
+        let mut rel: Vec<PathBuf> =
+            collect_sources(&cfg).unwrap().sources.into_iter().map(|s| s.rel_path).collect();
+        rel.sort();
+        assert_eq!(rel, vec![PathBuf::from("main.cpp"), PathBuf::from("net/http.cpp")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_multiple_source_roots_do_not_collide() {
+        let cfg = ProjectConfig {
+            source_dirs: ["src/", "platform/linux/", "../shared"].iter().map(PathBuf::from).collect(),
+            temp_dir: PathBuf::from("target"),
+            ..ProjectConfig::default()
+        };
+        let obj_for = |path: &str| {
+            let src = SourceFile {
+                path: PathBuf::from(path),
+                rel_path: PathBuf::from("util.cpp"),
+                language: Language::Cpp,
+            };
+            object_path_for(&src, &cfg).obj_path
+        };
+
+        assert_eq!(obj_for("src/util.cpp"), PathBuf::from("target/src/util.o"));
+        assert_eq!(
+            obj_for("platform/linux/util.cpp"),
+            PathBuf::from("target/platform/linux/util.o")
+        );
+        assert_eq!(obj_for("../shared/util.cpp"), PathBuf::from("target/__/shared/util.o"));
+    }
+
+    #[test]
+    fn test_vendor_dirs() {
+        let dir = std::env::temp_dir().join("drakkar_test_vendor_dirs");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/third_party/stb")).unwrap();
+        fs::create_dir_all(dir.join("imgui")).unwrap();
+        fs::write(dir.join("src/main.cpp"), "").unwrap();
+        fs::write(dir.join("src/third_party/stb/stb.c"), "").unwrap();
+        fs::write(dir.join("imgui/imgui.cpp"), "").unwrap();
+
+        let cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            vendor_dirs: vec![dir.join("src/third_party/stb"), dir.join("imgui")],
+            temp_dir: PathBuf::from("target"),
+            cxx_flags: vec!["-Wall".to_string(), "-Werror".to_string()],
+            cxx_standard: Some("c++20".to_string()),
+            vendor_cxx_flags: vec!["-DIMGUI_DISABLE_OBSOLETE_FUNCTIONS".to_string()],
+            vendor_cxx_standard: Some("c++11".to_string()),
+            ..ProjectConfig::default()
+        };
+        let mut objects: Vec<ObjectFile> = collect_sources(&cfg)
+            .unwrap()
+            .sources
+            .iter()
+            .map(|src| object_path_for(src, &cfg))
+            .collect();
+        objects.sort_by_key(|o| (cfg.is_vendored(&o.src.path), o.obj_path.clone()));
+        let obj_paths: Vec<&Path> = objects.iter().map(|o| o.obj_path.as_path()).collect();
+        assert_eq!(obj_paths.len(), 3, "each source once: {:?}", obj_paths);
+        assert_eq!(obj_paths[0], Path::new("target/main.o"));
+        assert!(obj_paths[1].starts_with("target/.drakkar/vendor"), "{:?}", obj_paths[1]);
+        assert!(obj_paths[1].ends_with("imgui/imgui.o"), "{:?}", obj_paths[1]);
+        assert!(obj_paths[2].starts_with("target/.drakkar/vendor"), "{:?}", obj_paths[2]);
+        assert!(obj_paths[2].ends_with("src/third_party/stb/stb.o"), "{:?}", obj_paths[2]);
+
+        let (_, own) = build_compile_args(&objects[0], &cfg, &BuildProfile::Debug, &[]);
+        assert!(own.contains(&"-Wall".to_string()) && own.contains(&"-std=c++20".to_string()));
+        assert!(!own.contains(&"-w".to_string()));
+        let (_, vendored) = build_compile_args(&objects[1], &cfg, &BuildProfile::Debug, &[]);
+        assert!(!vendored.contains(&"-Wall".to_string()), "{:?}", vendored);
+        for flag in ["-w", "-std=c++11", "-DIMGUI_DISABLE_OBSOLETE_FUNCTIONS", "-isystem"] {
+            assert!(vendored.contains(&flag.to_string()), "{} in {:?}", flag, vendored);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_collect_sources_skips_nested_project() {
+        let dir = std::env::temp_dir().join("drakkar_test_collect_nested");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src/vendor/lib/src")).unwrap();
+        fs::write(dir.join("src/main.cpp"), "").unwrap();
+        fs::write(dir.join("src/vendor/lib/config.txt"), "").unwrap();
+        fs::write(dir.join("src/vendor/lib/src/main.cpp"), "").unwrap();
+
+        let cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            ..ProjectConfig::default()
+        };
+        let scan = collect_sources(&cfg).unwrap();
+        assert_eq!(scan.sources.len(), 1);
+        assert_eq!(scan.nested_projects, vec![dir.join("src/vendor/lib")]);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_object_path_for_mirror() {
+        use crate::config::ProjectConfig;
+        let cfg = ProjectConfig {
+            temp_dir: PathBuf::from("target"),
+            ..ProjectConfig::default()
+        };
+
+        let src = SourceFile {
+            path: PathBuf::from("src/math/utils.cpp"),
+            rel_path: PathBuf::from("math/utils.cpp"),
+            language: Language::Cpp,
+        };
+
+        let obj = object_path_for(&src, &cfg);
+        assert_eq!(obj.obj_path, PathBuf::from("target/math/utils.o"));
+        assert_eq!(obj.dep_path, PathBuf::from("target/math/utils.d"));
+    }
+
+    #[test]
+    fn test_rebuild_stats_summary() {
+        let mut stats = RebuildStats::default();
+        stats.record(None);
+        stats.record(Some(RebuildReason::NewFile));
+        stats.record(Some(RebuildReason::HeaderChanged));
+        stats.record(Some(RebuildReason::NewFile));
+        assert_eq!(
+            stats.summary(),
+            "1 up-to-date, 3 rebuilt (2 new file, 1 header changed)"
+        );
+    }
+
+    #[test]
+    fn test_flags_change_detected() {
+        let dir = std::env::temp_dir().join("drakkar_test_flags_changed");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.cpp"), "").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(dir.join("a.o"), "").unwrap();
+        fs::write(dir.join("a.d"), format!("a.o: {}\n", dir.join("src/a.cpp").display())).unwrap();
+
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: dir.join("src/a.cpp"),
+                rel_path: PathBuf::from("a.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join("a.o"),
+            dep_path: dir.join("a.d"),
+        };
+        let cfg = ProjectConfig::default();
+        let mut state = BuildState::load(&dir);
+        assert_eq!(rebuild_reason(&obj, &cfg, &state, "h1"), None);
+
+        state.set(
+            &obj.obj_path,
+            crate::state::ObjectState {
+                flags_hash: "h1".to_string(),
+                ..Default::default()
+            },
+        );
+        assert_eq!(rebuild_reason(&obj, &cfg, &state, "h1"), None);
+        assert_eq!(
+            rebuild_reason(&obj, &cfg, &state, "h2"),
+            Some(RebuildReason::FlagsChanged)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_hash_policy_ignores_touched_files() {
+        let dir = std::env::temp_dir().join("drakkar_test_hash_policy");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/a.cpp"), "#include \"a.h\"\n").unwrap();
+        fs::write(dir.join("src/a.h"), "int a();\n").unwrap();
+        fs::write(dir.join("a.o"), "").unwrap();
+        fs::write(
+            dir.join("a.d"),
+            format!("a.o: {} {}\n", dir.join("src/a.cpp").display(), dir.join("src/a.h").display()),
+        )
+        .unwrap();
+
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: dir.join("src/a.cpp"),
+                rel_path: PathBuf::from("a.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join("a.o"),
+            dep_path: dir.join("a.d"),
+        };
+        let (source_hash, headers_hash) = content_hashes(&obj).unwrap();
+        let mut state = BuildState::load(&dir);
+        state.set(
+            &obj.obj_path,
+            crate::state::ObjectState {
+                flags_hash: "h1".to_string(),
+                source_hash: Some(source_hash),
+                headers_hash: Some(headers_hash),
+                ..Default::default()
+            },
+        );
+
+        // Rewritten with the same content, so newer than the object
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(dir.join("src/a.cpp"), "#include \"a.h\"\n").unwrap();
+        let mut cfg = ProjectConfig::default();
+        assert_eq!(
+            rebuild_reason(&obj, &cfg, &state, "h1"),
+            Some(RebuildReason::SourceChanged)
+        );
+        cfg.rebuild_policy = RebuildPolicy::Hash;
+        assert_eq!(rebuild_reason(&obj, &cfg, &state, "h1"), None);
+
+        fs::write(dir.join("src/a.h"), "long a();\n").unwrap();
+        assert_eq!(
+            rebuild_reason(&obj, &cfg, &state, "h1"),
+            Some(RebuildReason::HeaderChanged)
+        );
+        fs::remove_file(dir.join("src/a.h")).unwrap();
+        assert_eq!(
+            rebuild_reason(&obj, &cfg, &state, "h1"),
+            Some(RebuildReason::HeaderChanged)
+        );
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_pipeline_stage_args() {
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: PathBuf::from("src/a.cpp"),
+                rel_path: PathBuf::from("a.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: PathBuf::from("target/a.o"),
+            dep_path: PathBuf::from("target/a.d"),
+        };
+        let (_, args) = build_compile_args(&obj, &ProjectConfig::default(), &BuildProfile::Debug, &[]);
+        let (pre, gen) = pipeline_stage_args(&obj, &args);
+
+        assert_eq!(&pre[..4], ["-E", "src/a.cpp", "-o", "target/a.ii"]);
+        assert!(pre.contains(&"-MMD".to_string()));
+        assert_eq!(&gen[..4], ["-c", "target/a.ii", "-o", "target/a.o"]);
+        assert!(!gen.iter().any(|a| a.starts_with("-M") || a == "target/a.d"));
+        assert!(gen.contains(&"-O0".to_string()));
+    }
+
+    #[test]
+    fn test_msvc_compile_and_link_args() {
+        let cfg = ProjectConfig {
+            toolchain: Toolchain::Msvc,
+            link_libs: vec!["-Lvendor/lib".to_string(), "user32".to_string()],
+            ..ProjectConfig::default()
+        };
+        let src = SourceFile {
+            path: PathBuf::from("src/a.cpp"),
+            rel_path: PathBuf::from("a.cpp"),
+            language: Language::Cpp,
+        };
+        let obj = object_path_for(&src, &cfg);
+        assert_eq!(obj.obj_path, PathBuf::from("target/a.obj"));
+
+        let (cl, args) = build_compile_args(&obj, &cfg, &BuildProfile::Debug, &[]);
+        assert_eq!(cl, "cl");
+        assert_eq!(&args[..4], ["/nologo", "-c", "src/a.cpp", "/Fotarget/a.obj"]);
+        assert!(args.contains(&"/showIncludes".to_string()));
+        assert!(!args.iter().any(|a| a.starts_with("-M")));
+
+        let (link, args) = build_link_args(&[obj], Path::new("out/app"), &cfg, &BuildProfile::Release, &[]);
+        assert_eq!(link, "link");
+        assert!(args.contains(&"/LIBPATH:vendor/lib".to_string()));
+        assert!(args.contains(&"user32.lib".to_string()));
+        // Release strips fully by default: no PDB
+        assert!(!args.contains(&"/DEBUG".to_string()));
+        assert_eq!(static_lib_path(&cfg, "core"), PathBuf::from("out/core.lib"));
+    }
+
+    #[test]
+    fn test_executable_path_follows_target() {
+        let mut cfg = ProjectConfig {
+            output_dir: PathBuf::from("out"),
+            target_triple: Some("x86_64-windows-gnu".to_string()),
+            ..ProjectConfig::default()
+        };
+        assert_eq!(executable_path(&cfg, "app"), PathBuf::from("out/app.exe"));
+        assert_eq!(plugin_path(&cfg, "codec"), PathBuf::from("out/codec.dll"));
+        cfg.target_triple = Some("aarch64-linux-gnu".to_string());
+        assert_eq!(executable_path(&cfg, "app"), PathBuf::from("out/app"));
+    }
+
+    #[test]
+    fn test_language_policy() {
+        let mut cfg = ProjectConfig::default();
+        assert_eq!(language_for(Path::new("a.C"), &cfg), Some(Language::C));
+        assert_eq!(language_for(Path::new("a.CPP"), &cfg), Some(Language::Cpp));
+        assert_eq!(language_for(Path::new("m.cppm"), &cfg), Some(Language::Cpp));
+        assert_eq!(language_for(Path::new("m.ixx"), &cfg), Some(Language::Cpp));
+        assert_eq!(language_for(Path::new("a.inl"), &cfg), None);
+        cfg.uppercase_c_is_cpp = true;
+        assert_eq!(language_for(Path::new("a.C"), &cfg), Some(Language::Cpp));
+
+        let src = |path: &str, language| SourceFile {
+            path: PathBuf::from(path),
+            rel_path: PathBuf::from(path),
+            language,
+        };
+        assert_eq!(language_override(&src("a.C", Language::C)), Some("c"));
+        assert_eq!(language_override(&src("a.C", Language::Cpp)), None);
+        assert_eq!(language_override(&src("m.ixx", Language::Cpp)), Some("c++"));
+        assert_eq!(language_override(&src("a.cpp", Language::Cpp)), None);
+    }
+
+    #[test]
+    fn test_entry_roles() {
+        assert_eq!(entry_role(Path::new("main.cpp")), EntryRole::Primary);
+        assert_eq!(entry_role(Path::new("net/main.cpp")), EntryRole::Common);
+        assert_eq!(entry_role(Path::new("tools/dump_main.cpp")), EntryRole::Bin("dump".to_string()));
+        assert_eq!(entry_role(Path::new("bin/fmt.c")), EntryRole::Bin("fmt".to_string()));
+        assert_eq!(entry_role(Path::new("bin/conv/args.cpp")), EntryRole::Bin("conv".to_string()));
+        assert_eq!(entry_role(Path::new("util.cpp")), EntryRole::Common);
+    }
+
+    #[test]
+    fn test_plan_executables_shares_common() {
+        let cfg = ProjectConfig {
+            app_name: "app".to_string(),
+            bin_discovery: true,
+            ..ProjectConfig::default()
+        };
+        let obj = |rel: &str| object_path_for(
+            &SourceFile {
+                path: PathBuf::from("src").join(rel),
+                rel_path: PathBuf::from(rel),
+                language: Language::Cpp,
+            },
+            &cfg,
+        );
+        let exes = plan_executables(
+            vec![obj("main.cpp"), obj("util.cpp"), obj("bin/b.cpp"), obj("a_main.cpp")],
+            &cfg,
+        )
+        .unwrap();
+        let names: Vec<_> = exes.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, vec!["app", "a", "b"]);
+        for exe in &exes {
+            assert_eq!(exe.objects.len(), 2);
+            assert!(exe.objects.iter().any(|o| o.src.rel_path == Path::new("util.cpp")));
+        }
+
+        let dir = plan_executables(vec![obj("bin/c/one.cpp"), obj("bin/c/two.cpp")], &cfg).unwrap();
+        assert_eq!(dir[0].objects.len(), 2);
+        let clashes = [
+            ["main.cpp", "bin/app.cpp"],
+            ["bin/b.cpp", "b_main.cpp"],
+            ["bin/c.cpp", "bin/c/x.cpp"],
+        ];
+        for clash in clashes {
+            let err = plan_executables(vec![obj(clash[0]), obj(clash[1])], &cfg).unwrap_err();
+            let msg = err.to_string();
+            assert!(msg.contains(&format!("src/{} and src/", clash[0])), "{}", msg);
+        }
+    }
+
+    #[test]
+    fn test_no_name_collision() {
+        use crate::config::ProjectConfig;
+        let cfg = ProjectConfig::default();
+
+        let src1 = SourceFile {
+            path: PathBuf::from("src/math/utils.cpp"),
+            rel_path: PathBuf::from("math/utils.cpp"),
+            language: Language::Cpp,
+        };
+        let src2 = SourceFile {
+            path: PathBuf::from("src/network/utils.cpp"),
+            rel_path: PathBuf::from("network/utils.cpp"),
+            language: Language::Cpp,
+        };
+
+        let obj1 = object_path_for(&src1, &cfg);
+        let obj2 = object_path_for(&src2, &cfg);
+        assert_ne!(obj1.obj_path, obj2.obj_path);
+    }
+
+    #[test]
+    fn test_clean_dir_keeps_matching_files() {
+        let dir = std::env::temp_dir().join("drakkar_test_clean_dir");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("a/b")).unwrap();
+        std::fs::create_dir_all(dir.join("c")).unwrap();
+        std::fs::write(dir.join("a/b/x.o"), "1234").unwrap();
+        std::fs::write(dir.join("a/b/x.ii"), "keep").unwrap();
+        std::fs::write(dir.join("c/y.o"), "12").unwrap();
+        std::fs::write(dir.join(".drakkar_state"), "").unwrap();
+
+        let keep = |p: &Path| p.extension() == Some(std::ffi::OsStr::new("ii"));
+        let stats = clean_dir(&dir, &keep).unwrap();
+        assert_eq!(stats, CleanStats { files: 3, bytes: 6, kept: 1 });
+        assert!(dir.join("a/b/x.ii").exists());
+        assert!(!dir.join("c").exists());
+        assert!(dir.exists());
+
+        assert_eq!(clean_dir(&dir.join("missing"), &|_| false).unwrap(), CleanStats::default());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_static_archives_grouped() {
+        let libs = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            group_static_archives(libs(&["-lm", "lib/libcore.a", "-lz", "lib/libnet.a"]), "linux"),
+            libs(&[
+                "-lm",
+                "-Wl,--start-group",
+                "lib/libcore.a",
+                "lib/libnet.a",
+                "-Wl,--end-group",
+                "-lz"
+            ])
+        );
+        // A single archive, or ld64, needs no group
+        assert_eq!(
+            group_static_archives(libs(&["libz.a", "-lm"]), "linux"),
+            libs(&["libz.a", "-lm"])
+        );
+        assert_eq!(
+            group_static_archives(libs(&["liba.a", "libb.a"]), "macos"),
+            libs(&["liba.a", "libb.a"])
+        );
+    }
+
+    #[test]
+    fn test_relink_needed() {
+        use crate::state::ObjectState;
+
+        let dir = std::env::temp_dir().join("drakkar_test_relink");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let cfg = ProjectConfig {
+            temp_dir: dir.clone(),
+            ..ProjectConfig::default()
+        };
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: dir.join("main.cpp"),
+                rel_path: PathBuf::from("main.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join("main.o"),
+            dep_path: dir.join("main.d"),
+        };
+        let exe = dir.join("app");
+        let objects = vec![obj];
+        let mut state = BuildState::load(&dir);
+
+        std::fs::write(&objects[0].obj_path, "").unwrap();
+        assert!(relink_needed(&objects, &exe, &cfg, &state, "h1"));
+
+        std::fs::write(&exe, "").unwrap();
+        state.set(
+            &exe,
+            ObjectState {
+                flags_hash: "h1".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(!relink_needed(&objects, &exe, &cfg, &state, "h1"));
+        assert!(relink_needed(&objects, &exe, &cfg, &state, "h2"));
+
+        let with_missing_extra = ProjectConfig {
+            extra_objects: vec![dir.join("gone.o")],
+            ..cfg
+        };
+        assert!(relink_needed(&objects, &exe, &with_missing_extra, &state, "h1"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_targets_resolve_after_their_libraries() {
+        let target = |name: &str, target_type: TargetType, deps: &[&str]| TargetConfig {
+            name: name.to_string(),
+            target_type,
+            source_dirs: vec![PathBuf::from(name)],
+            output_name: None,
+            c_flags: vec![],
+            cxx_flags: vec![],
+            ld_flags: vec![],
+            include_dirs: vec![],
+            link_libs: vec![],
+            deps: deps.iter().map(|d| d.to_string()).collect(),
+            exports: vec![],
+            vendor_dirs: vec![],
+        };
+        let mut cfg = ProjectConfig {
+            targets: vec![
+                target("app", TargetType::Executable, &["net"]),
+                target("net", TargetType::StaticLib, &["util"]),
+                target("util", TargetType::StaticLib, &[]),
+            ],
+            ..ProjectConfig::default()
+        };
+        cfg.targets[1].link_libs = vec!["-lz".to_string()];
+        cfg.targets[2].include_dirs = vec![PathBuf::from("util/include")];
+        cfg.targets[2].output_name = Some("myutil".to_string());
+
+        let names = |targets: Vec<&TargetConfig>| -> Vec<String> {
+            targets.iter().map(|t| t.name.clone()).collect()
+        };
+        assert_eq!(names(resolve_targets(&cfg, None).unwrap()), ["util", "net", "app"]);
+        assert_eq!(names(resolve_targets(&cfg, Some("net")).unwrap()), ["util", "net"]);
+        assert!(resolve_targets(&cfg, Some("nope")).is_err());
+
+        let app = target_config(&cfg, &cfg.targets[0]).unwrap();
+        assert_eq!(app.app_name, "app");
+        assert_eq!(app.temp_dir, PathBuf::from("target/app"));
+        assert_eq!(
+            app.extra_objects,
+            vec![PathBuf::from("out/libnet.a"), PathBuf::from("out/libmyutil.a")]
+        );
+        assert_eq!(app.include_dirs, vec![PathBuf::from("util/include")]);
+        assert_eq!(app.link_libs, vec!["-lz"]);
+
+        // A library may not depend on an executable, nor on itself
+        cfg.targets[2].deps = vec!["app".to_string()];
+        assert!(resolve_targets(&cfg, None).is_err());
+        cfg.targets[2].deps = vec!["util".to_string()];
+        assert!(resolve_targets(&cfg, None).unwrap_err().to_string().contains("cycle"));
+    }
+
+    #[test]
+    fn test_sign_commands_per_tool() {
+        let exe = Path::new("out/app");
+        let (sign, verify) = sign_commands(&ToolCommand::new("codesign"), "Dev ID", exe);
+        assert_eq!(sign, ["--force", "--sign", "Dev ID", "out/app"]);
+        assert_eq!(verify.unwrap(), ["--verify", "--strict", "--verbose=2", "out/app"]);
+
+        let signtool = ToolCommand {
+            program: "C:/sdk/signtool.exe".to_string(),
+            args: vec!["/tr".to_string(), "http://ts".to_string()],
+        };
+        let (sign, verify) = sign_commands(&signtool, "Acme", exe);
+        assert_eq!(sign, ["sign", "/fd", "SHA256", "/n", "Acme", "/tr", "http://ts", "out/app"]);
+        assert_eq!(verify.unwrap(), ["verify", "/pa", "out/app"]);
+
+        let custom = ToolCommand {
+            program: "osslsigncode".to_string(),
+            args: vec!["sign".to_string(), "-certs".to_string(), "{identity}".to_string()],
+        };
+        let (sign, verify) = sign_commands(&custom, "acme.pem", exe);
+        assert_eq!(sign, ["sign", "-certs", "acme.pem", "out/app"]);
+        assert!(verify.is_none());
+
+        let linux = ProjectConfig {
+            target_triple: Some("x86_64-linux-gnu".to_string()),
+            ..ProjectConfig::default()
+        };
+        assert!(signer(&linux).is_err());
+    }
+
+    #[test]
+    fn test_plugin_target_is_pic_with_export_list() {
+        let mut cfg = ProjectConfig {
+            target_triple: Some("x86_64-linux-gnu".to_string()),
+            plugins_dir: PathBuf::from("plugins"),
+            ..ProjectConfig::default()
+        };
+        for (name, target_type) in [("codec", TargetType::Plugin), ("dsp", TargetType::StaticLib)] {
+            cfg.targets.push(TargetConfig {
+                name: name.to_string(),
+                target_type,
+                source_dirs: vec![PathBuf::from(name)],
+                output_name: None,
+                c_flags: vec![],
+                cxx_flags: vec![],
+                ld_flags: vec![],
+                include_dirs: vec![],
+                link_libs: vec![],
+                deps: vec![],
+                exports: vec![],
+                vendor_dirs: vec![],
+            });
+        }
+        cfg.targets[0].deps = vec!["dsp".to_string()];
+        cfg.targets[0].exports = vec!["codec_init".to_string(), "codec_run".to_string()];
+
+        let codec = target_config(&cfg, &cfg.targets[0]).unwrap();
+        assert!(codec.cxx_flags.contains(&"-fPIC".to_string()));
+        assert!(codec.ld_flags.contains(&"-shared".to_string()));
+        assert_eq!(codec.extra_objects, vec![PathBuf::from("out/libdsp.a")]);
+        // Linked into a plugin, so the library must be PIC too
+        let dsp = target_config(&cfg, &cfg.targets[1]).unwrap();
+        assert!(dsp.c_flags.contains(&"-fPIC".to_string()));
+
+        let out = plugin_path(&codec, "codec");
+        assert_eq!(out, PathBuf::from("out/plugins/codec.so"));
+        let (_, args) = build_link_args(&[], &out, &codec, &BuildProfile::Debug, &[]);
+        assert!(args.contains(&"-Wl,--version-script=target/codec/codec.so.exports".to_string()));
+        assert!(!args.iter().any(|a| a.contains("soname")));
+        assert_eq!(
+            export_list(&codec),
+            "{\n  global:\n    codec_init;\n    codec_run;\n  local: *;\n};\n"
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Built-in object cache (`object_cache = "true"`), in the spirit of ccache.
+//!
+//! Objects are kept under `~/.cache/drakkar/objects` (or `object_cache_dir`),
+//! keyed on the full compile command, the source and the content of every
+//! header it included, so switching branches back and forth or cleaning
+//! temp_dir doesn't mean compiling the same thing twice. The store is the
+//! one `shared_temp_dir` uses, with the project directory added to the
+//! key: debug info records the directory an object was compiled in.
+//!
+//! With `remote_cache` set, a local miss is looked up there next (see
+//! `remote`) and a remote hit is kept locally too. Compiled objects are
+//! uploaded when `remote_cache_push` is on. Hit and miss counts add up
+//! across builds in a `stats` file for `drakkar cache stats`.
+//!
+//! Sources under `vendor_dirs` are cached even with the cache off: they
+//! rarely change and are often the slowest to compile.
+
+use std::path::{Path, PathBuf};
+
+use crate::build::ObjectFile;
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::platform;
+use crate::remote::RemoteCache;
+use crate::shared::SharedDir;
+
+/// The running hit and miss totals, in the cache root.
+pub const STATS_FILE: &str = "stats";
+
+/// The object cache of one build: a local store, and optionally a remote one.
+pub struct ObjectCache {
+    root: PathBuf,
+    store: SharedDir,
+    remote: Option<RemoteCache>,
+    push: bool,
+    /// With the cache off: the `vendor_dirs`, the only sources it keeps.
+    only: Option<Vec<PathBuf>>,
+    /// The first remote failure; the remote isn't used after it.
+    remote_error: Option<BuildError>,
+    hits: usize,
+    remote_hits: usize,
+    misses: usize,
+}
+
+impl ObjectCache {
+    /// The cache for `config`, or None when it is off and nothing is
+    /// vendored, or there is no home directory to put it in.
+    /// `remote_cache` turns it on.
+    pub fn open(config: &ProjectConfig) -> Option<Self> {
+        let only = if config.object_cache || config.remote_cache.is_some() {
+            None
+        } else if config.vendor_dirs.is_empty() {
+            return None;
+        } else {
+            Some(config.vendor_dirs.clone())
+        };
+        let root = local_root(config)?;
+        let project = std::fs::canonicalize(".").unwrap_or_else(|_| PathBuf::from("."));
+        Some(ObjectCache {
+            store: SharedDir::new(&root).salted(&project.to_string_lossy()),
+            root,
+            remote: config.remote_cache.as_ref().map(RemoteCache::new),
+            push: config.remote_cache_push,
+            only,
+            remote_error: None,
+            hits: 0,
+            remote_hits: 0,
+            misses: 0,
+        })
+    }
+
+    /// Copy a cached build of `obj` into place, counting the hit or miss.
+    pub fn fetch(&mut self, obj: &ObjectFile, flags_hash: &str) -> bool {
+        if !self.keeps(obj) {
+            return false;
+        }
+        if self.store.fetch(obj, flags_hash) {
+            self.hits += 1;
+            return true;
+        }
+        if self.fetch_remote(obj, flags_hash) {
+            self.remote_hits += 1;
+            // Best effort: the object is in place either way
+            let _ = self.store.store(obj, flags_hash);
+            return true;
+        }
+        self.misses += 1;
+        false
+    }
+
+    fn fetch_remote(&mut self, obj: &ObjectFile, flags_hash: &str) -> bool {
+        let remote = match &self.remote {
+            Some(remote) if self.remote_error.is_none() => remote,
+            _ => return false,
+        };
+        match remote.fetch(obj, flags_hash) {
+            Ok(hit) => hit,
+            Err(e) => {
+                self.remote_error = Some(e);
+                false
+            }
+        }
+    }
+
+    /// Keep a freshly compiled `obj`, and upload it with `remote_cache_push`.
+    pub fn store(&mut self, obj: &ObjectFile, flags_hash: &str) -> Result<(), BuildError> {
+        if !self.keeps(obj) {
+            return Ok(());
+        }
+        self.store.store(obj, flags_hash)?;
+        if let Some(remote) = self.remote.as_ref().filter(|_| self.push) {
+            if self.remote_error.is_none() {
+                if let Err(e) = remote.store(obj, flags_hash) {
+                    self.remote_error = Some(e);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn keeps(&self, obj: &ObjectFile) -> bool {
+        match &self.only {
+            Some(dirs) => dirs.iter().any(|dir| obj.src.path.starts_with(dir)),
+            None => true,
+        }
+    }
+
+    /// Why the remote cache stopped being used during this build, once.
+    pub fn take_remote_error(&mut self) -> Option<BuildError> {
+        self.remote_error.take()
+    }
+
+    /// Hits from either tier.
+    pub fn hits(&self) -> usize {
+        self.hits + self.remote_hits
+    }
+
+    /// Hits served by the remote cache.
+    pub fn remote_hits(&self) -> usize {
+        self.remote_hits
+    }
+
+    /// Objects that had to be compiled.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// Add this build's counts to the running totals.
+    pub fn save_stats(&self) -> Result<(), BuildError> {
+        let mut stats = CacheStats::load(&self.root);
+        stats.hits += self.hits as u64;
+        stats.remote_hits += self.remote_hits as u64;
+        stats.misses += self.misses as u64;
+        stats.save(&self.root)
+    }
+}
+
+/// Where the local cache lives for `config`.
+pub fn local_root(config: &ProjectConfig) -> Option<PathBuf> {
+    match &config.object_cache_dir {
+        Some(dir) => Some(dir.clone()),
+        None => Some(platform::user_cache_dir()?.join("objects")),
+    }
+}
+
+/// Hit and miss totals of every build that used a cache root.
+#[derive(Debug, Default, PartialEq)]
+pub struct CacheStats {
+    /// Objects found in the local cache.
+    pub hits: u64,
+    /// Objects fetched from the remote cache.
+    pub remote_hits: u64,
+    /// Objects compiled because neither had them.
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// Zero when the file is missing or unreadable.
+    pub fn load(root: &Path) -> Self {
+        let mut stats = CacheStats::default();
+        let content = std::fs::read_to_string(root.join(STATS_FILE)).unwrap_or_default();
+        for line in content.lines() {
+            let (key, value) = match line.split_once('=') {
+                Some((key, value)) => (key, value.trim().parse().unwrap_or(0)),
+                None => continue,
+            };
+            match key.trim() {
+                "hits" => stats.hits = value,
+                "remote_hits" => stats.remote_hits = value,
+                "misses" => stats.misses = value,
+                _ => {}
+            }
+        }
+        stats
+    }
+
+    fn save(&self, root: &Path) -> Result<(), BuildError> {
+        std::fs::create_dir_all(root)?;
+        std::fs::write(
+            root.join(STATS_FILE),
+            format!(
+                "hits={}\nremote_hits={}\nmisses={}\n",
+                self.hits, self.remote_hits, self.misses
+            ),
+        )?;
+        Ok(())
+    }
+}
+
+/// Number of objects in the cache at `root` and the bytes it takes.
+pub fn usage(root: &Path) -> (usize, u64) {
+    let mut objects = 0;
+    let mut bytes = 0;
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => dirs.push(path),
+                Ok(meta) => {
+                    bytes += meta.len();
+                    if path.extension().map(|e| e == "o").unwrap_or(false) {
+                        objects += 1;
+                    }
+                }
+                Err(_) => {}
+            }
+        }
+    }
+    (objects, bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+
+    #[test]
+    fn test_hits_and_misses() {
+        let dir = std::env::temp_dir().join("drakkar_test_object_cache");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        std::fs::write(dir.join("src/a.c"), "int a;\n").unwrap();
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: dir.join("src/a.c"),
+                rel_path: PathBuf::from("a.c"),
+                language: Language::C,
+            },
+            obj_path: dir.join("a.o"),
+            dep_path: dir.join("a.d"),
+        };
+        std::fs::write(&obj.obj_path, "OBJ").unwrap();
+        std::fs::write(&obj.dep_path, format!("a.o: {}\n", obj.src.path.display())).unwrap();
+
+        let config = ProjectConfig {
+            object_cache: true,
+            object_cache_dir: Some(dir.join("cache")),
+            ..ProjectConfig::default()
+        };
+        let mut cache = ObjectCache::open(&config).unwrap();
+        assert!(!cache.fetch(&obj, "flags"));
+        cache.store(&obj, "flags").unwrap();
+        std::fs::remove_file(&obj.obj_path).unwrap();
+        assert!(cache.fetch(&obj, "flags"));
+        assert_eq!(std::fs::read_to_string(&obj.obj_path).unwrap(), "OBJ");
+        assert_eq!((cache.hits(), cache.misses()), (1, 1));
+
+        // Another project directory doesn't share entries
+        let other = SharedDir::new(&dir.join("cache")).salted("/elsewhere");
+        assert!(!other.fetch(&obj, "flags"));
+
+        assert!(ObjectCache::open(&ProjectConfig::default()).is_none());
+
+        // With the cache off, only vendored sources are kept
+        let vendored = ProjectConfig {
+            vendor_dirs: vec![dir.join("vendor")],
+            object_cache_dir: Some(dir.join("cache")),
+            ..ProjectConfig::default()
+        };
+        let mut vendor_cache = ObjectCache::open(&vendored).unwrap();
+        assert!(!vendor_cache.fetch(&obj, "flags"));
+        assert_eq!(vendor_cache.misses(), 0);
+
+        cache.save_stats().unwrap();
+        cache.save_stats().unwrap();
+        let stats = CacheStats::load(&dir.join("cache"));
+        assert_eq!((stats.hits, stats.remote_hits, stats.misses), (2, 0, 2));
+        assert_eq!(usage(&dir.join("cache")).0, 1);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,228 @@
+//! Code generators (`[generator.<name>]`): commands such as `protoc` that
+//! turn each input into sources and headers before the build.
+//!
+//! Outputs go to `temp_dir/gen/<name>/`, which is on the include path, and
+//! the generated C/C++ sources are compiled with the rest. An input is only
+//! regenerated when an output is missing or older than it, or the command
+//! changed since the last run (kept in `<stem>.cmd` next to the outputs),
+//! so unchanged inputs don't rebuild their objects.
+
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::build::{language_for, SourceFile};
+use crate::config::{GeneratorRule, ProjectConfig};
+use crate::error::BuildError;
+use crate::glob;
+use crate::shell;
+use crate::ui;
+
+/// Where `rule` writes its outputs.
+pub fn out_dir(config: &ProjectConfig, rule: &GeneratorRule) -> PathBuf {
+    config.temp_dir.join("gen").join(&rule.name)
+}
+
+/// Files matching `rule`'s input patterns, sorted.
+fn inputs(rule: &GeneratorRule) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    for pattern in &rule.inputs {
+        let pattern = Path::new(pattern);
+        let name = pattern.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+        if !name.contains(['*', '?']) {
+            found.push(pattern.to_path_buf());
+            continue;
+        }
+        let dir = match pattern.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+            let path = entry.path();
+            if path.is_file() && glob::matches(&name, &entry.file_name().to_string_lossy()) {
+                found.push(dir.join(entry.file_name()));
+            }
+        }
+    }
+    found.sort();
+    found.dedup();
+    found
+}
+
+fn stem(input: &Path) -> String {
+    input.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default()
+}
+
+/// `template` with `{input}`, `{stem}` and `{out_dir}` replaced.
+fn render(template: &str, input: &Path, out_dir: &Path) -> String {
+    template
+        .replace("{input}", &input.to_string_lossy())
+        .replace("{stem}", &stem(input))
+        .replace("{out_dir}", &out_dir.to_string_lossy())
+}
+
+/// The outputs `rule` writes for `input`.
+fn outputs(rule: &GeneratorRule, input: &Path, out_dir: &Path) -> Vec<PathBuf> {
+    rule.outputs.iter().map(|o| out_dir.join(render(o, input, out_dir))).collect()
+}
+
+/// Whether `input` has to be run through the generator again.
+fn stale(input: &Path, outputs: &[PathBuf], stamp: &Path, command: &str) -> bool {
+    let modified = |p: &Path| std::fs::metadata(p).and_then(|m| m.modified()).ok();
+    let oldest_output = outputs
+        .iter()
+        .map(|o| modified(o))
+        .collect::<Option<Vec<SystemTime>>>()
+        .and_then(|times| times.into_iter().min());
+    let oldest_output = match oldest_output {
+        Some(t) => t,
+        None => return true,
+    };
+    let input_newer = match modified(input) {
+        Some(t) => t > oldest_output,
+        None => true,
+    };
+    input_newer || std::fs::read_to_string(stamp).ok().as_deref() != Some(command)
+}
+
+/// Run every generator on its stale inputs. A failing command stops the
+/// build with its output, like a compile error in the input.
+pub fn generate(config: &ProjectConfig) -> Result<(), BuildError> {
+    for rule in &config.generators {
+        if rule.command.is_empty() || rule.outputs.is_empty() {
+            return Err(BuildError::ConfigError(format!(
+                "generator '{}' needs both command and outputs",
+                rule.name
+            )));
+        }
+        let dir = out_dir(config, rule);
+        std::fs::create_dir_all(&dir)
+            .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+        for input in inputs(rule) {
+            let args: Vec<String> = rule.command.iter().map(|a| render(a, &input, &dir)).collect();
+            let command = shell::command_line(&args[0], &args[1..]);
+            let outputs = outputs(rule, &input, &dir);
+            let stamp = dir.join(format!("{}.cmd", stem(&input)));
+            if !stale(&input, &outputs, &stamp, &command) {
+                continue;
+            }
+            ui::line(&format!("  \x1b[36mGenerating\x1b[0m {} ({})", ui::path(&input), rule.name));
+            if config.verbose {
+                ui::line(&format!("  \x1b[2m$ {}\x1b[0m", command));
+            }
+            let output = std::process::Command::new(&args[0])
+                .args(&args[1..])
+                .output()
+                .map_err(|e| {
+                    BuildError::IoError(format!("Failed to run generator '{}': {}", rule.name, e))
+                })?;
+            let missing: Vec<&PathBuf> = outputs.iter().filter(|o| !o.is_file()).collect();
+            if !output.status.success() || !missing.is_empty() {
+                let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+                if output.status.success() {
+                    let missing: Vec<String> =
+                        missing.iter().map(|p| p.display().to_string()).collect();
+                    stderr.push_str(&format!(
+                        "generator '{}' did not write {}\n",
+                        rule.name,
+                        missing.join(", ")
+                    ));
+                }
+                return Err(BuildError::CompileError {
+                    src: input,
+                    stderr,
+                    code: output.status.code(),
+                });
+            }
+            std::fs::write(&stamp, &command)
+                .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", stamp, e)))?;
+        }
+    }
+    Ok(())
+}
+
+/// The C and C++ sources the generators produce (or will, for inputs not
+/// run yet), named `gen/<rule>/<file>` relative to temp_dir.
+pub fn sources(config: &ProjectConfig) -> Vec<SourceFile> {
+    let mut sources = Vec::new();
+    for rule in &config.generators {
+        let dir = out_dir(config, rule);
+        for input in inputs(rule) {
+            for path in outputs(rule, &input, &dir) {
+                let rel = match path.strip_prefix(&config.temp_dir) {
+                    Ok(rel) => rel.to_path_buf(),
+                    Err(_) => continue,
+                };
+                if let Some(language) = language_for(&path, config) {
+                    sources.push(SourceFile {
+                        path,
+                        rel_path: rel,
+                        language,
+                    });
+                }
+            }
+        }
+    }
+    sources
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(dir: &Path) -> GeneratorRule {
+        GeneratorRule {
+            name: "idl".to_string(),
+            inputs: vec![dir.join("idl/*.idl").to_string_lossy().into_owned()],
+            command: vec!["gen".to_string(), "{input}".to_string(), "{out_dir}/{stem}".to_string()],
+            outputs: vec!["{stem}.cpp".to_string(), "{stem}.h".to_string()],
+            target: None,
+        }
+    }
+
+    #[test]
+    fn test_inputs_and_sources() {
+        let dir = std::env::temp_dir().join("drakkar_test_codegen");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("idl")).unwrap();
+        for name in ["b.idl", "a.idl", "notes.txt"] {
+            std::fs::write(dir.join("idl").join(name), "").unwrap();
+        }
+        let rule = rule(&dir);
+        assert_eq!(inputs(&rule), vec![dir.join("idl/a.idl"), dir.join("idl/b.idl")]);
+
+        let out = Path::new("target/gen/idl");
+        let input = dir.join("idl/a.idl");
+        assert_eq!(render("{out_dir}/{stem}", &input, out), "target/gen/idl/a");
+        assert_eq!(outputs(&rule, &input, out), vec![out.join("a.cpp"), out.join("a.h")]);
+
+        let config = ProjectConfig {
+            temp_dir: PathBuf::from("target"),
+            generators: vec![rule],
+            ..ProjectConfig::default()
+        };
+        let sources = sources(&config);
+        let rel: Vec<&Path> = sources.iter().map(|s| s.rel_path.as_path()).collect();
+        assert_eq!(rel, vec![Path::new("gen/idl/a.cpp"), Path::new("gen/idl/b.cpp")]);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_stale() {
+        let dir = std::env::temp_dir().join("drakkar_test_codegen_stale");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("a.idl");
+        let outputs = [dir.join("a.cpp")];
+        let stamp = dir.join("a.cmd");
+        std::fs::write(&input, "").unwrap();
+        assert!(stale(&input, &outputs, &stamp, "gen a.idl"));
+
+        std::fs::write(&outputs[0], "").unwrap();
+        std::fs::write(&stamp, "gen a.idl").unwrap();
+        assert!(!stale(&input, &outputs, &stamp, "gen a.idl"));
+        assert!(stale(&input, &outputs, &stamp, "gen --v2 a.idl"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
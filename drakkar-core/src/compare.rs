@@ -0,0 +1,281 @@
+//! A/B builds with two compilers (`drakkar build --compare <compiler>`).
+//!
+//! Each side builds from scratch into its own directory under
+//! `temp_dir/compare`, with the object caches off so neither side gets
+//! the other's objects, and the report lines up build time, warnings and
+//! the size of every executable and library.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::build::clean_dir;
+use crate::config::{BuildProfile, ProjectConfig};
+use crate::diagnostics::{parse_diagnostics, Diagnostic, Severity};
+use crate::engine::build_project;
+use crate::error::BuildError;
+use crate::status::BuildSummary;
+use crate::ui;
+
+/// Warnings listed per side before the rest are only counted.
+const MAX_LISTED: usize = 10;
+
+/// One compiler's build.
+pub struct Side {
+    /// The compiler as given, or the configured C++ compiler.
+    pub compiler: String,
+    /// Wall time of the build.
+    pub duration: Duration,
+    /// The build's result.
+    pub outcome: Result<BuildSummary, BuildError>,
+    /// The warnings it printed.
+    pub warnings: Vec<Diagnostic>,
+    /// Executables and libraries, relative to the side's output dir, with
+    /// their size in bytes.
+    pub artifacts: Vec<(String, u64)>,
+}
+
+/// Build with each of `compilers` in turn; None keeps the configured
+/// compilers. Errors are setup problems; a failed build is a side's
+/// `outcome`.
+pub fn run(
+    config: &ProjectConfig,
+    compilers: [Option<&str>; 2],
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    target: Option<&str>,
+) -> Result<[Side; 2], BuildError> {
+    let root = config.temp_dir.join("compare");
+    clean_dir(&root, &|_| false)?;
+    let build = |n: usize| -> Result<Side, BuildError> {
+        let side = side_config(config, compilers[n], &root.join((n + 1).to_string()))?;
+        let compiler = match compilers[n] {
+            Some(command) => command.to_string(),
+            None => side.cxx_compiler().to_string(),
+        };
+        ui::line(&format!("\x1b[1mComparing\x1b[0m {} ({}/2)", compiler, n + 1));
+        let side = Arc::new(side);
+        let started = Instant::now();
+        let outcome = build_project(&side, profile, extra_flags, target);
+        let duration = started.elapsed();
+        let artifacts = match &outcome {
+            Ok(summary) => summary
+                .executables
+                .iter()
+                .chain(&summary.libraries)
+                .map(|path| artifact(path, &side.output_dir))
+                .collect(),
+            Err(_) => vec![],
+        };
+        Ok(Side {
+            compiler,
+            duration,
+            outcome,
+            warnings: warnings_in(&side.temp_dir),
+            artifacts,
+        })
+    };
+    Ok([build(0)?, build(1)?])
+}
+
+/// `config` building with `compiler` into `dir`, from scratch.
+fn side_config(
+    config: &ProjectConfig,
+    compiler: Option<&str>,
+    dir: &Path,
+) -> Result<ProjectConfig, BuildError> {
+    let mut side = config.clone();
+    if let Some(command) = compiler {
+        side.use_compiler(command)?;
+    }
+    side.temp_dir = dir.join("build");
+    side.output_dir = dir.join("out");
+    side.object_cache = false;
+    side.remote_cache = None;
+    side.shared_temp_dir = None;
+    Ok(side)
+}
+
+fn artifact(path: &Path, output_dir: &Path) -> (String, u64) {
+    let name = path.strip_prefix(output_dir).unwrap_or(path);
+    let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    (name.to_string_lossy().into_owned(), size)
+}
+
+/// Warnings in the compiler output kept next to each object under `dir`.
+fn warnings_in(dir: &Path) -> Vec<Diagnostic> {
+    let mut files = Vec::new();
+    find_warning_files(dir, &mut files);
+    files.sort();
+    files
+        .iter()
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .flat_map(|output| parse_diagnostics(&output))
+        .filter(|d| d.severity == Severity::Warning)
+        .collect()
+}
+
+fn find_warning_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    for entry in std::fs::read_dir(dir).into_iter().flatten().flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            find_warning_files(&path, files);
+        } else if path.extension() == Some(std::ffi::OsStr::new("warn")) {
+            files.push(path);
+        }
+    }
+}
+
+/// `+12.5%`: how much larger `b` is than `a`.
+fn change(a: f64, b: f64) -> String {
+    if a > 0.0 {
+        format!("{:+.1}%", (b - a) * 100.0 / a)
+    } else {
+        String::new()
+    }
+}
+
+/// The comparison as table rows, followed by the warnings only one
+/// compiler gave (matched by file and line, as wording differs).
+pub fn report(sides: &[Side; 2]) -> Vec<String> {
+    let mut rows: Vec<(String, [String; 2], String)> = Vec::new();
+    if sides.iter().any(|s| s.outcome.is_err()) {
+        let result = |s: &Side| if s.outcome.is_ok() { "ok" } else { "failed" }.to_string();
+        rows.push(("result".to_string(), [result(&sides[0]), result(&sides[1])], String::new()));
+    }
+    let secs = [sides[0].duration.as_secs_f64(), sides[1].duration.as_secs_f64()];
+    rows.push((
+        "build time".to_string(),
+        [format!("{:.2}s", secs[0]), format!("{:.2}s", secs[1])],
+        change(secs[0], secs[1]),
+    ));
+    rows.push((
+        "warnings".to_string(),
+        [sides[0].warnings.len().to_string(), sides[1].warnings.len().to_string()],
+        String::new(),
+    ));
+    let mut names: Vec<&String> = Vec::new();
+    for (name, _) in sides.iter().flat_map(|s| &s.artifacts) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    for name in names {
+        let size = |s: &Side| s.artifacts.iter().find(|(n, _)| n == name).map(|(_, size)| *size);
+        let (a, b) = (size(&sides[0]), size(&sides[1]));
+        let shown = |size: Option<u64>| size.map_or_else(|| "-".to_string(), ui::human_size);
+        let diff = match (a, b) {
+            (Some(a), Some(b)) => change(a as f64, b as f64),
+            _ => String::new(),
+        };
+        rows.push((name.clone(), [shown(a), shown(b)], diff));
+    }
+
+    let name_width = rows.iter().map(|r| r.0.chars().count()).max().unwrap_or(0);
+    let width = |i: usize| {
+        rows.iter()
+            .map(|r| r.1[i].chars().count())
+            .chain(std::iter::once(sides[i].compiler.chars().count()))
+            .max()
+            .unwrap_or(0)
+    };
+    let widths = [width(0), width(1)];
+    let mut lines = vec![format!(
+        "{:nw$}  {:>w0$}  {:>w1$}",
+        "",
+        sides[0].compiler,
+        sides[1].compiler,
+        nw = name_width,
+        w0 = widths[0],
+        w1 = widths[1]
+    )];
+    for (name, values, diff) in &rows {
+        let line = format!(
+            "{:nw$}  {:>w0$}  {:>w1$}  {}",
+            name,
+            values[0],
+            values[1],
+            diff,
+            nw = name_width,
+            w0 = widths[0],
+            w1 = widths[1]
+        );
+        lines.push(line.trim_end().to_string());
+    }
+
+    for (side, other) in [(&sides[0], &sides[1]), (&sides[1], &sides[0])] {
+        let seen: HashSet<(&str, usize)> =
+            other.warnings.iter().map(|d| (d.file.as_str(), d.line)).collect();
+        let only: Vec<&Diagnostic> = side
+            .warnings
+            .iter()
+            .filter(|d| !seen.contains(&(d.file.as_str(), d.line)))
+            .collect();
+        if only.is_empty() {
+            continue;
+        }
+        lines.push(format!("Warnings only from {} ({}):", side.compiler, only.len()));
+        for d in only.iter().take(MAX_LISTED) {
+            lines.push(format!("  {}:{}: {}", d.file, d.line, d.message));
+        }
+        if only.len() > MAX_LISTED {
+            lines.push(format!("  ... and {} more", only.len() - MAX_LISTED));
+        }
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn warning(file: &str, line: usize, message: &str) -> Diagnostic {
+        Diagnostic {
+            file: file.to_string(),
+            line,
+            col: Some(1),
+            severity: Severity::Warning,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_report() {
+        let summary = || BuildSummary {
+            compiled: 2,
+            ..Default::default()
+        };
+        let sides = [
+            Side {
+                compiler: "gcc-13".to_string(),
+                duration: Duration::from_millis(2000),
+                outcome: Ok(summary()),
+                warnings: vec![warning("src/a.c", 3, "unused variable 'x'")],
+                artifacts: vec![("app".to_string(), 2048), ("libcore.a".to_string(), 512)],
+            },
+            Side {
+                compiler: "clang++-18".to_string(),
+                duration: Duration::from_millis(2500),
+                outcome: Ok(summary()),
+                warnings: vec![
+                    warning("src/a.c", 3, "unused variable 'x' [-Wunused-variable]"),
+                    warning("src/b.c", 7, "implicit conversion loses precision"),
+                ],
+                artifacts: vec![("app".to_string(), 1024)],
+            },
+        ];
+        assert_eq!(
+            report(&sides),
+            vec![
+                "             gcc-13  clang++-18",
+                "build time    2.00s       2.50s  +25.0%",
+                "warnings          1           2",
+                "app         2.0 KiB     1.0 KiB  -50.0%",
+                "libcore.a     512 B           -",
+                "Warnings only from clang++-18 (1):",
+                "  src/b.c:7: implicit conversion loses precision",
+            ]
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! `compile_commands.json`, the compilation database clangd and most
+//! editors read.
+//!
+//! Entries use the exact command lines from `build_compile_args`, so the
+//! editor sees the same flags, include dirs and standard as the build.
+//! Paths are written as drakkar uses them, relative to `directory` (the
+//! project root).
+
+use std::path::{Path, PathBuf};
+
+use crate::build::{build_compile_args, ObjectFile};
+use crate::config::{BuildProfile, CompileCommands, ProjectConfig};
+use crate::error::BuildError;
+use crate::json::Json;
+
+/// The database's file name, in the project root unless configured elsewhere.
+pub const FILE_NAME: &str = "compile_commands.json";
+
+/// Where the database goes, or None when `compile_commands = "off"`.
+pub fn output_path(config: &ProjectConfig) -> Option<PathBuf> {
+    match config.compile_commands {
+        CompileCommands::Off => None,
+        CompileCommands::Root => Some(PathBuf::from(FILE_NAME)),
+        CompileCommands::TempDir => Some(config.temp_dir.join(FILE_NAME)),
+    }
+}
+
+/// One entry per object, in the given order.
+pub fn entries(
+    objects: &[ObjectFile],
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    directory: &Path,
+) -> Vec<Json> {
+    let directory = directory.to_string_lossy().into_owned();
+    objects
+        .iter()
+        .map(|obj| {
+            let (compiler, args) = build_compile_args(obj, config, profile, extra_flags);
+            let mut arguments = vec![compiler];
+            arguments.extend(args);
+            Json::object()
+                .field("directory", directory.as_str())
+                .field("file", obj.src.path.to_string_lossy().into_owned())
+                .field("arguments", arguments)
+                .field("output", obj.obj_path.to_string_lossy().into_owned())
+        })
+        .collect()
+}
+
+/// Write the database for each `(config, objects)` unit to `path`, one
+/// entry per line. Returns the number of entries.
+pub fn write(
+    path: &Path,
+    units: &[(&ProjectConfig, &[ObjectFile])],
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> Result<usize, BuildError> {
+    let directory = std::env::current_dir()
+        .map_err(|e| BuildError::IoError(format!("Cannot read current directory: {}", e)))?;
+    let entries: Vec<Json> = units
+        .iter()
+        .flat_map(|(config, objects)| entries(objects, config, profile, extra_flags, &directory))
+        .collect();
+
+    let mut out = String::from("[\n");
+    for (i, entry) in entries.iter().enumerate() {
+        let sep = if i + 1 < entries.len() { "," } else { "" };
+        out.push_str(&format!("  {}{}\n", entry, sep));
+    }
+    out.push_str("]\n");
+
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+            })?;
+        }
+    }
+    std::fs::write(path, out)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))?;
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{object_path_for, Language, SourceFile};
+
+    #[test]
+    fn test_entry_uses_compile_args() {
+        let cfg = ProjectConfig {
+            include_dirs: vec![PathBuf::from("include")],
+            ..ProjectConfig::default()
+        };
+        let obj = object_path_for(
+            &SourceFile {
+                path: PathBuf::from("src/main.cpp"),
+                rel_path: PathBuf::from("main.cpp"),
+                language: Language::Cpp,
+            },
+            &cfg,
+        );
+        let db = Json::Array(entries(&[obj], &cfg, &BuildProfile::Debug, &[], Path::new("/proj")))
+            .to_string();
+        assert!(db.starts_with(r#"[{"directory":"/proj","file":"src/main.cpp","arguments":["g++","-c""#));
+        assert!(db.contains(r#""-Iinclude""#));
+        assert!(db.ends_with(r#""output":"target/main.o"}]"#));
+    }
+
+    #[test]
+    fn test_output_path() {
+        let mut cfg = ProjectConfig::default();
+        assert_eq!(output_path(&cfg), Some(PathBuf::from("compile_commands.json")));
+        cfg.compile_commands = CompileCommands::TempDir;
+        assert_eq!(output_path(&cfg), Some(PathBuf::from("target/compile_commands.json")));
+        cfg.compile_commands = CompileCommands::Off;
+        assert_eq!(output_path(&cfg), None);
+    }
+}
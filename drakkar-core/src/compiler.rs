@@ -0,0 +1,202 @@
+//! The compiler as the worker pool sees it.
+//!
+//! `WorkerPool` only ever asks a [`Compiler`] to turn an object's source
+//! into the object file, so the scheduler can run against something other
+//! than gcc: [`Simulated`] sleeps for each source's recorded compile time
+//! (`drakkar build --simulate`), and tests plug in compilers that sleep,
+//! fail or wait to be cancelled.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::build::{compile_preprocessed, compile_source_to_object, preprocess_source, ObjectFile};
+use crate::config::{BuildProfile, ProjectConfig};
+use crate::error::BuildError;
+use crate::platform::is_cancelled;
+use crate::worker::ActiveChildren;
+
+/// Turns sources into object files for the worker pool.
+pub trait Compiler: Send + Sync {
+    /// Compile `obj` to its object file. Ok holds the compiler's stderr,
+    /// usually warnings.
+    fn compile(&self, obj: &ObjectFile, children: &ActiveChildren) -> Result<String, BuildError>;
+
+    /// First half of a pipelined compile: preprocess `obj`, returning the
+    /// hash of the preprocessed output and the diagnostics.
+    fn preprocess(
+        &self,
+        obj: &ObjectFile,
+        children: &ActiveChildren,
+    ) -> Result<(String, String), BuildError>;
+
+    /// Second half of a pipelined compile: code generation from the output
+    /// of `preprocess`.
+    fn compile_preprocessed(
+        &self,
+        obj: &ObjectFile,
+        children: &ActiveChildren,
+    ) -> Result<String, BuildError>;
+}
+
+/// The configured toolchain, run as child processes.
+pub struct ProcessCompiler {
+    config: Arc<ProjectConfig>,
+    profile: BuildProfile,
+    extra_flags: Vec<String>,
+    verbose: bool,
+}
+
+impl ProcessCompiler {
+    /// A compiler for `config` built with `profile` and the extra `--` flags.
+    pub fn new(
+        config: Arc<ProjectConfig>,
+        profile: BuildProfile,
+        extra_flags: Vec<String>,
+        verbose: bool,
+    ) -> Self {
+        ProcessCompiler {
+            config,
+            profile,
+            extra_flags,
+            verbose,
+        }
+    }
+}
+
+impl Compiler for ProcessCompiler {
+    fn compile(&self, obj: &ObjectFile, children: &ActiveChildren) -> Result<String, BuildError> {
+        compile_source_to_object(
+            obj,
+            &self.config,
+            &self.profile,
+            &self.extra_flags,
+            self.verbose,
+            children,
+        )
+    }
+
+    fn preprocess(
+        &self,
+        obj: &ObjectFile,
+        children: &ActiveChildren,
+    ) -> Result<(String, String), BuildError> {
+        preprocess_source(
+            obj,
+            &self.config,
+            &self.profile,
+            &self.extra_flags,
+            self.verbose,
+            children,
+        )
+    }
+
+    fn compile_preprocessed(
+        &self,
+        obj: &ObjectFile,
+        children: &ActiveChildren,
+    ) -> Result<String, BuildError> {
+        compile_preprocessed(
+            obj,
+            &self.config,
+            &self.profile,
+            &self.extra_flags,
+            self.verbose,
+            children,
+        )
+    }
+}
+
+/// Takes as long as each object's predicted compile time, divided by
+/// `speedup`, and writes nothing. Preprocessing takes a quarter of it.
+pub struct Simulated {
+    times: HashMap<PathBuf, Duration>,
+    default: Duration,
+    speedup: u32,
+}
+
+impl Simulated {
+    /// Objects missing from `times` take `default`.
+    pub fn new(times: HashMap<PathBuf, Duration>, default: Duration, speedup: u32) -> Self {
+        Simulated {
+            times,
+            default,
+            speedup: speedup.max(1),
+        }
+    }
+
+    /// Simulated compile time of `obj`, before the speedup.
+    pub fn time(&self, obj: &ObjectFile) -> Duration {
+        self.times.get(&obj.obj_path).copied().unwrap_or(self.default)
+    }
+
+    fn wait(&self, time: Duration) -> Result<(), BuildError> {
+        sleep_unless_cancelled(time / self.speedup)
+    }
+}
+
+impl Compiler for Simulated {
+    fn compile(&self, obj: &ObjectFile, _: &ActiveChildren) -> Result<String, BuildError> {
+        self.wait(self.time(obj))?;
+        Ok(String::new())
+    }
+
+    fn preprocess(
+        &self,
+        obj: &ObjectFile,
+        _: &ActiveChildren,
+    ) -> Result<(String, String), BuildError> {
+        self.wait(self.time(obj) / 4)?;
+        Ok((String::new(), String::new()))
+    }
+
+    fn compile_preprocessed(
+        &self,
+        obj: &ObjectFile,
+        _: &ActiveChildren,
+    ) -> Result<String, BuildError> {
+        let time = self.time(obj);
+        self.wait(time - time / 4)?;
+        Ok(String::new())
+    }
+}
+
+/// Sleep for `time`, or until the build is cancelled.
+fn sleep_unless_cancelled(time: Duration) -> Result<(), BuildError> {
+    let until = Instant::now() + time;
+    loop {
+        if is_cancelled() {
+            return Err(BuildError::Cancelled);
+        }
+        let now = Instant::now();
+        if now >= until {
+            return Ok(());
+        }
+        std::thread::sleep((until - now).min(Duration::from_millis(10)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+
+    #[test]
+    fn test_simulated_times() {
+        let obj = |name: &str| ObjectFile {
+            src: SourceFile {
+                path: PathBuf::from(format!("src/{}.c", name)),
+                rel_path: PathBuf::from(format!("{}.c", name)),
+                language: Language::C,
+            },
+            obj_path: PathBuf::from(format!("target/{}.o", name)),
+            dep_path: PathBuf::from(format!("target/{}.d", name)),
+        };
+        let mut times = HashMap::new();
+        times.insert(PathBuf::from("target/slow.o"), Duration::from_millis(400));
+        let sim = Simulated::new(times, Duration::from_millis(100), 20);
+        assert_eq!(sim.time(&obj("slow")), Duration::from_millis(400));
+        assert_eq!(sim.time(&obj("new")), Duration::from_millis(100));
+    }
+}
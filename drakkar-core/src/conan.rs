@@ -0,0 +1,350 @@
+//! Conan packages. With a `conanfile.txt` next to the config, or package
+//! references in `conan_deps`, `conan install` runs before the build and the
+//! packages' flags are read back from the pkg-config files its
+//! `PkgConfigDeps` generator writes. Package headers become system include
+//! dirs, so they don't warn, don't trip include hygiene and stay out of
+//! depfiles.
+//!
+//! The install is skipped while the conanfile, the conan command and the
+//! build type are unchanged since the last one.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::{shell_tokenize, BuildProfile, ProjectConfig};
+use crate::error::BuildError;
+use crate::hash::Sha256;
+use crate::shell;
+use crate::ui;
+
+/// The project's own conanfile, next to the config; written from
+/// `conan_deps` when it has none.
+pub const CONANFILE: &str = "conanfile.txt";
+const GENERATOR: &str = "PkgConfigDeps";
+const STAMP: &str = "install.stamp";
+
+/// Where the install for `profile` goes: one folder per build type, so
+/// switching profiles doesn't reinstall.
+pub fn install_dir(config: &ProjectConfig, profile: &BuildProfile) -> PathBuf {
+    let name = match profile {
+        BuildProfile::Debug => "debug",
+        BuildProfile::Release => "release",
+    };
+    config.temp_dir.join("conan").join(name)
+}
+
+/// The project's conanfile contents and whether it is the user's own;
+/// None for a project without Conan packages.
+fn conanfile(config: &ProjectConfig) -> Result<Option<(String, bool)>, BuildError> {
+    let path = Path::new(CONANFILE);
+    match (path.is_file(), config.conan_deps.is_empty()) {
+        (true, false) => Err(BuildError::ConfigError(format!(
+            "both {} and conan_deps list Conan packages; keep one of them",
+            CONANFILE
+        ))),
+        (true, true) => std::fs::read_to_string(path)
+            .map(|content| Some((content, true)))
+            .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e))),
+        (false, false) => {
+            let requires = config.conan_deps.join("\n");
+            let content = format!("[requires]\n{}\n\n[generators]\n{}\n", requires, GENERATOR);
+            Ok(Some((content, false)))
+        }
+        (false, true) => Ok(None),
+    }
+}
+
+/// Run `conan install` when the packages changed, then merge their flags
+/// into `config`. Does nothing for a project without Conan packages.
+pub fn resolve(config: &mut ProjectConfig, profile: &BuildProfile) -> Result<(), BuildError> {
+    let (content, own) = match conanfile(config)? {
+        Some(found) => found,
+        None => return Ok(()),
+    };
+    let dir = install_dir(config, profile);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+    let file = if own {
+        PathBuf::from(CONANFILE)
+    } else {
+        let file = dir.join(CONANFILE);
+        write(&file, &content)?;
+        file
+    };
+
+    let build_type = match profile {
+        BuildProfile::Debug => "Debug",
+        BuildProfile::Release => "Release",
+    };
+    let mut args = config.conan_path.args.clone();
+    args.extend(["install".to_string(), file.to_string_lossy().into_owned()]);
+    args.push(format!("--output-folder={}", dir.display()));
+    args.push("--build=missing".to_string());
+    args.extend(["-s".to_string(), format!("build_type={}", build_type)]);
+    // Naming the generator twice is an error
+    if !content.contains(GENERATOR) {
+        args.extend(["-g".to_string(), GENERATOR.to_string()]);
+    }
+    let command = shell::command_line(&config.conan_path.program, &args);
+
+    let mut hasher = Sha256::new();
+    hasher.update_field(&content);
+    hasher.update_field(&command);
+    let key = hasher.finish_hex();
+    let stamp = dir.join(STAMP);
+    if std::fs::read_to_string(&stamp).ok().as_deref() != Some(key.as_str()) {
+        config.conan_path.validate("conan_path")?;
+        ui::line(&format!("  \x1b[36mInstalling\x1b[0m Conan packages ({})", build_type));
+        if config.verbose {
+            ui::line(&format!("  \x1b[2m$ {}\x1b[0m", command));
+        }
+        let output = std::process::Command::new(&config.conan_path.program)
+            .args(&args)
+            .output()
+            .map_err(|e| BuildError::IoError(format!("Failed to run conan: {}", e)))?;
+        if !output.status.success() {
+            return Err(BuildError::ConfigError(format!(
+                "conan install failed:\n{}",
+                String::from_utf8_lossy(&output.stderr).trim_end()
+            )));
+        }
+        write(&stamp, &key)?;
+    }
+    apply(config, &read_packages(&dir)?);
+    Ok(())
+}
+
+fn write(path: &Path, content: &str) -> Result<(), BuildError> {
+    std::fs::write(path, content)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))
+}
+
+/// One pkg-config file: its flags and the packages it requires.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Package {
+    /// The `.pc` file's name without the extension.
+    pub name: String,
+    /// Its `Cflags`, expanded.
+    pub cflags: Vec<String>,
+    /// Its `Libs`, expanded.
+    pub libs: Vec<String>,
+    /// The packages it `Requires`.
+    pub requires: Vec<String>,
+}
+
+/// Parse a `.pc` file: `name=value` variables, expanded as `${name}` in
+/// the `Cflags`, `Libs` and `Requires` fields that follow them.
+pub fn parse_pc(name: &str, content: &str, pc_dir: &Path) -> Result<Package, BuildError> {
+    let mut vars = BTreeMap::new();
+    vars.insert("pcfiledir".to_string(), pc_dir.to_string_lossy().into_owned());
+    let mut package = Package { name: name.to_string(), ..Package::default() };
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value, is_var) = match line.find(['=', ':']) {
+            Some(i) => (line[..i].trim(), line[i + 1..].trim(), line.as_bytes()[i] == b'='),
+            None => continue,
+        };
+        let value = expand(value, &vars);
+        if is_var {
+            vars.insert(key.to_string(), value);
+            continue;
+        }
+        let tokens = || {
+            shell_tokenize(&value).map_err(|e| {
+                BuildError::ParseError(format!("{}.pc: {}: {}", name, key, e))
+            })
+        };
+        match key {
+            "Cflags" => package.cflags = tokens()?,
+            "Libs" => package.libs = tokens()?,
+            "Requires" => package.requires = requires(&value),
+            _ => {}
+        }
+    }
+    Ok(package)
+}
+
+fn expand(value: &str, vars: &BTreeMap<String, String>) -> String {
+    let mut out = String::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("${") {
+        out.push_str(&rest[..start]);
+        match rest[start..].find('}') {
+            Some(end) => {
+                let name = &rest[start + 2..start + end];
+                out.push_str(vars.get(name).map(String::as_str).unwrap_or(""));
+                rest = &rest[start + end + 1..];
+            }
+            None => {
+                out.push_str(&rest[start..]);
+                rest = "";
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Package names of a `Requires` field, without version constraints
+/// (`fmt >= 10, zlib`).
+fn requires(value: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut constraint = false;
+    for word in value.split(|c: char| c == ',' || c.is_whitespace()).filter(|w| !w.is_empty()) {
+        if word.starts_with(['<', '>', '=', '!']) {
+            constraint = true;
+        } else if constraint {
+            constraint = false;
+        } else {
+            names.push(word.to_string());
+        }
+    }
+    names
+}
+
+/// Every package Conan generated a `.pc` file for, each one before the
+/// packages it requires, as the linker wants them.
+pub fn read_packages(dir: &Path) -> Result<Vec<Package>, BuildError> {
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", dir, e)))?;
+    let mut packages = BTreeMap::new();
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.extension().map(|e| e == "pc").unwrap_or(false) {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy().into_owned();
+            let content = std::fs::read_to_string(&path)
+                .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e)))?;
+            packages.insert(name.clone(), parse_pc(&name, &content, dir)?);
+        }
+    }
+    Ok(link_order(packages))
+}
+
+fn link_order(mut packages: BTreeMap<String, Package>) -> Vec<Package> {
+    fn visit(
+        name: &str,
+        packages: &BTreeMap<String, Package>,
+        seen: &mut HashSet<String>,
+        order: &mut Vec<String>,
+    ) {
+        if !seen.insert(name.to_string()) {
+            return;
+        }
+        if let Some(package) = packages.get(name) {
+            for dep in &package.requires {
+                visit(dep, packages, seen, order);
+            }
+            order.push(name.to_string());
+        }
+    }
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    for name in packages.keys() {
+        visit(name, &packages, &mut seen, &mut order);
+    }
+    order.iter().rev().filter_map(|name| packages.remove(name)).collect()
+}
+
+/// Merge the packages' flags: `-I` dirs become system include dirs, other
+/// compile flags go to both languages, `-l` and frameworks to link_libs
+/// and the rest of `Libs` to ld_flags.
+pub fn apply(config: &mut ProjectConfig, packages: &[Package]) {
+    fn push<T: PartialEq>(list: &mut Vec<T>, item: T) {
+        if !list.contains(&item) {
+            list.push(item);
+        }
+    }
+    let mut libs = Vec::new();
+    for package in packages {
+        let mut cflags = package.cflags.iter();
+        while let Some(flag) = cflags.next() {
+            let dir = match flag.strip_prefix("-I") {
+                Some("") => cflags.next().map(String::as_str),
+                Some(dir) => Some(dir),
+                None => None,
+            };
+            match dir {
+                Some(dir) => push(&mut config.system_include_dirs, PathBuf::from(dir)),
+                None => {
+                    push(&mut config.c_flags, flag.clone());
+                    push(&mut config.cxx_flags, flag.clone());
+                }
+            }
+        }
+        let mut flags = package.libs.iter();
+        while let Some(flag) = flags.next() {
+            if flag == "-framework" {
+                libs.push(flag.clone());
+                libs.extend(flags.next().cloned());
+            } else if flag.starts_with("-l") {
+                libs.push(flag.clone());
+            } else {
+                push(&mut config.ld_flags, flag.clone());
+            }
+        }
+    }
+    config.link_libs.extend(libs);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pc() {
+        let content = "\
+# generated by Conan
+prefix=/home/me/.conan2/p/spdlog
+includedir=${prefix}/include
+libdir=${prefix}/lib
+
+Name: spdlog
+Version: 1.12.0
+Requires: fmt >= 10.0, zlib
+Libs: -L\"${libdir}\" -lspdlog -pthread
+Cflags: -I\"${includedir}\" -DSPDLOG_FMT_EXTERNAL
+";
+        let package = parse_pc("spdlog", content, Path::new("/tmp")).unwrap();
+        assert_eq!(
+            package.cflags,
+            ["-I/home/me/.conan2/p/spdlog/include", "-DSPDLOG_FMT_EXTERNAL"]
+        );
+        assert_eq!(package.libs, ["-L/home/me/.conan2/p/spdlog/lib", "-lspdlog", "-pthread"]);
+        assert_eq!(package.requires, ["fmt", "zlib"]);
+    }
+
+    #[test]
+    fn test_link_order_and_apply() {
+        let package = |name: &str, requires: &[&str], libs: &[&str], cflags: &[&str]| {
+            let strings = |list: &[&str]| list.iter().map(|s| s.to_string()).collect();
+            let package = Package {
+                name: name.to_string(),
+                cflags: strings(cflags),
+                libs: strings(libs),
+                requires: strings(requires),
+            };
+            (name.to_string(), package)
+        };
+        let packages = BTreeMap::from([
+            package("fmt", &[], &["-L/p/fmt/lib", "-lfmt"], &["-I/p/fmt/include"]),
+            package("spdlog", &["fmt"], &["-L/p/spd/lib", "-lspdlog"], &["-I", "/p/spd/include"]),
+            package("app-extras", &["spdlog", "fmt"], &[], &["-DEXTRAS"]),
+        ]);
+        let ordered = link_order(packages);
+        let names: Vec<&str> = ordered.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["app-extras", "spdlog", "fmt"]);
+
+        let mut config = ProjectConfig { link_libs: vec!["m".to_string()], ..Default::default() };
+        apply(&mut config, &ordered);
+        assert_eq!(config.link_libs, ["m", "-lspdlog", "-lfmt"]);
+        assert_eq!(config.ld_flags, ["-L/p/spd/lib", "-L/p/fmt/lib"]);
+        assert_eq!(
+            config.system_include_dirs,
+            [PathBuf::from("/p/spd/include"), PathBuf::from("/p/fmt/include")]
+        );
+        assert_eq!(config.cxx_flags, ["-DEXTRAS"]);
+    }
+}
@@ -0,0 +1,2152 @@
+//! The project configuration: `config.txt` or `drakkar.toml` (with
+//! `config.local.txt` on top) read into a [`ProjectConfig`], and the
+//! build profiles and `[target.<name>]` sections it holds.
+
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use crate::error::BuildError;
+use crate::migrate::CURRENT_CONFIG_VERSION;
+use crate::parse::{self, Problem, Span};
+use crate::remote::CacheLocation;
+use crate::toml;
+use crate::toolchain::Toolchain;
+
+/// Which set of [`ProfileSettings`] and flags a build uses.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BuildProfile {
+    /// `drakkar build`: debug info, no optimization.
+    Debug,
+    /// `drakkar build --release`: optimized.
+    Release,
+}
+
+/// How symbols are handled when linking a profile.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StripMode {
+    /// Keep symbols in the binary.
+    None,
+    /// Strip symbols at link time (`-s`).
+    Full,
+    /// Move debug info into `<exe>.debug` and link it via `.gnu_debuglink`,
+    /// so crash dumps can still be symbolicated.
+    Separate,
+}
+
+impl StripMode {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "none" | "false" => Ok(StripMode::None),
+            "full" | "true" => Ok(StripMode::Full),
+            "separate" => Ok(StripMode::Separate),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: strip must be none, full or separate, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// Link-time optimization for a profile (`lto`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Lto {
+    /// No LTO.
+    Off,
+    /// Partitioned, parallel LTO: ThinLTO on clang-based toolchains,
+    /// gcc's default WHOPR mode, `/LTCG:INCREMENTAL` on MSVC.
+    Thin,
+    /// Whole-program optimization in one unit; slower to link.
+    Full,
+}
+
+impl Lto {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "off" | "false" => Ok(Lto::Off),
+            "thin" => Ok(Lto::Thin),
+            "full" | "true" => Ok(Lto::Full),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: lto must be off, thin or full, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// A tool to invoke: the program plus any leading arguments, so wrappers
+/// such as `"ccache g++"` or `"zig cc"` work wherever a compiler path does.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolCommand {
+    /// The program, looked up on `PATH` unless it is a path.
+    pub program: String,
+    /// Arguments passed before drakkar's own.
+    pub args: Vec<String>,
+}
+
+impl ToolCommand {
+    /// `program` without leading arguments.
+    pub fn new(program: &str) -> Self {
+        ToolCommand {
+            program: program.to_string(),
+            args: vec![],
+        }
+    }
+
+    fn from_tokens(key: &str, mut tokens: Vec<String>, line_no: usize) -> Result<Self, BuildError> {
+        if tokens.is_empty() {
+            return Err(BuildError::ParseError(format!(
+                "Line {}: {} must not be empty",
+                line_no, key
+            )));
+        }
+        let program = tokens.remove(0);
+        Ok(ToolCommand {
+            program,
+            args: tokens,
+        })
+    }
+
+    /// Check that the program exists and is executable, so a typo fails
+    /// before any compiler is spawned. `key` names the config setting.
+    pub fn validate(&self, key: &str) -> Result<(), BuildError> {
+        match crate::platform::find_executable(&self.program) {
+            Some(_) => Ok(()),
+            None => Err(BuildError::ConfigError(format!(
+                "{} = \"{}\": '{}' is not an executable file or not on PATH",
+                key, self, self.program
+            ))),
+        }
+    }
+
+    /// The C and C++ commands of the family `command` belongs to, whichever
+    /// of the two it names: `gcc-13` and `g++-13` both give
+    /// (`gcc-13`, `g++-13`), `ccache clang++-18` gives (`ccache clang-18`,
+    /// `ccache clang++-18`). Unknown compilers are used for both.
+    pub fn compiler_pair(command: &str) -> Result<(Self, Self), BuildError> {
+        let mut tokens = shell_tokenize(command)?;
+        if tokens.is_empty() {
+            return Err(BuildError::ParseError("compiler command must not be empty".to_string()));
+        }
+        let given = ToolCommand {
+            program: tokens.remove(0),
+            args: tokens,
+        };
+        let (c, cxx) = match given.args.last() {
+            // Wrappers name the compiler last: "ccache g++", "zig c++"
+            Some(last) => {
+                let (c, cxx) = family_names(last);
+                let with_last = |name: String| {
+                    let mut cmd = given.clone();
+                    *cmd.args.last_mut().unwrap() = name;
+                    cmd
+                };
+                (with_last(c), with_last(cxx))
+            }
+            None => {
+                let (c, cxx) = family_names(&given.program);
+                (ToolCommand::new(&c), ToolCommand::new(&cxx))
+            }
+        };
+        Ok((c, cxx))
+    }
+}
+
+/// C and C++ spellings of compiler names. Each list is searched in this
+/// order, as "clang++" also contains "g++" and "gcc" contains "cc".
+const COMPILER_FAMILIES: &[(&str, &str)] = &[
+    ("armclang", "armclang"),
+    ("clang", "clang++"),
+    ("gcc", "g++"),
+    ("icx", "icpx"),
+    ("icc", "icpc"),
+    ("nvc", "nvc++"),
+    ("cc", "c++"),
+];
+
+/// `(c, cxx)` for one compiler path, keeping its directory, target prefix
+/// and version suffix.
+fn family_names(path: &str) -> (String, String) {
+    let name_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+    let (dir, name) = path.split_at(name_start);
+    let swap = |from: &str, to: &str| format!("{}{}", dir, name.replacen(from, to, 1));
+    for (c, cxx) in COMPILER_FAMILIES {
+        if name.contains(cxx) {
+            return (swap(cxx, c), path.to_string());
+        }
+    }
+    for (c, cxx) in COMPILER_FAMILIES {
+        if name.contains(c) {
+            return (path.to_string(), swap(c, cxx));
+        }
+    }
+    (path.to_string(), path.to_string())
+}
+
+impl std::fmt::Display for ToolCommand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.program)?;
+        for arg in &self.args {
+            write!(f, " {}", arg)?;
+        }
+        Ok(())
+    }
+}
+
+/// Editor backups and merge leftovers skipped during source collection.
+pub const DEFAULT_IGNORE_PATTERNS: &[&str] = &["*~", "#*#", ".#*", "*.swp", "*.orig", "*.rej"];
+
+/// What to do when a TU includes headers from outside the project roots.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IncludeCheck {
+    /// No check.
+    Off,
+    /// Report each header, but build.
+    Warn,
+    /// Fail the build.
+    Error,
+}
+
+impl IncludeCheck {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "off" | "false" => Ok(IncludeCheck::Off),
+            "warn" => Ok(IncludeCheck::Warn),
+            "error" => Ok(IncludeCheck::Error),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: include_check must be off, warn or error, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// Where `drakkar build` writes `compile_commands.json`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompileCommands {
+    /// Not written.
+    Off,
+    /// Next to config.txt, where clangd looks by default.
+    Root,
+    /// In temp_dir, to keep the project root clean.
+    TempDir,
+}
+
+impl CompileCommands {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "off" | "false" => Ok(CompileCommands::Off),
+            "root" | "true" => Ok(CompileCommands::Root),
+            "temp_dir" => Ok(CompileCommands::TempDir),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: compile_commands must be root, temp_dir or off, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// Where compile commands run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Executor {
+    /// On this machine, as child processes.
+    Local,
+    /// One `srun` job step per source.
+    Slurm,
+    /// One interactive `bsub -I` job per source.
+    Lsf,
+}
+
+impl Executor {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "local" => Ok(Executor::Local),
+            "slurm" => Ok(Executor::Slurm),
+            "lsf" => Ok(Executor::Lsf),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: executor must be local, slurm or lsf, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// What compile commands may see of the filesystem.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sandbox {
+    /// Compiles see the whole filesystem.
+    Off,
+    /// Under bubblewrap, with only the system directories, the declared
+    /// source, vendor and include dirs and temp_dir mounted (Linux only).
+    Bwrap,
+}
+
+impl Sandbox {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "off" => Ok(Sandbox::Off),
+            "bwrap" => Ok(Sandbox::Bwrap),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: sandbox must be off or bwrap, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// How `drakkar build` decides a source is stale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RebuildPolicy {
+    /// The source or a header is newer than the object.
+    Mtime,
+    /// The source or a header differs from the content it was last
+    /// compiled from; for caches restored with fresh timestamps.
+    Hash,
+}
+
+impl RebuildPolicy {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "mtime" => Ok(RebuildPolicy::Mtime),
+            "hash" => Ok(RebuildPolicy::Hash),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: rebuild_policy must be mtime or hash, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+}
+
+/// What a `[target.<name>]` section builds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TargetType {
+    /// An executable in output_dir.
+    Executable,
+    /// `lib<name>.a` in output_dir, linked into the targets that list it in `deps`.
+    StaticLib,
+    /// A position-independent module for `dlopen`, without a SONAME.
+    Plugin,
+}
+
+impl TargetType {
+    fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "executable" => Ok(TargetType::Executable),
+            "static_lib" => Ok(TargetType::StaticLib),
+            "plugin" => Ok(TargetType::Plugin),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: target_type must be executable, static_lib or plugin, got '{}'",
+                line_no, s
+            ))),
+        }
+    }
+
+    /// The value `target_type` takes for it.
+    pub fn name(&self) -> &'static str {
+        match self {
+            TargetType::Executable => "executable",
+            TargetType::StaticLib => "static_lib",
+            TargetType::Plugin => "plugin",
+        }
+    }
+}
+
+/// One `[target.<name>]` section. Its flags, include dirs and libraries
+/// are added to the project-wide ones.
+#[derive(Debug, Clone)]
+pub struct TargetConfig {
+    /// The `<name>` of the section.
+    pub name: String,
+    /// What it builds.
+    pub target_type: TargetType,
+    /// Its source roots.
+    pub source_dirs: Vec<PathBuf>,
+    /// Artifact name (without `lib`/`.a`); defaults to the target name.
+    pub output_name: Option<String>,
+    /// Added to the project's `c_flags`.
+    pub c_flags: Vec<String>,
+    /// Added to the project's `cxx_flags`.
+    pub cxx_flags: Vec<String>,
+    /// Added to the project's `ld_flags`.
+    pub ld_flags: Vec<String>,
+    /// Added to the project's `include_dirs`.
+    pub include_dirs: Vec<PathBuf>,
+    /// Added to the project's `link_libs`.
+    pub link_libs: Vec<String>,
+    /// Targets built first. Static libraries are linked in and their
+    /// include dirs and link_libs carry over; plugins are only built.
+    pub deps: Vec<String>,
+    /// Symbols a plugin exports; everything else stays hidden.
+    pub exports: Vec<String>,
+    /// Bundled third-party source roots compiled into this target.
+    pub vendor_dirs: Vec<PathBuf>,
+}
+
+impl TargetConfig {
+    fn new(name: &str) -> Self {
+        TargetConfig {
+            name: name.to_string(),
+            target_type: TargetType::Executable,
+            source_dirs: vec![],
+            output_name: None,
+            c_flags: vec![],
+            cxx_flags: vec![],
+            ld_flags: vec![],
+            include_dirs: vec![],
+            link_libs: vec![],
+            deps: vec![],
+            exports: vec![],
+            vendor_dirs: vec![],
+        }
+    }
+
+    /// `output_name`, or the target name without one.
+    pub fn output_name(&self) -> &str {
+        self.output_name.as_deref().unwrap_or(&self.name)
+    }
+}
+
+/// One `[generator.<name>]` section: a command that turns each input file
+/// into sources and headers before the build.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratorRule {
+    /// The `<name>` of the section.
+    pub name: String,
+    /// Input patterns such as `proto/*.proto`; wildcards only in the
+    /// file name.
+    pub inputs: Vec<String>,
+    /// Run once per stale input, with `{input}`, `{stem}` and `{out_dir}`
+    /// replaced.
+    pub command: Vec<String>,
+    /// What the command writes into `{out_dir}` for one input, with
+    /// `{stem}` replaced (`{stem}.pb.cc {stem}.pb.h`).
+    pub outputs: Vec<String>,
+    /// With `[target.*]` sections, the one target that compiles the output.
+    pub target: Option<String>,
+}
+
+/// A `[deps]` entry with a git URL and tag, cloned into `deps_dir`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GitDep {
+    /// The key in `[deps]`, and its directory under `deps_dir`.
+    pub name: String,
+    /// The repository to clone.
+    pub url: String,
+    /// The tag to check out, pinned to a commit in `drakkar.lock`.
+    pub tag: String,
+}
+
+/// Settings that differ between debug and release builds.
+/// Set in config.txt with a profile prefix, e.g. `release.ld_flags`.
+#[derive(Debug, Clone)]
+pub struct ProfileSettings {
+    /// Linker flags for this profile only (`release.ld_flags`).
+    pub ld_flags: Vec<String>,
+    /// What happens to symbols (`strip`).
+    pub strip: StripMode,
+    /// Link-time optimization (`lto`).
+    pub lto: Lto,
+    /// Pack linked executables with `upx_path` (`compress_binary`).
+    pub compress: bool,
+    /// Commands run on each linked binary, in order (`post_link`).
+    pub post_link: Vec<ToolCommand>,
+}
+
+/// Everything a project's config says, with defaults for what it leaves out.
+#[derive(Debug, Clone)]
+pub struct ProjectConfig {
+    /// Name of the main executable.
+    pub app_name: String,
+    /// Source roots; `source_dir` in config.txt takes a list.
+    pub source_dirs: Vec<PathBuf>,
+    /// Where executables and libraries go.
+    pub output_dir: PathBuf,
+    /// Where objects, depfiles and the build state go.
+    pub temp_dir: PathBuf,
+    /// Evict least recently used objects after a build to keep temp_dir
+    /// under this many megabytes (`temp_max_size_mb`).
+    pub temp_max_size_mb: Option<u64>,
+    /// Content-keyed object store shared by several checkouts (worktrees)
+    /// of the project.
+    pub shared_temp_dir: Option<PathBuf>,
+    /// Keep compiled objects in a per-user cache (`object_cache`).
+    pub object_cache: bool,
+    /// Where the object cache lives; default `~/.cache/drakkar/objects`.
+    pub object_cache_dir: Option<PathBuf>,
+    /// Team-wide object cache behind the local one (`remote_cache`).
+    pub remote_cache: Option<CacheLocation>,
+    /// Upload compiled objects to `remote_cache`.
+    pub remote_cache_push: bool,
+    /// Check for free disk space before compiling (`disk_check`).
+    pub disk_check: bool,
+    /// Flags for compiling C sources.
+    pub c_flags: Vec<String>,
+    /// Flags for compiling C++ sources.
+    pub cxx_flags: Vec<String>,
+    /// Flags for every link, before the profile's.
+    pub ld_flags: Vec<String>,
+    /// The project's own include roots (`-I`).
+    pub include_dirs: Vec<PathBuf>,
+    /// Third-party headers, passed as system include dirs: no warnings
+    /// and not in depfiles. Filled in from Conan packages.
+    pub system_include_dirs: Vec<PathBuf>,
+    /// Libraries to link (`m` or `-lm`), looked up in `[libs]` first.
+    pub link_libs: Vec<String>,
+    /// Prebuilt objects and archives linked into every executable.
+    pub extra_objects: Vec<PathBuf>,
+    /// `[libs]` table: (logical name, OS) → libraries to link instead.
+    pub lib_map: HashMap<(String, String), Vec<String>>,
+    /// System libraries (`z curl`, resolved like link_libs) and headers
+    /// (`zlib.h`) probed for before the build.
+    pub requires_libs: Vec<String>,
+    /// See `requires_libs`.
+    pub requires_headers: Vec<String>,
+    /// `-std=` for C sources (`c11`); the compiler's default when None.
+    pub c_standard: Option<String>,
+    /// `-std=` for C++ sources (`c++20`); the compiler's default when None.
+    pub cxx_standard: Option<String>,
+    /// Bundled third-party source roots, compiled without warnings and
+    /// with the `vendor_*` flags instead of the project's.
+    pub vendor_dirs: Vec<PathBuf>,
+    /// Flags for vendored C sources, instead of `c_flags`.
+    pub vendor_c_flags: Vec<String>,
+    /// Flags for vendored C++ sources, instead of `cxx_flags`.
+    pub vendor_cxx_flags: Vec<String>,
+    /// Standards for vendored sources; the project's when None.
+    pub vendor_c_standard: Option<String>,
+    /// See `vendor_c_standard`.
+    pub vendor_cxx_standard: Option<String>,
+    /// Compiles run at once; the CPU count by default.
+    pub parallel_jobs: usize,
+    /// Skip sources whose objects are up to date; off rebuilds everything.
+    pub incremental: bool,
+    /// Keep the `.i`/`.ii` files of pipelined builds; unset keeps them during
+    /// builds but lets `drakkar clean` remove them. See [`Self::keeps_temp`].
+    pub preserve_temp: Option<bool>,
+    /// Start each compile in its own process group, so cancelling kills what it
+    /// spawned too.
+    pub use_process_groups: bool,
+    /// The compiler family, picking default commands and flag spellings.
+    pub toolchain: Toolchain,
+    /// Cross-compilation target (`target`): passed to toolchains that take
+    /// one (zig, armclang), or selects prefixed gcc binaries.
+    pub target_triple: Option<String>,
+    /// Add the toolchain's OpenMP flags (`openmp`).
+    pub openmp: bool,
+    /// Add the toolchain's thread flags, `-pthread` on gcc (`threads`).
+    pub threads: bool,
+    /// Explicit compiler commands; None means the toolchain default.
+    pub gcc_path: Option<ToolCommand>,
+    /// See `gcc_path`.
+    pub gpp_path: Option<ToolCommand>,
+    /// `objcopy` for `strip = "separate"`.
+    pub objcopy_path: String,
+    /// Executable packer for `compress_binary`.
+    pub upx_path: ToolCommand,
+    /// Conan client for `conan_deps` and `conanfile.txt`.
+    pub conan_path: ToolCommand,
+    /// Signing tool; None means codesign / signtool for the target OS.
+    pub sign_command: Option<ToolCommand>,
+    /// Certificate to sign release binaries with; None disables signing.
+    pub sign_identity: Option<String>,
+    /// Archiver for `static_lib` targets.
+    pub ar_path: String,
+    /// Write a linker map and report which library satisfied each symbol.
+    pub link_map: bool,
+    /// Where plugin targets go, relative to output_dir.
+    pub plugins_dir: PathBuf,
+    /// Sources of `drakkar test`, one test program per file.
+    pub tests_dir: PathBuf,
+    /// Where `drakkar promote` keeps its channels, one directory each.
+    pub channels_dir: PathBuf,
+    /// Where `drakkar install` copies to without `--prefix`.
+    pub install_prefix: Option<PathBuf>,
+    /// Export allow-list of the plugin being linked (set per target).
+    pub exports: Vec<String>,
+    /// Link every entry point (`bin/*.cpp`, `*_main.cpp`) into an executable of
+    /// its own.
+    pub bin_discovery: bool,
+    /// Executable `drakkar run` launches when none is named.
+    pub default_target: Option<String>,
+    /// Command `drakkar run` starts the executable under, such as an
+    /// emulator for cross-compiled binaries; the executable path is appended.
+    pub run_wrapper: Option<ToolCommand>,
+    /// Program started with each build that reads its events as JSON
+    /// lines on stdin (`on_event = "./notify.sh"`).
+    pub on_event: Option<ToolCommand>,
+    /// Config files pulled in with `include`, for watch mode.
+    pub included_configs: Vec<PathBuf>,
+    /// Whether headers from outside the project roots are reported.
+    pub include_check: IncludeCheck,
+    /// Where `compile_commands.json` is written.
+    pub compile_commands: CompileCommands,
+    /// Split compiles into a preprocess and a codegen step (`pipeline`).
+    pub pipeline: bool,
+    /// How staleness is decided.
+    pub rebuild_policy: RebuildPolicy,
+    /// Environment variables whose values are part of every compile and
+    /// link command's hash, so changing one rebuilds (`tracked_env`).
+    pub tracked_env: Vec<String>,
+    /// Where compile commands run.
+    pub executor: Executor,
+    /// Extra submission options (partition, account, time limit) passed to
+    /// `srun` or `bsub` before the compile command.
+    pub executor_args: Vec<String>,
+    /// What compile commands may see of the filesystem.
+    pub sandbox: Sandbox,
+    /// Extra read-only paths for sandboxed compiles: toolchains and SDKs
+    /// outside the system directories.
+    pub sandbox_paths: Vec<PathBuf>,
+    /// File and directory names never collected as sources; see
+    /// [`DEFAULT_IGNORE_PATTERNS`].
+    pub ignore_patterns: Vec<String>,
+    /// `sources`: path patterns (`src/**/*.cpp`) a file under source_dir
+    /// must match to be compiled; empty compiles every source.
+    pub source_patterns: Vec<String>,
+    /// `exclude`: path patterns for files and directories under source_dir
+    /// that are never compiled.
+    pub exclude_patterns: Vec<String>,
+    /// Compile `.C` sources as C++ rather than C.
+    pub uppercase_c_is_cpp: bool,
+    /// The `debug.*` settings.
+    pub debug: ProfileSettings,
+    /// The `release.*` settings.
+    pub release: ProfileSettings,
+    /// `[target.<name>]` sections in config order; empty for a
+    /// single-artifact project.
+    pub targets: Vec<TargetConfig>,
+    /// `[generator.<name>]` sections, run in config order.
+    pub generators: Vec<GeneratorRule>,
+    /// Member project dirs of a workspace root (`[workspace] members`).
+    pub workspace_members: Vec<PathBuf>,
+    /// Other drakkar projects this one depends on, relative to its dir.
+    pub deps: Vec<PathBuf>,
+    /// `[deps]` entries fetched with git, in config order.
+    pub git_deps: Vec<GitDep>,
+    /// Where git deps are checked out, one directory each.
+    pub deps_dir: PathBuf,
+    /// Conan package references (`fmt/10.2.1`) installed before the build,
+    /// instead of a `conanfile.txt`.
+    pub conan_deps: Vec<String>,
+    /// Print each command as it runs (`--verbose`).
+    pub verbose: bool,
+    /// Keep compiling after a failure and report every error
+    /// (`--aggregate-errors`).
+    pub aggregate_errors: bool,
+    /// Re-print cached warnings of up-to-date sources (`--replay-warnings`).
+    pub replay_warnings: bool,
+    /// Print and annotate each compile, link and archive command
+    /// (`--explain-commands`).
+    pub explain_commands: bool,
+    /// Bundle a repro when the compiler crashes (`--save-ice-repro`).
+    pub save_ice_repro: bool,
+    /// Show build progress in the terminal title / taskbar.
+    pub terminal_title: bool,
+    /// Replace the scrolling "Compiling" lines with a live board of what
+    /// each worker is compiling, when stdout is a terminal.
+    pub status_board: bool,
+    /// Replace them with a one-line progress bar instead (the default);
+    /// `status_board` wins when both are on.
+    pub progress_bar: bool,
+}
+
+impl Default for ProjectConfig {
+    fn default() -> Self {
+        let parallelism = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4);
+        ProjectConfig {
+            app_name: "program".to_string(),
+            source_dirs: vec![PathBuf::from("src")],
+            output_dir: PathBuf::from("out"),
+            temp_dir: PathBuf::from("target"),
+            temp_max_size_mb: None,
+            shared_temp_dir: None,
+            object_cache: false,
+            object_cache_dir: None,
+            remote_cache: None,
+            remote_cache_push: false,
+            disk_check: true,
+            c_flags: vec![],
+            cxx_flags: vec![],
+            ld_flags: vec![],
+            include_dirs: vec![],
+            system_include_dirs: vec![],
+            link_libs: vec![],
+            extra_objects: vec![],
+            lib_map: HashMap::new(),
+            requires_libs: vec![],
+            requires_headers: vec![],
+            c_standard: None,
+            cxx_standard: None,
+            vendor_dirs: vec![],
+            vendor_c_flags: vec![],
+            vendor_cxx_flags: vec![],
+            vendor_c_standard: None,
+            vendor_cxx_standard: None,
+            parallel_jobs: parallelism,
+            incremental: true,
+            preserve_temp: None,
+            use_process_groups: false,
+            toolchain: Toolchain::Gcc,
+            target_triple: None,
+            openmp: false,
+            threads: false,
+            gcc_path: None,
+            gpp_path: None,
+            objcopy_path: "objcopy".to_string(),
+            upx_path: ToolCommand::new("upx"),
+            conan_path: ToolCommand::new("conan"),
+            sign_command: None,
+            sign_identity: None,
+            ar_path: "ar".to_string(),
+            link_map: false,
+            plugins_dir: PathBuf::new(),
+            tests_dir: PathBuf::from("tests/"),
+            channels_dir: PathBuf::from("channels"),
+            install_prefix: None,
+            exports: vec![],
+            bin_discovery: false,
+            default_target: None,
+            run_wrapper: None,
+            on_event: None,
+            included_configs: vec![],
+            include_check: IncludeCheck::Off,
+            compile_commands: CompileCommands::Root,
+            pipeline: false,
+            rebuild_policy: RebuildPolicy::Mtime,
+            tracked_env: vec![],
+            executor: Executor::Local,
+            executor_args: vec![],
+            sandbox: Sandbox::Off,
+            sandbox_paths: vec![],
+            ignore_patterns: DEFAULT_IGNORE_PATTERNS.iter().map(|p| p.to_string()).collect(),
+            source_patterns: vec![],
+            exclude_patterns: vec![],
+            uppercase_c_is_cpp: false,
+            debug: ProfileSettings {
+                ld_flags: vec![],
+                strip: StripMode::None,
+                lto: Lto::Off,
+                compress: false,
+                post_link: vec![],
+            },
+            release: ProfileSettings {
+                ld_flags: vec![],
+                strip: StripMode::Full,
+                lto: Lto::Off,
+                compress: false,
+                post_link: vec![],
+            },
+            targets: vec![],
+            generators: vec![],
+            workspace_members: vec![],
+            deps: vec![],
+            git_deps: vec![],
+            deps_dir: PathBuf::from("deps"),
+            conan_deps: vec![],
+            verbose: false,
+            aggregate_errors: false,
+            replay_warnings: false,
+            explain_commands: false,
+            save_ice_repro: false,
+            terminal_title: false,
+            status_board: false,
+            progress_bar: true,
+        }
+    }
+}
+
+impl ProjectConfig {
+    /// Command used to compile C sources.
+    pub fn c_compiler(&self) -> ToolCommand {
+        self.gcc_path
+            .clone()
+            .unwrap_or_else(|| self.toolchain.c_compiler())
+    }
+
+    /// Command used to compile C++ sources and to link.
+    pub fn cxx_compiler(&self) -> ToolCommand {
+        self.gpp_path
+            .clone()
+            .unwrap_or_else(|| self.toolchain.cxx_compiler())
+    }
+
+    /// Compile with `command`'s family instead of the configured compilers,
+    /// for `--compiler` and `--compare`.
+    pub fn use_compiler(&mut self, command: &str) -> Result<(), BuildError> {
+        let (c, cxx) = ToolCommand::compiler_pair(command)?;
+        self.gcc_path = Some(c);
+        self.gpp_path = Some(cxx);
+        Ok(())
+    }
+
+    /// Batch-system command each compile is submitted through, None for
+    /// local compiles. Both wait for the job and forward its output and
+    /// exit status, so the compile looks local to the caller.
+    pub fn executor_command(&self) -> Option<ToolCommand> {
+        let (program, base): (&str, &[&str]) = match self.executor {
+            Executor::Local => return None,
+            Executor::Slurm => ("srun", &["--quiet", "--ntasks=1"]),
+            Executor::Lsf => ("bsub", &["-I"]),
+        };
+        let mut args: Vec<String> = base.iter().map(|a| a.to_string()).collect();
+        args.extend(self.executor_args.iter().cloned());
+        Some(ToolCommand {
+            program: program.to_string(),
+            args,
+        })
+    }
+
+    /// Every directory the build reads sources or headers from: the
+    /// source, vendor, include and system include dirs.
+    pub fn input_dirs(&self) -> impl Iterator<Item = &PathBuf> {
+        self.source_dirs
+            .iter()
+            .chain(&self.vendor_dirs)
+            .chain(&self.include_dirs)
+            .chain(&self.system_include_dirs)
+    }
+
+    /// Whether `path` is a source under one of the `vendor_dirs`.
+    pub fn is_vendored(&self, path: &Path) -> bool {
+        self.vendor_dirs.iter().any(|dir| path.starts_with(dir))
+    }
+
+    /// Whether binaries built for the target can run here directly, without
+    /// `run_wrapper`: no target is set, or it names the host's CPU and OS.
+    pub fn runs_on_host(&self) -> bool {
+        let triple = match &self.target_triple {
+            Some(triple) => triple,
+            None => return true,
+        };
+        let arch = match triple.split('-').next().unwrap_or("") {
+            "arm64" => "aarch64",
+            "amd64" => "x86_64",
+            "i386" | "i486" | "i586" | "i686" => "x86",
+            arch => arch,
+        };
+        arch == std::env::consts::ARCH && self.target_os() == std::env::consts::OS
+    }
+
+    /// Toolchain flags added to every compile and link command.
+    pub fn target_flags(&self) -> Vec<String> {
+        match &self.target_triple {
+            Some(triple) => self.toolchain.target_flags(triple),
+            None => vec![],
+        }
+    }
+
+    /// OS being built for: from `target_triple` when set, else the host.
+    pub fn target_os(&self) -> &str {
+        match &self.target_triple {
+            Some(triple) => {
+                const KNOWN: &[(&str, &str)] = &[
+                    ("windows", "windows"),
+                    ("mingw", "windows"),
+                    ("macos", "macos"),
+                    ("darwin", "macos"),
+                    ("linux", "linux"),
+                    ("freebsd", "freebsd"),
+                ];
+                KNOWN
+                    .iter()
+                    .find(|(needle, _)| triple.contains(needle))
+                    .map(|(_, os)| *os)
+                    .unwrap_or("none")
+            }
+            None => std::env::consts::OS,
+        }
+    }
+
+    /// Whether builds leave their `.i`/`.ii` files behind: unless
+    /// `preserve_temp = "false"`.
+    pub fn keeps_temp(&self) -> bool {
+        self.preserve_temp != Some(false)
+    }
+
+    /// `link_libs` with logical names resolved for [`Self::target_os`].
+    ///
+    /// Entries that look like flags (`-lfoo`) or files (`libfoo.a`) pass
+    /// through. Bare names are looked up in the `[libs]` table; without an
+    /// entry for this OS they become `-l<name>`. Mapped names may expand to
+    /// several libraries or to nothing.
+    pub fn resolved_link_libs(&self) -> Vec<String> {
+        let os = self.target_os();
+        let mut out: Vec<String> = Vec::new();
+        for lib in &self.link_libs {
+            // `-framework Cocoa`: the operand is not a library name
+            if !is_logical_lib(lib) || out.last().map(String::as_str) == Some("-framework") {
+                out.push(lib.clone());
+                continue;
+            }
+            match self.lib_map.get(&(lib.clone(), os.to_string())) {
+                Some(mapped) => out.extend(mapped.iter().map(|m| {
+                    if is_logical_lib(m) {
+                        format!("-l{}", m)
+                    } else {
+                        m.clone()
+                    }
+                })),
+                None => out.push(format!("-l{}", lib)),
+            }
+        }
+        out
+    }
+
+    /// Flags for the `openmp` / `threads` switches, as (compile, link).
+    /// Both sides come from one place so they can't drift apart.
+    pub fn feature_flags(&self) -> (Vec<String>, Vec<String>) {
+        let mut compile = Vec::new();
+        let mut link = Vec::new();
+        if self.openmp {
+            if let Some((c, l)) = self.toolchain.openmp_flags() {
+                compile.extend(c);
+                link.extend(l);
+            }
+        }
+        if self.threads {
+            let (c, l) = self.toolchain.thread_flags();
+            compile.extend(c);
+            link.extend(l);
+        }
+        (compile, link)
+    }
+
+    /// Flags for the profile's `lto` setting, as (compile, link), so the
+    /// objects and the link step always agree on it.
+    pub fn lto_flags(&self, profile: &BuildProfile) -> (Vec<String>, Vec<String>) {
+        self.toolchain
+            .lto_flags(self.profile(profile).lto, self.parallel_jobs)
+            .unwrap_or_default()
+    }
+
+    /// The settings of `profile`.
+    pub fn profile(&self, profile: &BuildProfile) -> &ProfileSettings {
+        match profile {
+            BuildProfile::Debug => &self.debug,
+            BuildProfile::Release => &self.release,
+        }
+    }
+}
+
+/// Shell-like tokenizer: splits a string respecting single/double quotes and backslash escaping.
+/// Commas within tokens are preserved. See `parse::tokenize`.
+pub fn shell_tokenize(input: &str) -> Result<Vec<String>, BuildError> {
+    parse::tokenize(input).map_err(|e| BuildError::ParseError(e.message.to_string()))
+}
+
+/// Parse the outer quoted value string from config line.
+/// The value_str is the full RHS after `=`, e.g. `"some value"` or `"flag1 flag2"`.
+/// We strip the outer quotes then tokenize the interior.
+pub fn parse_value_str(value_str: &str, line_no: usize) -> Result<Vec<String>, BuildError> {
+    value_tokens(value_str)
+        .map_err(|e| BuildError::ParseError(format!("Line {}: {}", line_no, e.message)))
+}
+
+/// `parse_value_str` with the error's offsets counted from the start of
+/// `value_str`.
+fn value_tokens(value_str: &str) -> Result<Vec<String>, parse::TokenError> {
+    let leading = value_str.chars().count() - value_str.trim_start().chars().count();
+    let v = value_str.trim();
+    // Strip optional leading/trailing outer quotes
+    let quoted = v.len() >= 2
+        && ((v.starts_with('"') && v.ends_with('"')) || (v.starts_with('\'') && v.ends_with('\'')));
+    let (inner, skipped) = if quoted {
+        (&v[1..v.len() - 1], leading + 1)
+    } else {
+        // No outer quotes: tokenize as-is (bare value)
+        (v, leading)
+    };
+    parse::tokenize(inner).map_err(|e| parse::TokenError {
+        offset: e.offset + skipped,
+        ..e
+    })
+}
+
+fn parse_bool(s: &str, line_no: usize) -> Result<bool, BuildError> {
+    match s.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err(BuildError::ParseError(format!(
+            "Line {}: expected bool (true/false), got '{}'",
+            line_no, s
+        ))),
+    }
+}
+
+fn parse_usize(s: &str, line_no: usize) -> Result<usize, BuildError> {
+    s.parse::<usize>().map_err(|_| {
+        BuildError::ParseError(format!(
+            "Line {}: expected integer, got '{}'",
+            line_no, s
+        ))
+    })
+}
+
+/// Project config file names, in order of preference.
+pub const TOML_CONFIG: &str = "drakkar.toml";
+/// The plain `key = "value"` config.
+pub const TEXT_CONFIG: &str = "config.txt";
+
+/// The config file of the project in `dir`: `drakkar.toml` when present,
+/// otherwise `config.txt` (which may not exist either).
+pub fn find_config(dir: &Path) -> PathBuf {
+    let toml = dir.join(TOML_CONFIG);
+    if toml.is_file() {
+        toml
+    } else {
+        dir.join(TEXT_CONFIG)
+    }
+}
+
+/// Read and parse config.txt or drakkar.toml, returning a ProjectConfig.
+///
+/// `include = "common.cfg"` applies another config file at that point, so
+/// keys after it override the shared ones; include cycles are an error.
+/// If a sibling `config.local.txt` (`drakkar.local.toml`) exists it is
+/// applied on top, so
+/// developers can override compiler paths or job counts without touching
+/// the committed config.
+pub fn read_config(path: &Path) -> Result<ProjectConfig, BuildError> {
+    let mut cfg = ProjectConfig::default();
+    apply_config_file(&mut cfg, path)?;
+
+    let local = local_override_path(path);
+    if local.is_file() {
+        apply_config_file(&mut cfg, &local)?;
+    }
+
+    if let Some(triple) = cfg.target_triple.clone() {
+        if cfg.toolchain == Toolchain::Gcc {
+            apply_cross_prefix(&mut cfg, &triple);
+        } else if !cfg.toolchain.supports_target_triple() {
+            return Err(BuildError::ConfigError(format!(
+                "target = \"{}\" is not supported by toolchain \"{}\"; use toolchain = \"gcc\", \"zig\" or \"armclang\", or point gcc_path/gpp_path at a cross compiler",
+                triple,
+                cfg.toolchain.name()
+            )));
+        }
+        // Objects for different targets must not overwrite each other
+        cfg.temp_dir = cfg.temp_dir.join(&triple);
+    }
+
+    // Pipelining splits GCC-style `-E` / `-c` stages
+    if cfg.pipeline && cfg.toolchain == Toolchain::Msvc {
+        return Err(BuildError::ConfigError(
+            "pipeline = \"true\" is not supported by toolchain \"msvc\"".to_string(),
+        ));
+    }
+
+    for settings in [&cfg.debug, &cfg.release] {
+        if cfg.toolchain.lto_flags(settings.lto, cfg.parallel_jobs).is_none() {
+            return Err(BuildError::ConfigError(format!(
+                "lto is not supported by toolchain \"{}\"",
+                cfg.toolchain.name()
+            )));
+        }
+    }
+
+    if cfg.openmp && cfg.toolchain.openmp_flags().is_none() {
+        return Err(BuildError::ConfigError(format!(
+            "openmp = \"true\" is not supported by toolchain \"{}\"",
+            cfg.toolchain.name()
+        )));
+    }
+
+    Ok(cfg)
+}
+
+/// GNU cross toolchains are installed as prefixed binaries
+/// (`aarch64-linux-gnu-gcc`); use them for every tool not set explicitly.
+fn apply_cross_prefix(cfg: &mut ProjectConfig, triple: &str) {
+    let prefixed = |tool: &str| format!("{}-{}", triple, tool);
+    if cfg.gcc_path.is_none() {
+        cfg.gcc_path = Some(ToolCommand::new(&prefixed("gcc")));
+    }
+    if cfg.gpp_path.is_none() {
+        cfg.gpp_path = Some(ToolCommand::new(&prefixed("g++")));
+    }
+    if cfg.ar_path == "ar" {
+        cfg.ar_path = prefixed("ar");
+    }
+    if cfg.objcopy_path == "objcopy" {
+        cfg.objcopy_path = prefixed("objcopy");
+    }
+}
+
+/// `config.txt` → `config.local.txt`, `drakkar.toml` → `drakkar.local.toml`
+/// (same directory).
+pub fn local_override_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config".to_string());
+    let name = match path.extension() {
+        Some(ext) => format!("{}.local.{}", stem, ext.to_string_lossy()),
+        None => format!("{}.local", stem),
+    };
+    path.with_file_name(name)
+}
+
+/// Append `dirs` to include_dirs in the config file at `path` (not its
+/// local override), for `drakkar build --fix`. Only the include_dirs line
+/// is rewritten; comments and the rest of the file stay as they are.
+pub fn add_include_dirs(path: &Path, dirs: &[PathBuf]) -> Result<(), BuildError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        BuildError::ConfigError(format!("Cannot read {:?}: {}", path, e))
+    })?;
+    // Only the file's own dirs: those of included files stay in those files
+    let mut current = ProjectConfig::default();
+    SKIP_INCLUDES.with(|skip| skip.set(true));
+    let own = apply_config_file(&mut current, path);
+    SKIP_INCLUDES.with(|skip| skip.set(false));
+    own?;
+    let mut include_dirs = current.include_dirs;
+    for dir in dirs {
+        if !include_dirs.contains(dir) {
+            include_dirs.push(dir.clone());
+        }
+    }
+    let tokens = include_dirs.iter().map(|d| d.to_string_lossy().into_owned());
+
+    let is_toml = path.extension() == Some(std::ffi::OsStr::new("toml"));
+    let line = if is_toml {
+        let items: Vec<String> = tokens.map(|t| toml::quote(&t)).collect();
+        format!("include_dirs = [{}]", items.join(", "))
+    } else {
+        let words: Vec<String> = tokens
+            .map(|t| if t.contains(char::is_whitespace) { format!("'{}'", t) } else { t })
+            .collect();
+        format!("include_dirs = \"{}\"", words.join(" "))
+    };
+    let updated = set_top_level_line(&content, "include_dirs", &line, is_toml);
+    std::fs::write(path, updated)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))
+}
+
+/// `content` with the project-wide `key` line replaced by `line`, or with
+/// `line` added before the first section when the key isn't set. In TOML
+/// that is the `[build]` table, if there is one.
+fn set_top_level_line(content: &str, key: &str, line: &str, is_toml: bool) -> String {
+    let mut lines: Vec<&str> = content.lines().collect();
+    let mut section: Option<&str> = None;
+    let mut existing: Option<(usize, usize)> = None;
+    let mut first_section = None;
+    let mut build_table = None;
+    let mut i = 0;
+    while i < lines.len() {
+        let trimmed = lines[i].trim();
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            section = Some(name.trim());
+            first_section = first_section.or(Some(i));
+            if is_toml && section == Some("build") {
+                build_table = Some(i);
+            }
+        }
+        let top_level = match section {
+            None => true,
+            Some(name) => is_toml && name == "build",
+        };
+        let this_key = trimmed.split('=').next().unwrap_or("").trim();
+        if top_level && !trimmed.starts_with('#') && trimmed.contains('=') && this_key == key {
+            // A TOML array may continue over several lines
+            let value = trimmed.split_once('=').map(|(_, v)| v.trim()).unwrap_or("");
+            let mut end = i;
+            if is_toml && value.starts_with('[') && !value.contains(']') {
+                while end + 1 < lines.len() && !lines[end].contains(']') {
+                    end += 1;
+                }
+            }
+            existing = Some((i, end));
+            i = end;
+        }
+        i += 1;
+    }
+    match (existing, build_table, first_section) {
+        (Some((start, end)), _, _) => {
+            lines.splice(start..=end, [line]);
+        }
+        (None, Some(table), _) => lines.insert(table + 1, line),
+        (None, None, Some(first)) => lines.insert(first, line),
+        (None, None, None) => lines.push(line),
+    }
+    let mut out = lines.join("\n");
+    out.push('\n');
+    out
+}
+
+thread_local! {
+    /// Config files being read, outermost first, to catch include cycles.
+    static READING: RefCell<Vec<PathBuf>> = const { RefCell::new(Vec::new()) };
+    /// Set while reading a file for its own keys, leaving out its includes.
+    static SKIP_INCLUDES: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Parse one config file and apply its keys over `cfg`.
+fn apply_config_file(cfg: &mut ProjectConfig, path: &Path) -> Result<(), BuildError> {
+    let id = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let cycle = READING.with(|reading| {
+        let reading = reading.borrow();
+        let start = reading.iter().position(|p| *p == id)?;
+        let chain: Vec<String> =
+            reading[start..].iter().chain([&id]).map(|p| p.display().to_string()).collect();
+        Some(chain.join(" → "))
+    });
+    if let Some(chain) = cycle {
+        return Err(BuildError::ConfigError(format!("include cycle: {}", chain)));
+    }
+    READING.with(|reading| reading.borrow_mut().push(id));
+    let result = apply_config_file_contents(cfg, path);
+    READING.with(|reading| reading.borrow_mut().pop());
+    result
+}
+
+/// Apply the files named by `include` in `path`, in order, as if their
+/// keys were written where the include is. Relative names are resolved
+/// from the directory of `path`.
+fn apply_includes(cfg: &mut ProjectConfig, files: &[String], path: &Path) -> Result<(), BuildError> {
+    if SKIP_INCLUDES.with(Cell::get) {
+        return Ok(());
+    }
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    for file in files {
+        let file = dir.join(file);
+        if !file.is_file() {
+            return Err(BuildError::ConfigError(format!(
+                "{}: included config {:?} does not exist",
+                path.display(),
+                file
+            )));
+        }
+        apply_config_file(cfg, &file)?;
+        if !cfg.included_configs.contains(&file) {
+            cfg.included_configs.push(file);
+        }
+    }
+    Ok(())
+}
+
+fn apply_config_file_contents(cfg: &mut ProjectConfig, path: &Path) -> Result<(), BuildError> {
+    let content = std::fs::read_to_string(path).map_err(|e| {
+        BuildError::ConfigError(format!("Cannot read {:?}: {}", path, e))
+    })?;
+
+    if path.extension() != Some(std::ffi::OsStr::new("toml")) {
+        // Its errors already carry the path, with line and column
+        return apply_config_str(cfg, &content, path);
+    }
+    apply_toml_str(cfg, &content, path).map_err(|e| match e {
+        BuildError::ParseError(msg) => {
+            BuildError::ParseError(format!("{}: {}", path.display(), msg))
+        }
+        other => other,
+    })
+}
+
+/// Apply config.txt `content` over `cfg`. Syntax and value errors don't
+/// stop at the first one: every one in the file is reported together.
+fn apply_config_str(
+    cfg: &mut ProjectConfig,
+    content: &str,
+    path: &Path,
+) -> Result<(), BuildError> {
+    // `[name]` prefixes the keys that follow with `name.`
+    let mut section: Option<String> = None;
+    let mut problems = Vec::new();
+
+    for (line_idx, line) in content.lines().enumerate() {
+        let line_no = line_idx + 1;
+        let trimmed = line.trim();
+        let mut problem = |part: &str, message: String| {
+            problems.push(Problem {
+                span: Span::within(line, line_no, part),
+                message,
+            })
+        };
+
+        // Skip comments and empty lines
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            let name = name.trim();
+            if name.is_empty() {
+                problem(trimmed, "empty section header '[]'".to_string());
+            } else {
+                section = Some(name.to_string());
+            }
+            continue;
+        }
+
+        // Split on first '='
+        let eq_pos = match trimmed.find('=') {
+            Some(pos) => pos,
+            None => {
+                problem(trimmed, "expected 'key = value'".to_string());
+                continue;
+            }
+        };
+
+        let key = trimmed[..eq_pos].trim();
+        if key.is_empty() {
+            problem(&trimmed[..eq_pos + 1], "missing key before '='".to_string());
+            continue;
+        }
+        let sectioned;
+        let key = match &section {
+            Some(name) => {
+                sectioned = format!("{}.{}", name, key);
+                sectioned.as_str()
+            }
+            None => key,
+        };
+        let value_str = trimmed[eq_pos + 1..].trim();
+
+        // Strip inline comments after the closing quote
+        let value_str = strip_inline_comment(value_str);
+
+        let tokens = match value_tokens(value_str) {
+            Ok(tokens) => tokens,
+            Err(e) => {
+                let skip = value_str.char_indices().nth(e.offset).map(|(i, _)| i).unwrap_or(value_str.len());
+                let end = value_str[skip..]
+                    .char_indices()
+                    .nth(e.len)
+                    .map(|(i, _)| skip + i)
+                    .unwrap_or(value_str.len());
+                problem(&value_str[skip..end], e.message.to_string());
+                continue;
+            }
+        };
+        if key == "include" {
+            apply_includes(cfg, &tokens, path)?;
+            continue;
+        }
+        match apply_key(cfg, key, tokens, line_no, path) {
+            Ok(()) => {}
+            Err(BuildError::ParseError(msg)) => {
+                let prefix = format!("Line {}: ", line_no);
+                let msg = msg.strip_prefix(&prefix).map(str::to_string).unwrap_or(msg);
+                problem(if value_str.is_empty() { trimmed } else { value_str }, msg);
+            }
+            Err(other) => return Err(other),
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(BuildError::ParseError(parse::report(path, content, &problems)))
+    }
+}
+
+/// Apply a `drakkar.toml` document over `cfg`.
+///
+/// Tables map onto the config.txt keys: `[build]` holds the top-level
+/// keys, `[profile.<name>]` the `<name>.*` ones, and `[libs.*]` and
+/// `[target.*]` keep their path. A string value is split like a config.txt
+/// value; each element of an array is taken verbatim as one token.
+fn apply_toml_str(
+    cfg: &mut ProjectConfig,
+    content: &str,
+    path: &Path,
+) -> Result<(), BuildError> {
+    for entry in toml::parse(content)? {
+        let parts = match entry.path.first().map(String::as_str) {
+            Some("build") | Some("profile") if entry.path.len() > 1 => &entry.path[1..],
+            _ => &entry.path[..],
+        };
+        let key = parts.join(".");
+        let tokens = match (&entry.value, parts.last().map(String::as_str)) {
+            // One command per element
+            (toml::Value::Array(items), Some("post_link")) => {
+                let mut tokens = Vec::new();
+                for item in items {
+                    tokens.extend(toml_tokens(item, entry.line_no)?);
+                    tokens.push(";".to_string());
+                }
+                tokens
+            }
+            _ => toml_tokens(&entry.value, entry.line_no)?,
+        };
+        if key == "include" {
+            apply_includes(cfg, &tokens, path)?;
+            continue;
+        }
+        apply_key(cfg, &key, tokens, entry.line_no, path)?;
+    }
+    Ok(())
+}
+
+fn toml_tokens(value: &toml::Value, line_no: usize) -> Result<Vec<String>, BuildError> {
+    let scalar = |v: &toml::Value| match v {
+        toml::Value::String(s) => Ok(s.clone()),
+        toml::Value::Integer(n) => Ok(n.to_string()),
+        toml::Value::Bool(b) => Ok(b.to_string()),
+        toml::Value::Array(_) => Err(BuildError::ParseError(format!(
+            "Line {}: nested arrays are not supported",
+            line_no
+        ))),
+    };
+    match value {
+        toml::Value::String(s) => shell_tokenize(s).map_err(|e| {
+            BuildError::ParseError(format!("Line {}: {}", line_no, e))
+        }),
+        toml::Value::Array(items) => items.iter().map(scalar).collect(),
+        other => Ok(vec![scalar(other)?]),
+    }
+}
+
+/// Apply one setting, given its full key (`release.strip`, `libs.m.linux`).
+fn apply_key(
+    cfg: &mut ProjectConfig,
+    key: &str,
+    tokens: Vec<String>,
+    line_no: usize,
+    path: &Path,
+) -> Result<(), BuildError> {
+    let first = tokens.first().map(String::as_str).unwrap_or("");
+
+    match key {
+        "config_version" => {
+            let version = parse_usize(first, line_no)? as u32;
+            if version > CURRENT_CONFIG_VERSION {
+                return Err(BuildError::ConfigError(format!(
+                    "{:?} has config_version {}, but this drakkar only understands up to {}; upgrade drakkar",
+                    path, version, CURRENT_CONFIG_VERSION
+                )));
+            }
+            if version < CURRENT_CONFIG_VERSION {
+                eprintln!(
+                    "\x1b[33mwarning:\x1b[0m {:?} uses config_version {}; run `drakkar migrate` to upgrade",
+                    path, version
+                );
+            }
+        }
+        "app_name" => cfg.app_name = first.to_string(),
+        "source_dir" => cfg.source_dirs = tokens.iter().map(PathBuf::from).collect(),
+        "output_dir" => cfg.output_dir = PathBuf::from(first),
+        "temp_dir" => cfg.temp_dir = PathBuf::from(first),
+        "temp_max_size_mb" => {
+            cfg.temp_max_size_mb = match first {
+                "" => None,
+                _ => Some(parse_usize(first, line_no)? as u64),
+            }
+        }
+        "c_flags" => cfg.c_flags = tokens,
+        "cxx_flags" => cfg.cxx_flags = tokens,
+        "ld_flags" => cfg.ld_flags = tokens,
+        "include_dirs" => {
+            cfg.include_dirs = tokens.iter().map(PathBuf::from).collect();
+        }
+        "link_libs" => cfg.link_libs = tokens,
+        "requires_libs" => cfg.requires_libs = tokens,
+        "requires_headers" => cfg.requires_headers = tokens,
+        "extra_objects" => cfg.extra_objects = tokens.iter().map(PathBuf::from).collect(),
+        "c_standard" => cfg.c_standard = if first.is_empty() { None } else { Some(first.to_string()) },
+        "cxx_standard" => cfg.cxx_standard = if first.is_empty() { None } else { Some(first.to_string()) },
+        "vendor_dirs" => cfg.vendor_dirs = tokens.iter().map(PathBuf::from).collect(),
+        "vendor_c_flags" => cfg.vendor_c_flags = tokens,
+        "vendor_cxx_flags" => cfg.vendor_cxx_flags = tokens,
+        "vendor_c_standard" => {
+            cfg.vendor_c_standard = if first.is_empty() { None } else { Some(first.to_string()) }
+        }
+        "vendor_cxx_standard" => {
+            cfg.vendor_cxx_standard = if first.is_empty() { None } else { Some(first.to_string()) }
+        }
+        "parallel_jobs" => cfg.parallel_jobs = parse_usize(first, line_no)?,
+        "incremental" => cfg.incremental = parse_bool(first, line_no)?,
+        "preserve_temp" => cfg.preserve_temp = Some(parse_bool(first, line_no)?),
+        "use_process_groups" => cfg.use_process_groups = parse_bool(first, line_no)?,
+        "toolchain" => cfg.toolchain = Toolchain::parse(first, line_no)?,
+        "target" | "target_triple" => {
+            cfg.target_triple = if first.is_empty() { None } else { Some(first.to_string()) }
+        }
+        "openmp" => cfg.openmp = parse_bool(first, line_no)?,
+        "threads" => cfg.threads = parse_bool(first, line_no)?,
+        "gcc_path" => cfg.gcc_path = Some(ToolCommand::from_tokens(key, tokens, line_no)?),
+        "gpp_path" => cfg.gpp_path = Some(ToolCommand::from_tokens(key, tokens, line_no)?),
+        "objcopy_path" => cfg.objcopy_path = first.to_string(),
+        "upx_path" => cfg.upx_path = ToolCommand::from_tokens(key, tokens, line_no)?,
+        "conan_path" => cfg.conan_path = ToolCommand::from_tokens(key, tokens, line_no)?,
+        "ar_path" => cfg.ar_path = first.to_string(),
+        "link_map" => cfg.link_map = parse_bool(first, line_no)?,
+        "plugins_dir" => cfg.plugins_dir = PathBuf::from(first),
+        "tests_dir" => cfg.tests_dir = PathBuf::from(first),
+        "channels_dir" => cfg.channels_dir = PathBuf::from(first),
+        "install_prefix" => {
+            cfg.install_prefix = if first.is_empty() { None } else { Some(PathBuf::from(first)) }
+        }
+        "bin_discovery" => cfg.bin_discovery = parse_bool(first, line_no)?,
+        "terminal_title" => cfg.terminal_title = parse_bool(first, line_no)?,
+        "status_board" => cfg.status_board = parse_bool(first, line_no)?,
+        "progress_bar" => cfg.progress_bar = parse_bool(first, line_no)?,
+        "object_cache" => cfg.object_cache = parse_bool(first, line_no)?,
+        "disk_check" => cfg.disk_check = parse_bool(first, line_no)?,
+        "remote_cache" => {
+            cfg.remote_cache = if first.is_empty() {
+                None
+            } else {
+                Some(CacheLocation::parse(first).map_err(|e| {
+                    BuildError::ParseError(format!("Line {}: remote_cache: {}", line_no, e))
+                })?)
+            }
+        }
+        "remote_cache_push" => cfg.remote_cache_push = parse_bool(first, line_no)?,
+        "object_cache_dir" => {
+            cfg.object_cache_dir = if first.is_empty() { None } else { Some(PathBuf::from(first)) }
+        }
+        "shared_temp_dir" => {
+            cfg.shared_temp_dir = if first.is_empty() { None } else { Some(PathBuf::from(first)) }
+        }
+        "default_target" => {
+            cfg.default_target = if first.is_empty() { None } else { Some(first.to_string()) }
+        }
+        "sign_command" => {
+            cfg.sign_command = if first.is_empty() {
+                None
+            } else {
+                Some(ToolCommand::from_tokens(key, tokens, line_no)?)
+            }
+        }
+        "sign_identity" => {
+            cfg.sign_identity = if first.is_empty() { None } else { Some(first.to_string()) }
+        }
+        "run_wrapper" => {
+            cfg.run_wrapper = if first.is_empty() {
+                None
+            } else {
+                Some(ToolCommand::from_tokens(key, tokens, line_no)?)
+            }
+        }
+        "on_event" => {
+            cfg.on_event = if first.is_empty() {
+                None
+            } else {
+                Some(ToolCommand::from_tokens(key, tokens, line_no)?)
+            }
+        }
+        "include_check" => cfg.include_check = IncludeCheck::parse(first, line_no)?,
+        "compile_commands" => cfg.compile_commands = CompileCommands::parse(first, line_no)?,
+        "pipeline" => cfg.pipeline = parse_bool(first, line_no)?,
+        "rebuild_policy" => cfg.rebuild_policy = RebuildPolicy::parse(first, line_no)?,
+        "tracked_env" => cfg.tracked_env = tokens,
+        "executor" => cfg.executor = Executor::parse(first, line_no)?,
+        "executor_args" => cfg.executor_args = tokens,
+        "sandbox" => cfg.sandbox = Sandbox::parse(first, line_no)?,
+        "sandbox_paths" => cfg.sandbox_paths = tokens.iter().map(PathBuf::from).collect(),
+        "ignore_patterns" => cfg.ignore_patterns = tokens,
+        "sources" => cfg.source_patterns = tokens,
+        "exclude" => cfg.exclude_patterns = tokens,
+        "workspace.members" => cfg.workspace_members = tokens.iter().map(PathBuf::from).collect(),
+        "deps" => cfg.deps = tokens.iter().map(PathBuf::from).collect(),
+        "conan_deps" => cfg.conan_deps = tokens,
+        "deps_dir" => cfg.deps_dir = PathBuf::from(first),
+        "uppercase_c_is_cpp" => cfg.uppercase_c_is_cpp = parse_bool(first, line_no)?,
+        // Shorthand: stripping only ever applies to release by default
+        "strip" => cfg.release.strip = StripMode::parse(first, line_no)?,
+        "compress_binary" => cfg.release.compress = parse_bool(first, line_no)?,
+        "lto" => cfg.release.lto = Lto::parse(first, line_no)?,
+        "post_link" => {
+            let steps = post_link_steps(tokens);
+            cfg.debug.post_link = steps.clone();
+            cfg.release.post_link = steps;
+        }
+        // target.<name>.<key> = "..." — settings of one named target
+        _ if key.starts_with("target.") => match key["target.".len()..].rsplit_once('.') {
+            Some((name, field)) if !name.is_empty() => {
+                let target = match cfg.targets.iter().position(|t| t.name == name) {
+                    Some(idx) => &mut cfg.targets[idx],
+                    None => {
+                        cfg.targets.push(TargetConfig::new(name));
+                        cfg.targets.last_mut().unwrap()
+                    }
+                };
+                match field {
+                    "target_type" => target.target_type = TargetType::parse(first, line_no)?,
+                    "source_dir" => target.source_dirs = tokens.iter().map(PathBuf::from).collect(),
+                    "output_name" => {
+                        target.output_name = if first.is_empty() { None } else { Some(first.to_string()) }
+                    }
+                    "c_flags" => target.c_flags = tokens,
+                    "cxx_flags" => target.cxx_flags = tokens,
+                    "ld_flags" => target.ld_flags = tokens,
+                    "include_dirs" => target.include_dirs = tokens.iter().map(PathBuf::from).collect(),
+                    "link_libs" => target.link_libs = tokens,
+                    "deps" => target.deps = tokens,
+                    "exports" => target.exports = tokens,
+                    "vendor_dirs" => {
+                        target.vendor_dirs = tokens.iter().map(PathBuf::from).collect()
+                    }
+                    _ => warn_unknown_key(path, line_no, key),
+                }
+            }
+            _ => warn_unknown_key(path, line_no, key),
+        },
+        // generator.<name>.<key> = "..." — a code generation rule
+        _ if key.starts_with("generator.") => match key["generator.".len()..].rsplit_once('.') {
+            Some((name, field)) if !name.is_empty() => {
+                let rule = match cfg.generators.iter().position(|g| g.name == name) {
+                    Some(idx) => &mut cfg.generators[idx],
+                    None => {
+                        cfg.generators.push(GeneratorRule {
+                            name: name.to_string(),
+                            inputs: vec![],
+                            command: vec![],
+                            outputs: vec![],
+                            target: None,
+                        });
+                        cfg.generators.last_mut().unwrap()
+                    }
+                };
+                match field {
+                    "inputs" => rule.inputs = tokens,
+                    "command" => rule.command = tokens,
+                    "outputs" => rule.outputs = tokens,
+                    "target" => rule.target = if first.is_empty() { None } else { Some(first.to_string()) },
+                    _ => warn_unknown_key(path, line_no, key),
+                }
+            }
+            _ => warn_unknown_key(path, line_no, key),
+        },
+        // deps.<name> = "<git url> <tag>" — a git dependency; a lone path is
+        // a path dependency like the ones in `deps`
+        _ if key.starts_with("deps.") => {
+            let name = &key["deps.".len()..];
+            match tokens.as_slice() {
+                _ if name.is_empty() || name.contains(['.', '/']) => {
+                    warn_unknown_key(path, line_no, key)
+                }
+                [dir] if !dir.contains("://") => cfg.deps.push(PathBuf::from(dir)),
+                [url, tag] => {
+                    cfg.git_deps.retain(|d| d.name != name);
+                    cfg.git_deps.push(GitDep {
+                        name: name.to_string(),
+                        url: url.clone(),
+                        tag: tag.clone(),
+                    });
+                }
+                _ => {
+                    return Err(BuildError::ParseError(format!(
+                        "Line {}: {} must be a path, or a git URL and a tag",
+                        line_no, key
+                    )))
+                }
+            }
+        }
+        // libs.<name>.<os> = "..." — what a logical link_libs name means per OS
+        _ if key.starts_with("libs.") => match key["libs.".len()..].rsplit_once('.') {
+            Some((name, os)) if !name.is_empty() && !os.is_empty() => {
+                cfg.lib_map.insert((name.to_string(), os.to_string()), tokens);
+            }
+            _ => warn_unknown_key(path, line_no, key),
+        },
+        _ => {
+            let profile = if let Some(sub) = key.strip_prefix("debug.") {
+                Some((&mut cfg.debug, sub))
+            } else {
+                key.strip_prefix("release.").map(|sub| (&mut cfg.release, sub))
+            };
+            match profile {
+                Some((settings, "ld_flags")) => settings.ld_flags = tokens,
+                Some((settings, "strip")) => settings.strip = StripMode::parse(first, line_no)?,
+                Some((settings, "compress_binary")) => settings.compress = parse_bool(first, line_no)?,
+                Some((settings, "lto")) => settings.lto = Lto::parse(first, line_no)?,
+                Some((settings, "post_link")) => settings.post_link = post_link_steps(tokens),
+                _ => warn_unknown_key(path, line_no, key),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Split a `post_link` value into commands at `;`, which may stand alone
+/// or end a word: `"strip; sign --key k"`.
+fn post_link_steps(tokens: Vec<String>) -> Vec<ToolCommand> {
+    let mut steps: Vec<Vec<String>> = vec![Vec::new()];
+    for token in tokens {
+        let word = token.trim_end_matches(';');
+        if !word.is_empty() {
+            steps.last_mut().unwrap().push(word.to_string());
+        }
+        if token.ends_with(';') {
+            steps.push(Vec::new());
+        }
+    }
+    steps
+        .into_iter()
+        .filter(|words| !words.is_empty())
+        .map(|mut words| ToolCommand {
+            program: words.remove(0),
+            args: words,
+        })
+        .collect()
+}
+
+fn is_logical_lib(lib: &str) -> bool {
+    !lib.starts_with('-') && !lib.contains('/') && !lib.contains('\\') && !lib.contains('.')
+}
+
+/// Unknown keys are reported but otherwise ignored.
+fn warn_unknown_key(path: &Path, line_no: usize, key: &str) {
+    eprintln!(
+        "\x1b[33mwarning:\x1b[0m {}: Line {}: unknown config key '{}'",
+        path.display(), line_no, key
+    );
+}
+
+/// Strip trailing inline comment (anything after `"` followed by whitespace and `#`).
+pub fn strip_inline_comment(s: &str) -> &str {
+    // If the value ends with a closing quote, look for # after it
+    if let Some(idx) = s.rfind('"') {
+        let after = s[idx + 1..].trim();
+        if after.starts_with('#') || after.is_empty() {
+            return &s[..idx + 1];
+        }
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize_simple_flags() {
+        let t = shell_tokenize("-Wall -Wextra -std=c++17").unwrap();
+        assert_eq!(t, vec!["-Wall", "-Wextra", "-std=c++17"]);
+    }
+
+    #[test]
+    fn test_tokenize_rpath_comma() {
+        let t = shell_tokenize("-Wall -Wl,-rpath,./lib").unwrap();
+        assert_eq!(t, vec!["-Wall", "-Wl,-rpath,./lib"]);
+    }
+
+    #[test]
+    fn test_tokenize_quoted_spaces() {
+        let t = shell_tokenize(r#"-DNAME="my name" -Wall"#).unwrap();
+        assert_eq!(t, vec!["-DNAME=my name", "-Wall"]);
+    }
+
+    #[test]
+    fn test_tokenize_single_quotes() {
+        let t = shell_tokenize("include/ 'third party/include/'").unwrap();
+        assert_eq!(t, vec!["include/", "third party/include/"]);
+    }
+
+    #[test]
+    fn test_local_override_path() {
+        assert_eq!(
+            local_override_path(Path::new("proj/config.txt")),
+            PathBuf::from("proj/config.local.txt")
+        );
+    }
+
+    #[test]
+    fn test_local_override_applied_last() {
+        let dir = std::env::temp_dir().join("drakkar_test_local_override");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.txt"), "app_name = \"demo\"\ngpp_path = \"g++\"\nparallel_jobs = \"4\"\n").unwrap();
+        std::fs::write(dir.join("config.local.txt"), "gpp_path = \"/opt/gcc-14/bin/g++\"\n").unwrap();
+
+        let cfg = read_config(&dir.join("config.txt")).unwrap();
+        assert_eq!(cfg.app_name, "demo");
+        assert_eq!(cfg.cxx_compiler(), ToolCommand::new("/opt/gcc-14/bin/g++"));
+        assert_eq!(cfg.parallel_jobs, 4);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_include_config() {
+        let dir = std::env::temp_dir().join("drakkar_test_include_config");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("app")).unwrap();
+        std::fs::write(dir.join("common.cfg"), "parallel_jobs = \"2\"\nc_standard = \"c11\"\n").unwrap();
+        std::fs::write(dir.join("strict.toml"), "[build]\nc_standard = \"c17\"\n").unwrap();
+        std::fs::write(
+            dir.join("app/config.txt"),
+            "parallel_jobs = \"8\"\ninclude = \"../common.cfg ../strict.toml\"\napp_name = \"app\"\n",
+        )
+        .unwrap();
+        let cfg = read_config(&dir.join("app/config.txt")).unwrap();
+        // Keys after the include win, the included ones win over keys before it
+        assert_eq!(cfg.parallel_jobs, 2);
+        assert_eq!(cfg.c_standard.as_deref(), Some("c17"));
+        assert_eq!(cfg.app_name, "app");
+        assert_eq!(
+            cfg.included_configs,
+            vec![dir.join("app/../common.cfg"), dir.join("app/../strict.toml")]
+        );
+
+        std::fs::write(dir.join("common.cfg"), "include = \"app/config.txt\"\n").unwrap();
+        let err = read_config(&dir.join("app/config.txt")).unwrap_err().to_string();
+        let canonical = |p: &str| dir.join(p).canonicalize().unwrap().display().to_string();
+        let cycle = format!(
+            "include cycle: {} → {} → {}",
+            canonical("app/config.txt"),
+            canonical("common.cfg"),
+            canonical("app/config.txt")
+        );
+        assert!(err.contains(&cycle), "{}", err);
+
+        std::fs::write(dir.join("app/config.txt"), "include = \"missing.cfg\"\n").unwrap();
+        let err = read_config(&dir.join("app/config.txt")).unwrap_err().to_string();
+        assert!(err.contains("included config") && err.contains("missing.cfg"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_add_include_dirs() {
+        let dir = std::env::temp_dir().join("drakkar_test_add_include_dirs");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let text = dir.join("config.txt");
+        std::fs::write(&text, "# demo\ninclude_dirs = \"include\"\n\n[target.tool]\nsources = \"tool/\"\n").unwrap();
+        add_include_dirs(&text, &[PathBuf::from("third party/include")]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&text).unwrap(),
+            "# demo\ninclude_dirs = \"include 'third party/include'\"\n\n[target.tool]\nsources = \"tool/\"\n"
+        );
+        let cfg = read_config(&text).unwrap();
+        assert_eq!(cfg.include_dirs, vec![PathBuf::from("include"), PathBuf::from("third party/include")]);
+
+        let toml = dir.join("drakkar.toml");
+        std::fs::write(&toml, "[build]\napp_name = \"demo\"\n\n[profile.release]\nstrip = \"full\"\n").unwrap();
+        add_include_dirs(&toml, &[PathBuf::from("vendor/include")]).unwrap();
+        add_include_dirs(&toml, &[PathBuf::from("vendor/include"), PathBuf::from("gen")]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&toml).unwrap(),
+            "[build]\ninclude_dirs = [\"vendor/include\", \"gen\"]\napp_name = \"demo\"\n\n[profile.release]\nstrip = \"full\"\n"
+        );
+
+        // Dirs from an included file are not copied into the including one
+        std::fs::write(dir.join("common.txt"), "include_dirs = \"shared\"\n").unwrap();
+        std::fs::write(&text, "include = \"common.txt\"\ninclude_dirs = \"include\"\n").unwrap();
+        add_include_dirs(&text, &[PathBuf::from("gen")]).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&text).unwrap(),
+            "include = \"common.txt\"\ninclude_dirs = \"include gen\"\n"
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_compiler_wrapper_command() {
+        let mut cfg = ProjectConfig::default();
+        apply_config_str(&mut cfg, "gpp_path = \"ccache g++\"\n", Path::new("config.txt")).unwrap();
+        let gpp = cfg.cxx_compiler();
+        assert_eq!(gpp.program, "ccache");
+        assert_eq!(gpp.args, vec!["g++"]);
+        assert_eq!(gpp.to_string(), "ccache g++");
+
+        assert!(apply_config_str(&mut cfg, "gcc_path = \"\"\n", Path::new("config.txt")).is_err());
+        assert!(ToolCommand::new("drakkar-no-such-compiler").validate("gcc_path").is_err());
+
+        apply_config_str(
+            &mut cfg,
+            "run_wrapper = \"qemu-aarch64 -L /usr/aarch64-linux-gnu\"\n",
+            Path::new("config.txt"),
+        )
+        .unwrap();
+        let wrapper = cfg.run_wrapper.clone().unwrap();
+        assert_eq!(wrapper.program, "qemu-aarch64");
+        assert_eq!(wrapper.args, vec!["-L", "/usr/aarch64-linux-gnu"]);
+        apply_config_str(&mut cfg, "run_wrapper = \"\"\n", Path::new("config.txt")).unwrap();
+        assert!(cfg.run_wrapper.is_none());
+        apply_config_str(&mut cfg, "on_event = \"./hooks/notify.sh --quiet\"\n", Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.on_event.clone().unwrap().to_string(), "./hooks/notify.sh --quiet");
+
+        assert!(cfg.executor_command().is_none());
+        apply_config_str(
+            &mut cfg,
+            "executor = \"slurm\"\nexecutor_args = \"--partition=build --time=5\"\n",
+            Path::new("config.txt"),
+        )
+        .unwrap();
+        assert_eq!(
+            cfg.executor_command().unwrap().to_string(),
+            "srun --quiet --ntasks=1 --partition=build --time=5"
+        );
+        assert!(apply_config_str(&mut cfg, "executor = \"pbs\"\n", Path::new("config.txt")).is_err());
+    }
+
+    #[test]
+    fn test_compiler_pair() {
+        let pair = |cmd: &str| {
+            let (c, cxx) = ToolCommand::compiler_pair(cmd).unwrap();
+            (c.to_string(), cxx.to_string())
+        };
+        let owned = |c: &str, cxx: &str| (c.to_string(), cxx.to_string());
+        assert_eq!(pair("gcc-13"), owned("gcc-13", "g++-13"));
+        assert_eq!(pair("clang++-18"), owned("clang-18", "clang++-18"));
+        assert_eq!(pair("/opt/gcc-14/bin/g++"), owned("/opt/gcc-14/bin/gcc", "/opt/gcc-14/bin/g++"));
+        assert_eq!(pair("aarch64-linux-gnu-gcc"), owned("aarch64-linux-gnu-gcc", "aarch64-linux-gnu-g++"));
+        assert_eq!(pair("ccache clang++"), owned("ccache clang", "ccache clang++"));
+        assert_eq!(pair("zig cc"), owned("zig cc", "zig c++"));
+        assert_eq!(pair("icpx"), owned("icx", "icpx"));
+        assert_eq!(pair("armclang"), owned("armclang", "armclang"));
+        assert_eq!(pair("cl"), owned("cl", "cl"));
+        assert!(ToolCommand::compiler_pair("").is_err());
+    }
+
+    #[test]
+    fn test_toolchain_target_triple() {
+        let dir = std::env::temp_dir().join("drakkar_test_toolchain");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.txt");
+
+        std::fs::write(&path, "toolchain = \"zig\"\ntarget_triple = \"aarch64-linux-gnu.2.28\"\n").unwrap();
+        let cfg = read_config(&path).unwrap();
+        assert_eq!(cfg.c_compiler().to_string(), "zig cc");
+        assert_eq!(cfg.target_flags(), vec!["-target", "aarch64-linux-gnu.2.28"]);
+
+        // An explicit compiler command still wins over the toolchain default
+        std::fs::write(&path, "gpp_path = \"g++-13\"\ntoolchain = \"zig\"\n").unwrap();
+        assert_eq!(read_config(&path).unwrap().cxx_compiler().to_string(), "g++-13");
+
+        // gcc cross-compiles through prefixed binaries, in a temp_dir of its own
+        std::fs::write(&path, "target = \"aarch64-linux-gnu\"\nar_path = \"llvm-ar\"\n").unwrap();
+        let cfg = read_config(&path).unwrap();
+        assert_eq!(cfg.c_compiler().to_string(), "aarch64-linux-gnu-gcc");
+        assert_eq!(cfg.cxx_compiler().to_string(), "aarch64-linux-gnu-g++");
+        assert_eq!(cfg.objcopy_path, "aarch64-linux-gnu-objcopy");
+        assert_eq!(cfg.ar_path, "llvm-ar");
+        assert!(cfg.target_flags().is_empty());
+        assert_eq!(cfg.temp_dir, PathBuf::from("target/aarch64-linux-gnu"));
+        assert_eq!(cfg.runs_on_host(), std::env::consts::ARCH == "aarch64" && cfg!(target_os = "linux"));
+
+        std::fs::write(&path, "toolchain = \"intel\"\ntarget = \"x86_64-windows-gnu\"\n").unwrap();
+        assert!(matches!(read_config(&path), Err(BuildError::ConfigError(_))));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_openmp_and_threads_flags() {
+        let mut cfg = ProjectConfig::default();
+        apply_config_str(&mut cfg, "openmp = \"true\"\nthreads = \"true\"\n", Path::new("config.txt")).unwrap();
+        let (compile, link) = cfg.feature_flags();
+        assert_eq!(compile, vec!["-fopenmp", "-pthread"]);
+        assert_eq!(link, vec!["-fopenmp", "-pthread"]);
+
+        cfg.toolchain = Toolchain::Intel;
+        assert_eq!(cfg.feature_flags().0, vec!["-qopenmp", "-pthread"]);
+    }
+
+    #[test]
+    fn test_lib_platform_mapping() {
+        let mut cfg = ProjectConfig::default();
+        let content = "link_libs = \"socket -lm libz.a\"\n[libs]\nsocket.windows = \"ws2_32 mswsock\"\nsocket.linux = \"\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+
+        cfg.target_triple = Some("x86_64-windows-gnu".to_string());
+        assert_eq!(cfg.resolved_link_libs(), vec!["-lws2_32", "-lmswsock", "-lm", "libz.a"]);
+        cfg.target_triple = Some("x86_64-linux-gnu".to_string());
+        assert_eq!(cfg.resolved_link_libs(), vec!["-lm", "libz.a"]);
+        cfg.target_triple = Some("aarch64-macos".to_string());
+        assert_eq!(cfg.resolved_link_libs(), vec!["-lsocket", "-lm", "libz.a"]);
+
+        cfg.link_libs = vec!["-framework".to_string(), "Cocoa".to_string()];
+        assert_eq!(cfg.resolved_link_libs(), vec!["-framework", "Cocoa"]);
+    }
+
+    #[test]
+    fn test_section_headers_prefix_keys() {
+        let mut cfg = ProjectConfig::default();
+        let content = "app_name = \"x\"\n[release]\nstrip = \"separate\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.app_name, "x");
+        assert_eq!(cfg.release.strip, StripMode::Separate);
+        assert!(apply_config_str(&mut cfg, "[]\n", Path::new("config.txt")).is_err());
+    }
+
+    #[test]
+    fn test_deps_section() {
+        let mut cfg = ProjectConfig::default();
+        let content = "deps = \"../util\"\n[deps]\nmath = \"../libmath\"\n\
+                       fmt = \"https://github.com/fmtlib/fmt.git 10.2.1\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.deps, [PathBuf::from("../util"), PathBuf::from("../libmath")]);
+        let fmt = GitDep {
+            name: "fmt".to_string(),
+            url: "https://github.com/fmtlib/fmt.git".to_string(),
+            tag: "10.2.1".to_string(),
+        };
+        assert_eq!(cfg.git_deps, [fmt]);
+        let content = "[deps]\nfmt = \"https://x/fmt.git\"\n";
+        assert!(apply_config_str(&mut cfg, content, Path::new("config.txt")).is_err());
+    }
+
+    #[test]
+    fn test_profile_keys() {
+        let mut cfg = ProjectConfig::default();
+        let content = "release.ld_flags = \"-Wl,--gc-sections\"\nstrip = \"separate\"\ndebug.strip = \"full\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.release.ld_flags, vec!["-Wl,--gc-sections"]);
+        assert_eq!(cfg.release.strip, StripMode::Separate);
+        assert_eq!(cfg.profile(&BuildProfile::Debug).strip, StripMode::Full);
+        assert!(apply_config_str(&mut cfg, "strip = \"maybe\"", Path::new("config.txt")).is_err());
+
+        let content = "lto = \"thin\"\ndebug.lto = \"off\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.release.lto, Lto::Thin);
+        assert_eq!(cfg.debug.lto, Lto::Off);
+        assert!(apply_config_str(&mut cfg, "release.lto = \"fat\"", Path::new("config.txt")).is_err());
+
+        let content = "compress_binary = \"true\"\nupx_path = \"upx --best\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert!(cfg.release.compress);
+        assert!(!cfg.debug.compress);
+        assert_eq!(cfg.upx_path.args, vec!["--best"]);
+
+        let content = "post_link = \"strip ; objcopy --add-section .sig=sig.bin {exe}; sign\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        let steps: Vec<String> = cfg.release.post_link.iter().map(|c| c.to_string()).collect();
+        assert_eq!(steps, ["strip", "objcopy --add-section .sig=sig.bin {exe}", "sign"]);
+        assert_eq!(cfg.debug.post_link, cfg.release.post_link);
+
+        let toml = "[profile.release]\npost_link = [\"strip\", \"sign --key 'my key'\"]\n";
+        apply_toml_str(&mut cfg, toml, Path::new("drakkar.toml")).unwrap();
+        assert_eq!(cfg.release.post_link[1].args, vec!["--key", "my key"]);
+        assert_eq!(cfg.debug.post_link.len(), 3);
+    }
+
+    #[test]
+    fn test_toml_config() {
+        let content = r#"
+[build]
+app_name = "demo"
+parallel_jobs = 8
+incremental = false
+rebuild_policy = "hash"
+cxx_flags = "-Wall -O2"
+c_flags = ["-DNAME=\"a b\"", "-g"]
+
+[profile.release]
+strip = "separate"
+
+[libs.socket]
+windows = "ws2_32"
+"#;
+        let mut cfg = ProjectConfig::default();
+        apply_toml_str(&mut cfg, content, Path::new("drakkar.toml")).unwrap();
+        assert_eq!(cfg.app_name, "demo");
+        assert_eq!(cfg.parallel_jobs, 8);
+        assert!(!cfg.incremental);
+        assert_eq!(cfg.rebuild_policy, RebuildPolicy::Hash);
+        assert_eq!(cfg.cxx_flags, vec!["-Wall", "-O2"]);
+        assert_eq!(cfg.c_flags, vec!["-DNAME=\"a b\"", "-g"]);
+        assert_eq!(cfg.release.strip, StripMode::Separate);
+        assert_eq!(
+            cfg.lib_map.get(&("socket".to_string(), "windows".to_string())),
+            Some(&vec!["ws2_32".to_string()])
+        );
+        assert!(apply_toml_str(&mut cfg, "[build]\nparallel_jobs = \"x\"\n", Path::new("drakkar.toml")).is_err());
+        assert!(apply_toml_str(&mut cfg, "[build]\nrebuild_policy = \"ctime\"\n", Path::new("drakkar.toml")).is_err());
+
+        let dir = std::env::temp_dir().join("drakkar_test_find_config");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.txt"), "").unwrap();
+        assert_eq!(find_config(&dir), dir.join("config.txt"));
+        std::fs::write(dir.join("drakkar.toml"), "").unwrap();
+        assert_eq!(find_config(&dir), dir.join("drakkar.toml"));
+        assert_eq!(local_override_path(&dir.join("drakkar.toml")), dir.join("drakkar.local.toml"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_target_sections() {
+        let content = "[target.server]\nsource_dir = \"apps/server\"\ndeps = \"common\"\n\
+                       cxx_flags = \"-DSERVER\"\n\
+                       [target.common]\ntarget_type = \"static_lib\"\nsource_dir = \"lib/common\"\n";
+        let mut cfg = ProjectConfig::default();
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.targets.len(), 2);
+        assert_eq!(cfg.targets[0].name, "server");
+        assert_eq!(cfg.targets[0].target_type, TargetType::Executable);
+        assert_eq!(cfg.targets[0].source_dirs, vec![PathBuf::from("apps/server")]);
+        assert_eq!(cfg.targets[0].deps, vec!["common"]);
+        assert_eq!(cfg.targets[0].cxx_flags, vec!["-DSERVER"]);
+        assert_eq!(cfg.targets[1].target_type, TargetType::StaticLib);
+        assert_eq!(cfg.targets[1].output_name(), "common");
+
+        let toml = "[target.tool]\nsource_dir = \"tools\"\noutput_name = \"mytool\"\n\
+                    [target.codec]\ntarget_type = \"plugin\"\nexports = [\"codec_init\"]\n";
+        let mut cfg = ProjectConfig::default();
+        apply_toml_str(&mut cfg, toml, Path::new("drakkar.toml")).unwrap();
+        assert_eq!(cfg.targets[0].output_name(), "mytool");
+        assert_eq!(cfg.targets[1].target_type, TargetType::Plugin);
+        assert_eq!(cfg.targets[1].exports, vec!["codec_init"]);
+
+        assert!(apply_config_str(&mut cfg, "target.x.target_type = \"dll\"\n", Path::new("config.txt")).is_err());
+    }
+
+    #[test]
+    fn test_generator_sections() {
+        let toml = "[generator.proto]\ninputs = [\"proto/*.proto\"]\n\
+                    command = [\"protoc\", \"--cpp_out={out_dir}\", \"{input}\"]\n\
+                    outputs = [\"{stem}.pb.cc\", \"{stem}.pb.h\"]\ntarget = \"server\"\n";
+        let mut cfg = ProjectConfig::default();
+        apply_toml_str(&mut cfg, toml, Path::new("drakkar.toml")).unwrap();
+        assert_eq!(
+            cfg.generators,
+            vec![GeneratorRule {
+                name: "proto".to_string(),
+                inputs: vec!["proto/*.proto".to_string()],
+                command: vec![
+                    "protoc".to_string(),
+                    "--cpp_out={out_dir}".to_string(),
+                    "{input}".to_string()
+                ],
+                outputs: vec!["{stem}.pb.cc".to_string(), "{stem}.pb.h".to_string()],
+                target: Some("server".to_string()),
+            }]
+        );
+
+        let mut cfg = ProjectConfig::default();
+        let content = "generator.idl.command = \"idlc -o {out_dir} {input}\"\n";
+        apply_config_str(&mut cfg, content, Path::new("config.txt")).unwrap();
+        assert_eq!(cfg.generators[0].command, vec!["idlc", "-o", "{out_dir}", "{input}"]);
+        assert_eq!(cfg.generators[0].target, None);
+    }
+
+    #[test]
+    fn test_all_errors_reported() {
+        let mut cfg = ProjectConfig::default();
+        let content = "app_name = \"x\"\nthreads = \"maybe\"\njust words\ncxx_flags = \"-O2 'x\"\n";
+        let msg = match apply_config_str(&mut cfg, content, Path::new("config.txt")) {
+            Err(BuildError::ParseError(msg)) => msg,
+            other => panic!("{:?}", other),
+        };
+        let headers: Vec<&str> = msg.lines().filter(|l| l.starts_with("config.txt:")).collect();
+        assert_eq!(headers.len(), 3);
+        assert!(headers[0].starts_with("config.txt:2:11: "));
+        assert_eq!(headers[1], "config.txt:3:1: expected 'key = value'");
+        assert_eq!(headers[2], "config.txt:4:18: Unterminated single quote");
+        assert!(msg.ends_with("3 errors in config.txt"));
+        assert_eq!(cfg.app_name, "x");
+    }
+
+    #[test]
+    fn test_fuzz_config() {
+        for input in crate::parse::fuzz_inputs(3000) {
+            let mut cfg = ProjectConfig::default();
+            let _ = apply_config_str(&mut cfg, &input, Path::new("fuzz.txt"));
+        }
+    }
+
+    #[test]
+    fn test_tokenize_backslash_escape() {
+        let t = shell_tokenize(r"-DFOO=bar\ baz").unwrap();
+        assert_eq!(t, vec!["-DFOO=bar baz"]);
+    }
+}
@@ -0,0 +1,368 @@
+//! Dependency files: the make-style `.d` files compilers write next to
+//! each object, and `cl /showIncludes` output turned into one.
+
+use std::path::{Path, PathBuf};
+use crate::error::BuildError;
+use crate::parse::{self, Problem, Span};
+
+/// A parsed make-style dependency file: what GCC and clang write for
+/// `-MD`/`--write-dependencies`, clang's NMake flavour (`-MV`), and
+/// anything else using the same rules.
+///
+/// Format example:
+///   target/math/utils.o: src/math/utils.cpp src/math/utils.h \
+///    src/common.h
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Depfile {
+    /// What the rules build: the object, and the depfile itself when it
+    /// is given as a second target (`-MT a.o -MT a.d`).
+    pub targets: Vec<PathBuf>,
+    /// Every file the targets were built from, source first, each once.
+    pub prerequisites: Vec<PathBuf>,
+}
+
+/// Read and parse a dependency file.
+pub fn parse_depfile(dep_path: &Path) -> Result<Depfile, BuildError> {
+    let content = std::fs::read_to_string(dep_path).map_err(|e| {
+        BuildError::IoError(format!("Cannot read depfile {:?}: {}", dep_path, e))
+    })?;
+    parse_depfile_text(&content, dep_path)
+}
+
+/// `parse_depfile` for a depfile already in memory; `dep_path` only names
+/// it in errors.
+///
+/// Rules for several targets are merged. The empty rules `-MP` adds for
+/// each header (`src/common.h:`) and `.PHONY` rules only repeat
+/// prerequisites, so they are skipped.
+pub fn parse_depfile_text(content: &str, dep_path: &Path) -> Result<Depfile, BuildError> {
+    let expected_rule = |line_idx: usize| {
+        let line = content.lines().nth(line_idx).unwrap_or("");
+        let problem = Problem {
+            span: Span::within(line, line_idx + 1, line.trim()),
+            message: "expected 'target: prerequisites'".to_string(),
+        };
+        BuildError::ParseError(parse::report(dep_path, content, &[problem]))
+    };
+
+    let mut depfile = Depfile::default();
+    for (line_idx, line) in logical_lines(content) {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        let (start, end) = rule_colon(&line).ok_or_else(|| expected_rule(line_idx))?;
+        let targets = split_depfile_deps(&line[..start]);
+        let prerequisites = split_depfile_deps(&line[end..]);
+        let phony = targets.iter().any(|t| t.as_os_str() == ".PHONY")
+            || (prerequisites.is_empty() && !depfile.targets.is_empty());
+        if !phony {
+            push_new(&mut depfile.targets, targets);
+            push_new(&mut depfile.prerequisites, prerequisites);
+        }
+    }
+    if depfile.targets.is_empty() {
+        let first = content.lines().position(|l| !l.trim().is_empty()).unwrap_or(0);
+        return Err(expected_rule(first));
+    }
+    Ok(depfile)
+}
+
+/// Append the paths not already in `list`.
+fn push_new(list: &mut Vec<PathBuf>, paths: Vec<PathBuf>) {
+    for path in paths {
+        if !list.contains(&path) {
+            list.push(path);
+        }
+    }
+}
+
+/// Start and end of the colon (`:`, or NMake's `::`) ending the targets:
+/// the first one outside quotes that is followed by whitespace or the end
+/// of the line, so a drive letter (`C:\obj\main.o`) isn't mistaken for it.
+fn rule_colon(line: &str) -> Option<(usize, usize)> {
+    let bytes = line.as_bytes();
+    let mut quoted = false;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'"' => quoted = !quoted,
+            b'\\' => i += 1,
+            b':' if !quoted => {
+                let end = if bytes.get(i + 1) == Some(&b':') { i + 2 } else { i + 1 };
+                match bytes.get(end) {
+                    Some(c) if !c.is_ascii_whitespace() => {}
+                    _ => return Some((i, end)),
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+/// The rules of `content`, one per line with `\` + newline continuations
+/// joined, each with the index of the line it starts on.
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (idx, line) in content.split('\n').enumerate() {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let (start, mut joined) = current.take().unwrap_or((idx, String::new()));
+        match line.strip_suffix('\\') {
+            Some(head) => {
+                joined.push_str(head);
+                joined.push(' ');
+                current = Some((start, joined));
+            }
+            None => {
+                joined.push_str(line);
+                lines.push((start, joined));
+            }
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+/// Split dependency string by unescaped whitespace.
+/// `\ ` (backslash space) is a literal space inside a path, and `\#` and
+/// `$$` are a literal `#` and `$`. NMake-style depfiles quote such paths
+/// instead (`"my dir/a.h"`).
+/// Each resulting token is a path.
+fn split_depfile_deps(deps_str: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut current = String::new();
+    let mut chars = deps_str.chars().peekable();
+
+    while let Some(ch) = chars.next() {
+        match ch {
+            '"' => {
+                for c in chars.by_ref() {
+                    if c == '"' {
+                        break;
+                    }
+                    current.push(c);
+                }
+            }
+            '\\' => {
+                match chars.peek() {
+                    Some(' ') => {
+                        chars.next();
+                        current.push(' ');
+                    }
+                    Some('\\') => {
+                        chars.next();
+                        current.push('\\');
+                    }
+                    Some('#') => {
+                        chars.next();
+                        current.push('#');
+                    }
+                    _ => {
+                        // Keep the backslash (already handled continuation)
+                        current.push('\\');
+                    }
+                }
+            }
+            // Make's escape for a literal `$`
+            '$' if chars.peek() == Some(&'$') => {
+                chars.next();
+                current.push('$');
+            }
+            ' ' | '\t' | '\n' | '\r' => {
+                if !current.is_empty() {
+                    paths.push(PathBuf::from(&current));
+                    current.clear();
+                }
+            }
+            c => {
+                current.push(c);
+            }
+        }
+    }
+
+    if !current.is_empty() {
+        paths.push(PathBuf::from(current));
+    }
+
+    paths
+}
+
+/// Prefix `cl /showIncludes` puts before each included file (English
+/// Visual Studio installs).
+const SHOW_INCLUDES_NOTE: &str = "Note: including file:";
+
+/// Split `cl /showIncludes` output into the headers it included and the
+/// remaining output. Headers under one of `system_dirs` (the `INCLUDE`
+/// environment variable) are left out, like GCC's `-MMD`, and those under
+/// `root` are made relative to it. cl also echoes the source file name
+/// first; that line is dropped too.
+pub fn parse_show_includes(
+    output: &str,
+    source: &Path,
+    root: &Path,
+    system_dirs: &[PathBuf],
+) -> (Vec<PathBuf>, String) {
+    // Windows paths are case-insensitive
+    let is_under = |path: &Path, dir: &Path| {
+        let path = path.to_string_lossy().to_lowercase();
+        let dir = dir.to_string_lossy().to_lowercase();
+        let dir = dir.trim_end_matches(['\\', '/']);
+        !dir.is_empty()
+            && path.starts_with(dir)
+            && path[dir.len()..].starts_with(['\\', '/'])
+    };
+    let source_name = source.file_name().map(|n| n.to_string_lossy().into_owned());
+    let mut headers: Vec<PathBuf> = Vec::new();
+    let mut rest = String::new();
+    for line in output.lines() {
+        if let Some(file) = line.strip_prefix(SHOW_INCLUDES_NOTE) {
+            let path = PathBuf::from(file.trim());
+            if system_dirs.iter().any(|dir| is_under(&path, dir)) {
+                continue;
+            }
+            let path = path.strip_prefix(root).map(Path::to_path_buf).unwrap_or(path);
+            if !headers.contains(&path) {
+                headers.push(path);
+            }
+        } else if Some(line.trim()) != source_name.as_deref() {
+            rest.push_str(line);
+            rest.push('\n');
+        }
+    }
+    (headers, rest)
+}
+
+/// Write a make-style depfile for `target` that [`parse_depfile`] reads
+/// back: spaces and backslashes in paths are escaped.
+pub fn write_depfile(dep_path: &Path, target: &Path, deps: &[PathBuf]) -> Result<(), BuildError> {
+    let escape = |p: &Path| p.to_string_lossy().replace('\\', "\\\\").replace(' ', "\\ ");
+    let mut content = format!("{}:", escape(target));
+    for dep in deps {
+        content.push_str(" \\\n  ");
+        content.push_str(&escape(dep));
+    }
+    content.push('\n');
+    std::fs::write(dep_path, content)
+        .map_err(|e| BuildError::IoError(format!("Cannot write depfile {:?}: {}", dep_path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_continuation() {
+        let s = "target/a.o: src/a.cpp \\\n src/b.h\r\n\nsrc/b.h:\n";
+        let lines = logical_lines(s);
+        assert_eq!(lines[0], (0, "target/a.o: src/a.cpp   src/b.h".to_string()));
+        assert_eq!(lines[2], (3, "src/b.h:".to_string()));
+    }
+
+    #[test]
+    fn test_phony_rules_ignored() {
+        let dir = std::env::temp_dir().join("drakkar_test_depfile_phony");
+        let _ = std::fs::create_dir_all(&dir);
+        let dep = dir.join("a.d");
+        std::fs::write(&dep, "target/a.o: src/a.cpp src/common.h\n\nsrc/common.h:\n").unwrap();
+        let deps = parse_depfile(&dep).unwrap();
+        assert_eq!(deps.targets, vec![PathBuf::from("target/a.o")]);
+        assert_eq!(deps.prerequisites, vec![PathBuf::from("src/a.cpp"), PathBuf::from("src/common.h")]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_drive_letters_and_escapes() {
+        let text = "C:\\obj\\a.o: C:\\src\\a.c src/\\#1.h $$HOME.h\n";
+        let deps = parse_depfile_text(text, Path::new("a.d")).unwrap();
+        assert_eq!(deps.targets, vec![PathBuf::from("C:\\obj\\a.o")]);
+        assert_eq!(
+            deps.prerequisites,
+            vec![
+                PathBuf::from("C:\\src\\a.c"),
+                PathBuf::from("src/#1.h"),
+                PathBuf::from("$HOME.h")
+            ]
+        );
+        match parse_depfile_text("\n  garbage\n", Path::new("a.d")) {
+            Err(BuildError::ParseError(msg)) => assert_eq!(
+                msg,
+                "a.d:2:3: expected 'target: prerequisites'\n  2 |   garbage\n    |   ^^^^^^^"
+            ),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_multiple_targets_and_nmake_style() {
+        // -MT a.o -MT a.d, prerequisites split over rules, -MP and .PHONY
+        let text = "target/a.o target/a.d: src/a.c \\\n  src/a.h\n\
+                    target/a.o: src/gen.h src/a.h\n\
+                    src/a.h:\n\
+                    .PHONY: src/gen.h\n";
+        let deps = parse_depfile_text(text, Path::new("a.d")).unwrap();
+        assert_eq!(deps.targets, vec![PathBuf::from("target/a.o"), PathBuf::from("target/a.d")]);
+        assert_eq!(
+            deps.prerequisites,
+            vec![PathBuf::from("src/a.c"), PathBuf::from("src/a.h"), PathBuf::from("src/gen.h")]
+        );
+
+        // clang -MV: spaced colon, quoted paths, CRLF
+        let text = "\"C:\\obj dir\\a.obj\" : \"C:\\src\\a b.c\" \\\r\n  C:\\src\\a.h\r\n";
+        let deps = parse_depfile_text(text, Path::new("a.d")).unwrap();
+        assert_eq!(deps.targets, vec![PathBuf::from("C:\\obj dir\\a.obj")]);
+        assert_eq!(
+            deps.prerequisites,
+            vec![PathBuf::from("C:\\src\\a b.c"), PathBuf::from("C:\\src\\a.h")]
+        );
+        let deps = parse_depfile_text("lib.o:: lib.c\n", Path::new("a.d")).unwrap();
+        assert_eq!(deps.prerequisites, vec![PathBuf::from("lib.c")]);
+
+        match parse_depfile_text("a.o: a.c\nstray words\n", Path::new("a.d")) {
+            Err(BuildError::ParseError(msg)) => assert!(msg.starts_with("a.d:2:1: "), "{}", msg),
+            other => panic!("{:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_split_deps() {
+        let deps = split_depfile_deps(" src/a.cpp src/b.h  src/c.h ");
+        assert_eq!(deps.len(), 3);
+    }
+
+    #[test]
+    fn test_escaped_space_in_path() {
+        let deps = split_depfile_deps(r" src/a\ b.h src/c.h");
+        assert_eq!(deps.len(), 2);
+        assert_eq!(deps[0], PathBuf::from("src/a b.h"));
+    }
+
+    #[test]
+    fn test_show_includes_roundtrip() {
+        let output = "main.cpp\n\
+                      Note: including file: /work/proj/src/util.h\n\
+                      Note: including file:  /opt/msvc/include/vector\n\
+                      Note: including file:  /work/proj/src/my dir/a.h\n\
+                      src/main.cpp(3): warning C4100: 'x': unreferenced parameter\n";
+        let (headers, rest) = parse_show_includes(
+            output,
+            Path::new("src/main.cpp"),
+            Path::new("/work/proj"),
+            &[PathBuf::from("/opt/msvc/include/")],
+        );
+        assert_eq!(headers, vec![PathBuf::from("src/util.h"), PathBuf::from("src/my dir/a.h")]);
+        assert_eq!(rest, "src/main.cpp(3): warning C4100: 'x': unreferenced parameter\n");
+
+        let dir = std::env::temp_dir().join("drakkar_test_depfile_write");
+        let _ = std::fs::create_dir_all(&dir);
+        let dep = dir.join("main.d");
+        let mut deps = vec![PathBuf::from("src/main.cpp")];
+        deps.extend(headers);
+        deps.push(PathBuf::from(r"C:\sdk\gl.h"));
+        write_depfile(&dep, Path::new("target/main.obj"), &deps).unwrap();
+        assert_eq!(parse_depfile(&dep).unwrap().prerequisites, deps);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,520 @@
+//! Actionable hints for common build failures, and compiler output split
+//! into individual diagnostics.
+//!
+//! Compiler and linker output is pattern-matched for a handful of well-known
+//! failure shapes (missing header, undefined reference, missing library,
+//! missing compiler) and turned into short suggestions pointing at the
+//! config key that usually fixes them.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::ice;
+
+/// How deep the project tree is searched for a missing header.
+const MAX_SEARCH_DEPTH: usize = 8;
+
+/// Collect hints for an error, recursing into `MultipleErrors`.
+/// Duplicate hints are reported once.
+pub fn hints_for(err: &BuildError) -> Vec<String> {
+    let mut hints = Vec::new();
+    collect_hints(err, &mut hints);
+    hints
+}
+
+fn collect_hints(err: &BuildError, out: &mut Vec<String>) {
+    let found = match err {
+        BuildError::CompileError { stderr, code, .. } => {
+            let mut hints = compile_hints(stderr);
+            if ice::is_ice(stderr, *code) {
+                hints.push(
+                    "the compiler crashed, which is a compiler bug rather than a problem in \
+                     the code: rebuild with --save-ice-repro to bundle the preprocessed source, \
+                     flags and compiler version for a bug report"
+                        .to_string(),
+                );
+            }
+            hints
+        }
+        BuildError::LinkError { stderr, .. } => link_hints(stderr),
+        BuildError::IoError(msg) => io_hints(msg),
+        BuildError::MultipleErrors(errs) => {
+            for e in errs {
+                collect_hints(e, out);
+            }
+            Vec::new()
+        }
+        _ => Vec::new(),
+    };
+    for hint in found {
+        if !out.contains(&hint) {
+            out.push(hint);
+        }
+    }
+}
+
+fn compile_hints(stderr: &str) -> Vec<String> {
+    let mut hints = Vec::new();
+    for header in missing_headers(stderr) {
+        hints.push(format!(
+            "'{}' was not found: add its directory to include_dirs in config.txt, \
+             or install the development package that provides it{}",
+            header,
+            dev_package_suggestion(&header)
+                .map(|p| format!(" (e.g. {})", p))
+                .unwrap_or_default()
+        ));
+    }
+    hints
+}
+
+fn link_hints(stderr: &str) -> Vec<String> {
+    let mut hints = Vec::new();
+    let mut symbols: Vec<String> = Vec::new();
+
+    for line in stderr.lines() {
+        if let Some(sym) = undefined_symbol(line) {
+            if !symbols.contains(&sym) {
+                symbols.push(sym);
+            }
+        } else if let Some(idx) = line.find("cannot find -l") {
+            let lib = line[idx + "cannot find -l".len()..]
+                .split(|c: char| c.is_whitespace() || c == ':')
+                .next()
+                .unwrap_or("");
+            hints.push(format!(
+                "library '{}' is not installed or not on the search path: install it, \
+                 or add -L<dir> to ld_flags",
+                lib
+            ));
+        }
+    }
+
+    for sym in symbols {
+        match fix_for_symbol(&sym) {
+            Some(fix) => hints.push(format!("undefined reference to '{}': {}", sym, fix)),
+            None => hints.push(format!(
+                "undefined reference to '{}': add the library that defines it to link_libs \
+                 (e.g. -lfoo), or check that its source file is under source_dir",
+                sym
+            )),
+        }
+    }
+    hints
+}
+
+fn io_hints(msg: &str) -> Vec<String> {
+    let not_found = msg.contains("No such file") || msg.contains("not found")
+        || msg.contains("cannot find the file");
+    if !not_found {
+        return Vec::new();
+    }
+    if msg.starts_with("Failed to spawn compiler") || msg.starts_with("Failed to spawn linker") {
+        vec![
+            "the compiler could not be started: install gcc/g++, or point gcc_path / gpp_path \
+             in config.txt at the compiler you want to use"
+                .to_string(),
+        ]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Headers a failed compile couldn't find, each paired with a directory
+/// that has it and isn't in include_dirs yet: the nearest one in the
+/// project tree, or under the `include` dir next to an `-L` dir in ld_flags.
+pub fn include_dir_suggestions(err: &BuildError, config: &ProjectConfig) -> Vec<(String, PathBuf)> {
+    let mut headers = Vec::new();
+    collect_missing_headers(err, &mut headers);
+    if headers.is_empty() {
+        return Vec::new();
+    }
+
+    let mut roots = vec![PathBuf::from(".")];
+    let mut flags = config.ld_flags.iter();
+    while let Some(flag) = flags.next() {
+        let dir = match flag.strip_prefix("-L") {
+            Some("") => match flags.next() {
+                Some(dir) => dir.as_str(),
+                None => break,
+            },
+            Some(dir) => dir,
+            None => continue,
+        };
+        if let Some(include) = Path::new(dir).parent().map(|p| p.join("include")) {
+            if include.is_dir() && !roots.contains(&include) {
+                roots.push(include);
+            }
+        }
+    }
+    let skip = [config.temp_dir.clone(), config.output_dir.clone()];
+    let known = |dir: &Path| config.include_dirs.iter().any(|d| same_path(d, dir));
+
+    let mut suggestions = Vec::new();
+    for header in headers {
+        let found = roots
+            .iter()
+            .flat_map(|root| find_include_dirs(&header, root, &skip))
+            .find(|dir| !known(dir));
+        if let Some(dir) = found {
+            suggestions.push((header, dir));
+        }
+    }
+    suggestions
+}
+
+fn collect_missing_headers(err: &BuildError, out: &mut Vec<String>) {
+    match err {
+        BuildError::CompileError { stderr, .. } => {
+            for header in missing_headers(stderr) {
+                if !out.contains(&header) {
+                    out.push(header);
+                }
+            }
+        }
+        BuildError::MultipleErrors(errs) => {
+            for e in errs {
+                collect_missing_headers(e, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `src/` and `./src` are the same directory.
+fn same_path(a: &Path, b: &Path) -> bool {
+    let a = a.components().filter(|c| *c != Component::CurDir);
+    a.eq(b.components().filter(|c| *c != Component::CurDir))
+}
+
+/// Every directory under `root` that `#include "<header>"` would resolve
+/// from, shallowest first. Hidden and `skip`ped directories are left out.
+pub fn find_include_dirs(header: &str, root: &Path, skip: &[PathBuf]) -> Vec<PathBuf> {
+    let header = Path::new(header);
+    let mut found = Vec::new();
+    let mut level = vec![root.to_path_buf()];
+    for _ in 0..MAX_SEARCH_DEPTH {
+        let mut next = Vec::new();
+        for dir in level {
+            if dir.join(header).is_file() && !found.contains(&dir) {
+                found.push(dir.clone());
+            }
+            let mut entries: Vec<PathBuf> = std::fs::read_dir(&dir)
+                .into_iter()
+                .flatten()
+                .flatten()
+                .filter(|e| !e.file_name().to_string_lossy().starts_with('.'))
+                // Relative to the project, like include_dirs
+                .map(|e| if dir == Path::new(".") { e.file_name().into() } else { e.path() })
+                .filter(|p| p.is_dir() && !skip.iter().any(|s| same_path(s, p)))
+                .collect();
+            entries.sort();
+            next.extend(entries);
+        }
+        level = next;
+    }
+    found
+}
+
+/// Extract header names from `fatal error: foo.h: No such file or directory`.
+fn missing_headers(stderr: &str) -> Vec<String> {
+    let mut headers = Vec::new();
+    for line in stderr.lines() {
+        if !line.contains("No such file or directory") && !line.contains("file not found") {
+            continue;
+        }
+        // gcc: "fatal error: foo.h: No such file or directory"
+        // clang: "fatal error: 'foo.h' file not found"
+        let after = match line.find("fatal error:") {
+            Some(idx) => line[idx + "fatal error:".len()..].trim(),
+            None => continue,
+        };
+        let name = if let Some(rest) = after.strip_prefix('\'') {
+            rest.split('\'').next().unwrap_or("")
+        } else {
+            after.split(": ").next().unwrap_or("")
+        };
+        if !name.is_empty() && !headers.iter().any(|h| h == name) {
+            headers.push(name.to_string());
+        }
+    }
+    headers
+}
+
+/// Extract the symbol from `undefined reference to `foo'` (GNU ld) or
+/// `undefined symbol: foo` (lld).
+fn undefined_symbol(line: &str) -> Option<String> {
+    if let Some(idx) = line.find("undefined reference to") {
+        let rest = line[idx + "undefined reference to".len()..].trim();
+        let sym = rest.trim_matches(|c| c == '`' || c == '\'' || c == '‘' || c == '’');
+        return Some(sym.to_string());
+    }
+    if let Some(idx) = line.find("undefined symbol:") {
+        return Some(line[idx + "undefined symbol:".len()..].trim().to_string());
+    }
+    None
+}
+
+/// What to set for well-known symbols whose library is not linked by
+/// default. Threads need `-pthread` when compiling too, which `threads`
+/// adds to both steps.
+fn fix_for_symbol(sym: &str) -> Option<String> {
+    let base = sym.split('(').next().unwrap_or(sym);
+    let lib = match base {
+        "sqrt" | "sin" | "cos" | "tan" | "pow" | "exp" | "log" | "floor" | "ceil" | "fmod"
+        | "sqrtf" | "sinf" | "cosf" | "powf" => "-lm",
+        "pthread_create" | "pthread_join" | "pthread_mutex_lock" | "pthread_detach" => {
+            return Some("set threads = \"true\"".to_string())
+        }
+        "dlopen" | "dlsym" | "dlclose" | "dlerror" => "-ldl",
+        "clock_gettime" | "shm_open" => "-lrt",
+        _ => return None,
+    };
+    Some(format!("add \"{}\" to link_libs", lib))
+}
+
+/// Best-effort Debian-style package name for well-known third-party headers.
+pub(crate) fn dev_package_suggestion(header: &str) -> Option<&'static str> {
+    let first = header.split('/').next().unwrap_or(header);
+    match first {
+        "zlib.h" => Some("zlib1g-dev"),
+        "curl" => Some("libcurl4-openssl-dev"),
+        "openssl" => Some("libssl-dev"),
+        "SDL2" | "SDL.h" => Some("libsdl2-dev"),
+        "GL" => Some("libgl-dev"),
+        "png.h" => Some("libpng-dev"),
+        "sqlite3.h" => Some("libsqlite3-dev"),
+        "boost" => Some("libboost-dev"),
+        "ncurses.h" | "curses.h" => Some("libncurses-dev"),
+        _ => None,
+    }
+}
+
+/// How serious a compiler diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// `error:` and `fatal error:`.
+    Error,
+    /// `warning:`
+    Warning,
+    /// `note:`, context for the diagnostic before it.
+    Note,
+}
+
+impl Severity {
+    /// The lowercase name, as compilers print it.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+        }
+    }
+}
+
+/// One located message from a compiler.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    /// The file it points at, as the compiler printed it.
+    pub file: String,
+    /// 1-based line.
+    pub line: usize,
+    /// 1-based column, when the compiler gave one.
+    pub col: Option<usize>,
+    /// How serious it is.
+    pub severity: Severity,
+    /// The message after the severity.
+    pub message: String,
+}
+
+const SEVERITIES: [(&str, Severity); 4] = [
+    ("fatal error", Severity::Error),
+    ("error", Severity::Error),
+    ("warning", Severity::Warning),
+    ("note", Severity::Note),
+];
+
+/// The diagnostics in compiler output: GCC/Clang `file:line[:col]:
+/// severity: message` and MSVC `file(line[,col]): severity C1234: message`
+/// lines. Context such as `In file included from`, source excerpts and
+/// carets is skipped.
+pub fn parse_diagnostics(output: &str) -> Vec<Diagnostic> {
+    output
+        .lines()
+        .filter_map(|line| gcc_diagnostic(line).or_else(|| msvc_diagnostic(line)))
+        .collect()
+}
+
+fn gcc_diagnostic(line: &str) -> Option<Diagnostic> {
+    // The earliest marker, so a message quoting "error: " isn't split there
+    let (pos, marker, severity) = SEVERITIES
+        .iter()
+        .filter_map(|&(word, severity)| {
+            let marker = format!(": {}: ", word);
+            line.find(&marker).map(|pos| (pos, marker, severity))
+        })
+        .min_by_key(|(pos, _, _)| *pos)?;
+    let loc = &line[..pos];
+    let mut parts = loc.rsplitn(3, ':');
+    let last = parts.next()?.parse::<usize>().ok()?;
+    let (file, line_no, col) = match parts.next().map(str::parse::<usize>) {
+        Some(Ok(line_no)) => (parts.next()?, line_no, Some(last)),
+        // `C:\src\a.c:12`: what precedes the line number is all file name
+        _ => (&loc[..loc.rfind(':')?], last, None),
+    };
+    if file.is_empty() {
+        return None;
+    }
+    Some(Diagnostic {
+        file: file.to_string(),
+        line: line_no,
+        col,
+        severity,
+        message: line[pos + marker.len()..].to_string(),
+    })
+}
+
+fn msvc_diagnostic(line: &str) -> Option<Diagnostic> {
+    let pos = line.find("): ")?;
+    let open = line[..pos].rfind('(')?;
+    let mut numbers = line[open + 1..pos].split(',').map(|n| n.trim().parse::<usize>());
+    let line_no = numbers.next()?.ok()?;
+    let col = match numbers.next() {
+        Some(n) => Some(n.ok()?),
+        None => None,
+    };
+    let rest = &line[pos + 3..];
+    let (word, severity) = SEVERITIES.iter().find(|(word, _)| rest.starts_with(word))?;
+    // `warning C4100: ...` keeps its code; `note: ...` has none
+    let message = rest[word.len()..].trim_start_matches(':').trim();
+    if open == 0 || message.is_empty() {
+        return None;
+    }
+    Some(Diagnostic {
+        file: line[..open].to_string(),
+        line: line_no,
+        col,
+        severity: *severity,
+        message: message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_missing_header_hint() {
+        let err = BuildError::CompileError {
+            src: PathBuf::from("src/main.cpp"),
+            stderr: "src/main.cpp:1:10: fatal error: zlib.h: No such file or directory\n".to_string(),
+            code: Some(1),
+        };
+        let hints = hints_for(&err);
+        assert_eq!(hints.len(), 1);
+        assert!(hints[0].contains("include_dirs"));
+        assert!(hints[0].contains("zlib1g-dev"));
+    }
+
+    #[test]
+    fn test_include_dir_suggestions() {
+        let dir = std::env::temp_dir().join("drakkar_test_include_suggestions");
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["third_party/v/include/v", "sdk/include", "sdk/lib", "target/gen", ".git"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        for header in ["third_party/v/include/v/v.h", "target/gen/v/v.h", "sdk/include/sdk.h"] {
+            std::fs::create_dir_all(dir.join(header).parent().unwrap()).unwrap();
+            std::fs::write(dir.join(header), "").unwrap();
+        }
+        let skip = [dir.join("target")];
+        assert_eq!(find_include_dirs("v/v.h", &dir, &skip), vec![dir.join("third_party/v/include")]);
+        assert!(find_include_dirs("w.h", &dir, &skip).is_empty());
+
+        let err = BuildError::MultipleErrors(vec![BuildError::CompileError {
+            src: PathBuf::from("src/main.cpp"),
+            stderr: "src/main.cpp:2:10: fatal error: sdk.h: No such file or directory\n".to_string(),
+            code: Some(1),
+        }]);
+        let mut config = ProjectConfig {
+            ld_flags: vec!["-L".to_string(), dir.join("sdk/lib").to_string_lossy().into_owned()],
+            ..ProjectConfig::default()
+        };
+        let sdk = dir.join("sdk/include");
+        assert_eq!(include_dir_suggestions(&err, &config), vec![("sdk.h".to_string(), sdk.clone())]);
+        config.include_dirs.push(sdk);
+        assert!(include_dir_suggestions(&err, &config).is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_undefined_reference_hint() {
+        let err = BuildError::LinkError {
+            stderr: "main.cpp:(.text+0x1a): undefined reference to `sqrt'\n\
+                     main.cpp:(.text+0x2a): undefined reference to `sqrt'\n"
+                .to_string(),
+            code: Some(1),
+        };
+        let hints = hints_for(&err);
+        assert_eq!(hints, vec!["undefined reference to 'sqrt': add \"-lm\" to link_libs"]);
+
+        let err = BuildError::LinkError {
+            stderr: "main.c:(.text+0x1a): undefined reference to `pthread_create'\n".to_string(),
+            code: Some(1),
+        };
+        let hints = hints_for(&err);
+        assert_eq!(hints, vec!["undefined reference to 'pthread_create': set threads = \"true\""]);
+    }
+
+    #[test]
+    fn test_compiler_not_found_hint() {
+        let err = BuildError::IoError(
+            "Failed to spawn compiler 'gcc-99': No such file or directory (os error 2)".to_string(),
+        );
+        assert!(hints_for(&err)[0].contains("gcc_path"));
+    }
+
+    #[test]
+    fn test_parse_diagnostics() {
+        let output = "In file included from src/main.cpp:1:\n\
+                      src/util.h:3:5: warning: unused variable 'x' [-Wunused-variable]\n\
+                      \x20   3 |     int x;\n\
+                      \x20     |         ^\n\
+                      C:\\proj\\a.c:12: error: expected ';': error: twice\n\
+                      src/b.c:1:10: fatal error: foo.h: No such file or directory\n\
+                      src\\c.cpp(7,3): error C2065: 'y': undeclared identifier\n\
+                      src\\c.cpp(9): note: see declaration\n\
+                      collect2: error: ld returned 1 exit status\n";
+        let found: Vec<(String, usize, Option<usize>, &str, String)> = parse_diagnostics(output)
+            .into_iter()
+            .map(|d| (d.file, d.line, d.col, d.severity.as_str(), d.message))
+            .collect();
+        let expect = |file: &str, line, col, sev, msg: &str| {
+            (file.to_string(), line, col, sev, msg.to_string())
+        };
+        assert_eq!(
+            found,
+            vec![
+                expect("src/util.h", 3, Some(5), "warning", "unused variable 'x' [-Wunused-variable]"),
+                expect("C:\\proj\\a.c", 12, None, "error", "expected ';': error: twice"),
+                expect("src/b.c", 1, Some(10), "error", "foo.h: No such file or directory"),
+                expect("src\\c.cpp", 7, Some(3), "error", "C2065: 'y': undeclared identifier"),
+                expect("src\\c.cpp", 9, None, "note", "see declaration"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hints_deduplicated_across_errors() {
+        let compile = || BuildError::CompileError {
+            src: PathBuf::from("a.c"),
+            stderr: "a.c:1:10: fatal error: 'foo.h' file not found".to_string(),
+            code: Some(1),
+        };
+        let err = BuildError::MultipleErrors(vec![compile(), compile()]);
+        assert_eq!(hints_for(&err).len(), 1);
+    }
+}
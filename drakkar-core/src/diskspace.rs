@@ -0,0 +1,132 @@
+//! Disk-space preflight (`disk_check`, on by default).
+//!
+//! Before compiling, the space the build will take is estimated and
+//! compared with what is free where temp_dir and output_dir live, so a full
+//! disk stops the build with a clear message rather than leaving truncated
+//! objects halfway through.
+//!
+//! Objects that already exist are assumed to be replaced by ones of about
+//! the same size. A new object is predicted from its source's size, scaled
+//! by the object-to-source ratio of the objects already built, or by 8 on
+//! a first build. The linked output is assumed to grow by as much as the
+//! new objects add up to.
+
+use std::path::Path;
+
+use crate::build::ObjectFile;
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::platform;
+use crate::ui;
+
+/// Object size per byte of source when nothing has been built yet; debug
+/// info and inlined headers make objects much larger than their sources.
+const DEFAULT_RATIO: f64 = 8.0;
+
+/// Room kept free for depfiles, linker temporaries and the state file.
+const HEADROOM: u64 = 16 * 1024 * 1024;
+
+/// Bytes `to_compile` will add to temp_dir. `built` are objects of the
+/// same build that are up to date, used for the size ratio.
+pub fn estimate(to_compile: &[ObjectFile], built: &[ObjectFile]) -> u64 {
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).ok();
+
+    let (obj_bytes, src_bytes) = to_compile
+        .iter()
+        .chain(built)
+        .filter_map(|o| Some((size(&o.obj_path)?, size(&o.src.path)?)))
+        .fold((0u64, 0u64), |(o, s), (ob, sb)| (o + ob, s + sb));
+    let ratio = if src_bytes > 0 {
+        obj_bytes as f64 / src_bytes as f64
+    } else {
+        DEFAULT_RATIO
+    };
+
+    to_compile
+        .iter()
+        .filter(|o| !o.obj_path.exists())
+        .map(|o| (size(&o.src.path).unwrap_or(0) as f64 * ratio) as u64)
+        .sum()
+}
+
+/// Fail if temp_dir or output_dir doesn't have room for `needed` bytes of
+/// new objects and the output they link into. Filesystems whose free space
+/// can't be queried are not checked.
+pub fn check(config: &ProjectConfig, needed: u64) -> Result<(), BuildError> {
+    let temp = &config.temp_dir;
+    let output = &config.output_dir;
+    let mut wants = vec![(temp, needed + HEADROOM, "temp_dir")];
+    if platform::same_filesystem(temp, output) {
+        wants[0].1 += needed;
+    } else {
+        wants.push((output, needed + HEADROOM, "output_dir"));
+    }
+
+    for (dir, want, key) in wants {
+        if let Some(free) = platform::free_space(dir) {
+            if free < want {
+                return Err(BuildError::IoError(format!(
+                    "Not enough disk space: the build needs about {} in {} {:?}, but only {} is free. \
+                     Free some space or point {} at another disk (disk_check = \"false\" skips this check)",
+                    ui::human_size(want),
+                    key,
+                    dir,
+                    ui::human_size(free),
+                    key
+                )));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use std::path::PathBuf;
+
+    fn object(dir: &Path, name: &str, src_bytes: usize, obj_bytes: Option<usize>) -> ObjectFile {
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: dir.join(format!("{}.c", name)),
+                rel_path: PathBuf::from(format!("{}.c", name)),
+                language: Language::C,
+            },
+            obj_path: dir.join(format!("{}.o", name)),
+            dep_path: dir.join(format!("{}.d", name)),
+        };
+        std::fs::write(&obj.src.path, "x".repeat(src_bytes)).unwrap();
+        if let Some(n) = obj_bytes {
+            std::fs::write(&obj.obj_path, "x".repeat(n)).unwrap();
+        }
+        obj
+    }
+
+    #[test]
+    fn test_estimate_uses_ratio_of_built_objects() {
+        let dir = std::env::temp_dir().join("drakkar_test_diskspace");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let fresh = object(&dir, "fresh", 100, None);
+        assert_eq!(estimate(std::slice::from_ref(&fresh), &[]), 800);
+
+        // a.o is 3x its source; the rebuilt b.o replaces itself
+        let a = object(&dir, "a", 100, Some(300));
+        let b = object(&dir, "b", 50, Some(150));
+        assert_eq!(estimate(&[fresh, b], &[a]), 300);
+
+        let config = ProjectConfig {
+            temp_dir: dir.clone(),
+            output_dir: dir.clone(),
+            ..ProjectConfig::default()
+        };
+        assert!(check(&config, 1024).is_ok());
+        if platform::free_space(&dir).is_some() {
+            let err = check(&config, u64::MAX / 4).unwrap_err().to_string();
+            assert!(err.contains("Not enough disk space"), "{}", err);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
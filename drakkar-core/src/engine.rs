@@ -0,0 +1,773 @@
+//! The incremental build: collect a unit's sources, decide which objects
+//! are stale, compile those on the worker pool (or fetch them from a shared
+//! temp dir or the object cache), then link or archive the result.
+//!
+//! [`build_project`] is the whole of `drakkar build`; [`compile_unit`] stops
+//! after compiling, for callers that link objects themselves.
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::build::{
+    archive_objects, build_archive_args, build_compile_args, collect_sources,
+    compile_flag_conflicts, compile_flags_hash, compress_binary, content_hashes, executable_path,
+    install_linked, link_map_path, link_objects, linked_path, object_path_for, plan_executables,
+    plugin_path, post_link_hash, post_link_steps, prepare_build_dirs, rebuild_reason, relink_needed,
+    resolve_targets, run_post_link_command, sign_binary, signer, split_debug_info, static_lib_path,
+    target_config, warnings_path, build_link_args, Language, ObjectFile, PostLinkStep, RebuildStats,
+};
+use crate::cache::ObjectCache;
+use crate::codegen;
+use crate::compdb;
+use crate::config::{BuildProfile, IncludeCheck, ProjectConfig, RebuildPolicy, TargetType};
+use crate::diskspace;
+use crate::error::BuildError;
+use crate::estimate;
+use crate::events;
+use crate::hygiene::check_includes;
+use crate::linkmap;
+use crate::messages::{tr, Msg};
+use crate::probe;
+use crate::prune;
+use crate::sandbox;
+use crate::shared::SharedDir;
+use crate::state::{BuildState, ObjectState};
+use crate::status::{count_warnings, BuildSummary};
+use crate::trace;
+use crate::ui::{self, report_warning, PhaseTimings};
+use crate::worker::WorkerPool;
+
+/// Copy the linked binary to `out_exe` and run the post-link steps on it.
+fn run_post_link(
+    steps: &[PostLinkStep],
+    linked: &Path,
+    out_exe: &Path,
+    config: &ProjectConfig,
+) -> Result<(), BuildError> {
+    install_linked(linked, out_exe)?;
+    for step in steps {
+        match step {
+            PostLinkStep::SplitDebug => split_debug_info(out_exe, config, config.verbose)?,
+            PostLinkStep::Compress => {
+                let (before, after) = compress_binary(out_exe, config, config.verbose)?;
+                ui::line(&format!(
+                    "  \x1b[36mCompressed\x1b[0m {}: {} → {} ({:.0}%)",
+                    ui::path(out_exe),
+                    ui::human_size(before),
+                    ui::human_size(after),
+                    after as f64 * 100.0 / before.max(1) as f64
+                ));
+            }
+            PostLinkStep::Command(cmd) => run_post_link_command(cmd, out_exe, config.verbose)?,
+            PostLinkStep::Sign => {
+                sign_binary(out_exe, config, config.verbose)?;
+                ui::line(&format!("  \x1b[36mSigned\x1b[0m {}", ui::path(out_exe)));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Write the compilation database for the whole project: every target when
+/// `[target.*]` sections exist, whichever of them is being built.
+pub fn write_compdb(
+    path: &Path,
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> Result<usize, BuildError> {
+    let configs = if config.targets.is_empty() {
+        vec![config.clone()]
+    } else {
+        config
+            .targets
+            .iter()
+            .map(|t| target_config(config, t))
+            .collect::<Result<_, _>>()?
+    };
+    let mut units = Vec::new();
+    for cfg in &configs {
+        let objects: Vec<ObjectFile> = collect_sources(cfg)?
+            .sources
+            .iter()
+            .map(|src| object_path_for(src, cfg))
+            .collect();
+        units.push((cfg, objects));
+    }
+    let units: Vec<(&ProjectConfig, &[ObjectFile])> =
+        units.iter().map(|(cfg, objects)| (*cfg, objects.as_slice())).collect();
+    compdb::write(path, &units, profile, extra_flags)
+}
+
+/// Print the cached warnings of up-to-date objects; returns how many.
+fn replay_warnings(up_to_date: &[ObjectFile]) -> usize {
+    let cached: Vec<String> = up_to_date
+        .iter()
+        .filter_map(|obj| std::fs::read_to_string(warnings_path(obj)).ok())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if cached.is_empty() {
+        return 0;
+    }
+    ui::line(&format!(
+        "  \x1b[33mReplaying\x1b[0m warnings from {} up-to-date file(s)",
+        cached.len()
+    ));
+    for w in &cached {
+        eprint!("{}", w);
+    }
+    cached.iter().map(|w| count_warnings(w)).sum()
+}
+
+/// Report headers included from outside source_dir/vendor_dirs/include_dirs.
+/// Violations are warnings, or a build failure with `include_check = "error"`.
+fn check_include_hygiene(objects: &[ObjectFile], config: &ProjectConfig) -> Result<(), BuildError> {
+    let mut errors: Vec<BuildError> = check_includes(objects, config)
+        .into_iter()
+        .map(|v| BuildError::IncludeViolation {
+            src: v.src,
+            header: v.header,
+        })
+        .collect();
+
+    if config.include_check == IncludeCheck::Warn || errors.is_empty() {
+        for e in &errors {
+            report_warning(e);
+        }
+        return Ok(());
+    }
+
+    if errors.len() == 1 {
+        Err(errors.remove(0))
+    } else {
+        Err(BuildError::MultipleErrors(errors))
+    }
+}
+
+/// Build the project, or with `[target.*]` sections the `target` named
+/// (every target when None) after the libraries it depends on. Then, with
+/// `temp_max_size_mb`, evict objects the build didn't use.
+pub fn build_project(
+    config: &Arc<ProjectConfig>,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    target: Option<&str>,
+) -> Result<BuildSummary, BuildError> {
+    probe::check(config, profile, extra_flags)?;
+    let summary = build_targets(config, profile, extra_flags, target)?;
+    if let Some(max_mb) = config.temp_max_size_mb {
+        let keep: HashSet<PathBuf> = summary.objects.iter().cloned().collect();
+        let evicted = prune::enforce_limit(&config.temp_dir, max_mb * 1024 * 1024, &keep);
+        if evicted.files > 0 {
+            ui::line(&format!(
+                "  \x1b[36mEvicted\x1b[0m {} old object(s), {}, to keep temp_dir under {} MB",
+                evicted.files,
+                ui::human_size(evicted.bytes),
+                max_mb
+            ));
+        }
+    }
+    Ok(summary)
+}
+
+fn build_targets(
+    config: &Arc<ProjectConfig>,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    target: Option<&str>,
+) -> Result<BuildSummary, BuildError> {
+    if config.targets.is_empty() {
+        if let Some(name) = target {
+            return Err(BuildError::ConfigError(format!(
+                "no target named '{}': the config has no [target.<name>] sections",
+                name
+            )));
+        }
+        return build_unit(config, profile, extra_flags, TargetType::Executable);
+    }
+
+    let targets = resolve_targets(config, target)?;
+    // Written before compiling so editors pick up new files even if the build fails
+    if let Some(path) = compdb::output_path(config) {
+        if let Err(e) = write_compdb(&path, config, profile, extra_flags) {
+            report_warning(&e);
+        }
+    }
+
+    let mut summary = BuildSummary::default();
+    for target in targets {
+        let unit_config = Arc::new(target_config(config, target)?);
+        summary.add(build_unit(&unit_config, profile, extra_flags, target.target_type)?);
+    }
+    Ok(summary)
+}
+
+/// Compile one set of sources and link them into executables (or a
+/// loadable module for a `plugin` target), or for a `static_lib` target,
+/// archive them.
+fn build_unit(
+    config: &Arc<ProjectConfig>,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    target_type: TargetType,
+) -> Result<BuildSummary, BuildError> {
+    let t_start = std::time::Instant::now();
+    let mut timings = PhaseTimings::default();
+    let CompiledUnit {
+        objects: all_objects,
+        mut state,
+        compiled: compiled_count,
+        up_to_date: up_to_date_count,
+        warnings,
+        cache_hits,
+        cache_misses,
+    } = compile_unit(config, profile, extra_flags, &mut timings)?;
+    let objects: Vec<PathBuf> = all_objects.iter().map(|o| o.obj_path.clone()).collect();
+
+    if target_type == TargetType::StaticLib {
+        let out_lib = static_lib_path(config, &config.app_name);
+        let (archiver, args) = build_archive_args(&all_objects, &out_lib, config);
+        let archive_hash = compile_flags_hash(&archiver, &args, config);
+        if relink_needed(&all_objects, &out_lib, config, &state, &archive_hash) {
+            ui::line(&format!("  \x1b[36m{}\x1b[0m {}", tr(Msg::Archiving), ui::path(&out_lib)));
+            let started = Instant::now();
+            PhaseTimings::measure(&mut timings.link, || {
+                archive_objects(&all_objects, &out_lib, config, config.verbose)
+            })?;
+            trace::record(&out_lib.display().to_string(), "archive", trace::MAIN_THREAD, started);
+            events::link(&out_lib, all_objects.len());
+            state.set(
+                &out_lib,
+                ObjectState {
+                    flags_hash: archive_hash,
+                    ..Default::default()
+                },
+            );
+            state.save()?;
+        } else {
+            ui::line(&format!("  \x1b[32m{}\x1b[0m {}", tr(Msg::UpToDate), ui::path(&out_lib)));
+        }
+        ui::line(&format!(
+            "\x1b[32m{}\x1b[0m {:?} in {:.2}s → {}",
+            tr(Msg::Finished),
+            profile,
+            t_start.elapsed().as_secs_f64(),
+            ui::path(&out_lib)
+        ));
+        return Ok(BuildSummary {
+            libraries: vec![out_lib],
+            compiled: compiled_count,
+            up_to_date: up_to_date_count,
+            warnings,
+            cache_hits,
+            cache_misses,
+            objects,
+            ..Default::default()
+        });
+    }
+
+    // Link
+    let executables = plan_executables(all_objects, config)?;
+    if executables.is_empty() {
+        return Err(BuildError::ConfigError(
+            "bin_discovery found no entry points (main.*, *_main.* or bin/)".to_string(),
+        ));
+    }
+
+    for extra in &config.extra_objects {
+        if !extra.is_file() {
+            return Err(BuildError::IoError(format!(
+                "extra_objects: {:?} does not exist",
+                extra
+            )));
+        }
+    }
+
+    let mut out_exes = Vec::new();
+    for exe in &executables {
+        let out_exe = match target_type {
+            TargetType::Plugin => plugin_path(config, &exe.name),
+            _ => executable_path(config, &exe.name),
+        };
+        let steps = post_link_steps(config, profile, target_type);
+        let link_out = if steps.is_empty() {
+            out_exe.clone()
+        } else {
+            linked_path(config, &out_exe)
+        };
+        let (linker, mut args) =
+            build_link_args(&exe.objects, &link_out, config, profile, extra_flags);
+        // The export list is a file; changing it must still relink
+        args.extend(config.exports.iter().cloned());
+        let link_hash = compile_flags_hash(&linker, &args, config);
+        let relink = relink_needed(&exe.objects, &link_out, config, &state, &link_hash);
+
+        if relink {
+            if steps.contains(&PostLinkStep::Compress) {
+                config.upx_path.validate("upx_path")?;
+            }
+            if steps.contains(&PostLinkStep::Sign) {
+                signer(config)?.validate("sign_command")?;
+            }
+            ui::line(&format!("  \x1b[36m{}\x1b[0m {}", tr(Msg::Linking), ui::path(&out_exe)));
+            let started = Instant::now();
+            PhaseTimings::measure(&mut timings.link, || {
+                link_objects(
+                    &exe.objects,
+                    &link_out,
+                    config,
+                    profile,
+                    extra_flags,
+                    config.verbose,
+                )
+            })?;
+            trace::record(&out_exe.display().to_string(), "link", trace::MAIN_THREAD, started);
+            events::link(&out_exe, exe.objects.len());
+            state.set(
+                &link_out,
+                ObjectState {
+                    flags_hash: link_hash,
+                    ..Default::default()
+                },
+            );
+            state.save()?;
+            if config.link_map && config.target_os() != "macos" {
+                // Diagnostics only: an unreadable map must not fail the build
+                if let Err(e) = linkmap::report(&link_map_path(config, &link_out), &exe.objects) {
+                    report_warning(&e);
+                }
+            }
+        }
+
+        let mut processed = false;
+        if !steps.is_empty() {
+            let pipeline_hash = post_link_hash(&steps, config, &link_out)?;
+            let current = out_exe.exists()
+                && state.get(&out_exe).map(|prev| prev.flags_hash.as_str())
+                    == Some(pipeline_hash.as_str());
+            if !current {
+                if !relink {
+                    ui::line(&format!("  \x1b[36mPost-processing\x1b[0m {}", ui::path(&out_exe)));
+                }
+                let started = Instant::now();
+                PhaseTimings::measure(&mut timings.link, || {
+                    run_post_link(&steps, &link_out, &out_exe, config)
+                })?;
+                trace::record(&out_exe.display().to_string(), "post-link", trace::MAIN_THREAD, started);
+                state.set(
+                    &out_exe,
+                    ObjectState {
+                        flags_hash: pipeline_hash,
+                        ..Default::default()
+                    },
+                );
+                state.save()?;
+                processed = true;
+            }
+        }
+        if !relink && !processed {
+            ui::line(&format!("  \x1b[32m{}\x1b[0m {}", tr(Msg::UpToDate), ui::path(&out_exe)));
+        }
+        out_exes.push(out_exe);
+    }
+
+    let elapsed = t_start.elapsed();
+    let outputs: Vec<String> = out_exes.iter().map(|p| ui::path(p)).collect();
+    ui::line(&format!(
+        "\x1b[32m{}\x1b[0m {:?} in {:.2}s → {}",
+        tr(Msg::Finished),
+        profile,
+        elapsed.as_secs_f64(),
+        outputs.join(", ")
+    ));
+    if ui::show_timestamps() {
+        ui::line(&format!("  \x1b[2m{}\x1b[0m", timings.summary()));
+    }
+
+    let (executables, libraries) = match target_type {
+        TargetType::Plugin => (Vec::new(), out_exes),
+        _ => (out_exes, Vec::new()),
+    };
+    Ok(BuildSummary {
+        executables,
+        libraries,
+        compiled: compiled_count,
+        up_to_date: up_to_date_count,
+        warnings,
+        cache_hits,
+        cache_misses,
+        objects,
+    })
+}
+
+/// A unit's objects once every one of them is up to date.
+pub struct CompiledUnit {
+    /// Every object of the unit, compiled or not.
+    pub objects: Vec<ObjectFile>,
+    /// Build state with the new objects recorded, already saved.
+    pub state: BuildState,
+    /// Objects compiled, or fetched from a shared dir or cache.
+    pub compiled: usize,
+    /// Objects that were already current.
+    pub up_to_date: usize,
+    /// Warnings printed, including replayed ones.
+    pub warnings: usize,
+    /// Object cache lookups that found, or didn't find, the object.
+    pub cache_hits: usize,
+    /// Lookups that didn't.
+    pub cache_misses: usize,
+}
+
+/// State of an object just compiled or fetched, with content hashes when
+/// `rebuild_policy = "hash"`.
+fn compiled_state(config: &ProjectConfig, obj: &ObjectFile, flags_hash: String) -> ObjectState {
+    let (source_hash, headers_hash) = match config.rebuild_policy {
+        RebuildPolicy::Hash => match content_hashes(obj) {
+            Some((source, headers)) => (Some(source), Some(headers)),
+            None => (None, None),
+        },
+        RebuildPolicy::Mtime => (None, None),
+    };
+    ObjectState {
+        flags_hash,
+        source_hash,
+        headers_hash,
+        ..ObjectState::default()
+    }
+}
+
+/// Warn once per build about flags a later flag overrides; the compile
+/// commands only pass the last one.
+fn warn_flag_conflicts(
+    config: &ProjectConfig,
+    objects: &[ObjectFile],
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) {
+    let mut seen = Vec::new();
+    for language in [Language::C, Language::Cpp] {
+        let obj = match objects.iter().find(|o| o.src.language == language) {
+            Some(obj) => obj,
+            None => continue,
+        };
+        for conflict in compile_flag_conflicts(obj, config, profile, extra_flags) {
+            if seen.contains(&conflict) {
+                continue;
+            }
+            report_warning(&BuildError::ConfigError(format!(
+                "conflicting flags '{}' and '{}'; using '{}', the later one",
+                conflict.dropped, conflict.kept, conflict.kept
+            )));
+            seen.push(conflict);
+        }
+    }
+}
+
+/// The project, or the `[target.*]` configs `target` selects (all of
+/// them when None) in build order.
+pub fn build_units(
+    config: &Arc<ProjectConfig>,
+    target: Option<&str>,
+) -> Result<Vec<Arc<ProjectConfig>>, BuildError> {
+    if config.targets.is_empty() {
+        return Ok(vec![Arc::clone(config)]);
+    }
+    resolve_targets(config, target)?
+        .into_iter()
+        .map(|t| target_config(config, t).map(Arc::new))
+        .collect()
+}
+
+/// Collect and compile one set of sources.
+pub fn compile_unit(
+    config: &Arc<ProjectConfig>,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    timings: &mut PhaseTimings,
+) -> Result<CompiledUnit, BuildError> {
+    ui::line(&format!(
+        "\x1b[1mBuilding\x1b[0m {} [{:?}]",
+        config.app_name,
+        profile
+    ));
+
+    // Collect sources
+    if config.source_dirs.is_empty() {
+        return Err(BuildError::ConfigError("source_dir is empty".to_string()));
+    }
+    for source_dir in &config.source_dirs {
+        if !source_dir.exists() {
+            return Err(BuildError::IoError(format!(
+                "source_dir {:?} does not exist",
+                source_dir
+            )));
+        }
+    }
+
+    let scan = PhaseTimings::measure(&mut timings.collect, || {
+        codegen::generate(config)?;
+        collect_sources(config)
+    })?;
+    for nested in &scan.nested_projects {
+        ui::line(&format!(
+            "  \x1b[33mSkipping\x1b[0m {} (nested project with its own config)",
+            ui::path(nested)
+        ));
+    }
+    let sources = scan.sources;
+
+    if sources.is_empty() {
+        return Err(BuildError::IoError(format!(
+            "No source files found in {:?}",
+            config.source_dirs
+        )));
+    }
+
+    ui::line(&format!("  Found {} source file(s)", sources.len()));
+
+    // g++ also links, so it is always needed
+    if sources.iter().any(|s| s.language == Language::C) {
+        config.c_compiler().validate("gcc_path")?;
+    }
+    config.cxx_compiler().validate("gpp_path")?;
+    if let Some(executor) = config.executor_command() {
+        executor.validate("executor")?;
+    }
+    sandbox::check(config)?;
+
+    // Compute object paths and create directories
+    let objects: Vec<_> = PhaseTimings::measure(&mut timings.collect, || {
+        sources
+            .iter()
+            .map(|src| object_path_for(src, config))
+            .collect()
+    });
+    PhaseTimings::measure(&mut timings.collect, || prepare_build_dirs(config, &objects))?;
+    warn_flag_conflicts(config, &objects, profile, extra_flags);
+    let source_order: HashMap<PathBuf, usize> =
+        objects.iter().enumerate().map(|(i, o)| (o.obj_path.clone(), i)).collect();
+
+    // Written before compiling so editors pick up new files even if the build fails
+    if let Some(path) = compdb::output_path(config) {
+        if let Err(e) = compdb::write(&path, &[(config, &objects)], profile, extra_flags) {
+            report_warning(&e);
+        }
+    }
+
+    // Divide into: needs recompile vs already up-to-date
+    let mut state = BuildState::load(&config.temp_dir);
+    let mut stats = RebuildStats::default();
+    let mut flags_hashes: HashMap<PathBuf, String> = HashMap::new();
+    let mut to_compile = Vec::new();
+    let mut up_to_date = Vec::new();
+    // Stale objects another checkout already built from identical inputs,
+    // then ones this user built before
+    let shared = config.shared_temp_dir.as_deref().map(SharedDir::new);
+    let mut cache = ObjectCache::open(config);
+    let mut reused = Vec::new();
+    let mut reused_shared = 0;
+    // Objects built from the same flags, whose codegen a pipelined build
+    // can skip if the preprocessed source hashes the same again
+    let mut preprocessed = HashMap::new();
+
+    PhaseTimings::measure(&mut timings.dep_check, || {
+        for obj in objects {
+            let (compiler, args) = build_compile_args(&obj, config, profile, extra_flags);
+            let flags_hash = compile_flags_hash(&compiler, &args, config);
+            let reason = rebuild_reason(&obj, config, &state, &flags_hash);
+            let obj_path = obj.obj_path.clone();
+            stats.record(reason);
+            match reason {
+                Some(reason) => {
+                    if config.verbose {
+                        ui::line(&format!(
+                            "  \x1b[2m{}: {}\x1b[0m",
+                            ui::path(&obj.src.path),
+                            reason.describe()
+                        ));
+                    }
+                    if shared.as_ref().map(|s| s.fetch(&obj, &flags_hash)).unwrap_or(false) {
+                        reused_shared += 1;
+                        reused.push(obj);
+                    } else if cache.as_mut().map(|c| c.fetch(&obj, &flags_hash)).unwrap_or(false) {
+                        reused.push(obj);
+                    } else {
+                        let prev = state.get(&obj.obj_path).filter(|p| p.flags_hash == flags_hash);
+                        match prev.and_then(|p| p.preprocessed_hash.clone()) {
+                            Some(pp) if config.pipeline && obj.obj_path.is_file() => {
+                                preprocessed.insert(obj.obj_path.clone(), pp);
+                            }
+                            _ => {}
+                        }
+                        to_compile.push(obj);
+                    }
+                }
+                None => up_to_date.push(obj),
+            }
+            flags_hashes.insert(obj_path, flags_hash);
+        }
+    });
+
+    // Parallel compilation, slowest sources first
+    let estimates = estimate::longest_first(&mut to_compile, &state);
+    if config.disk_check && !to_compile.is_empty() {
+        diskspace::check(config, diskspace::estimate(&to_compile, &up_to_date))?;
+    }
+    let pool = WorkerPool::new(
+        Arc::clone(config),
+        profile.clone(),
+        extra_flags.to_vec(),
+        config.verbose,
+        config.aggregate_errors,
+    )
+    .with_estimates(estimates)
+    .with_preprocessed(preprocessed);
+
+    let compiled = PhaseTimings::measure(&mut timings.compile, || pool.run(to_compile))?;
+    let built_count = compiled.len();
+    let compiled_count = built_count + reused.len();
+    let up_to_date_count = up_to_date.len();
+    let mut warnings: usize = compiled.iter().map(|c| count_warnings(&c.diagnostics)).sum();
+
+    let mut compiled_objects = Vec::with_capacity(compiled_count);
+    for c in compiled {
+        // Best effort: a missing cache only means nothing to replay
+        let warn_path = warnings_path(&c.obj);
+        if c.diagnostics.is_empty() {
+            let _ = std::fs::remove_file(&warn_path);
+        } else {
+            let _ = std::fs::write(&warn_path, &c.diagnostics);
+        }
+        if let Some(flags_hash) = flags_hashes.remove(&c.obj.obj_path) {
+            if let Some(shared) = &shared {
+                if let Err(e) = shared.store(&c.obj, &flags_hash) {
+                    report_warning(&e);
+                }
+            }
+            if let Some(cache) = &mut cache {
+                if let Err(e) = cache.store(&c.obj, &flags_hash) {
+                    report_warning(&e);
+                }
+            }
+            let object_state = ObjectState {
+                compile_ms: Some(c.duration.as_millis() as u64),
+                preprocessed_hash: c.preprocessed_hash,
+                ..compiled_state(config, &c.obj, flags_hash)
+            };
+            state.set(&c.obj.obj_path, object_state);
+        }
+        compiled_objects.push(c.obj);
+    }
+    for obj in reused {
+        if let Some(flags_hash) = flags_hashes.remove(&obj.obj_path) {
+            // Keep the last local compile time for cost estimates
+            let object_state = ObjectState {
+                compile_ms: state.get(&obj.obj_path).and_then(|prev| prev.compile_ms),
+                ..compiled_state(config, &obj, flags_hash)
+            };
+            state.set(&obj.obj_path, object_state);
+        }
+        compiled_objects.push(obj);
+    }
+    // Objects found up-to-date by mtime when the policy was switched on
+    if config.rebuild_policy == RebuildPolicy::Hash {
+        for obj in &up_to_date {
+            let prev = state.get(&obj.obj_path).cloned().unwrap_or_default();
+            if prev.source_hash.is_none() {
+                if let Some((source_hash, headers_hash)) = content_hashes(obj) {
+                    state.set(
+                        &obj.obj_path,
+                        ObjectState {
+                            flags_hash: flags_hashes.remove(&obj.obj_path).unwrap_or_default(),
+                            source_hash: Some(source_hash),
+                            headers_hash: Some(headers_hash),
+                            ..prev
+                        },
+                    );
+                }
+            }
+        }
+    }
+    state.save()?;
+
+    if compiled_count == 0 {
+        ui::line(&format!(
+            "  \x1b[32m{}\x1b[0m — {}",
+            tr(Msg::AllUpToDate),
+            tr(Msg::NothingToRecompile)
+        ));
+    } else if built_count == 0 && reused_shared == compiled_count {
+        ui::line(&format!(
+            "  \x1b[32m{}\x1b[0m {} {} — {}",
+            tr(Msg::Reused),
+            compiled_count,
+            tr(Msg::FromSharedTempDir),
+            stats.summary()
+        ));
+    } else {
+        ui::line(&format!(
+            "  \x1b[32m{}\x1b[0m {} {} — {}",
+            tr(Msg::Compiled),
+            built_count,
+            tr(Msg::Files),
+            stats.summary()
+        ));
+        if reused_shared > 0 {
+            ui::line(&format!(
+                "  \x1b[32m{}\x1b[0m {} {}",
+                tr(Msg::Reused),
+                reused_shared,
+                tr(Msg::FromSharedTempDir)
+            ));
+        }
+    }
+    if let Some(cache) = cache.as_mut().filter(|c| c.hits() + c.misses() > 0) {
+        let remote = match cache.remote_hits() {
+            0 => String::new(),
+            n => format!(" ({} {})", n, tr(Msg::FromRemoteCache)),
+        };
+        ui::line(&format!(
+            "  \x1b[32m{}\x1b[0m {} {}{}, {} {}",
+            tr(Msg::Cache),
+            cache.hits(),
+            tr(Msg::Hits),
+            remote,
+            cache.misses(),
+            tr(Msg::Misses)
+        ));
+        if let Some(e) = cache.take_remote_error() {
+            report_warning(&e);
+        }
+        if let Err(e) = cache.save_stats() {
+            report_warning(&e);
+        }
+    }
+
+    if config.replay_warnings {
+        warnings += replay_warnings(&up_to_date);
+    }
+
+    // Combine compiled + up-to-date, back in source order: the link and
+    // archive commands list them, and a different order would relink next time
+    let mut all_objects = compiled_objects;
+    all_objects.extend(up_to_date);
+    all_objects.sort_by_key(|o| source_order[&o.obj_path]);
+    let (cache_hits, cache_misses) =
+        cache.as_ref().map(|c| (c.hits(), c.misses())).unwrap_or((0, 0));
+
+    if config.include_check != IncludeCheck::Off {
+        check_include_hygiene(&all_objects, config)?;
+    }
+
+    Ok(CompiledUnit {
+        objects: all_objects,
+        state,
+        compiled: compiled_count,
+        up_to_date: up_to_date_count,
+        warnings,
+        cache_hits,
+        cache_misses,
+    })
+}
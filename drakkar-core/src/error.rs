@@ -0,0 +1,224 @@
+//! [`BuildError`], the error every drakkar operation returns, with a
+//! stable code per variant for tools to match on.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::json::Json;
+use crate::messages::{tr, Msg};
+
+/// Everything that can go wrong in a build, from a missing file to a failed
+/// link.
+#[derive(Debug)]
+pub enum BuildError {
+    /// A file or process operation failed.
+    IoError(String),
+    /// A config, depfile or other input couldn't be parsed.
+    ParseError(String),
+    /// The compiler failed on a source.
+    CompileError {
+        /// The source being compiled.
+        src: PathBuf,
+        /// What the compiler printed.
+        stderr: String,
+        /// Its exit code; None when it was killed.
+        code: Option<i32>,
+    },
+    /// The linker or archiver failed.
+    LinkError {
+        /// What it printed.
+        stderr: String,
+        /// Its exit code; None when it was killed.
+        code: Option<i32>,
+    },
+    /// The configuration is invalid or asks for something impossible.
+    ConfigError(String),
+    /// A source included a header from outside the project's roots, under
+    /// `include_check = "error"`.
+    IncludeViolation {
+        /// The source that included it.
+        src: PathBuf,
+        /// The header, as the depfile names it.
+        header: PathBuf,
+    },
+    /// The build was interrupted, by Ctrl+C or a stop request, before it
+    /// finished.
+    Cancelled,
+    /// Several operations failed, such as compiles running in parallel.
+    MultipleErrors(Vec<BuildError>),
+}
+
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BuildError::IoError(msg) => write!(f, "{}: {}", tr(Msg::IoError), msg),
+            BuildError::ParseError(msg) => write!(f, "{}: {}", tr(Msg::ParseError), msg),
+            BuildError::CompileError { src, stderr, code } => {
+                write!(f, "{} {:?}", tr(Msg::CompileErrorIn), src)?;
+                if let Some(c) = code {
+                    write!(f, " (exit {})", c)?;
+                }
+                if !stderr.is_empty() {
+                    write!(f, "\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            BuildError::LinkError { stderr, code } => {
+                write!(f, "{}", tr(Msg::LinkError))?;
+                if let Some(c) = code {
+                    write!(f, " (exit {})", c)?;
+                }
+                if !stderr.is_empty() {
+                    write!(f, "\n{}", stderr)?;
+                }
+                Ok(())
+            }
+            BuildError::ConfigError(msg) => write!(f, "{}: {}", tr(Msg::ConfigError), msg),
+            BuildError::IncludeViolation { src, header } => write!(
+                f,
+                "Include hygiene: {:?} includes {:?}, which is outside source_dir and include_dirs",
+                src, header
+            ),
+            BuildError::Cancelled => write!(f, "{}", tr(Msg::Cancelled)),
+            BuildError::MultipleErrors(errs) => {
+                writeln!(f, "{} {}:", errs.len(), tr(Msg::ErrorsOccurred))?;
+                for (i, e) in errs.iter().enumerate() {
+                    writeln!(f, "  [{}] {}", i + 1, e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl BuildError {
+    /// Stable identifier for this error class. Codes never change meaning
+    /// once released, so tools can match on them instead of message text.
+    pub fn code(&self) -> &'static str {
+        match self {
+            BuildError::IoError(_) => "E0001",
+            BuildError::ParseError(_) => "E0002",
+            BuildError::ConfigError(_) => "E0003",
+            BuildError::CompileError { .. } => "E0101",
+            BuildError::IncludeViolation { .. } => "E0102",
+            BuildError::LinkError { .. } => "E0201",
+            BuildError::Cancelled => "E0301",
+            BuildError::MultipleErrors(_) => "E0400",
+        }
+    }
+
+    /// Short machine-friendly name of the variant.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            BuildError::IoError(_) => "io",
+            BuildError::ParseError(_) => "parse",
+            BuildError::ConfigError(_) => "config",
+            BuildError::CompileError { .. } => "compile",
+            BuildError::IncludeViolation { .. } => "include",
+            BuildError::LinkError { .. } => "link",
+            BuildError::Cancelled => "cancelled",
+            BuildError::MultipleErrors(_) => "multiple",
+        }
+    }
+
+    /// One-line description without compiler output attached.
+    pub fn summary(&self) -> String {
+        match self {
+            BuildError::CompileError { src, code, .. } => match code {
+                Some(c) => format!("{} {:?} (exit {})", tr(Msg::CompileErrorIn), src, c),
+                None => format!("{} {:?}", tr(Msg::CompileErrorIn), src),
+            },
+            BuildError::LinkError { code, .. } => match code {
+                Some(c) => format!("{} (exit {})", tr(Msg::LinkError), c),
+                None => tr(Msg::LinkError).to_string(),
+            },
+            BuildError::MultipleErrors(errs) => format!("{} {}", errs.len(), tr(Msg::ErrorsOccurred)),
+            other => other.to_string(),
+        }
+    }
+
+    /// The error as one line of JSON for `--message-format=json`, with the
+    /// given hints attached.
+    pub fn to_json_line(&self, hints: &[String]) -> String {
+        self.to_json().field("hints", hints.to_vec()).to_string()
+    }
+
+    /// Structured form, also nested inside build events.
+    pub(crate) fn to_json(&self) -> Json {
+        let json = Json::object()
+            .field("code", self.code())
+            .field("kind", self.kind())
+            .field("message", self.summary());
+
+        match self {
+            BuildError::CompileError { src, stderr, code } => json
+                .field("src", src.to_string_lossy().into_owned())
+                .field("exit_code", *code)
+                .field("stderr", stderr.as_str()),
+            BuildError::LinkError { stderr, code } => json
+                .field("exit_code", *code)
+                .field("stderr", stderr.as_str()),
+            BuildError::IncludeViolation { src, header } => json
+                .field("src", src.to_string_lossy().into_owned())
+                .field("header", header.to_string_lossy().into_owned()),
+            BuildError::MultipleErrors(errs) => json.field(
+                "errors",
+                Json::Array(errs.iter().map(BuildError::to_json).collect()),
+            ),
+            _ => json,
+        }
+    }
+}
+
+impl From<std::io::Error> for BuildError {
+    fn from(e: std::io::Error) -> Self {
+        BuildError::IoError(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes_are_distinct() {
+        let errs = [
+            BuildError::IoError(String::new()),
+            BuildError::ParseError(String::new()),
+            BuildError::ConfigError(String::new()),
+            BuildError::CompileError { src: PathBuf::new(), stderr: String::new(), code: None },
+            BuildError::LinkError { stderr: String::new(), code: None },
+            BuildError::IncludeViolation { src: PathBuf::new(), header: PathBuf::new() },
+            BuildError::Cancelled,
+            BuildError::MultipleErrors(vec![]),
+        ];
+        let mut codes: Vec<_> = errs.iter().map(BuildError::code).collect();
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errs.len());
+    }
+
+    #[test]
+    fn test_multiple_errors_json_nests() {
+        let e = BuildError::MultipleErrors(vec![
+            BuildError::CompileError {
+                src: PathBuf::from("src/a.cpp"),
+                stderr: "a.cpp:1: error".to_string(),
+                code: Some(1),
+            },
+            BuildError::Cancelled,
+        ]);
+        let json = e.to_json().to_string();
+        assert!(json.starts_with(r#"{"code":"E0400","kind":"multiple","message":"2 error(s) occurred","errors":["#));
+        assert!(json.contains(r#""code":"E0101""#));
+        assert!(json.contains(r#""src":"src/a.cpp","exit_code":1"#));
+        assert!(json.contains(r#""code":"E0301""#));
+    }
+
+    #[test]
+    fn test_json_line_carries_hints() {
+        let line = BuildError::Cancelled.to_json_line(&["run it again".to_string()]);
+        assert!(line.starts_with(r#"{"code":"E0301","kind":"#));
+        assert!(line.ends_with(r#","hints":["run it again"]}"#));
+    }
+}
@@ -0,0 +1,140 @@
+//! Compile-time predictions for the ETA and for scheduling.
+//!
+//! Every compile's wall time is kept in the build state (`ms=`). A source
+//! with a recorded time is predicted to take as long again; one without is
+//! estimated from its size, at the average milliseconds per byte of the
+//! sources that do have one. With no history at all nothing is predicted
+//! and sources are just ordered by size.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::build::ObjectFile;
+use crate::state::BuildState;
+
+/// Predicted compile time of each object, in the same order; None for all
+/// of them when there is no history to go by.
+pub fn predict(objects: &[ObjectFile], state: &BuildState) -> Vec<Option<Duration>> {
+    let sizes: Vec<u64> = objects.iter().map(source_size).collect();
+    let history: Vec<Option<u64>> = objects
+        .iter()
+        .map(|obj| state.get(&obj.obj_path).and_then(|s| s.compile_ms))
+        .collect();
+
+    let (known_ms, known_bytes) = history
+        .iter()
+        .zip(&sizes)
+        .filter_map(|(ms, size)| ms.map(|ms| (ms, *size)))
+        .fold((0u64, 0u64), |(ms, bytes), (m, b)| (ms + m, bytes + b));
+    let ms_per_byte = if known_bytes > 0 {
+        Some(known_ms as f64 / known_bytes as f64)
+    } else {
+        None
+    };
+
+    history
+        .iter()
+        .zip(&sizes)
+        .map(|(ms, size)| match (ms, ms_per_byte) {
+            (Some(ms), _) => Some(Duration::from_millis(*ms)),
+            (None, Some(rate)) => Some(Duration::from_millis((*size as f64 * rate) as u64)),
+            (None, None) => None,
+        })
+        .collect()
+}
+
+/// Sort `objects` so the slowest start first, which keeps one long compile
+/// from running alone at the end of the build. Returns the predictions by
+/// object path for the ETA.
+pub fn longest_first(objects: &mut Vec<ObjectFile>, state: &BuildState) -> HashMap<PathBuf, Duration> {
+    let predictions = predict(objects, state);
+    let mut keyed: Vec<(u64, ObjectFile, Option<Duration>)> = std::mem::take(objects)
+        .into_iter()
+        .zip(predictions)
+        .map(|(obj, p)| {
+            let key = match p {
+                Some(d) => d.as_millis() as u64,
+                None => source_size(&obj),
+            };
+            (key, obj, p)
+        })
+        .collect();
+    keyed.sort_by_key(|(key, _, _)| std::cmp::Reverse(*key));
+
+    let mut estimates = HashMap::new();
+    for (_, obj, p) in keyed {
+        if let Some(d) = p {
+            estimates.insert(obj.obj_path.clone(), d);
+        }
+        objects.push(obj);
+    }
+    estimates
+}
+
+/// Wall time left when `remaining` compile time is spread over up to
+/// `jobs` workers.
+pub fn eta(remaining: Duration, files_left: usize, jobs: usize) -> Duration {
+    let workers = jobs.min(files_left).max(1) as u32;
+    remaining / workers
+}
+
+fn source_size(obj: &ObjectFile) -> u64 {
+    std::fs::metadata(&obj.src.path).map(|m| m.len()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use crate::state::ObjectState;
+
+    fn object(dir: &std::path::Path, name: &str, bytes: usize) -> ObjectFile {
+        let path = dir.join(format!("{}.cpp", name));
+        std::fs::write(&path, "x".repeat(bytes)).unwrap();
+        ObjectFile {
+            src: SourceFile {
+                path,
+                rel_path: PathBuf::from(format!("{}.cpp", name)),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join(format!("{}.o", name)),
+            dep_path: dir.join(format!("{}.d", name)),
+        }
+    }
+
+    #[test]
+    fn test_history_then_size() {
+        let dir = std::env::temp_dir().join("drakkar_test_estimate");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let small = object(&dir, "small", 50);
+        let big = object(&dir, "big", 400);
+        let slow = object(&dir, "slow", 100);
+
+        // No history: biggest first, no predictions
+        let mut objs = vec![small.clone(), big.clone()];
+        let estimates = longest_first(&mut objs, &BuildState::default());
+        assert!(estimates.is_empty());
+        assert_eq!(objs[0].obj_path, big.obj_path);
+
+        // slow.cpp took 2s for 100 bytes, so big.cpp should take about 8s
+        let mut state = BuildState::default();
+        state.set(
+            &slow.obj_path,
+            ObjectState {
+                compile_ms: Some(2000),
+                ..Default::default()
+            },
+        );
+        let mut objs = vec![small, slow.clone(), big.clone()];
+        let estimates = longest_first(&mut objs, &state);
+        assert_eq!(objs[0].obj_path, big.obj_path);
+        assert_eq!(objs[1].obj_path, slow.obj_path);
+        assert_eq!(estimates[&big.obj_path], Duration::from_millis(8000));
+
+        assert_eq!(eta(Duration::from_secs(12), 10, 4), Duration::from_secs(3));
+        assert_eq!(eta(Duration::from_secs(12), 2, 4), Duration::from_secs(6));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,308 @@
+//! Machine-readable build output for tools.
+//!
+//! With [`EventFormat::Json`] (`--message-format json`) each step of a
+//! build is printed to stdout as one JSON object per line, and the human
+//! progress lines move to stderr:
+//!
+//! ```json
+//! {"event":"compile-start","src":"src/main.c","obj":"target/main.o","index":1,"total":2}
+//! {"event":"diagnostic","src":"src/main.c","file":"src/main.c","line":3,"col":9,"severity":"warning","message":"unused variable 'x'"}
+//! {"event":"compile-finish","src":"src/main.c","success":true,"duration_ms":41}
+//! {"event":"link","output":"out/app","objects":2}
+//! {"event":"done","success":true,"duration_secs":0.12,"compiled":2,"up_to_date":0,"warnings":1}
+//! ```
+//!
+//! With [`EventFormat::Vscode`] the progress lines stay, but compiler
+//! output is replaced by one `file(line,col): severity: message` line per
+//! diagnostic, between `[drakkar] build started` and `[drakkar] build
+//! finished` (or `failed`) markers, for a VS Code problem matcher.
+//!
+//! Independently of the format, `on_event = "./notify.sh"` starts that
+//! program with each build and writes a few coarser events to its stdin,
+//! one JSON object per line, then closes stdin and waits for it to exit.
+//! What the hook prints goes to stderr, out of the way of JSON events:
+//!
+//! ```json
+//! {"event":"build_started","app":"demo"}
+//! {"event":"file_compiled","src":"src/main.c","success":true,"warnings":1,"duration_ms":41}
+//! {"event":"build_finished","duration_secs":0.12,"compiled":2,"up_to_date":0,"warnings":1}
+//! ```
+//!
+//! A failed build ends with `build_failed` and the error instead.
+
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use crate::build::ObjectFile;
+use crate::config::{ProjectConfig, ToolCommand};
+use crate::diagnostics::{parse_diagnostics, Diagnostic, Severity};
+use crate::error::BuildError;
+use crate::json::Json;
+use crate::status::BuildSummary;
+use crate::ui;
+
+/// What a build reports for tools, besides the human output.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EventFormat {
+    /// Only the human output.
+    Off,
+    /// Newline-delimited JSON events on stdout.
+    Json,
+    /// Normalized diagnostics and begin/end markers.
+    Vscode,
+}
+
+static FORMAT: AtomicU8 = AtomicU8::new(0);
+static HOOK: Mutex<Option<Hook>> = Mutex::new(None);
+
+/// Choose the format for the rest of the process.
+pub fn set_format(format: EventFormat) {
+    let n = match format {
+        EventFormat::Off => 0,
+        EventFormat::Json => 1,
+        EventFormat::Vscode => 2,
+    };
+    FORMAT.store(n, Ordering::Relaxed);
+}
+
+/// The format chosen with [`set_format`], `Off` until then.
+pub fn format() -> EventFormat {
+    match FORMAT.load(Ordering::Relaxed) {
+        1 => EventFormat::Json,
+        2 => EventFormat::Vscode,
+        _ => EventFormat::Off,
+    }
+}
+
+fn emit(event: Json) {
+    if format() == EventFormat::Json {
+        // One println per event, so lines from workers never interleave
+        println!("{}", event);
+    }
+}
+
+fn event(name: &str) -> Json {
+    Json::object().field("event", name)
+}
+
+/// The `on_event` program of the current build.
+struct Hook {
+    child: Child,
+    /// Lines for the thread writing to the hook's stdin, so a hook that
+    /// reads slowly never holds up a compile worker. Dropping it closes
+    /// stdin once the queue is written.
+    events: mpsc::Sender<String>,
+    writer: JoinHandle<()>,
+}
+
+fn start_hook(command: &ToolCommand) -> Result<Hook, BuildError> {
+    // Its stdout would land in the middle of `--message-format json` output
+    let mut child = Command::new(&command.program)
+        .args(&command.args)
+        .stdin(Stdio::piped())
+        .stdout(std::io::stderr())
+        .spawn()
+        .map_err(|e| BuildError::IoError(format!("Cannot start on_event {}: {}", command, e)))?;
+    let mut stdin = child.stdin.take();
+    let (events, queue) = mpsc::channel::<String>();
+    let writer = std::thread::spawn(move || {
+        for line in queue {
+            // One that exits early just stops getting events
+            let written = match &mut stdin {
+                Some(stdin) => writeln!(stdin, "{}", line).and_then(|_| stdin.flush()),
+                None => return,
+            };
+            if written.is_err() {
+                return;
+            }
+        }
+    });
+    Ok(Hook { child, events, writer })
+}
+
+fn hook_running() -> bool {
+    HOOK.lock().map(|h| h.is_some()).unwrap_or(false)
+}
+
+/// Queue `event` for the hook; it never fails the build.
+fn to_hook(event: Json) {
+    if let Ok(hook) = HOOK.lock() {
+        if let Some(hook) = hook.as_ref() {
+            let _ = hook.events.send(event.to_string());
+        }
+    }
+}
+
+/// Close the hook's stdin once every event is written and wait for it,
+/// so a notification it sends after the last event isn't cut short when
+/// drakkar exits.
+fn finish_hook() {
+    let hook = HOOK.lock().ok().and_then(|mut h| h.take());
+    if let Some(Hook { mut child, events, writer }) = hook {
+        drop(events);
+        let _ = writer.join();
+        match child.wait() {
+            Ok(status) if !status.success() => ui::report_warning(&BuildError::IoError(
+                format!("on_event hook exited with {}", status),
+            )),
+            Ok(_) => {}
+            Err(e) => ui::report_warning(&BuildError::IoError(format!(
+                "Cannot wait for on_event hook: {}",
+                e
+            ))),
+        }
+    }
+}
+
+fn path(p: &Path) -> String {
+    ui::path(p)
+}
+
+/// A worker picked up `obj`, the `index`-th of `total` to compile.
+pub fn compile_start(obj: &ObjectFile, index: usize, total: usize) {
+    emit(event("compile-start")
+        .field("src", path(&obj.src.path))
+        .field("obj", path(&obj.obj_path))
+        .field("index", index)
+        .field("total", total));
+}
+
+/// `src` finished compiling: a `diagnostic` event for each message in
+/// `output`, then `compile-finish`.
+pub fn compile_finish(src: &Path, output: &str, success: bool, duration: Option<Duration>) {
+    let duration_ms = duration.map(|d| d.as_millis() as i64);
+    if hook_running() {
+        let diagnostics = parse_diagnostics(output);
+        let warnings = diagnostics.iter().filter(|d| d.severity == Severity::Warning).count();
+        to_hook(event("file_compiled")
+            .field("src", path(src))
+            .field("success", success)
+            .field("warnings", warnings)
+            .field("duration_ms", duration_ms));
+    }
+    match format() {
+        EventFormat::Off => return,
+        EventFormat::Vscode => {
+            for d in parse_diagnostics(output) {
+                println!("{}", vscode_line(&d));
+            }
+            return;
+        }
+        EventFormat::Json => {}
+    }
+    for d in parse_diagnostics(output) {
+        emit(event("diagnostic")
+            .field("src", path(src))
+            .field("file", path(Path::new(&d.file)))
+            .field("line", d.line)
+            .field("col", d.col)
+            .field("severity", d.severity.as_str())
+            .field("message", d.message));
+    }
+    emit(event("compile-finish")
+        .field("src", path(src))
+        .field("success", success)
+        .field("duration_ms", duration_ms));
+}
+
+/// `compile-finish` with the diagnostics of a failed compile; other
+/// errors are reported once the build ends.
+pub fn compile_failed(err: &BuildError) {
+    if let BuildError::CompileError { src, stderr, .. } = err {
+        compile_finish(src, stderr, false, None);
+    }
+}
+
+/// `src/a.c(3,9): warning: unused variable 'x'`. Notes are `info`, the
+/// lowest severity VS Code knows; a missing column is 1.
+fn vscode_line(d: &Diagnostic) -> String {
+    let severity = match d.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "info",
+    };
+    let file = ui::path(Path::new(&d.file));
+    format!("{}({},{}): {}: {}", file, d.line, d.col.unwrap_or(1), severity, d.message)
+}
+
+/// A build is about to start: start the `on_event` hook, if any.
+pub fn build_started(config: &ProjectConfig) {
+    if format() == EventFormat::Vscode {
+        println!("[drakkar] build started");
+    }
+    if let Some(command) = &config.on_event {
+        match start_hook(command) {
+            Ok(hook) => {
+                if let Ok(mut h) = HOOK.lock() {
+                    *h = Some(hook);
+                }
+            }
+            Err(e) => ui::report_warning(&e),
+        }
+    }
+    to_hook(event("build_started").field("app", config.app_name.as_str()));
+}
+
+/// `objects` were linked or archived into `output`.
+pub fn link(output: &Path, objects: usize) {
+    emit(event("link").field("output", path(output)).field("objects", objects));
+}
+
+/// The build ended; the error, if any, is included as with
+/// `--message-format json` errors.
+pub fn done(outcome: &Result<BuildSummary, BuildError>, duration: Duration) {
+    if format() == EventFormat::Vscode {
+        let result = if outcome.is_ok() { "finished" } else { "failed" };
+        println!("[drakkar] build {}", result);
+    }
+    let json = event("done")
+        .field("success", outcome.is_ok())
+        .field("duration_secs", duration.as_secs_f64());
+    emit(match outcome {
+        Ok(summary) => json
+            .field("compiled", summary.compiled)
+            .field("up_to_date", summary.up_to_date)
+            .field("warnings", summary.warnings),
+        Err(e) => json.field("error", e.to_json()),
+    });
+    if hook_running() {
+        let duration_secs = duration.as_secs_f64();
+        to_hook(match outcome {
+            Ok(summary) => event("build_finished")
+                .field("duration_secs", duration_secs)
+                .field("compiled", summary.compiled)
+                .field("up_to_date", summary.up_to_date)
+                .field("warnings", summary.warnings),
+            Err(e) => event("build_failed")
+                .field("duration_secs", duration_secs)
+                .field("error", e.to_json()),
+        });
+        finish_hook();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_vscode_line() {
+        let output = "src/a.c:3:9: warning: unused variable 'x'\n\
+                      src/a.c:4: note: declared here\n\
+                      src/b.cpp(7,3): error C2065: 'y': undeclared identifier\n";
+        let lines: Vec<String> = parse_diagnostics(output).iter().map(vscode_line).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "src/a.c(3,9): warning: unused variable 'x'",
+                "src/a.c(4,1): info: declared here",
+                "src/b.cpp(7,3): error: C2065: 'y': undeclared identifier",
+            ]
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! `--explain-commands`: annotate each printed compiler, linker and
+//! archiver command with what its major flags do, for people learning the
+//! toolchain through drakkar.
+//!
+//! Flags are looked up in a built-in table, first by exact spelling and
+//! then by prefix (`-I`, `-std=`). Unknown flags are left out rather than
+//! guessed at.
+
+/// Flags whose value is the next argument.
+const TAKES_OPERAND: &[&str] = &["-o", "-MF", "-x", "-target", "-include"];
+
+const EXACT: &[(&str, &str)] = &[
+    ("-c", "compile to an object file only; linking happens later"),
+    ("-o", "name of the file to write"),
+    ("-E", "stop after preprocessing: expand #include and macros"),
+    ("-x", "treat the next input as this language, whatever its extension"),
+    ("-g", "include debug info, so a debugger can map code back to source lines"),
+    ("-O0", "no optimization: fastest compile, easiest to debug"),
+    ("-O1", "basic optimizations"),
+    ("-O2", "most optimizations that don't trade size for speed"),
+    ("-O3", "aggressive optimizations, including vectorization"),
+    ("-Os", "optimize for size"),
+    ("-Wall", "enable the common warnings"),
+    ("-Wextra", "enable extra warnings beyond -Wall"),
+    ("-Werror", "treat every warning as an error"),
+    ("-pedantic", "warn about anything the language standard doesn't allow"),
+    ("-MMD", "also write a depfile listing the project headers this source includes"),
+    ("-MP", "add an empty rule per header so deleting one doesn't break make"),
+    ("-MF", "where to write the depfile; drakkar reads it to know what to rebuild"),
+    ("-fPIC", "position-independent code, required inside shared libraries"),
+    ("-shared", "produce a shared library instead of an executable"),
+    ("-pthread", "compile and link with POSIX threads support"),
+    ("-fopenmp", "enable OpenMP #pragma omp parallelism"),
+    ("-flto", "keep compiler IR in the object so the link step can optimize across files"),
+    ("/GL", "keep compiler IR in the object so the link step can optimize across files"),
+    ("/LTCG", "link-time code generation: optimize across all /GL objects"),
+    ("/LTCG:INCREMENTAL", "link-time code generation, redoing only changed functions"),
+    ("-s", "strip symbols from the output to make it smaller"),
+    ("rcs", "ar: replace members, create the archive, write a symbol index"),
+    ("/nologo", "don't print the compiler banner"),
+    ("/showIncludes", "list every included header; drakkar turns this into a depfile"),
+    ("/Z7", "put debug info in each object file"),
+    ("/Od", "no optimization: fastest compile, easiest to debug"),
+    ("/O2", "optimize for speed"),
+    ("/DEBUG", "write a PDB with debug info next to the executable"),
+    ("/DLL", "produce a DLL instead of an executable"),
+];
+
+const PREFIX: &[(&str, &str)] = &[
+    ("-std=", "language standard to compile against"),
+    ("-I", "also search this directory for #include files"),
+    ("-D", "define a preprocessor macro, like #define at the top of every file"),
+    ("-L", "also search this directory for libraries"),
+    ("-l", "link against this library (libNAME.so or libNAME.a)"),
+    ("-Wl,", "pass the comma-separated options straight to the linker"),
+    ("-W", "enable (or with no-, disable) one specific warning"),
+    ("-flto=", "link-time optimization: a number is how many parallel jobs"),
+    ("-flto-jobs=", "how many parallel jobs ThinLTO uses at link time"),
+    ("-flto-partition=", "how gcc splits the program for LTO; one means whole-program"),
+    ("-fsanitize=", "instrument the code to catch bugs at run time"),
+    ("-march=", "generate instructions for this CPU family"),
+    ("/Fo", "name of the object file to write"),
+    ("/std:", "language standard to compile against"),
+    ("/OUT:", "name of the file to write"),
+    ("/LIBPATH:", "also search this directory for libraries"),
+    ("/MAP:", "write a link map showing where each symbol ended up"),
+];
+
+/// Annotation lines for `args`: the flag (with its operand, if it takes
+/// one) and what it does. Each explanation appears once.
+pub fn annotate(args: &[String]) -> Vec<(String, &'static str)> {
+    let mut notes: Vec<(String, &'static str)> = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let explanation = EXACT
+            .iter()
+            .find(|(flag, _)| flag == arg)
+            .or_else(|| PREFIX.iter().find(|(prefix, _)| arg.starts_with(prefix) && arg.len() > prefix.len()))
+            .map(|(_, text)| *text);
+        let mut shown = arg.clone();
+        if TAKES_OPERAND.contains(&arg.as_str()) {
+            if let Some(operand) = iter.next() {
+                shown = format!("{} {}", arg, operand);
+            }
+        }
+        if let Some(text) = explanation {
+            if !notes.iter().any(|(_, t)| *t == text) {
+                notes.push((shown, text));
+            }
+        }
+    }
+    notes
+}
+
+/// The annotations as lines to append under the printed command, each
+/// starting with a newline so the whole block is printed at once and
+/// doesn't interleave with parallel compiles.
+pub fn notes(args: &[String]) -> String {
+    let notes = annotate(args);
+    let width = notes.iter().map(|(flag, _)| flag.chars().count()).max().unwrap_or(0).min(32);
+    notes
+        .iter()
+        .map(|(flag, text)| format!("\n      \x1b[36m{:<width$}\x1b[0m  {}", flag, text, width = width))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_annotate_gcc_command() {
+        let args: Vec<String> = ["-c", "src/a.cpp", "-o", "target/a.o", "-std=c++17", "-Iinclude", "-Isrc", "-Wfoo", "-O2"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        let notes = annotate(&args);
+        let flags: Vec<&str> = notes.iter().map(|(f, _)| f.as_str()).collect();
+        // The second -I repeats the first explanation; the source isn't a flag
+        assert_eq!(flags, ["-c", "-o target/a.o", "-std=c++17", "-Iinclude", "-Wfoo", "-O2"]);
+        assert!(annotate(&["-fno-such-flag".to_string()]).is_empty());
+    }
+}
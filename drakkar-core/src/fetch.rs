@@ -0,0 +1,233 @@
+//! Git dependencies. `[deps]` entries with a URL and a tag are checked out
+//! into `deps_dir/<name>` by `drakkar fetch`, or by the first build that
+//! needs them. `drakkar.lock` pins the commit each tag resolved to, so every
+//! checkout of the project builds the same code until the tag in the config
+//! changes.
+//!
+//! A checkout with a drakkar config is a project dependency like the ones in
+//! `deps`: built first and linked in. Any other checkout is headers only, and
+//! its `include/` (or its root) is added to include_dirs.
+
+use std::path::{Path, PathBuf};
+
+use crate::config::{find_config, GitDep, ProjectConfig};
+use crate::error::BuildError;
+use crate::ui;
+
+/// The lockfile pinning each `[deps]` tag to a commit.
+pub const LOCKFILE: &str = "drakkar.lock";
+
+const LOCK_HEADER: &str = "# drakkar.lock: commits of the [deps] git tags; commit this file\n";
+
+/// One pinned dependency in `drakkar.lock`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locked {
+    /// The dependency's name in `[deps]`.
+    pub name: String,
+    /// The repository it is cloned from.
+    pub url: String,
+    /// The tag it was pinned at.
+    pub tag: String,
+    /// The commit the tag pointed to.
+    pub commit: String,
+}
+
+impl Locked {
+    fn pins(&self, dep: &GitDep) -> bool {
+        self.name == dep.name && self.url == dep.url && self.tag == dep.tag
+    }
+}
+
+/// Parse `drakkar.lock`: one `name url tag commit` line per dependency.
+pub fn parse_lock(content: &str) -> Result<Vec<Locked>, BuildError> {
+    let mut locked = Vec::new();
+    for (i, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            [name, url, tag, commit] => locked.push(Locked {
+                name: name.to_string(),
+                url: url.to_string(),
+                tag: tag.to_string(),
+                commit: commit.to_string(),
+            }),
+            _ => {
+                return Err(BuildError::ParseError(format!(
+                    "{}:{}: expected name, url, tag and commit",
+                    LOCKFILE,
+                    i + 1
+                )))
+            }
+        }
+    }
+    Ok(locked)
+}
+
+fn lock_text(locked: &[Locked]) -> String {
+    let mut text = LOCK_HEADER.to_string();
+    for l in locked {
+        text.push_str(&format!("{} {} {} {}\n", l.name, l.url, l.tag, l.commit));
+    }
+    text
+}
+
+/// Where `dep` is checked out: `deps_dir/<name>`.
+pub fn checkout_dir(config: &ProjectConfig, dep: &GitDep) -> PathBuf {
+    config.deps_dir.join(&dep.name)
+}
+
+/// Run git, returning its trimmed stdout.
+fn git(dir: &Path, args: &[&str]) -> Result<String, BuildError> {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(args)
+        .output()
+        .map_err(|e| BuildError::IoError(format!("Failed to run git: {}", e)))?;
+    if !output.status.success() {
+        return Err(BuildError::IoError(format!(
+            "git {} failed in {:?}:\n{}",
+            args.join(" "),
+            dir,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Replace the checkout in `dir` with `rev` (a tag or commit) of `url`,
+/// returning the commit it is at.
+fn check_out(dir: &Path, url: &str, rev: &str) -> Result<String, BuildError> {
+    if dir.exists() {
+        std::fs::remove_dir_all(dir)
+            .map_err(|e| BuildError::IoError(format!("Cannot remove {:?}: {}", dir, e)))?;
+    }
+    std::fs::create_dir_all(dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+    git(dir, &["init", "-q"])?;
+    git(dir, &["fetch", "-q", "--depth", "1", url, rev])?;
+    git(dir, &["checkout", "-q", "--detach", "FETCH_HEAD"])?;
+    git(dir, &["rev-parse", "HEAD"])
+}
+
+/// A dependency after [`fetch`]: the commit it is at, and whether it had to
+/// be checked out.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Fetched {
+    /// The dependency's name in `[deps]`.
+    pub name: String,
+    /// Its tag.
+    pub tag: String,
+    /// The commit it is checked out at.
+    pub commit: String,
+    /// Whether it had to be fetched and checked out.
+    pub updated: bool,
+}
+
+/// Bring every git dependency's checkout to its locked commit, resolving
+/// tags that aren't locked yet, and rewrite the lockfile when that changed
+/// it. Checkouts already at their commit are left alone, so a build only
+/// touches the network when the config or lockfile changed.
+pub fn fetch(config: &ProjectConfig) -> Result<Vec<Fetched>, BuildError> {
+    let lock_path = Path::new(LOCKFILE);
+    let old = match std::fs::read_to_string(lock_path) {
+        Ok(content) => parse_lock(&content)?,
+        Err(_) => Vec::new(),
+    };
+    let mut locked = Vec::new();
+    let mut fetched = Vec::new();
+    for dep in &config.git_deps {
+        let dir = checkout_dir(config, dep);
+        let pinned = old.iter().find(|l| l.pins(dep)).map(|l| l.commit.clone());
+        // Without its own .git, git would answer for the enclosing repository
+        let head = if dir.join(".git").exists() {
+            git(&dir, &["rev-parse", "HEAD"]).ok()
+        } else {
+            None
+        };
+        let (commit, updated) = match pinned {
+            Some(commit) if head.as_deref() == Some(commit.as_str()) => (commit, false),
+            pinned => {
+                ui::line(&format!(
+                    "  \x1b[36mFetching\x1b[0m {} {} ({})",
+                    dep.name, dep.tag, dep.url
+                ));
+                let rev = pinned.as_deref().unwrap_or(&dep.tag);
+                (check_out(&dir, &dep.url, rev)?, true)
+            }
+        };
+        locked.push(Locked {
+            name: dep.name.clone(),
+            url: dep.url.clone(),
+            tag: dep.tag.clone(),
+            commit: commit.clone(),
+        });
+        fetched.push(Fetched { name: dep.name.clone(), tag: dep.tag.clone(), commit, updated });
+    }
+    if locked != old {
+        std::fs::write(lock_path, lock_text(&locked))
+            .map_err(|e| BuildError::IoError(format!("Cannot write {}: {}", LOCKFILE, e)))?;
+    }
+    Ok(fetched)
+}
+
+/// Use the fetched checkouts: drakkar projects become project deps, the
+/// rest add their headers.
+pub fn wire(config: &mut ProjectConfig) {
+    for dep in config.git_deps.clone() {
+        let dir = checkout_dir(config, &dep);
+        if find_config(&dir).is_file() {
+            config.deps.push(dir);
+        } else if dir.join("include").is_dir() {
+            config.include_dirs.push(dir.join("include"));
+        } else {
+            config.include_dirs.push(dir);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lockfile_round_trip() {
+        let locked = vec![Locked {
+            name: "fmt".to_string(),
+            url: "https://github.com/fmtlib/fmt.git".to_string(),
+            tag: "10.2.1".to_string(),
+            commit: "e69e5f977d458f2650bb346dadf2ad30c5320281".to_string(),
+        }];
+        let text = lock_text(&locked);
+        assert!(text.starts_with("# drakkar.lock"));
+        assert_eq!(parse_lock(&text).unwrap(), locked);
+        let err = parse_lock("fmt https://x 10.2.1\n").unwrap_err().to_string();
+        assert!(err.contains("drakkar.lock:1:"), "{}", err);
+    }
+
+    #[test]
+    fn test_wire() {
+        let dir = std::env::temp_dir().join("drakkar_test_fetch_wire");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("lib/include")).unwrap();
+        std::fs::create_dir_all(dir.join("header_only")).unwrap();
+        std::fs::create_dir_all(dir.join("project")).unwrap();
+        std::fs::write(dir.join("project/config.txt"), "app_name = \"p\"\n").unwrap();
+        let dep = |name: &str| GitDep {
+            name: name.to_string(),
+            url: format!("https://example.com/{}.git", name),
+            tag: "v1".to_string(),
+        };
+        let mut config = ProjectConfig {
+            deps_dir: dir.clone(),
+            git_deps: vec![dep("lib"), dep("header_only"), dep("project")],
+            ..ProjectConfig::default()
+        };
+        wire(&mut config);
+        assert_eq!(config.include_dirs, [dir.join("lib/include"), dir.join("header_only")]);
+        assert_eq!(config.deps, [dir.join("project")]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,159 @@
+//! Repeated and conflicting compile flags.
+//!
+//! A compile command collects flags from c_flags/cxx_flags, the language
+//! standard, the profile and `--`, so one setting can appear twice. Only
+//! the last flag for each setting is kept: exact repeats are dropped
+//! quietly, and flags that set it differently (`-O0` then `-O2`,
+//! `-std=c++17` then `-std=c++20`) are reported as conflicts.
+
+/// Flags whose value is the next argument, which is never a flag itself.
+/// Pass-through flags (`-Xclang -fno-x`) hand it to another tool, so it
+/// isn't one of the driver's settings either.
+const TAKES_VALUE: &[&str] = &[
+    "-o", "-x", "-I", "-D", "-U", "-include", "-imacros", "-isystem", "-iquote", "-idirafter",
+    "-MF", "-MT", "-MQ", "-Xlinker", "-Xpreprocessor", "-Xassembler", "-Xclang", "-mllvm",
+    "-arch", "-target",
+];
+
+fn takes_value(flag: &str) -> bool {
+    // `-Xarch_x86_64 <flag>`: for one architecture of a universal build
+    TAKES_VALUE.contains(&flag) || flag.starts_with("-Xarch_")
+}
+
+/// Optimization levels after `-O`.
+const OPT_LEVELS: &[&str] = &["", "0", "1", "2", "3", "s", "g", "z", "fast"];
+
+/// A flag dropped because a later one sets the same thing differently.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// The earlier flag, removed.
+    pub dropped: String,
+    /// The later one that replaced it.
+    pub kept: String,
+}
+
+/// The setting `flag` controls, for flags where a later one replaces an
+/// earlier one; None for flags that add up (`-I`, `-Wl,`, sources).
+fn setting(flag: &str, msvc: bool) -> Option<String> {
+    if msvc {
+        return match flag {
+            f if f.starts_with("/std:") => Some("/std:".to_string()),
+            "/O1" | "/O2" | "/Od" | "/Ox" => Some("/O".to_string()),
+            f if f.starts_with("/D") => Some(macro_setting(f)),
+            _ => None,
+        };
+    }
+    if let Some(level) = flag.strip_prefix("-O") {
+        return if OPT_LEVELS.contains(&level) { Some("-O".to_string()) } else { None };
+    }
+    for prefix in ["-std=", "-march=", "-mtune=", "-mcpu="] {
+        if flag.starts_with(prefix) {
+            return Some(prefix.to_string());
+        }
+    }
+    if flag.starts_with("-D") && flag.len() > 2 {
+        return Some(macro_setting(flag));
+    }
+    // -fPIC / -fno-PIC, -Wshadow / -Wno-shadow. Ones with a value may add
+    // up (-fsanitize=address -fsanitize=undefined), so only repeats count.
+    for prefix in ["-f", "-W"] {
+        if let Some(name) = flag.strip_prefix(prefix) {
+            if name.contains('=') {
+                return Some(flag.to_string());
+            }
+            if prefix == "-W" && name.contains(',') {
+                return None;
+            }
+            return Some(format!("{}{}", prefix, name.strip_prefix("no-").unwrap_or(name)));
+        }
+    }
+    None
+}
+
+/// `-DNAME=value` sets NAME.
+fn macro_setting(flag: &str) -> String {
+    flag.split('=').next().unwrap_or(flag).to_string()
+}
+
+/// Keep only the last flag of each setting, in place, and return the
+/// flags that were replaced by a different one.
+pub fn dedupe(args: &mut Vec<String>, msvc: bool) -> Vec<Conflict> {
+    let mut settings = Vec::with_capacity(args.len());
+    let mut value_next = false;
+    for arg in args.iter() {
+        settings.push(if value_next { None } else { setting(arg, msvc) });
+        value_next = !value_next && takes_value(arg);
+    }
+
+    let mut keep = vec![true; args.len()];
+    let mut conflicts = Vec::new();
+    for (i, key) in settings.iter().enumerate() {
+        let key = match key {
+            Some(key) => key,
+            None => continue,
+        };
+        let last = match settings.iter().rposition(|s| s.as_ref() == Some(key)) {
+            Some(last) if last > i => last,
+            _ => continue,
+        };
+        keep[i] = false;
+        let conflict = Conflict {
+            dropped: args[i].clone(),
+            kept: args[last].clone(),
+        };
+        if conflict.dropped != conflict.kept && !conflicts.contains(&conflict) {
+            conflicts.push(conflict);
+        }
+    }
+    let mut keep = keep.into_iter();
+    args.retain(|_| keep.next().unwrap_or(true));
+    conflicts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(flags: &str, msvc: bool) -> (String, Vec<(String, String)>) {
+        let mut args: Vec<String> = flags.split(' ').map(String::from).collect();
+        let conflicts = dedupe(&mut args, msvc);
+        let pairs = conflicts.into_iter().map(|c| (c.dropped, c.kept)).collect();
+        (args.join(" "), pairs)
+    }
+
+    fn pair(dropped: &str, kept: &str) -> (String, String) {
+        (dropped.to_string(), kept.to_string())
+    }
+
+    #[test]
+    fn test_dedupe_last_wins() {
+        let (args, conflicts) = run("-c a.c -Wall -std=c++17 -O0 -Wall -std=c++20 -O2", false);
+        assert_eq!(args, "-c a.c -Wall -std=c++20 -O2");
+        assert_eq!(conflicts, vec![pair("-std=c++17", "-std=c++20"), pair("-O0", "-O2")]);
+
+        let (args, conflicts) = run("-DLEVEL=1 -fPIC -DLEVEL=2 -fno-PIC -DOTHER", false);
+        assert_eq!(args, "-DLEVEL=2 -fno-PIC -DOTHER");
+        assert_eq!(conflicts, vec![pair("-DLEVEL=1", "-DLEVEL=2"), pair("-fPIC", "-fno-PIC")]);
+
+        let (args, conflicts) = run("/nologo /std:c++17 /Od /DDEBUG /std:c++20 /O2", true);
+        assert_eq!(args, "/nologo /DDEBUG /std:c++20 /O2");
+        assert_eq!(conflicts.len(), 2);
+    }
+
+    #[test]
+    fn test_dedupe_keeps_cumulative_flags() {
+        let flags = "-I inc -Iinc -include a.h -include a.h -Wl,-O1 -Wl,-O1 -x c -x c \
+                     -fsanitize=address -fsanitize=undefined -Os";
+        let (args, conflicts) = run(flags, false);
+        assert_eq!(args, flags);
+        assert!(conflicts.is_empty());
+
+        // A value that looks like a flag is left alone
+        let (args, _) = run("-o -O2 -O2", false);
+        assert_eq!(args, "-o -O2 -O2");
+        let flags = "-Xclang -fno-x -O2 -Xclang -fx -mllvm -O1 -Xarch_arm64 -O3";
+        let (args, conflicts) = run(flags, false);
+        assert_eq!(args, flags);
+        assert!(conflicts.is_empty());
+    }
+}
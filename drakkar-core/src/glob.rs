@@ -0,0 +1,113 @@
+//! Minimal shell-style wildcard matching for file names and paths.
+//!
+//! Supports `*` (any run of characters, including none) and `?` (exactly
+//! one character). Everything else matches literally; there are no
+//! character classes or escapes. In paths, `*` stays within one directory
+//! and a `**` component matches any number of them, including none.
+
+use std::path::{Component, Path};
+
+/// Match `name` against `pattern`.
+pub fn matches(pattern: &str, name: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+
+    let (mut pi, mut ni) = (0, 0);
+    // Position after the last `*` and the name index it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while ni < n.len() {
+        match p.get(pi) {
+            Some('*') => {
+                backtrack = Some((pi + 1, ni));
+                pi += 1;
+            }
+            Some(&c) if c == '?' || c == n[ni] => {
+                pi += 1;
+                ni += 1;
+            }
+            _ => match backtrack {
+                // Let the last `*` swallow one more character and retry
+                Some((star_p, star_n)) => {
+                    pi = star_p;
+                    ni = star_n + 1;
+                    backtrack = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// True if `name` matches any of `patterns`.
+pub fn matches_any(patterns: &[String], name: &str) -> bool {
+    patterns.iter().any(|p| matches(p, name))
+}
+
+/// Match a `/`-separated `pattern` such as `src/**/*.cpp` against `path`,
+/// component by component. `.` components and the root are ignored on
+/// both sides.
+pub fn matches_path(pattern: &str, path: &Path) -> bool {
+    let pattern: Vec<&str> =
+        pattern.split(['/', '\\']).filter(|c| !c.is_empty() && *c != ".").collect();
+    let path: Vec<String> = path
+        .components()
+        .filter(|c| !matches!(c, Component::CurDir | Component::RootDir))
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    matches_components(&pattern, &path)
+}
+
+fn matches_components(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_components(rest, &path[skip..])),
+        Some((first, rest)) => match path.split_first() {
+            Some((name, path)) => matches(first, name) && matches_components(rest, path),
+            None => false,
+        },
+    }
+}
+
+/// True if `path` matches any of `patterns`.
+pub fn matches_any_path(patterns: &[String], path: &Path) -> bool {
+    patterns.iter().any(|p| matches_path(p, path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wildcards() {
+        assert!(matches("*~", "main.cpp~"));
+        assert!(matches("#*#", "#main.cpp#"));
+        assert!(matches("*.swp", ".main.cpp.swp"));
+        assert!(matches("a?c", "abc"));
+        assert!(matches("*", ""));
+        assert!(matches("*.c*", "x.cpp"));
+        assert!(!matches("*.orig", "main.cpp"));
+        assert!(!matches("a?c", "ac"));
+        assert!(!matches("#*#", "#main.cpp"));
+    }
+
+    #[test]
+    fn test_paths() {
+        assert!(matches_path("src/**/*.cpp", Path::new("src/main.cpp")));
+        assert!(matches_path("src/**/*.cpp", Path::new("./src/net/http/client.cpp")));
+        assert!(matches_path("src/experimental/**", Path::new("src/experimental")));
+        assert!(matches_path("src/experimental/**", Path::new("src/experimental/a/b.c")));
+        assert!(matches_path("**/*_test.c", Path::new("src/util_test.c")));
+        assert!(!matches_path("src/*.cpp", Path::new("src/net/client.cpp")));
+        assert!(!matches_path("src/**/*.cpp", Path::new("tools/main.cpp")));
+        assert!(!matches_path("src/**/*.cpp", Path::new("src/main.c")));
+    }
+
+    #[test]
+    fn test_backtracking() {
+        assert!(matches("*ab*ab", "xabyabab"));
+        assert!(!matches("*ab*ab", "xabyab_"));
+    }
+}
@@ -0,0 +1,179 @@
+//! `drakkar graph`: which headers each source includes, as Graphviz DOT or
+//! JSON, to see include coupling and the headers that rebuild the most.
+//!
+//! Edges come from the `.d` files of the last build. A depfile lists every
+//! header a source read, directly or not, so each source points straight
+//! at all of its headers rather than along the include tree.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::build::ObjectFile;
+use crate::depfile::parse_depfile;
+use crate::json::Json;
+
+/// Headers included by at least this share of sources are highlighted.
+const HOT_SHARE: f64 = 0.5;
+const WARM_SHARE: f64 = 0.25;
+
+/// The include graph of the last build.
+#[derive(Debug, Default)]
+pub struct Graph {
+    /// Each built source with the headers its last compile read.
+    pub sources: Vec<(String, Vec<String>)>,
+    /// Sources with no depfile yet, whose headers are unknown.
+    pub unbuilt: usize,
+}
+
+/// `path` relative to `root` when it is inside it, as depfiles mix the two.
+fn display_path(path: &Path, root: &Path) -> String {
+    path.strip_prefix(root).unwrap_or(path).to_string_lossy().into_owned()
+}
+
+impl Graph {
+    /// Read the depfile of every object; `root` is the project directory
+    /// absolute header paths are shown relative to.
+    pub fn from_depfiles(objects: &[ObjectFile], root: &Path) -> Self {
+        let mut graph = Graph::default();
+        for obj in objects {
+            let deps = match parse_depfile(&obj.dep_path) {
+                Ok(deps) => deps.prerequisites,
+                Err(_) => {
+                    graph.unbuilt += 1;
+                    continue;
+                }
+            };
+            let src = display_path(&obj.src.path, root);
+            let mut headers: Vec<String> = deps
+                .iter()
+                .map(|dep| display_path(dep, root))
+                .filter(|dep| *dep != src)
+                .collect();
+            headers.dedup();
+            graph.sources.push((src, headers));
+        }
+        graph
+    }
+
+    /// Every header with the number of sources including it, most
+    /// included first.
+    pub fn fan_in(&self) -> Vec<(String, usize)> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for (_, headers) in &self.sources {
+            for header in headers {
+                *counts.entry(header).or_default() += 1;
+            }
+        }
+        let mut fan_in: Vec<(String, usize)> =
+            counts.into_iter().map(|(h, n)| (h.to_string(), n)).collect();
+        fan_in.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        fan_in
+    }
+
+    /// A `digraph` with boxes for sources and ellipses for headers, each
+    /// labelled with how many sources include it; the most included are
+    /// filled red, then orange.
+    pub fn to_dot(&self) -> String {
+        let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+        let quote = |s: &str| format!("\"{}\"", escape(s));
+        let total = self.sources.len().max(1) as f64;
+        let mut dot = String::from("digraph includes {\n  rankdir=LR;\n  node [shape=box];\n");
+        for (src, _) in &self.sources {
+            dot.push_str(&format!("  {};\n", quote(src)));
+        }
+        for (header, count) in self.fan_in() {
+            let share = count as f64 / total;
+            let fill = if share >= HOT_SHARE {
+                ", style=filled, fillcolor=\"#f4a6a6\""
+            } else if share >= WARM_SHARE {
+                ", style=filled, fillcolor=\"#f8d49a\""
+            } else {
+                ""
+            };
+            dot.push_str(&format!(
+                "  {} [shape=ellipse, label=\"{}\\n{} of {}\"{}];\n",
+                quote(&header),
+                escape(&header),
+                count,
+                self.sources.len(),
+                fill
+            ));
+        }
+        for (src, headers) in &self.sources {
+            for header in headers {
+                dot.push_str(&format!("  {} -> {};\n", quote(src), quote(header)));
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// `{"sources":[{"src":…,"headers":[…]}],"headers":[{"path":…,"included_by":n}],"unbuilt":n}`
+    pub fn to_json(&self) -> String {
+        let sources = self.sources.iter().map(|(src, headers)| {
+            Json::object().field("src", src.as_str()).field("headers", headers.clone())
+        });
+        let headers = self
+            .fan_in()
+            .into_iter()
+            .map(|(path, count)| Json::object().field("path", path).field("included_by", count));
+        Json::object()
+            .field("sources", Json::Array(sources.collect()))
+            .field("headers", Json::Array(headers.collect()))
+            .field("unbuilt", self.unbuilt)
+            .to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_graph_from_depfiles() {
+        let dir = std::env::temp_dir().join("drakkar_test_graph");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let object = |name: &str| ObjectFile {
+            src: SourceFile {
+                path: PathBuf::from(format!("src/{}.c", name)),
+                rel_path: PathBuf::from(format!("{}.c", name)),
+                language: Language::C,
+            },
+            obj_path: dir.join(format!("{}.o", name)),
+            dep_path: dir.join(format!("{}.d", name)),
+        };
+        let common = dir.join("include/common.h");
+        std::fs::write(
+            dir.join("a.d"),
+            format!("a.o: src/a.c include/a.h \\\n {}\n", common.display()),
+        )
+        .unwrap();
+        std::fs::write(dir.join("b.d"), format!("b.o: src/b.c {}\n", common.display())).unwrap();
+        let objects = [object("a"), object("b"), object("c")];
+
+        let graph = Graph::from_depfiles(&objects, &dir);
+        assert_eq!(graph.unbuilt, 1);
+        assert_eq!(
+            graph.fan_in(),
+            vec![("include/common.h".to_string(), 2), ("include/a.h".to_string(), 1)]
+        );
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph includes {\n"), "{}", dot);
+        assert!(dot.contains("  \"src/a.c\" -> \"include/a.h\";\n"), "{}", dot);
+        assert!(dot.contains(
+            "  \"include/common.h\" [shape=ellipse, label=\"include/common.h\\n2 of 2\", style=filled"
+        ), "{}", dot);
+        assert!(!dot.contains("\"src/a.c\" -> \"src/a.c\""), "{}", dot);
+
+        assert_eq!(
+            graph.to_json(),
+            r#"{"sources":[{"src":"src/a.c","headers":["include/a.h","include/common.h"]},{"src":"src/b.c","headers":["include/common.h"]}],"headers":[{"path":"include/common.h","included_by":2},{"path":"include/a.h","included_by":1}],"unbuilt":1}"#
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
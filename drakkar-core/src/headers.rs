@@ -0,0 +1,313 @@
+//! `drakkar check-headers`: include guard and self-containment checks.
+//!
+//! Every project header is scanned for an include guard (`#pragma once` or
+//! the `#ifndef X` / `#define X` idiom), and guard macros shared by two
+//! headers are reported, since the second one silently becomes empty.
+//!
+//! Public headers — those under `include_dirs`, or every header under
+//! `source_dir` when no include dirs are configured — are also compiled on
+//! their own through a one-line translation unit, which catches headers
+//! that only build because of what their includers happened to include
+//! first.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::build::{Language, ObjectFile, SourceFile};
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::glob;
+
+/// Extensions treated as headers.
+const HEADER_EXTENSIONS: &[&str] = &["h", "hh", "hpp", "hxx", "h++"];
+
+/// Where the generated translation units and their objects live.
+const CHECK_DIR: &str = "check-headers";
+
+/// How a header guards against being included twice.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Guard {
+    /// `#pragma once`
+    PragmaOnce,
+    /// `#ifndef X` / `#define X`, with the macro.
+    Macro(String),
+    /// Neither.
+    Missing,
+}
+
+/// A project header and the root it was found under.
+#[derive(Debug, Clone)]
+pub struct Header {
+    /// Where it is, as the config spells its root.
+    pub path: PathBuf,
+    /// Its path below that root, as it is included.
+    pub rel_path: PathBuf,
+    /// Whether it is part of the public interface; see [`find_headers`].
+    pub public: bool,
+}
+
+/// Something wrong with a header's include guard.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuardIssue {
+    /// The header has no include guard.
+    Missing(PathBuf),
+    /// The same guard macro in more than one header.
+    Duplicate(String, Vec<PathBuf>),
+}
+
+impl std::fmt::Display for GuardIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GuardIssue::Missing(path) => {
+                write!(f, "{}: no include guard or #pragma once", path.display())
+            }
+            GuardIssue::Duplicate(name, paths) => {
+                let names: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                write!(f, "include guard {} is used by {}", name, names.join(", "))
+            }
+        }
+    }
+}
+
+/// Find every header under the source and include roots. Headers under
+/// `include_dirs` are public; without include dirs, all of them are.
+pub fn find_headers(config: &ProjectConfig) -> Result<Vec<Header>, BuildError> {
+    let mut headers = Vec::new();
+    let public_sources = config.include_dirs.is_empty();
+    for root in &config.include_dirs {
+        walk(config, root, root, true, &mut headers)?;
+    }
+    for root in &config.source_dirs {
+        walk(config, root, root, public_sources, &mut headers)?;
+    }
+    // An include dir inside source_dir would otherwise list headers twice
+    let mut seen = std::collections::HashSet::new();
+    headers.retain(|h| {
+        seen.insert(std::fs::canonicalize(&h.path).unwrap_or_else(|_| h.path.clone()))
+    });
+    Ok(headers)
+}
+
+fn walk(
+    config: &ProjectConfig,
+    root: &Path,
+    dir: &Path,
+    public: bool,
+    out: &mut Vec<Header>,
+) -> Result<(), BuildError> {
+    if !dir.is_dir() {
+        return Ok(());
+    }
+    let entries = std::fs::read_dir(dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot read directory {:?}: {}", dir, e)))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if glob::matches_any(&config.ignore_patterns, &name) {
+            continue;
+        }
+        if path.is_dir() {
+            if name.starts_with('.') || name == "target" || name == "out" {
+                continue;
+            }
+            walk(config, root, &path, public, out)?;
+        } else if is_header(&path) {
+            out.push(Header {
+                rel_path: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+                path,
+                public,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Whether `path` has a header extension: `.h`, `.hh`, `.hpp`, `.hxx` or
+/// `.h++`.
+pub fn is_header(path: &Path) -> bool {
+    path.extension()
+        .map(|e| HEADER_EXTENSIONS.contains(&e.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Identify the include guard of a header from its leading directives.
+pub fn guard_of(content: &str) -> Guard {
+    let stripped = strip_comments(content);
+    let mut directives = stripped
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(|l| match l.strip_prefix('#') {
+            Some(rest) => rest.split_whitespace().collect::<Vec<_>>(),
+            None => Vec::new(),
+        });
+
+    let first = match directives.next() {
+        Some(d) => d,
+        None => return Guard::Missing,
+    };
+    let guarded = match first.as_slice() {
+        ["pragma", "once", ..] => return Guard::PragmaOnce,
+        ["ifndef", name, ..] => name.to_string(),
+        ["if", cond @ ..] => match defined_negation(&cond.concat()) {
+            Some(name) => name,
+            None => return Guard::Missing,
+        },
+        _ => return Guard::Missing,
+    };
+    match directives.next().as_deref() {
+        Some(["define", name, ..]) if *name == guarded => Guard::Macro(guarded),
+        _ => Guard::Missing,
+    }
+}
+
+/// `!defined(X)` or `!definedX` (whitespace already removed) → `X`.
+fn defined_negation(cond: &str) -> Option<String> {
+    let rest = cond.strip_prefix("!defined")?;
+    let name = rest.trim_start_matches('(').trim_end_matches(')');
+    if name.is_empty() {
+        None
+    } else {
+        Some(name.to_string())
+    }
+}
+
+/// Drop `//` and `/* */` comments, keeping line breaks.
+fn strip_comments(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let mut chars = content.chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('/', Some('/')) => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                        break;
+                    }
+                }
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        out.push('\n');
+                    }
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Check the include guards of `headers`.
+pub fn check_guards(headers: &[Header]) -> Result<Vec<GuardIssue>, BuildError> {
+    let mut issues = Vec::new();
+    let mut by_macro: BTreeMap<String, Vec<PathBuf>> = BTreeMap::new();
+    for header in headers {
+        let content = std::fs::read_to_string(&header.path)
+            .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", header.path, e)))?;
+        match guard_of(&content) {
+            Guard::PragmaOnce => {}
+            Guard::Macro(name) => by_macro.entry(name).or_default().push(header.path.clone()),
+            Guard::Missing => issues.push(GuardIssue::Missing(header.path.clone())),
+        }
+    }
+    for (name, paths) in by_macro {
+        if paths.len() > 1 {
+            issues.push(GuardIssue::Duplicate(name, paths));
+        }
+    }
+    Ok(issues)
+}
+
+/// Write a translation unit that includes `header` alone, and return it as
+/// an object for the worker pool. `.h` headers are compiled as C++ unless
+/// the project has no C++ sources.
+pub fn standalone_unit(
+    header: &Header,
+    config: &ProjectConfig,
+    c_project: bool,
+) -> Result<ObjectFile, BuildError> {
+    let is_c = c_project && header.path.extension() == Some(std::ffi::OsStr::new("h"));
+    let (language, ext) = if is_c {
+        (Language::C, "c")
+    } else {
+        (Language::Cpp, "cpp")
+    };
+
+    let base = config.temp_dir.join(CHECK_DIR).join(&header.rel_path);
+    let tu = PathBuf::from(format!("{}.{}", base.display(), ext));
+    if let Some(parent) = tu.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+        })?;
+    }
+    let target = std::fs::canonicalize(&header.path).unwrap_or_else(|_| header.path.clone());
+    std::fs::write(&tu, format!("#include \"{}\"\n", target.display()))
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", tu, e)))?;
+
+    Ok(ObjectFile {
+        src: SourceFile {
+            path: tu,
+            rel_path: header.rel_path.clone(),
+            language,
+        },
+        obj_path: PathBuf::from(format!("{}.o", base.display())),
+        dep_path: PathBuf::from(format!("{}.d", base.display())),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_guard_forms() {
+        assert_eq!(guard_of("#pragma once\nint x;\n"), Guard::PragmaOnce);
+        assert_eq!(
+            guard_of("// Copyright\n/* multi\n   line */\n#ifndef FOO_H\n#define FOO_H\n#endif\n"),
+            Guard::Macro("FOO_H".to_string())
+        );
+        assert_eq!(
+            guard_of("#if !defined(BAR_H)\n# define BAR_H\n#endif\n"),
+            Guard::Macro("BAR_H".to_string())
+        );
+        assert_eq!(guard_of("#ifndef A_H\n#define B_H\n#endif\n"), Guard::Missing);
+        assert_eq!(guard_of("#include <vector>\n#pragma once\n"), Guard::Missing);
+        assert_eq!(guard_of(""), Guard::Missing);
+    }
+
+    #[test]
+    fn test_missing_and_duplicate_guards() {
+        let dir = std::env::temp_dir().join("drakkar_test_headers");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/net")).unwrap();
+        std::fs::write(dir.join("src/a.h"), "#ifndef UTIL_H\n#define UTIL_H\n#endif\n").unwrap();
+        std::fs::write(dir.join("src/net/util.h"), "#ifndef UTIL_H\n#define UTIL_H\n#endif\n").unwrap();
+        std::fs::write(dir.join("src/b.hpp"), "int b();\n").unwrap();
+        std::fs::write(dir.join("src/c.hpp"), "#pragma once\n").unwrap();
+        std::fs::write(dir.join("src/main.cpp"), "").unwrap();
+
+        let cfg = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            ..ProjectConfig::default()
+        };
+        let headers = find_headers(&cfg).unwrap();
+        assert_eq!(headers.len(), 4);
+        assert!(headers.iter().all(|h| h.public));
+
+        let issues = check_guards(&headers).unwrap();
+        assert_eq!(issues.len(), 2);
+        assert!(issues.contains(&GuardIssue::Missing(dir.join("src/b.hpp"))));
+        assert!(issues.iter().any(|i| matches!(i, GuardIssue::Duplicate(name, paths)
+            if name == "UTIL_H" && paths.len() == 2)));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,126 @@
+//! Include hygiene: flag translation units that pull in headers from
+//! outside the project's declared include roots.
+//!
+//! `-MMD` already omits system headers, so anything left in a `.d` file
+//! that lives outside `source_dir`, `vendor_dirs` and `include_dirs` is
+//! usually an accident — typically an absolute include into another
+//! checkout.
+
+use std::path::{Path, PathBuf};
+
+use crate::build::ObjectFile;
+use crate::codegen;
+use crate::config::ProjectConfig;
+use crate::depfile::parse_depfile;
+
+/// A header included from outside the allowed roots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IncludeViolation {
+    /// The source whose depfile lists the header.
+    pub src: PathBuf,
+    /// The header, as the depfile names it.
+    pub header: PathBuf,
+}
+
+/// Directories headers may come from, canonicalized. Roots that don't
+/// exist are dropped.
+fn allowed_roots(config: &ProjectConfig) -> Vec<PathBuf> {
+    let generated = config.generators.iter().map(|rule| codegen::out_dir(config, rule));
+    config
+        .input_dirs()
+        .cloned()
+        .chain(generated)
+        .filter_map(|p| std::fs::canonicalize(p).ok())
+        .collect()
+}
+
+/// Check every object's depfile against the allowed roots.
+/// Objects without a readable depfile are skipped.
+pub fn check_includes(objects: &[ObjectFile], config: &ProjectConfig) -> Vec<IncludeViolation> {
+    let roots = allowed_roots(config);
+    let mut violations = Vec::new();
+
+    for obj in objects {
+        let deps = match parse_depfile(&obj.dep_path) {
+            Ok(d) => d.prerequisites,
+            Err(_) => continue,
+        };
+        for dep in deps {
+            if dep == obj.src.path {
+                continue;
+            }
+            if !is_under_any(&dep, &roots) {
+                violations.push(IncludeViolation {
+                    src: obj.src.path.clone(),
+                    header: dep,
+                });
+            }
+        }
+    }
+
+    violations
+}
+
+fn is_under_any(path: &Path, roots: &[PathBuf]) -> bool {
+    match std::fs::canonicalize(path) {
+        Ok(canonical) => roots.iter().any(|root| canonical.starts_with(root)),
+        // A vanished header is the incremental check's problem, not ours
+        Err(_) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{object_path_for, Language, SourceFile};
+
+    #[test]
+    fn test_outside_header_flagged() {
+        let root = std::env::temp_dir().join("drakkar_test_hygiene");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("src")).unwrap();
+        std::fs::create_dir_all(root.join("include")).unwrap();
+        std::fs::create_dir_all(root.join("elsewhere")).unwrap();
+        std::fs::create_dir_all(root.join("target")).unwrap();
+        std::fs::create_dir_all(root.join("imgui")).unwrap();
+        std::fs::write(root.join("src/a.cpp"), "").unwrap();
+        std::fs::write(root.join("src/a.h"), "").unwrap();
+        std::fs::write(root.join("include/pub.h"), "").unwrap();
+        std::fs::write(root.join("elsewhere/stray.h"), "").unwrap();
+        std::fs::write(root.join("imgui/imgui.h"), "").unwrap();
+
+        let cfg = ProjectConfig {
+            source_dirs: vec![root.join("src")],
+            include_dirs: vec![root.join("include")],
+            vendor_dirs: vec![root.join("imgui")],
+            temp_dir: root.join("target"),
+            ..ProjectConfig::default()
+        };
+        let obj = object_path_for(
+            &SourceFile {
+                path: root.join("src/a.cpp"),
+                rel_path: PathBuf::from("a.cpp"),
+                language: Language::Cpp,
+            },
+            &cfg,
+        );
+        std::fs::write(
+            &obj.dep_path,
+            format!(
+                "a.o: {} {} {} {} {}\n",
+                root.join("src/a.cpp").display(),
+                root.join("src/a.h").display(),
+                root.join("include/pub.h").display(),
+                root.join("imgui/imgui.h").display(),
+                root.join("elsewhere/stray.h").display()
+            ),
+        )
+        .unwrap();
+
+        let violations = check_includes(&[obj], &cfg);
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].header, root.join("elsewhere/stray.h"));
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,245 @@
+//! Compiler crashes (internal compiler errors).
+//!
+//! A compile that dies with an ICE, or is killed by a signal, isn't a
+//! problem in the project's code. With `--save-ice-repro` drakkar bundles
+//! what a compiler bug report needs into `temp_dir/ice/<object>.tar`: the
+//! preprocessed source, the original command and one that reproduces the
+//! crash from the bundle alone, the compiler's `--version` and its output.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::build::{preprocessed_path, ObjectFile};
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::shell;
+use crate::toolchain::Toolchain;
+use crate::ui;
+
+/// What compilers print when they crash: GCC, Clang and MSVC.
+const ICE_PATTERNS: &[&str] = &[
+    "internal compiler error",
+    "PLEASE submit a bug report",
+    "clang frontend command failed",
+    "fatal error C1001",
+];
+
+/// Whether a failed compile was the compiler crashing rather than an
+/// error in the source. `code` is None when it was killed by a signal.
+pub fn is_ice(stderr: &str, code: Option<i32>) -> bool {
+    code.is_none() || ICE_PATTERNS.iter().any(|p| stderr.contains(p))
+}
+
+/// After a failed compile of `obj` with `compiler args`: save a repro
+/// bundle if it crashed and `--save-ice-repro` is on. Saving problems are
+/// warnings; the compile error is what gets reported.
+pub fn on_failure(
+    result: &Result<String, BuildError>,
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    compiler: &str,
+    args: &[String],
+) {
+    let (stderr, code) = match result {
+        Err(BuildError::CompileError { stderr, code, .. }) => (stderr, *code),
+        _ => return,
+    };
+    if !config.save_ice_repro || !is_ice(stderr, code) {
+        return;
+    }
+    match save_repro(obj, config, compiler, args, stderr) {
+        Ok(bundle) => ui::line(&format!(
+            "  \x1b[36mSaved\x1b[0m compiler crash repro for {} to {}",
+            ui::path(&obj.src.path),
+            ui::path(&bundle)
+        )),
+        Err(e) => ui::report_warning(&e),
+    }
+}
+
+fn save_repro(
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    compiler: &str,
+    args: &[String],
+    stderr: &str,
+) -> Result<PathBuf, BuildError> {
+    if config.toolchain == Toolchain::Msvc {
+        return Err(BuildError::ConfigError(
+            "--save-ice-repro needs a GCC-style compiler; run cl with /P to preprocess by hand"
+                .to_string(),
+        ));
+    }
+    let stem = obj.obj_path.file_stem().map(|s| s.to_string_lossy().into_owned());
+    let stem = stem.unwrap_or_default();
+    let pp = preprocessed_path(obj);
+    let pp_name = pp.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+
+    let output = Command::new(compiler)
+        .args(preprocess_args(args, &pp))
+        .output()
+        .map_err(|e| BuildError::IoError(format!("Cannot run {} for the repro: {}", compiler, e)))?;
+    if !output.status.success() {
+        return Err(BuildError::IoError(format!(
+            "Cannot preprocess {} for the repro: {}",
+            obj.src.path.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let preprocessed = std::fs::read(&pp)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", pp, e)))?;
+    if !config.keeps_temp() {
+        let _ = std::fs::remove_file(&pp);
+    }
+    let version = Command::new(compiler)
+        .arg("--version")
+        .output()
+        .map(|o| {
+            format!("{}{}", String::from_utf8_lossy(&o.stdout), String::from_utf8_lossy(&o.stderr))
+        })
+        .unwrap_or_else(|e| format!("{} --version failed: {}\n", compiler, e));
+    let src = obj.src.path.to_string_lossy();
+    let commands = format!(
+        "# Crashed while compiling {}:\n{}\n\n# Reproduce from this directory:\n{}\n",
+        src,
+        shell::command_line(compiler, args),
+        shell::command_line(compiler, &repro_args(args, &src, &pp_name, &stem))
+    );
+
+    let dir = format!("{}-ice", stem);
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let bundle = tar(now, &[
+        (format!("{}/{}", dir, pp_name), preprocessed),
+        (format!("{}/command.txt", dir), commands.into_bytes()),
+        (format!("{}/version.txt", dir), version.into_bytes()),
+        (format!("{}/stderr.txt", dir), stderr.as_bytes().to_vec()),
+    ]);
+    let out_dir = config.temp_dir.join("ice");
+    std::fs::create_dir_all(&out_dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", out_dir, e)))?;
+    let path = out_dir.join(format!("{}.tar", stem));
+    std::fs::write(&path, bundle)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))?;
+    Ok(path)
+}
+
+/// The compile command turned into one that only preprocesses into `pp`,
+/// without touching the depfile.
+fn preprocess_args(args: &[String], pp: &Path) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(a) = iter.next() {
+        match a.as_str() {
+            "-MMD" | "-MD" | "-MP" => {}
+            "-MF" | "-MT" | "-MQ" => {
+                iter.next();
+            }
+            "-c" => out.push("-E".to_string()),
+            "-o" => {
+                iter.next();
+                out.push("-o".to_string());
+                out.push(pp.to_string_lossy().into_owned());
+            }
+            _ => out.push(a.clone()),
+        }
+    }
+    out
+}
+
+/// The compile command run on the bundled preprocessed file instead of
+/// `src`, writing `<stem>.o` next to it.
+fn repro_args(args: &[String], src: &str, pp_name: &str, stem: &str) -> Vec<String> {
+    let mut out = Vec::with_capacity(args.len());
+    let mut iter = args.iter();
+    while let Some(a) = iter.next() {
+        match a.as_str() {
+            "-MMD" | "-MD" | "-MP" => {}
+            // The preprocessed file's extension already names its language
+            "-MF" | "-MT" | "-MQ" | "-x" => {
+                iter.next();
+            }
+            "-o" => {
+                iter.next();
+                out.push("-o".to_string());
+                out.push(format!("{}.o", stem));
+            }
+            a if a == src => out.push(pp_name.to_string()),
+            _ => out.push(a.clone()),
+        }
+    }
+    out
+}
+
+/// A ustar archive of regular files, readable by any `tar`.
+fn tar(mtime: u64, files: &[(String, Vec<u8>)]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for (name, data) in files {
+        let mut header = [0u8; 512];
+        let mut put = |offset: usize, value: &[u8]| {
+            header[offset..offset + value.len()].copy_from_slice(value);
+        };
+        put(0, &name.as_bytes()[..name.len().min(100)]);
+        put(100, b"0000644\0");
+        put(108, b"0000000\0");
+        put(116, b"0000000\0");
+        put(124, format!("{:011o}\0", data.len()).as_bytes());
+        put(136, format!("{:011o}\0", mtime).as_bytes());
+        put(156, b"0");
+        // Magic and version
+        put(257, b"ustar\0");
+        put(263, b"00");
+        // The checksum is computed with its own field as spaces
+        put(148, b"        ");
+        let sum: u32 = header.iter().map(|&b| b as u32).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+        out.extend_from_slice(&header);
+        out.extend_from_slice(data);
+        out.resize(out.len() + (512 - data.len() % 512) % 512, 0);
+    }
+    out.resize(out.len() + 1024, 0);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ice() {
+        let gcc = "src/a.c:3:1: internal compiler error: Segmentation fault\n";
+        assert!(is_ice(gcc, Some(4)));
+        assert!(is_ice("clang: error: clang frontend command failed with exit code 139\n", Some(1)));
+        assert!(is_ice("", None));
+        assert!(!is_ice("src/a.c:3:1: error: expected ';'\n", Some(1)));
+    }
+
+    #[test]
+    fn test_repro_args() {
+        let args: Vec<String> = ["-O2", "-MMD", "-MF", "target/a.d", "-c", "src/a.c", "-o", "target/a.o"]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        assert_eq!(
+            preprocess_args(&args, Path::new("target/a.i")),
+            vec!["-O2", "-E", "src/a.c", "-o", "target/a.i"]
+        );
+        assert_eq!(repro_args(&args, "src/a.c", "a.i", "a"), vec!["-O2", "-c", "a.i", "-o", "a.o"]);
+    }
+
+    #[test]
+    fn test_tar() {
+        let archive = tar(0, &[("a-ice/a.i".to_string(), b"int x;\n".to_vec())]);
+        assert_eq!(archive.len(), 512 * 4);
+        assert_eq!(&archive[..9], b"a-ice/a.i");
+        assert_eq!(&archive[124..136], b"00000000007\0");
+        assert_eq!(&archive[257..263], b"ustar\0");
+        let sum: u32 = archive[..512]
+            .iter()
+            .enumerate()
+            .map(|(i, &b)| if (148..156).contains(&i) { b' ' as u32 } else { b as u32 })
+            .sum();
+        assert_eq!(&archive[148..156], format!("{:06o}\0 ", sum).as_bytes());
+        assert_eq!(&archive[512..519], b"int x;\n");
+    }
+}
@@ -0,0 +1,167 @@
+//! `drakkar impact <header>`: which translation units a header change
+//! would rebuild, and roughly how long that takes.
+//!
+//! Uses the `.d` files and compile times recorded by the last build, so the
+//! answer is only as fresh as that build. Sources that have never been
+//! compiled have no depfile and are counted separately rather than guessed.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::build::ObjectFile;
+use crate::depfile::parse_depfile;
+use crate::error::BuildError;
+use crate::state::BuildState;
+
+/// A translation unit that includes the header.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AffectedUnit {
+    /// The source, relative to its source dir.
+    pub rel_path: PathBuf,
+    /// Last recorded compile time, if any.
+    pub compile_time: Option<Duration>,
+}
+
+/// What changing a header would rebuild.
+#[derive(Debug, Default)]
+pub struct Impact {
+    /// All the project's translation units.
+    pub total_units: usize,
+    /// The ones whose depfile lists the header.
+    pub affected: Vec<AffectedUnit>,
+    /// Units with no depfile yet (never built), so their includes are unknown.
+    pub unknown: usize,
+}
+
+impl Impact {
+    /// Sum of recorded compile times of affected units.
+    pub fn cpu_time(&self) -> Duration {
+        self.affected.iter().filter_map(|u| u.compile_time).sum()
+    }
+
+    /// Affected units without a recorded compile time.
+    pub fn untimed(&self) -> usize {
+        self.affected.iter().filter(|u| u.compile_time.is_none()).count()
+    }
+
+    /// Rough wall-clock estimate: CPU time spread over `jobs`, but never
+    /// less than the slowest single unit.
+    pub fn wall_estimate(&self, jobs: usize) -> Duration {
+        let slowest = self
+            .affected
+            .iter()
+            .filter_map(|u| u.compile_time)
+            .max()
+            .unwrap_or_default();
+        (self.cpu_time() / jobs.max(1) as u32).max(slowest)
+    }
+}
+
+/// Find every object whose depfile lists `header`.
+pub fn analyze(
+    header: &Path,
+    objects: &[ObjectFile],
+    state: &BuildState,
+) -> Result<Impact, BuildError> {
+    let target = std::fs::canonicalize(header)
+        .map_err(|e| BuildError::IoError(format!("Cannot read header {:?}: {}", header, e)))?;
+
+    let mut impact = Impact {
+        total_units: objects.len(),
+        ..Impact::default()
+    };
+
+    for obj in objects {
+        let deps = match parse_depfile(&obj.dep_path) {
+            Ok(d) => d.prerequisites,
+            Err(_) => {
+                impact.unknown += 1;
+                continue;
+            }
+        };
+        let includes = deps
+            .iter()
+            .any(|dep| std::fs::canonicalize(dep).ok().as_ref() == Some(&target));
+        if includes {
+            impact.affected.push(AffectedUnit {
+                rel_path: obj.src.rel_path.clone(),
+                compile_time: state
+                    .get(&obj.obj_path)
+                    .and_then(|s| s.compile_ms)
+                    .map(Duration::from_millis),
+            });
+        }
+    }
+
+    // Most expensive first; unknown timings last
+    impact
+        .affected
+        .sort_by_key(|u| std::cmp::Reverse(u.compile_time));
+    Ok(impact)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use crate::state::ObjectState;
+
+    fn object(dir: &Path, name: &str) -> ObjectFile {
+        ObjectFile {
+            src: SourceFile {
+                path: dir.join(format!("{}.cpp", name)),
+                rel_path: PathBuf::from(format!("{}.cpp", name)),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join(format!("{}.o", name)),
+            dep_path: dir.join(format!("{}.d", name)),
+        }
+    }
+
+    #[test]
+    fn test_affected_units_and_estimate() {
+        let dir = std::env::temp_dir().join("drakkar_test_impact");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("core.h"), "").unwrap();
+        std::fs::write(dir.join("other.h"), "").unwrap();
+
+        let a = object(&dir, "a");
+        let b = object(&dir, "b");
+        let c = object(&dir, "c");
+        let never_built = object(&dir, "d");
+        let core = dir.join("core.h").display().to_string();
+        let other = dir.join("other.h").display().to_string();
+        std::fs::write(&a.dep_path, format!("a.o: a.cpp {}\n", core)).unwrap();
+        std::fs::write(&b.dep_path, format!("b.o: b.cpp {} {}\n", other, core)).unwrap();
+        std::fs::write(&c.dep_path, format!("c.o: c.cpp {}\n", other)).unwrap();
+
+        let mut state = BuildState::load(&dir);
+        for (obj, ms) in [(&a, 300), (&b, 100)] {
+            state.set(
+                &obj.obj_path,
+                ObjectState {
+                    compile_ms: Some(ms),
+                    ..Default::default()
+                },
+            );
+        }
+
+        let impact = analyze(&dir.join("core.h"), &[a, b, c, never_built], &state).unwrap();
+        assert_eq!(impact.total_units, 4);
+        assert_eq!(impact.unknown, 1);
+        let names: Vec<_> = impact.affected.iter().map(|u| u.rel_path.clone()).collect();
+        assert_eq!(names, vec![PathBuf::from("a.cpp"), PathBuf::from("b.cpp")]);
+        assert_eq!(impact.cpu_time(), Duration::from_millis(400));
+        assert_eq!(impact.wall_estimate(4), Duration::from_millis(300));
+        assert_eq!(impact.wall_estimate(1), Duration::from_millis(400));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_missing_header_is_error() {
+        let state = BuildState::default();
+        assert!(analyze(Path::new("/nonexistent/drakkar.h"), &[], &state).is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! `drakkar install`: copy a build into a prefix the way `make install`
+//! does. Executables go to `bin/`, static libraries to `lib/` with their
+//! target's `include_dirs` headers under `include/`, and plugins to
+//! `lib/<plugins_dir>/`, where they are relative to output_dir.
+//!
+//! `DESTDIR` is put in front of the prefix, for packaging into a staging
+//! root. Every file installed is listed in `install_manifest.txt` in
+//! output_dir, so an install can be undone with
+//! `xargs rm < out/install_manifest.txt`.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+use crate::build::{plugin_path, resolve_targets};
+use crate::config::{ProjectConfig, TargetType};
+use crate::error::BuildError;
+use crate::headers::find_headers;
+use crate::status::BuildSummary;
+
+/// The list of installed paths [`write_manifest`] leaves in output_dir.
+pub const MANIFEST: &str = "install_manifest.txt";
+
+/// Prefix used without `--prefix` or `install_prefix`.
+pub const DEFAULT_PREFIX: &str = "/usr/local";
+
+/// A file to install and where it goes, relative to the prefix.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    /// The file in the project.
+    pub from: PathBuf,
+    /// Its path below the prefix.
+    pub to: PathBuf,
+}
+
+/// Where files for `prefix` are written: the prefix itself, or the prefix
+/// under `destdir`.
+pub fn install_root(prefix: &Path, destdir: Option<&Path>) -> PathBuf {
+    match destdir.filter(|d| !d.as_os_str().is_empty()) {
+        Some(destdir) => {
+            let relative: PathBuf = prefix
+                .components()
+                .filter(|c| !matches!(c, Component::RootDir | Component::Prefix(_)))
+                .collect();
+            destdir.join(relative)
+        }
+        None => prefix.to_path_buf(),
+    }
+}
+
+fn file_name(path: &Path) -> PathBuf {
+    PathBuf::from(path.file_name().unwrap_or_default())
+}
+
+/// What installing the build in `summary` copies. `target` is the
+/// `[target.<name>]` it was limited to, if any.
+pub fn plan(
+    config: &ProjectConfig,
+    summary: &BuildSummary,
+    target: Option<&str>,
+) -> Result<Vec<Item>, BuildError> {
+    let plugins: HashSet<PathBuf> = config
+        .targets
+        .iter()
+        .filter(|t| t.target_type == TargetType::Plugin)
+        .map(|t| plugin_path(config, t.output_name()))
+        .collect();
+    let mut items = Vec::new();
+    for path in summary.executables.iter().chain(&summary.libraries) {
+        let to = if plugins.contains(path) {
+            Path::new("lib").join(path.strip_prefix(&config.output_dir).unwrap_or(path))
+        } else if summary.executables.contains(path) {
+            Path::new("bin").join(file_name(path))
+        } else {
+            Path::new("lib").join(file_name(path))
+        };
+        items.push(Item { from: path.clone(), to });
+    }
+
+    // A library's own include dirs are its interface; project-wide ones
+    // are shared with everything else
+    let libraries = if config.targets.is_empty() {
+        vec![]
+    } else {
+        resolve_targets(config, target)?
+    };
+    for lib in libraries.iter().filter(|t| t.target_type == TargetType::StaticLib) {
+        let headers_of = ProjectConfig {
+            include_dirs: lib.include_dirs.clone(),
+            source_dirs: vec![],
+            ignore_patterns: config.ignore_patterns.clone(),
+            ..ProjectConfig::default()
+        };
+        for header in find_headers(&headers_of)? {
+            let to = Path::new("include").join(&header.rel_path);
+            if !items.iter().any(|i| i.to == to) {
+                items.push(Item { from: header.path, to });
+            }
+        }
+    }
+    Ok(items)
+}
+
+/// Copy `items` under `root`, returning the paths written. Each file is
+/// copied beside its destination and renamed into place, so a running
+/// executable being replaced keeps working.
+pub fn install(items: &[Item], root: &Path) -> Result<Vec<PathBuf>, BuildError> {
+    let mut installed = Vec::with_capacity(items.len());
+    for item in items {
+        let dest = root.join(&item.to);
+        let dir = dest.parent().unwrap_or(root);
+        std::fs::create_dir_all(dir)
+            .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+        let partial = dir.join(format!(".{}.partial", file_name(&dest).display()));
+        std::fs::copy(&item.from, &partial)
+            .and_then(|_| std::fs::rename(&partial, &dest))
+            .map_err(|e| {
+                let _ = std::fs::remove_file(&partial);
+                BuildError::IoError(format!("Cannot install {:?} to {:?}: {}", item.from, dest, e))
+            })?;
+        installed.push(dest);
+    }
+    Ok(installed)
+}
+
+/// Write the manifest of an install into output_dir: one installed path
+/// per line.
+pub fn write_manifest(
+    config: &ProjectConfig,
+    installed: &[PathBuf],
+) -> Result<PathBuf, BuildError> {
+    let path = config.output_dir.join(MANIFEST);
+    let text: String = installed.iter().map(|p| format!("{}\n", p.display())).collect();
+    std::fs::create_dir_all(&config.output_dir)
+        .and_then(|()| std::fs::write(&path, text))
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))?;
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_root() {
+        let prefix = Path::new("/usr/local");
+        assert_eq!(install_root(prefix, None), Path::new("/usr/local"));
+        assert_eq!(install_root(prefix, Some(Path::new(""))), Path::new("/usr/local"));
+        assert_eq!(
+            install_root(prefix, Some(Path::new("/tmp/stage"))),
+            Path::new("/tmp/stage/usr/local")
+        );
+        let relative = install_root(Path::new("opt"), Some(Path::new("stage")));
+        assert_eq!(relative, Path::new("stage/opt"));
+    }
+
+    #[test]
+    fn test_plan_and_install() {
+        let dir = std::env::temp_dir().join("drakkar_test_install");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        std::fs::write(dir.join("out/app"), b"exe").unwrap();
+        std::fs::write(dir.join("out/libcore.a"), b"lib").unwrap();
+        let config = ProjectConfig { output_dir: dir.join("out"), ..Default::default() };
+        let summary = BuildSummary {
+            executables: vec![dir.join("out/app")],
+            libraries: vec![dir.join("out/libcore.a")],
+            ..Default::default()
+        };
+        let items = plan(&config, &summary, None).unwrap();
+        let to: Vec<&Path> = items.iter().map(|i| i.to.as_path()).collect();
+        assert_eq!(to, [Path::new("bin/app"), Path::new("lib/libcore.a")]);
+
+        let root = install_root(Path::new("/usr"), Some(&dir.join("stage")));
+        let installed = install(&items, &root).unwrap();
+        assert_eq!(std::fs::read(dir.join("stage/usr/bin/app")).unwrap(), b"exe");
+        assert_eq!(installed[1], dir.join("stage/usr/lib/libcore.a"));
+        let manifest = write_manifest(&config, &installed).unwrap();
+        let text = std::fs::read_to_string(manifest).unwrap();
+        assert_eq!(text.lines().count(), 2);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,84 @@
+//! The drakkar build system as a library: config loading, source
+//! collection and the build graph, incremental rebuild decisions, and the
+//! worker pool. The `drakkar` binary is a command-line front end over it;
+//! IDE plugins and CI wrappers can drive a build the same way:
+//!
+//! ```no_run
+//! use std::path::Path;
+//! use std::sync::Arc;
+//!
+//! use drakkar_core::config::{find_config, read_config, BuildProfile};
+//! use drakkar_core::engine::build_project;
+//!
+//! // Paths in the config are relative to the working directory
+//! let config = Arc::new(read_config(&find_config(Path::new(".")))?);
+//! let summary = build_project(&config, &BuildProfile::Debug, &[], None)?;
+//! println!("{} compiled, {} up to date", summary.compiled, summary.up_to_date);
+//! # Ok::<(), drakkar_core::error::BuildError>(())
+//! ```
+//!
+//! Progress goes to stdout and compiler diagnostics to stderr, through
+//! [`ui`]. Errors are [`error::BuildError`]s with a stable code each.
+//!
+//! - [`config`]: `config.txt` / `drakkar.toml` into a [`config::ProjectConfig`]
+//! - [`build`]: sources, object paths, compile and link commands
+//! - [`engine`]: the incremental build of a project or one target
+//! - [`worker`]: the parallel compile pool, over a [`compiler::Compiler`]
+//! - [`state`]: what was built from which flags, between runs
+
+#![warn(missing_docs)]
+
+pub mod build;
+pub mod cache;
+pub mod codegen;
+pub mod compare;
+pub mod compdb;
+pub mod compiler;
+pub mod conan;
+pub mod config;
+pub mod depfile;
+pub mod diagnostics;
+pub mod diskspace;
+pub mod engine;
+pub mod error;
+pub mod estimate;
+pub mod events;
+pub mod fetch;
+pub mod flags;
+pub mod graph;
+pub mod hash;
+pub mod headers;
+pub mod hygiene;
+pub mod ice;
+pub mod impact;
+pub mod install;
+pub mod linkmap;
+pub mod messages;
+pub mod metrics;
+pub mod migrate;
+pub mod platform;
+pub mod probe;
+pub mod promote;
+pub mod prune;
+pub mod remote;
+pub mod sandbox;
+pub mod selfupdate;
+pub mod shared;
+pub mod shell;
+pub mod state;
+pub mod stats;
+pub mod status;
+pub mod template;
+pub mod testing;
+pub mod trace;
+pub mod ui;
+pub mod watch;
+pub mod worker;
+pub mod workspace;
+
+mod explain;
+mod glob;
+mod json;
+mod parse;
+mod toml;
+mod toolchain;
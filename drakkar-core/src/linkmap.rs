@@ -0,0 +1,193 @@
+//! `link_map = "true"`: where the linker found each symbol the project
+//! needed.
+//!
+//! The link writes a GNU ld map file with a cross reference table
+//! (`-Wl,-Map=... -Wl,--cref`). Each table entry lists the file defining a
+//! symbol first, then the files referencing it. Symbols a project object
+//! references but that some other file defines were satisfied from a
+//! library (or a prebuilt object), which is what you want to see when the
+//! wrong version of a library got linked.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::build::ObjectFile;
+use crate::error::BuildError;
+
+/// Column the file name starts at in ld's cross reference table.
+const FILE_COLUMN: usize = 50;
+
+/// One symbol of the cross reference table: the defining file first, then
+/// its referencers.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CrefEntry {
+    /// The symbol, as the map file prints it.
+    pub symbol: String,
+    /// The defining file, then each file referencing it.
+    pub files: Vec<String>,
+}
+
+/// Parse the "Cross Reference Table" section of a GNU ld map file.
+pub fn parse_cref(map: &str) -> Vec<CrefEntry> {
+    let mut entries: Vec<CrefEntry> = Vec::new();
+    // Skip the title, blank line and "Symbol  File" heading
+    let lines = map
+        .lines()
+        .skip_while(|l| l.trim() != "Cross Reference Table")
+        .skip(3);
+
+    for line in lines {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with(' ') {
+            if let Some(entry) = entries.last_mut() {
+                entry.files.push(line.trim().to_string());
+            }
+            continue;
+        }
+        // Names too long for the column are followed by a single space;
+        // demangled names may contain spaces themselves
+        let (symbol, file) = match (line.get(..FILE_COLUMN), line.get(FILE_COLUMN..)) {
+            (Some(name), Some(file)) if name.ends_with(' ') && !file.starts_with(' ') => {
+                (name.trim_end(), file)
+            }
+            _ => line.rsplit_once(' ').unwrap_or((line, "")),
+        };
+        let mut files = Vec::new();
+        if !file.trim().is_empty() {
+            files.push(file.trim().to_string());
+        }
+        entries.push(CrefEntry {
+            symbol: symbol.trim_end().to_string(),
+            files,
+        });
+    }
+    entries
+}
+
+/// Symbols referenced by `objects` but defined elsewhere, grouped by the
+/// file that provided them.
+pub fn provided_symbols(
+    entries: &[CrefEntry],
+    objects: &[ObjectFile],
+) -> BTreeMap<String, Vec<String>> {
+    let ours: Vec<String> = objects
+        .iter()
+        .map(|o| o.obj_path.to_string_lossy().into_owned())
+        .collect();
+    let mut providers: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for entry in entries {
+        let (definer, referencers) = match entry.files.split_first() {
+            Some(split) => split,
+            None => continue,
+        };
+        if ours.contains(definer) || !referencers.iter().any(|r| ours.contains(r)) {
+            continue;
+        }
+        providers
+            .entry(definer.clone())
+            .or_default()
+            .push(entry.symbol.clone());
+    }
+    providers
+}
+
+/// Print which file satisfied each external symbol of `objects`, from the
+/// map file the link just wrote.
+pub fn report(map_path: &Path, objects: &[ObjectFile]) -> Result<(), BuildError> {
+    let map = std::fs::read_to_string(map_path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read link map {:?}: {}", map_path, e)))?;
+    let providers = provided_symbols(&parse_cref(&map), objects);
+    if providers.is_empty() {
+        return Ok(());
+    }
+    crate::ui::line(&format!(
+        "  \x1b[1mSymbols resolved from libraries\x1b[0m ({})",
+        map_path.display()
+    ));
+    for (provider, symbols) in &providers {
+        crate::ui::line(&format!("    {}: {}", provider, symbols.join(", ")));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use std::path::PathBuf;
+
+    const MAP: &str = "\
+Archive member included to satisfy reference by file (symbol)
+
+lib/liba.a(a.o)               target/main.o (a1)
+
+Cross Reference Table
+
+Symbol                                            File
+_start                                            /usr/lib/Scrt1.o
+a1                                                lib/liba.a(a.o)
+                                                  target/main.o
+helper                                            target/util.o
+                                                  target/main.o
+printf                                            /lib/x86_64-linux-gnu/libc.so.6
+                                                  target/main.o
+puts                                              /lib/x86_64-linux-gnu/libc.so.6
+                                                  lib/liba.a(a.o)
+very_long_function_name_that_exceeds_the_column(int, char) /usr/lib/libv.so
+                                                  target/util.o
+";
+
+    fn object(path: &str) -> ObjectFile {
+        ObjectFile {
+            src: SourceFile {
+                path: PathBuf::from(path),
+                rel_path: PathBuf::from(path),
+                language: Language::Cpp,
+            },
+            obj_path: PathBuf::from(path),
+            dep_path: PathBuf::from(path),
+        }
+    }
+
+    #[test]
+    fn test_parse_cref() {
+        let entries = parse_cref(MAP);
+        assert_eq!(entries.len(), 6);
+        assert_eq!(
+            entries[1],
+            CrefEntry {
+                symbol: "a1".to_string(),
+                files: vec!["lib/liba.a(a.o)".to_string(), "target/main.o".to_string()],
+            }
+        );
+        assert_eq!(
+            entries[5].symbol,
+            "very_long_function_name_that_exceeds_the_column(int, char)"
+        );
+        assert_eq!(entries[5].files[0], "/usr/lib/libv.so");
+        assert!(parse_cref("no table here\n").is_empty());
+    }
+
+    #[test]
+    fn test_provided_symbols_skips_project_and_library_internals() {
+        let objects = [object("target/main.o"), object("target/util.o")];
+        let providers = provided_symbols(&parse_cref(MAP), &objects);
+        let summary: Vec<(&str, Vec<&str>)> = providers
+            .iter()
+            .map(|(p, s)| (p.as_str(), s.iter().map(String::as_str).collect()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/lib/x86_64-linux-gnu/libc.so.6", vec!["printf"]),
+                (
+                    "/usr/lib/libv.so",
+                    vec!["very_long_function_name_that_exceeds_the_column(int, char)"]
+                ),
+                ("lib/liba.a(a.o)", vec!["a1"]),
+            ]
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Message catalog for user-facing strings.
+//!
+//! Progress labels, error prefixes and help headings are looked up here by
+//! [`Msg`] key so they can be shown in the language named by `DRAKKAR_LANG`
+//! (`es`, `de`, `fr`; `en` is the default). Region and encoding suffixes
+//! are ignored, so `de_DE.UTF-8` selects German. A key missing from a
+//! catalog falls back to English.
+//!
+//! Compiler output, config keys, flag names and JSON field names are never
+//! translated; error codes stay the stable thing to match on.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::error::BuildError;
+
+/// A language with a catalog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lang {
+    En,
+    Es,
+    De,
+    Fr,
+}
+
+impl Lang {
+    const ALL: [Lang; 4] = [Lang::En, Lang::Es, Lang::De, Lang::Fr];
+
+    fn parse(s: &str) -> Option<Self> {
+        let code = s
+            .split(['_', '-', '.'])
+            .next()
+            .unwrap_or("")
+            .to_ascii_lowercase();
+        match code.as_str() {
+            "" | "c" | "en" => Some(Lang::En),
+            "es" => Some(Lang::Es),
+            "de" => Some(Lang::De),
+            "fr" => Some(Lang::Fr),
+            _ => None,
+        }
+    }
+}
+
+/// A translatable string, documented with its English text.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Msg {
+    /// `Compiling`
+    Compiling,
+    /// `Compiled`
+    Compiled,
+    /// `left`
+    Left,
+    /// `Linking`
+    Linking,
+    /// `Archiving`
+    Archiving,
+    /// `Up-to-date`
+    UpToDate,
+    /// `All up-to-date`
+    AllUpToDate,
+    /// `nothing to recompile.`
+    NothingToRecompile,
+    /// `Reused`
+    Reused,
+    /// `object(s) from shared_temp_dir`
+    FromSharedTempDir,
+    /// `Cache`
+    Cache,
+    /// `hit(s)`
+    Hits,
+    /// `miss(es)`
+    Misses,
+    /// `from the remote cache`
+    FromRemoteCache,
+    /// `failed`
+    Failed,
+    /// `Finished`
+    Finished,
+    /// `Running`
+    Running,
+    /// `Removed`
+    Removed,
+    /// `file(s)`
+    Files,
+    /// `error`
+    Error,
+    /// `warning`
+    Warning,
+    /// `help`
+    Hint,
+    /// `IO error`
+    IoError,
+    /// `Parse error`
+    ParseError,
+    /// `Compile error in`
+    CompileErrorIn,
+    /// `Link error`
+    LinkError,
+    /// `Config error`
+    ConfigError,
+    /// `Build cancelled by user`
+    Cancelled,
+    /// `error(s) occurred`
+    ErrorsOccurred,
+    /// `C/C++ build system`
+    HelpTitle,
+    /// `USAGE:`
+    HelpUsage,
+    /// `COMMANDS:`
+    HelpCommands,
+    /// `OPTIONS:`
+    HelpOptions,
+    /// `EXAMPLES:`
+    HelpExamples,
+}
+
+impl Msg {
+    #[cfg(test)]
+    const ALL: [Msg; 34] = [
+        Msg::Compiling,
+        Msg::Compiled,
+        Msg::Left,
+        Msg::Linking,
+        Msg::Archiving,
+        Msg::UpToDate,
+        Msg::AllUpToDate,
+        Msg::NothingToRecompile,
+        Msg::Reused,
+        Msg::FromSharedTempDir,
+        Msg::Cache,
+        Msg::Hits,
+        Msg::Misses,
+        Msg::FromRemoteCache,
+        Msg::Failed,
+        Msg::Finished,
+        Msg::Running,
+        Msg::Removed,
+        Msg::Files,
+        Msg::Error,
+        Msg::Warning,
+        Msg::Hint,
+        Msg::IoError,
+        Msg::ParseError,
+        Msg::CompileErrorIn,
+        Msg::LinkError,
+        Msg::ConfigError,
+        Msg::Cancelled,
+        Msg::ErrorsOccurred,
+        Msg::HelpTitle,
+        Msg::HelpUsage,
+        Msg::HelpCommands,
+        Msg::HelpOptions,
+        Msg::HelpExamples,
+    ];
+}
+
+static LANG: AtomicU8 = AtomicU8::new(0);
+
+/// Select the language from `DRAKKAR_LANG`. An unsupported value leaves
+/// English in place and is returned as an error for the caller to warn about.
+pub fn init() -> Result<(), BuildError> {
+    let value = match std::env::var("DRAKKAR_LANG") {
+        Ok(v) => v,
+        Err(_) => return Ok(()),
+    };
+    match Lang::parse(&value) {
+        Some(lang) => {
+            set_lang(lang);
+            Ok(())
+        }
+        None => Err(BuildError::ConfigError(format!(
+            "DRAKKAR_LANG='{}' is not a supported language (en, es, de, fr); using English",
+            value
+        ))),
+    }
+}
+
+fn set_lang(lang: Lang) {
+    let index = Lang::ALL.iter().position(|l| *l == lang).unwrap_or(0);
+    LANG.store(index as u8, Ordering::Relaxed);
+}
+
+fn lang() -> Lang {
+    Lang::ALL
+        .get(LANG.load(Ordering::Relaxed) as usize)
+        .copied()
+        .unwrap_or(Lang::En)
+}
+
+/// `msg` in the current language.
+pub fn tr(msg: Msg) -> &'static str {
+    lookup(lang(), msg)
+}
+
+fn lookup(lang: Lang, msg: Msg) -> &'static str {
+    let catalog: &[(Msg, &str)] = match lang {
+        Lang::En => &[],
+        Lang::Es => SPANISH,
+        Lang::De => GERMAN,
+        Lang::Fr => FRENCH,
+    };
+    catalog
+        .iter()
+        .find(|(m, _)| *m == msg)
+        .map(|(_, text)| *text)
+        .unwrap_or_else(|| english(msg))
+}
+
+/// The help text with its title and section headings translated. Command
+/// and option descriptions stay in English so they always match the flags.
+pub fn localize_help(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let translated = match line {
+                "drakkar — C/C++ build system" => format!("drakkar — {}", tr(Msg::HelpTitle)),
+                "USAGE:" => tr(Msg::HelpUsage).to_string(),
+                "COMMANDS:" => tr(Msg::HelpCommands).to_string(),
+                "OPTIONS:" => tr(Msg::HelpOptions).to_string(),
+                "EXAMPLES:" => tr(Msg::HelpExamples).to_string(),
+                other => other.to_string(),
+            };
+            translated + "\n"
+        })
+        .collect()
+}
+
+fn english(msg: Msg) -> &'static str {
+    match msg {
+        Msg::Compiling => "Compiling",
+        Msg::Compiled => "Compiled",
+        Msg::Left => "left",
+        Msg::Linking => "Linking",
+        Msg::Archiving => "Archiving",
+        Msg::UpToDate => "Up-to-date",
+        Msg::AllUpToDate => "All up-to-date",
+        Msg::NothingToRecompile => "nothing to recompile.",
+        Msg::Reused => "Reused",
+        Msg::FromSharedTempDir => "object(s) from shared_temp_dir",
+        Msg::Cache => "Cache",
+        Msg::Hits => "hit(s)",
+        Msg::Misses => "miss(es)",
+        Msg::FromRemoteCache => "from the remote cache",
+        Msg::Failed => "failed",
+        Msg::Finished => "Finished",
+        Msg::Running => "Running",
+        Msg::Removed => "Removed",
+        Msg::Files => "file(s)",
+        Msg::Error => "error",
+        Msg::Warning => "warning",
+        Msg::Hint => "help",
+        Msg::IoError => "IO error",
+        Msg::ParseError => "Parse error",
+        Msg::CompileErrorIn => "Compile error in",
+        Msg::LinkError => "Link error",
+        Msg::ConfigError => "Config error",
+        Msg::Cancelled => "Build cancelled by user",
+        Msg::ErrorsOccurred => "error(s) occurred",
+        Msg::HelpTitle => "C/C++ build system",
+        Msg::HelpUsage => "USAGE:",
+        Msg::HelpCommands => "COMMANDS:",
+        Msg::HelpOptions => "OPTIONS:",
+        Msg::HelpExamples => "EXAMPLES:",
+    }
+}
+
+const SPANISH: &[(Msg, &str)] = &[
+    (Msg::Compiling, "Compilando"),
+    (Msg::Compiled, "Compilados"),
+    (Msg::Left, "restantes"),
+    (Msg::Linking, "Enlazando"),
+    (Msg::Archiving, "Archivando"),
+    (Msg::UpToDate, "Al día"),
+    (Msg::AllUpToDate, "Todo al día"),
+    (Msg::NothingToRecompile, "nada que recompilar."),
+    (Msg::Reused, "Reutilizados"),
+    (Msg::FromSharedTempDir, "objeto(s) de shared_temp_dir"),
+    (Msg::Cache, "Caché"),
+    (Msg::Hits, "acierto(s)"),
+    (Msg::Misses, "fallo(s)"),
+    (Msg::FromRemoteCache, "del caché remoto"),
+    (Msg::Failed, "con errores"),
+    (Msg::Finished, "Terminado"),
+    (Msg::Running, "Ejecutando"),
+    (Msg::Removed, "Eliminados"),
+    (Msg::Files, "archivo(s)"),
+    (Msg::Error, "error"),
+    (Msg::Warning, "advertencia"),
+    (Msg::Hint, "ayuda"),
+    (Msg::IoError, "Error de E/S"),
+    (Msg::ParseError, "Error de sintaxis"),
+    (Msg::CompileErrorIn, "Error de compilación en"),
+    (Msg::LinkError, "Error de enlace"),
+    (Msg::ConfigError, "Error de configuración"),
+    (Msg::Cancelled, "Compilación cancelada por el usuario"),
+    (Msg::ErrorsOccurred, "error(es)"),
+    (Msg::HelpTitle, "sistema de compilación para C/C++"),
+    (Msg::HelpUsage, "USO:"),
+    (Msg::HelpCommands, "COMANDOS:"),
+    (Msg::HelpOptions, "OPCIONES:"),
+    (Msg::HelpExamples, "EJEMPLOS:"),
+];
+
+const GERMAN: &[(Msg, &str)] = &[
+    (Msg::Compiling, "Kompiliere"),
+    (Msg::Compiled, "Kompiliert"),
+    (Msg::Left, "verbleibend"),
+    (Msg::Linking, "Linke"),
+    (Msg::Archiving, "Archiviere"),
+    (Msg::UpToDate, "Aktuell"),
+    (Msg::AllUpToDate, "Alles aktuell"),
+    (Msg::NothingToRecompile, "nichts neu zu kompilieren."),
+    (Msg::Reused, "Wiederverwendet"),
+    (Msg::FromSharedTempDir, "Objekt(e) aus shared_temp_dir"),
+    (Msg::Cache, "Cache"),
+    (Msg::Hits, "Treffer"),
+    (Msg::Misses, "Fehlzugriff(e)"),
+    (Msg::FromRemoteCache, "aus dem Remote-Cache"),
+    (Msg::Failed, "fehlgeschlagen"),
+    (Msg::Finished, "Fertig"),
+    (Msg::Running, "Starte"),
+    (Msg::Removed, "Entfernt"),
+    (Msg::Files, "Datei(en)"),
+    (Msg::Error, "Fehler"),
+    (Msg::Warning, "Warnung"),
+    (Msg::Hint, "Hilfe"),
+    (Msg::IoError, "E/A-Fehler"),
+    (Msg::ParseError, "Syntaxfehler"),
+    (Msg::CompileErrorIn, "Kompilierfehler in"),
+    (Msg::LinkError, "Linkerfehler"),
+    (Msg::ConfigError, "Konfigurationsfehler"),
+    (Msg::Cancelled, "Build vom Benutzer abgebrochen"),
+    (Msg::ErrorsOccurred, "Fehler aufgetreten"),
+    (Msg::HelpTitle, "Buildsystem für C/C++"),
+    (Msg::HelpUsage, "AUFRUF:"),
+    (Msg::HelpCommands, "BEFEHLE:"),
+    (Msg::HelpOptions, "OPTIONEN:"),
+    (Msg::HelpExamples, "BEISPIELE:"),
+];
+
+const FRENCH: &[(Msg, &str)] = &[
+    (Msg::Compiling, "Compilation"),
+    (Msg::Compiled, "Compilé"),
+    (Msg::Left, "restantes"),
+    (Msg::Linking, "Édition des liens"),
+    (Msg::Archiving, "Archivage"),
+    (Msg::UpToDate, "À jour"),
+    (Msg::AllUpToDate, "Tout est à jour"),
+    (Msg::NothingToRecompile, "rien à recompiler."),
+    (Msg::Reused, "Réutilisé"),
+    (Msg::FromSharedTempDir, "objet(s) depuis shared_temp_dir"),
+    (Msg::Cache, "Cache"),
+    (Msg::Hits, "succès"),
+    (Msg::Misses, "échec(s)"),
+    (Msg::FromRemoteCache, "du cache distant"),
+    (Msg::Failed, "en échec"),
+    (Msg::Finished, "Terminé"),
+    (Msg::Running, "Exécution de"),
+    (Msg::Removed, "Supprimé"),
+    (Msg::Files, "fichier(s)"),
+    (Msg::Error, "erreur"),
+    (Msg::Warning, "avertissement"),
+    (Msg::Hint, "aide"),
+    (Msg::IoError, "Erreur d'E/S"),
+    (Msg::ParseError, "Erreur de syntaxe"),
+    (Msg::CompileErrorIn, "Erreur de compilation dans"),
+    (Msg::LinkError, "Erreur d'édition des liens"),
+    (Msg::ConfigError, "Erreur de configuration"),
+    (Msg::Cancelled, "Compilation annulée par l'utilisateur"),
+    (Msg::ErrorsOccurred, "erreur(s)"),
+    (Msg::HelpTitle, "système de build C/C++"),
+    (Msg::HelpUsage, "UTILISATION :"),
+    (Msg::HelpCommands, "COMMANDES :"),
+    (Msg::HelpOptions, "OPTIONS :"),
+    (Msg::HelpExamples, "EXEMPLES :"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lang_parse_ignores_region_and_encoding() {
+        assert_eq!(Lang::parse("de_DE.UTF-8"), Some(Lang::De));
+        assert_eq!(Lang::parse("es-MX"), Some(Lang::Es));
+        assert_eq!(Lang::parse("FR"), Some(Lang::Fr));
+        assert_eq!(Lang::parse("C"), Some(Lang::En));
+        assert_eq!(Lang::parse("tlh"), None);
+    }
+
+    #[test]
+    fn test_catalogs_are_complete() {
+        for catalog in [SPANISH, GERMAN, FRENCH] {
+            for msg in Msg::ALL {
+                assert!(catalog.iter().any(|(m, _)| *m == msg), "{:?} missing", msg);
+            }
+        }
+        assert_eq!(lookup(Lang::De, Msg::Compiling), "Kompiliere");
+        assert_eq!(lookup(Lang::En, Msg::Compiling), "Compiling");
+    }
+}
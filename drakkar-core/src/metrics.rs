@@ -0,0 +1,194 @@
+//! `--metrics prometheus:<path>`: build metrics in the Prometheus textfile
+//! format, for node_exporter's textfile collector on build machines.
+//! Nothing leaves the machine; drakkar only writes the file.
+//!
+//! Gauges describe the last build. The `_total` counters are read back from
+//! the previous file and carried forward, so they count every build that
+//! wrote to it.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::BuildProfile;
+use crate::error::BuildError;
+use crate::status::BuildSummary;
+
+/// Where `--metrics` writes, and in which format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum MetricsOutput {
+    /// Prometheus text format, to this file.
+    Prometheus(PathBuf),
+}
+
+impl MetricsOutput {
+    /// Parse `prometheus:out/metrics.prom`.
+    pub fn parse(s: &str) -> Result<Self, BuildError> {
+        match s.split_once(':') {
+            Some(("prometheus", path)) if !path.is_empty() => {
+                Ok(MetricsOutput::Prometheus(PathBuf::from(path)))
+            }
+            _ => Err(BuildError::ParseError(format!(
+                "--metrics {:?}: expected prometheus:<path>",
+                s
+            ))),
+        }
+    }
+}
+
+/// The last build, as the metrics report it.
+pub struct BuildMetrics<'a> {
+    /// How the build ended.
+    pub outcome: &'a Result<BuildSummary, BuildError>,
+    /// The profile it built.
+    pub profile: &'a BuildProfile,
+    /// Its wall time.
+    pub duration: Duration,
+    /// parallel_jobs the build ran with.
+    pub jobs: usize,
+}
+
+const BUILDS: &str = "drakkar_builds_total";
+const FAILURES: &str = "drakkar_build_failures_total";
+
+/// The value of the unlabelled sample `name` in a textfile; 0 when absent.
+fn previous(text: &str, name: &str) -> u64 {
+    text.lines()
+        .filter_map(|line| line.strip_prefix(name)?.strip_prefix(' '))
+        .find_map(|value| value.trim().parse().ok())
+        .unwrap_or(0)
+}
+
+/// One metric with its HELP and TYPE lines; `labels` is `{k="v"}` or empty.
+fn push(out: &mut String, name: &str, kind: &str, help: &str, labels: &str, value: String) {
+    out.push_str(&format!("# HELP {} {}\n# TYPE {} {}\n", name, help, name, kind));
+    out.push_str(&format!("{}{} {}\n", name, labels, value));
+}
+
+/// The metrics file for `build`, with counters continued from
+/// `previous_text` (the old file's contents).
+pub fn prometheus_text(build: &BuildMetrics, previous_text: &str) -> String {
+    let mut out = String::new();
+    let failed = build.outcome.is_err();
+    let builds = previous(previous_text, BUILDS) + 1;
+    push(&mut out, BUILDS, "counter", "Builds run", "", builds.to_string());
+    let failures = previous(previous_text, FAILURES) + u64::from(failed);
+    push(&mut out, FAILURES, "counter", "Builds that failed", "", failures.to_string());
+    let profile = match build.profile {
+        BuildProfile::Debug => "debug",
+        BuildProfile::Release => "release",
+    };
+    push(
+        &mut out,
+        "drakkar_build_success",
+        "gauge",
+        "Whether the last build succeeded",
+        &format!("{{profile=\"{}\"}}", profile),
+        u8::from(!failed).to_string(),
+    );
+    let gauges = [
+        (
+            "drakkar_build_duration_seconds",
+            "Wall time of the last build",
+            format!("{:.3}", build.duration.as_secs_f64()),
+        ),
+        ("drakkar_build_jobs", "Parallel jobs of the last build", build.jobs.to_string()),
+    ];
+    for (name, help, value) in gauges {
+        push(&mut out, name, "gauge", help, "", value);
+    }
+    // A failed build stopped before its counts were complete
+    if let Ok(summary) = build.outcome {
+        let counts = [
+            ("drakkar_build_compiled", "Files compiled by the last build", summary.compiled),
+            ("drakkar_build_up_to_date", "Files the last build left alone", summary.up_to_date),
+            ("drakkar_build_warnings", "Compiler warnings of the last build", summary.warnings),
+            ("drakkar_cache_hits", "Object cache hits in the last build", summary.cache_hits),
+            ("drakkar_cache_misses", "Object cache misses in the last build", summary.cache_misses),
+        ];
+        for (name, help, value) in counts {
+            push(&mut out, name, "gauge", help, "", value.to_string());
+        }
+        let lookups = summary.cache_hits + summary.cache_misses;
+        if lookups > 0 {
+            push(
+                &mut out,
+                "drakkar_cache_hit_ratio",
+                "gauge",
+                "Share of object cache lookups that hit in the last build",
+                "",
+                format!("{:.3}", summary.cache_hits as f64 / lookups as f64),
+            );
+        }
+    }
+    out
+}
+
+/// Write the metrics for `build`. The file is replaced in one rename, so
+/// the collector never reads it half-written.
+pub fn write(output: &MetricsOutput, build: &BuildMetrics) -> Result<(), BuildError> {
+    let MetricsOutput::Prometheus(path) = output;
+    let old = std::fs::read_to_string(path).unwrap_or_default();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent).map_err(|e| {
+            BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+        })?;
+    }
+    let partial = partial_path(path);
+    std::fs::write(&partial, prometheus_text(build, &old))
+        .and_then(|()| std::fs::rename(&partial, path))
+        .map_err(|e| BuildError::IoError(format!("Cannot write metrics {:?}: {}", path, e)))
+}
+
+/// `out/metrics.prom` → `out/.metrics.prom.partial`: the collector only
+/// reads `*.prom` files.
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.partial", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_output() {
+        assert_eq!(
+            MetricsOutput::parse("prometheus:out/metrics.prom").unwrap(),
+            MetricsOutput::Prometheus(PathBuf::from("out/metrics.prom"))
+        );
+        assert!(MetricsOutput::parse("statsd:localhost").is_err());
+        assert!(MetricsOutput::parse("prometheus:").is_err());
+    }
+
+    #[test]
+    fn test_prometheus_text() {
+        let ok = Ok(BuildSummary {
+            compiled: 3,
+            up_to_date: 5,
+            cache_hits: 1,
+            cache_misses: 3,
+            ..Default::default()
+        });
+        let build = BuildMetrics {
+            outcome: &ok,
+            profile: &BuildProfile::Release,
+            duration: Duration::from_millis(1250),
+            jobs: 8,
+        };
+        let text = prometheus_text(&build, "");
+        assert!(text.contains("# TYPE drakkar_builds_total counter\ndrakkar_builds_total 1\n"));
+        assert!(text.contains("\ndrakkar_build_failures_total 0\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_build_success{profile=\"release\"} 1\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_build_duration_seconds 1.250\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_build_jobs 8\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_cache_hit_ratio 0.250\n"), "{}", text);
+
+        let failed: Result<BuildSummary, _> = Err(BuildError::Cancelled);
+        let build = BuildMetrics { outcome: &failed, ..build };
+        let text = prometheus_text(&build, &text);
+        assert!(text.contains("\ndrakkar_builds_total 2\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_build_failures_total 1\n"), "{}", text);
+        assert!(text.contains("\ndrakkar_build_success{profile=\"release\"} 0\n"), "{}", text);
+        assert!(!text.contains("drakkar_build_compiled"), "{}", text);
+    }
+}
@@ -0,0 +1,365 @@
+//! Config schema versioning and `drakkar migrate`.
+//!
+//! Migrations are line-based so comments, blank lines and key order in the
+//! user's config.txt survive untouched. Each step upgrades one schema version
+//! by renaming keys and appending new ones; steps are chained until the file
+//! reaches `CURRENT_CONFIG_VERSION`.
+//!
+//! `drakkar migrate --to-toml` converts a config.txt into `drakkar.toml`.
+
+use crate::config::{parse_value_str, strip_inline_comment};
+use crate::error::BuildError;
+use crate::toml;
+
+/// Schema version written by this drakkar. Files without a
+/// `config_version` key are treated as version 1.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+struct Migration {
+    /// Version this step upgrades from (to `from + 1`).
+    from: u32,
+    /// Keys renamed in this version: (old, new).
+    renames: &'static [(&'static str, &'static str)],
+    /// Keys introduced in this version with their default: (key, value, comment).
+    added: &'static [(&'static str, &'static str, &'static str)],
+}
+
+/// Version 2 only introduced `config_version` itself: every version 1 key
+/// kept its name and meaning.
+const MIGRATIONS: &[Migration] = &[Migration {
+    from: 1,
+    renames: &[],
+    added: &[],
+}];
+
+/// Result of migrating a config file's contents.
+pub struct MigrationResult {
+    /// The migrated config text.
+    pub content: String,
+    /// The `config_version` it had before.
+    pub from_version: u32,
+    /// One line per change made, for the user.
+    pub changes: Vec<String>,
+}
+
+/// Parse the `config_version` value out of raw config text (1 if absent).
+pub fn detect_version(content: &str) -> Result<u32, BuildError> {
+    for (idx, line) in content.lines().enumerate() {
+        if let Some((key, value)) = split_key_value(line) {
+            if key == "config_version" {
+                let v = value.trim().trim_matches(|c| c == '"' || c == '\'');
+                return v.parse::<u32>().map_err(|_| {
+                    BuildError::ParseError(format!(
+                        "Line {}: config_version must be an integer, got '{}'",
+                        idx + 1,
+                        v
+                    ))
+                });
+            }
+        }
+    }
+    Ok(1)
+}
+
+/// Upgrade config text to `CURRENT_CONFIG_VERSION`, preserving comments.
+pub fn migrate_config(content: &str) -> Result<MigrationResult, BuildError> {
+    let from_version = detect_version(content)?;
+    if from_version > CURRENT_CONFIG_VERSION {
+        return Err(BuildError::ConfigError(format!(
+            "config_version {} is newer than this drakkar supports ({}); upgrade drakkar",
+            from_version, CURRENT_CONFIG_VERSION
+        )));
+    }
+
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+    let mut changes = Vec::new();
+
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from_version) {
+        for line in lines.iter_mut() {
+            let key = match split_key_value(line) {
+                Some((key, _)) => key.to_string(),
+                None => continue,
+            };
+            if let Some((_, new)) = migration.renames.iter().find(|(old, _)| *old == key) {
+                let eq = line.find('=').unwrap_or(0);
+                let indent_len = line.len() - line.trim_start().len();
+                let indent = line[..indent_len].to_string();
+                *line = format!("{}{} {}", indent, new, line[eq..].trim_end());
+                changes.push(format!("renamed '{}' to '{}'", key, new));
+            }
+        }
+
+        for (key, value, comment) in migration.added {
+            let present = lines
+                .iter()
+                .any(|l| split_key_value(l).map(|(k, _)| k == *key).unwrap_or(false));
+            if !present {
+                lines.push(String::new());
+                lines.push(format!("# {}", comment));
+                lines.push(format!("{} = \"{}\"", key, value));
+                changes.push(format!("added '{}' (default \"{}\")", key, value));
+            }
+        }
+    }
+
+    let version_line = format!("config_version = \"{}\"", CURRENT_CONFIG_VERSION);
+    match lines
+        .iter()
+        .position(|l| split_key_value(l).map(|(k, _)| k == "config_version").unwrap_or(false))
+    {
+        Some(idx) => {
+            if from_version != CURRENT_CONFIG_VERSION {
+                lines[idx] = version_line;
+            }
+        }
+        None => {
+            // Place it right after the leading comment block.
+            let idx = lines
+                .iter()
+                .position(|l| !l.trim().is_empty() && !l.trim_start().starts_with('#'))
+                .unwrap_or(lines.len());
+            lines.insert(idx, version_line);
+        }
+    }
+    if from_version != CURRENT_CONFIG_VERSION {
+        changes.push(format!(
+            "set config_version {} -> {}",
+            from_version, CURRENT_CONFIG_VERSION
+        ));
+    }
+
+    let mut out = lines.join("\n");
+    if content.ends_with('\n') || content.is_empty() {
+        out.push('\n');
+    }
+
+    Ok(MigrationResult {
+        content: out,
+        from_version,
+        changes,
+    })
+}
+
+/// Convert config.txt text into an equivalent `drakkar.toml`.
+///
+/// Plain keys go under `[build]`, `debug.*` / `release.*` under
+/// `[profile.<name>]`, `libs.<name>.<os>` under `[libs.<name>]` and
+/// `target.<name>.<key>` under `[target.<name>]`, with
+/// tables in the order they first appear. Comments move with the key below
+/// them; a comment block followed by a blank line at the top of the file
+/// stays at the top. Values of several tokens become arrays, so quoted
+/// flags survive without re-quoting.
+pub fn config_to_toml(content: &str) -> Result<String, BuildError> {
+    let mut header: Vec<String> = Vec::new();
+    let mut tables: Vec<(String, Vec<String>)> = Vec::new();
+    // Comments waiting for the key they describe
+    let mut pending: Vec<String> = Vec::new();
+    let mut section: Option<String> = None;
+
+    for (idx, line) in content.lines().enumerate() {
+        let line_no = idx + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            if tables.is_empty() {
+                header.append(&mut pending);
+            }
+            continue;
+        }
+        if trimmed.starts_with('#') {
+            pending.push(trimmed.to_string());
+            continue;
+        }
+        if let Some(name) = trimmed.strip_prefix('[').and_then(|r| r.strip_suffix(']')) {
+            section = Some(name.trim().to_string());
+            continue;
+        }
+
+        let (key, value) = split_key_value(line).ok_or_else(|| {
+            BuildError::ParseError(format!(
+                "Line {}: expected 'key = value', got '{}'",
+                line_no, trimmed
+            ))
+        })?;
+        let key = match &section {
+            Some(name) => format!("{}.{}", name, key),
+            None => key.to_string(),
+        };
+        let value_part = strip_inline_comment(value);
+        let comment = value[value_part.len()..].trim();
+        let tokens = parse_value_str(value_part, line_no)?;
+
+        let (table, name) = toml_table(&key);
+        let value = if name == "post_link" {
+            // Kept as one string: its `;` separators mean the same there
+            let raw = value_part.trim();
+            let inner = raw
+                .strip_prefix('"')
+                .and_then(|v| v.strip_suffix('"'))
+                .unwrap_or(raw);
+            toml::quote(inner)
+        } else {
+            toml_value(&tokens)
+        };
+        let mut entry = format!("{} = {}", toml_key(name), value);
+        if !comment.is_empty() {
+            entry.push(' ');
+            entry.push_str(comment);
+        }
+        let pos = match tables.iter().position(|(t, _)| *t == table) {
+            Some(pos) => pos,
+            None => {
+                tables.push((table, Vec::new()));
+                tables.len() - 1
+            }
+        };
+        tables[pos].1.append(&mut pending);
+        tables[pos].1.push(entry);
+    }
+
+    let mut blocks: Vec<String> = Vec::new();
+    if !header.is_empty() {
+        blocks.push(header.join("\n"));
+    }
+    for (table, lines) in tables {
+        blocks.push(format!("[{}]\n{}", table, lines.join("\n")));
+    }
+    if !pending.is_empty() {
+        blocks.push(pending.join("\n"));
+    }
+    let mut out = blocks.join("\n\n");
+    out.push('\n');
+    Ok(out)
+}
+
+/// Table and key name for a full config.txt key.
+fn toml_table(key: &str) -> (String, &str) {
+    for prefix in ["libs", "target"] {
+        let split = key
+            .strip_prefix(prefix)
+            .and_then(|k| k.strip_prefix('.'))
+            .and_then(|k| k.rsplit_once('.'));
+        if let Some((name, sub)) = split {
+            return (format!("{}.{}", prefix, toml_key(name)), sub);
+        }
+    }
+    for profile in ["debug", "release"] {
+        if let Some(sub) = key.strip_prefix(profile).and_then(|k| k.strip_prefix('.')) {
+            return (format!("profile.{}", profile), sub);
+        }
+    }
+    ("build".to_string(), key)
+}
+
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml::quote(key)
+    }
+}
+
+/// A single token that splits back to itself stays a scalar; anything else
+/// becomes an array of verbatim tokens.
+fn toml_value(tokens: &[String]) -> String {
+    match tokens {
+        [] => "\"\"".to_string(),
+        [token] if !token.contains(|c: char| c.is_whitespace() || "\"'\\".contains(c)) => {
+            if token == "true" || token == "false" || token.parse::<i64>().is_ok() {
+                token.clone()
+            } else {
+                toml::quote(token)
+            }
+        }
+        _ => {
+            let items: Vec<String> = tokens.iter().map(|t| toml::quote(t)).collect();
+            format!("[{}]", items.join(", "))
+        }
+    }
+}
+
+fn split_key_value(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let eq = trimmed.find('=')?;
+    Some((trimmed[..eq].trim(), trimmed[eq + 1..].trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_migrate_v1_keeps_keys_and_comments() {
+        // The demo config as it was before config_version existed
+        let v1 = "# drakkar config — project: demo\napp_name = \"demo\"\nsource_dir = \"src/\"\n\n\
+                  # Compiler flags\nc_flags = \"-Wall -Wextra -std=c11\" # inline\n\
+                  parallel_jobs = \"4\"\ngcc_path = \"gcc\"\n";
+        let res = migrate_config(v1).unwrap();
+        assert_eq!(res.from_version, 1);
+        let expected = v1.replacen("app_name", "config_version = \"2\"\napp_name", 1);
+        assert_eq!(res.content, expected);
+        assert_eq!(res.changes, ["set config_version 1 -> 2"]);
+    }
+
+    #[test]
+    fn test_migrate_current_is_noop() {
+        let v2 = "config_version = \"2\"\napp_name = \"demo\"\n";
+        let res = migrate_config(v2).unwrap();
+        assert_eq!(res.content, v2);
+        assert!(res.changes.is_empty());
+    }
+
+    #[test]
+    fn test_config_to_toml() {
+        let txt = r#"# demo project
+
+# name
+app_name = "demo"
+parallel_jobs = "4"
+release.strip = "full"
+release.post_link = "strip; sign --key k"
+cxx_flags = "-Wall '-DNAME="a b"'" # warnings
+[libs]
+socket.windows = "ws2_32"
+[debug]
+ld_flags = ""
+[target.tool]
+deps = "util"
+"#;
+        let expected = r#"# demo project
+
+[build]
+# name
+app_name = "demo"
+parallel_jobs = 4
+cxx_flags = ["-Wall", "-DNAME=\"a b\""] # warnings
+
+[profile.release]
+strip = "full"
+post_link = "strip; sign --key k"
+
+[libs.socket]
+windows = "ws2_32"
+
+[profile.debug]
+ld_flags = ""
+
+[target.tool]
+deps = "util"
+"#;
+        let converted = config_to_toml(txt).unwrap();
+        assert_eq!(converted, expected);
+        assert!(crate::toml::parse(&converted).is_ok());
+    }
+
+    #[test]
+    fn test_newer_version_rejected() {
+        assert!(migrate_config("config_version = \"99\"\n").is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Shared pieces of the config.txt and depfile parsers: shell-style value
+//! tokenizing, and error reports that point at the offending text.
+//!
+//! Parsers record a [`Problem`] for each error and carry on with the next
+//! line, so a file with several mistakes is reported in one go:
+//!
+//! ```text
+//! config.txt:4:18: Unterminated double quote
+//!   4 | cxx_flags = "-O2 "-DNAME=x
+//!     |                  ^^^^^^^^^
+//! ```
+
+use std::path::Path;
+
+/// 1-based line and column (in characters) of the first character to
+/// underline, and how many to underline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Span {
+    pub line: usize,
+    pub col: usize,
+    pub len: usize,
+}
+
+impl Span {
+    /// The span of `part`, a subslice of `line` (line number `line_no`).
+    pub fn within(line: &str, line_no: usize, part: &str) -> Span {
+        let start = offset_of(line, part);
+        Span {
+            line: line_no,
+            col: line[..start].chars().count() + 1,
+            len: part.chars().count().max(1),
+        }
+    }
+}
+
+/// Byte offset of `part` in `whole`, of which it must be a subslice.
+fn offset_of(whole: &str, part: &str) -> usize {
+    let offset = (part.as_ptr() as usize).wrapping_sub(whole.as_ptr() as usize);
+    if offset <= whole.len() {
+        offset
+    } else {
+        0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub span: Span,
+    pub message: String,
+}
+
+/// Why `tokenize` gave up, with character offsets into its input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenError {
+    pub offset: usize,
+    pub len: usize,
+    pub message: &'static str,
+}
+
+/// Split a value like a shell would: whitespace separates tokens, single
+/// quotes keep everything literal, double quotes allow `\"`, `\\`, `\n`
+/// and `\t`, and a backslash outside quotes escapes the next character.
+/// Commas are never separators, so `-Wl,-rpath,./lib` stays one token, and
+/// a quoted empty string is an (empty) token.
+pub fn tokenize(input: &str) -> Result<Vec<String>, TokenError> {
+    let chars: Vec<char> = input.chars().collect();
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        match chars[i] {
+            '\\' => {
+                in_token = true;
+                match chars.get(i + 1) {
+                    Some(&next) => current.push(next),
+                    None => {
+                        return Err(TokenError {
+                            offset: i,
+                            len: 1,
+                            message: "Trailing backslash in value",
+                        })
+                    }
+                }
+                i += 1;
+            }
+            '\'' => {
+                in_token = true;
+                i += 1;
+                while chars.get(i) != Some(&'\'') {
+                    match chars.get(i) {
+                        Some(&c) => current.push(c),
+                        None => {
+                            return Err(TokenError {
+                                offset: start,
+                                len: chars.len() - start,
+                                message: "Unterminated single quote",
+                            })
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            '"' => {
+                in_token = true;
+                i += 1;
+                loop {
+                    match chars.get(i) {
+                        Some('"') => break,
+                        Some('\\') if i + 1 < chars.len() => {
+                            i += 1;
+                            match chars[i] {
+                                '"' => current.push('"'),
+                                '\\' => current.push('\\'),
+                                ' ' => current.push(' '),
+                                'n' => current.push('\n'),
+                                't' => current.push('\t'),
+                                // Keep the backslash for unrecognized escapes
+                                c => {
+                                    current.push('\\');
+                                    current.push(c);
+                                }
+                            }
+                        }
+                        Some(&c) if c != '\\' => current.push(c),
+                        _ => {
+                            return Err(TokenError {
+                                offset: start,
+                                len: chars.len() - start,
+                                message: "Unterminated double quote",
+                            })
+                        }
+                    }
+                    i += 1;
+                }
+            }
+            ' ' | '\t' => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                in_token = true;
+                current.push(c);
+            }
+        }
+        i += 1;
+    }
+
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// `path:line:col: message` for every problem, each followed by its source
+/// line with the span underlined.
+pub fn report(path: &Path, source: &str, problems: &[Problem]) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let width = problems
+        .iter()
+        .map(|p| p.span.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+
+    let mut out = String::new();
+    for (n, problem) in problems.iter().enumerate() {
+        let Span { line, col, len } = problem.span;
+        if n > 0 {
+            out.push('\n');
+        }
+        out.push_str(&format!("{}:{}:{}: {}", path.display(), line, col, problem.message));
+        let text = match lines.get(line.wrapping_sub(1)) {
+            Some(text) => *text,
+            None => continue,
+        };
+        // Tabs are copied so the carets line up however they render
+        let indent: String = text
+            .chars()
+            .take(col.saturating_sub(1))
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        out.push_str(&format!(
+            "\n  {:>w$} | {}\n  {:>w$} | {}{}",
+            line,
+            text,
+            "",
+            indent,
+            "^".repeat(len.max(1)),
+            w = width
+        ));
+    }
+    if problems.len() > 1 {
+        out.push_str(&format!("\n{} errors in {}", problems.len(), path.display()));
+    }
+    out
+}
+
+/// `count` random short strings of characters the parsers treat specially,
+/// from a fixed-seed xorshift so a failure reproduces.
+#[cfg(test)]
+pub fn fuzz_inputs(count: usize) -> Vec<String> {
+    const ALPHABET: &[char] = &[
+        'a', 'Z', '0', ' ', '\t', '\n', '\r', '\\', '"', '\'', '=', ':', '#', '[', ']', '$',
+        ',', '.', '/', 'é', '€', '\u{0}',
+    ];
+    let mut state: u64 = 0x2545_f491_4f6c_dd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..count)
+        .map(|_| {
+            let len = (next() % 40) as usize;
+            (0..len).map(|_| ALPHABET[(next() % ALPHABET.len() as u64) as usize]).collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::depfile::parse_depfile_text;
+
+    #[test]
+    fn test_tokenize_errors_and_report() {
+        assert_eq!(tokenize("a '' b ''").unwrap(), vec!["a", "", "b", ""]);
+        let err = tokenize(r#"-O2 "-DNAME=x"#).unwrap_err();
+        assert_eq!((err.offset, err.len, err.message), (4, 9, "Unterminated double quote"));
+        assert_eq!(tokenize("x \\").unwrap_err().offset, 2);
+        assert_eq!(tokenize("\"a\\").unwrap_err().message, "Unterminated double quote");
+
+        let source = "app_name = \"x\"\n\tcxx_flags = \"-O2 \"-DNAME=x\n";
+        let line = source.lines().nth(1).unwrap();
+        let problems = [
+            Problem {
+                span: Span::within(line, 2, &line[13..]),
+                message: "Unterminated double quote".to_string(),
+            },
+            Problem {
+                span: Span { line: 1, col: 1, len: 8 },
+                message: "unknown".to_string(),
+            },
+        ];
+        assert_eq!(
+            report(Path::new("config.txt"), source, &problems),
+            "config.txt:2:14: Unterminated double quote\n\
+             \x20 2 | \tcxx_flags = \"-O2 \"-DNAME=x\n\
+             \x20   | \t            ^^^^^^^^^^^^^^\n\
+             config.txt:1:1: unknown\n\
+             \x20 1 | app_name = \"x\"\n\
+             \x20   | ^^^^^^^^\n\
+             2 errors in config.txt"
+        );
+    }
+
+    #[test]
+    fn test_fuzz_tokenize_and_depfile() {
+        for input in fuzz_inputs(5000) {
+            match tokenize(&input) {
+                Ok(_) => {}
+                Err(e) => assert!(e.offset + e.len <= input.chars().count(), "{:?}", input),
+            }
+            let _ = parse_depfile_text(&input, Path::new("fuzz.d"));
+        }
+    }
+}
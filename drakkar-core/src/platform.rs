@@ -1,33 +1,52 @@
-/// Platform-specific utilities for signal handling and process management.
-///
-/// Two variants are implemented:
-///
-/// - **Variant A (pure std)**: Uses a global AtomicBool cancellation token
-///   and kills child processes via `Child::kill()`.
-///
-/// - **Variant B (Unix FFI)**: When `use_process_groups` is true and we're
-///   on Unix, spawned children get their own process group (pgid). On Ctrl+C,
-///   the entire process group is killed via `killpg`. This guarantees that
-///   grandchildren (e.g. processes spawned by compiler scripts) are also killed.
-///
-/// On non-Unix platforms, Variant A is always used.
+//! Platform-specific utilities for signal handling and process management.
+//!
+//! Two variants are implemented:
+//!
+//! - **Variant A (pure std)**: Uses a global AtomicBool cancellation token
+//!   and kills child processes via `Child::kill()`.
+//!
+//! - **Variant B (Unix FFI)**: When `use_process_groups` is true and we're
+//!   on Unix, spawned children get their own process group (pgid). On Ctrl+C,
+//!   the entire process group is killed via `killpg`. This guarantees that
+//!   grandchildren (e.g. processes spawned by compiler scripts) are also killed.
+//!
+//! On non-Unix platforms, Variant A is always used.
 
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
 
 /// Global cancellation token. Workers check this flag in their loops.
 static CANCEL_TOKEN: AtomicBool = AtomicBool::new(false);
 
+/// Whether the build should stop: Ctrl+C, or a failure under fail-fast.
 pub fn is_cancelled() -> bool {
     CANCEL_TOKEN.load(Ordering::Relaxed)
 }
 
+/// Ask every worker to stop.
 pub fn cancel() {
     CANCEL_TOKEN.store(true, Ordering::Relaxed);
 }
 
+/// Set by Ctrl+C only. Fail-fast builds raise CANCEL_TOKEN too, so this is
+/// what tells the two apart.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Whether the user pressed Ctrl+C.
+pub fn is_interrupted() -> bool {
+    INTERRUPTED.load(Ordering::Relaxed)
+}
+
+fn interrupt() {
+    INTERRUPTED.store(true, Ordering::Relaxed);
+    cancel();
+}
+
+/// Clear the cancellation token before another build in the same process
+/// (`drakkar watch`). A Ctrl+C stays in effect.
 pub fn reset_cancel() {
-    CANCEL_TOKEN.store(false, Ordering::Relaxed);
+    if !is_interrupted() {
+        CANCEL_TOKEN.store(false, Ordering::Relaxed);
+    }
 }
 
 /// Register a Ctrl+C / SIGINT handler.
@@ -48,7 +67,6 @@ pub fn register_ctrlc_handler() {
 
     #[cfg(unix)]
     {
-        use std::os::unix::io::RawFd;
         unsafe {
             // Set up SIGINT handler using libc via raw syscall-free approach.
             // We use signal(SIGINT, SIG_DFL) as baseline and a background thread
@@ -79,8 +97,6 @@ unsafe fn register_unix_sigint_handler() {
     //
     // Self-pipe trick avoids async-signal-safety issues.
 
-    use std::os::unix::io::FromRawFd;
-
     extern "C" fn sigint_handler(_sig: libc_signum) {
         // Write a byte to the write end of the self-pipe.
         // SAFETY: write(2) is async-signal-safe.
@@ -101,32 +117,28 @@ unsafe fn register_unix_sigint_handler() {
     SIGNAL_PIPE_WRITE_FD.store(write_fd, std::sync::atomic::Ordering::Relaxed);
 
     // Install SIGINT handler
-    install_sigaction(sigint_handler as usize);
+    install_sigaction(sigint_handler as *const () as usize);
 
     // Spawn background thread that reads the pipe and sets CANCEL_TOKEN.
     let _ = std::thread::Builder::new()
         .name("drakkar-sigint-watcher".to_string())
         .spawn(move || {
             let mut buf = [0u8; 1];
-            loop {
-                let n = read_from_fd(read_fd, &mut buf);
-                if n > 0 {
-                    eprintln!("\n\x1b[33mCancelling build (Ctrl+C)...\x1b[0m");
-                    cancel();
-                    // Close write end to let subsequent reads return 0 (EOF)
-                    // so we don't spin, break after first signal.
-                    break;
-                } else {
-                    break;
-                }
+            // Only the first signal matters; the watcher exits afterwards
+            // so it never spins on a closed pipe.
+            if read_from_fd(read_fd, &mut buf) > 0 {
+                eprintln!("\n\x1b[33mCancelling build (Ctrl+C)...\x1b[0m");
+                interrupt();
             }
         });
 }
 
 // ---- Minimal Unix FFI (only used when compiling on Unix) ----
 #[cfg(unix)]
+#[allow(non_camel_case_types)]
 type libc_signum = libc_int;
 #[cfg(unix)]
+#[allow(non_camel_case_types)]
 type libc_int = std::ffi::c_int;
 
 #[cfg(unix)]
@@ -226,7 +238,7 @@ fn register_windows_ctrl_handler() {
             0 | 1 => {
                 // CTRL_C_EVENT or CTRL_BREAK_EVENT
                 eprintln!("\n\x1b[33mCancelling build (Ctrl+C)...\x1b[0m");
-                cancel();
+                interrupt();
                 1 // handled
             }
             _ => 0,
@@ -289,3 +301,170 @@ fn libc_setpgid(pid: i32, pgid: i32) -> i32 {
 pub fn set_process_group(_command: &mut std::process::Command) {
     // No-op
 }
+
+/// Whether stdout is an interactive terminal (not a pipe or file), so
+/// cursor movement won't end up in logs.
+#[cfg(unix)]
+pub fn stdout_is_terminal() -> bool {
+    extern "C" {
+        fn isatty(fd: libc_int) -> libc_int;
+    }
+    unsafe { isatty(1) == 1 }
+}
+
+#[cfg(windows)]
+pub fn stdout_is_terminal() -> bool {
+    extern "system" {
+        fn GetStdHandle(which: u32) -> *mut std::ffi::c_void;
+        fn GetConsoleMode(handle: *mut std::ffi::c_void, mode: *mut u32) -> i32;
+    }
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    let mut mode = 0u32;
+    unsafe { GetConsoleMode(GetStdHandle(STD_OUTPUT_HANDLE), &mut mode) != 0 }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn stdout_is_terminal() -> bool {
+    false
+}
+
+/// Create `dir` if needed and check that files can be created in it.
+pub fn check_writable(dir: &std::path::Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    let probe = dir.join(".drakkar_write_probe");
+    std::fs::write(&probe, b"")?;
+    std::fs::remove_file(&probe)
+}
+
+/// Bytes available to this user on the filesystem holding `path`, or
+/// None where that can't be asked.
+#[cfg(any(all(target_os = "linux", target_pointer_width = "64"), target_os = "macos"))]
+pub fn free_space(path: &std::path::Path) -> Option<u64> {
+    use std::os::unix::ffi::OsStrExt;
+
+    // Only the leading fields are read; the tail is padding for the rest
+    #[cfg(target_os = "linux")]
+    #[repr(C)]
+    struct StatVfs {
+        _f_bsize: u64,
+        f_frsize: u64,
+        _f_blocks: u64,
+        _f_bfree: u64,
+        f_bavail: u64,
+        _rest: [u64; 16],
+    }
+    #[cfg(target_os = "macos")]
+    #[repr(C)]
+    struct StatVfs {
+        _f_bsize: u64,
+        f_frsize: u64,
+        _f_blocks: u32,
+        _f_bfree: u32,
+        f_bavail: u32,
+        _rest: [u32; 16],
+    }
+    extern "C" {
+        fn statvfs(path: *const std::os::raw::c_char, buf: *mut StatVfs) -> libc_int;
+    }
+
+    let c_path = std::ffi::CString::new(path.as_os_str().as_bytes()).ok()?;
+    let mut buf: StatVfs = unsafe { std::mem::zeroed() };
+    if unsafe { statvfs(c_path.as_ptr(), &mut buf) } != 0 {
+        return None;
+    }
+    Some(buf.f_bavail as u64 * buf.f_frsize)
+}
+
+#[cfg(windows)]
+pub fn free_space(path: &std::path::Path) -> Option<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    extern "system" {
+        fn GetDiskFreeSpaceExW(dir: *const u16, avail: *mut u64, total: *mut u64, free: *mut u64) -> i32;
+    }
+    let wide: Vec<u16> = path.as_os_str().encode_wide().chain(Some(0)).collect();
+    let mut avail = 0u64;
+    let ok = unsafe {
+        GetDiskFreeSpaceExW(wide.as_ptr(), &mut avail, std::ptr::null_mut(), std::ptr::null_mut())
+    };
+    if ok != 0 {
+        Some(avail)
+    } else {
+        None
+    }
+}
+
+#[cfg(not(any(all(target_os = "linux", target_pointer_width = "64"), target_os = "macos", windows)))]
+pub fn free_space(_path: &std::path::Path) -> Option<u64> {
+    None
+}
+
+/// Whether two existing paths are on the same filesystem (same device).
+/// Assumed not where that can't be told.
+#[cfg(unix)]
+pub fn same_filesystem(a: &std::path::Path, b: &std::path::Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+pub fn same_filesystem(a: &std::path::Path, b: &std::path::Path) -> bool {
+    let root = |p: &std::path::Path| {
+        std::fs::canonicalize(p)
+            .ok()
+            .and_then(|p| p.components().next().map(|c| c.as_os_str().to_owned()))
+    };
+    root(a).is_some() && root(a) == root(b)
+}
+
+/// Per-user cache directory for drakkar: `$XDG_CACHE_HOME/drakkar`, else
+/// `~/.cache/drakkar`.
+pub fn user_cache_dir() -> Option<std::path::PathBuf> {
+    if let Some(xdg) = std::env::var_os("XDG_CACHE_HOME").filter(|v| !v.is_empty()) {
+        return Some(std::path::PathBuf::from(xdg).join("drakkar"));
+    }
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"))?;
+    Some(std::path::PathBuf::from(home).join(".cache").join("drakkar"))
+}
+
+/// Resolve `program` the way `Command::new` would: a path containing a
+/// separator is used as-is, anything else is searched for on `PATH`.
+/// Returns None unless an executable file is found.
+pub fn find_executable(program: &str) -> Option<std::path::PathBuf> {
+    let path = std::path::Path::new(program);
+    if path.components().count() > 1 {
+        return if is_executable(path) { Some(path.to_path_buf()) } else { None };
+    }
+
+    let dirs = std::env::var_os("PATH")?;
+    for dir in std::env::split_paths(&dirs) {
+        let candidate = dir.join(program);
+        if is_executable(&candidate) {
+            return Some(candidate);
+        }
+        #[cfg(windows)]
+        {
+            let exe = candidate.with_extension("exe");
+            if is_executable(&exe) {
+                return Some(exe);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(unix)]
+fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    match std::fs::metadata(path) {
+        Ok(meta) => meta.is_file() && meta.permissions().mode() & 0o111 != 0,
+        Err(_) => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file()
+}
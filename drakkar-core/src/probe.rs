@@ -0,0 +1,252 @@
+//! `requires_headers` and `requires_libs`: system headers and libraries the
+//! project needs, checked before anything compiles. Each header is
+//! compiled and each library linked into a one-line program with the
+//! project's own compile and link commands, so a missing `-lcurl` fails
+//! up front with the package to install rather than at the final link.
+//!
+//! Passing probes are stamped in temp_dir/.drakkar/probe, apart from the
+//! project's objects, and not run again until the requirements or the
+//! commands change.
+
+use std::path::{Path, PathBuf};
+
+use crate::build::{
+    build_compile_args, build_link_args, reserved_dir, Language, ObjectFile, SourceFile,
+};
+use crate::config::{BuildProfile, ProjectConfig};
+use crate::diagnostics::dev_package_suggestion;
+use crate::error::BuildError;
+use crate::hash::Sha256;
+use crate::shell;
+use crate::ui;
+
+/// One probe: what it checks and the command that checks it.
+struct Probe {
+    what: String,
+    program: String,
+    args: Vec<String>,
+    /// What to do when it fails.
+    hint: String,
+}
+
+fn probe_dir(config: &ProjectConfig) -> PathBuf {
+    reserved_dir(config).join("probe")
+}
+
+/// `<zlib.h>` is probed as C, anything else (`<vector>`, `<QtCore/QObject>`)
+/// as C++.
+fn header_language(header: &str) -> Language {
+    match Path::new(header).extension().and_then(|e| e.to_str()) {
+        Some("h") => Language::C,
+        _ => Language::Cpp,
+    }
+}
+
+fn object(dir: &Path, name: &str, language: Language) -> ObjectFile {
+    let ext = match language {
+        Language::C => "c",
+        Language::Cpp => "cpp",
+    };
+    let path = dir.join(format!("{}.{}", name, ext));
+    ObjectFile {
+        src: SourceFile { rel_path: PathBuf::from(path.file_name().unwrap()), path, language },
+        obj_path: dir.join(format!("{}.o", name)),
+        dep_path: dir.join(format!("{}.d", name)),
+    }
+}
+
+fn write(path: &Path, content: &str) -> Result<(), BuildError> {
+    std::fs::write(path, content)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", path, e)))
+}
+
+/// `-lz` → `z`, for the package hint.
+fn lib_name(flag: &str) -> &str {
+    flag.strip_prefix("-l").unwrap_or(flag)
+}
+
+/// The probes for `config`, writing their sources into `dir`.
+fn probes(
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+    dir: &Path,
+) -> Result<Vec<Probe>, BuildError> {
+    let mut probes = Vec::new();
+    for (i, header) in config.requires_headers.iter().enumerate() {
+        let obj = object(dir, &format!("header{}", i), header_language(header));
+        write(&obj.src.path, &format!("#include <{}>\nint main(void) {{ return 0; }}\n", header))?;
+        let (program, args) = build_compile_args(&obj, config, profile, extra_flags);
+        probes.push(Probe {
+            what: format!("header <{}>", header),
+            program,
+            args,
+            hint: format!(
+                "install its development package{}, or add the directory it is in to \
+                 include_dirs",
+                match dev_package_suggestion(header) {
+                    Some(package) => format!(" ({} on Debian/Ubuntu)", package),
+                    None => String::new(),
+                }
+            ),
+        });
+    }
+
+    // Linked alone, so a missing library is the only thing that can fail
+    let main = object(dir, "main", Language::C);
+    write(&main.src.path, "int main(void) { return 0; }\n")?;
+    let link_config = ProjectConfig {
+        link_libs: config.requires_libs.clone(),
+        exports: vec![],
+        link_map: false,
+        extra_objects: vec![],
+        ..config.clone()
+    };
+    let libs = link_config.resolved_link_libs();
+    for (i, lib) in libs.iter().enumerate() {
+        let single = ProjectConfig { link_libs: vec![lib.clone()], ..link_config.clone() };
+        let out = dir.join(format!("lib{}", i));
+        let objects = std::slice::from_ref(&main);
+        let (program, args) = build_link_args(objects, &out, &single, profile, extra_flags);
+        probes.push(Probe {
+            what: format!("library {}", lib),
+            program,
+            args,
+            hint: format!(
+                "install its development package (e.g. lib{0}-dev or lib{0}-devel), or add \
+                 its directory to ld_flags with -L",
+                lib_name(lib)
+            ),
+        });
+    }
+    if !libs.is_empty() {
+        let (program, args) = build_compile_args(&main, config, profile, extra_flags);
+        probes.insert(
+            0,
+            Probe {
+                what: "a program without libraries".to_string(),
+                program,
+                args,
+                hint: "check c_flags and the C compiler".to_string(),
+            },
+        );
+    }
+    Ok(probes)
+}
+
+/// The first line of the compiler's output that names an error.
+fn first_error(output: &std::process::Output) -> String {
+    let text = String::from_utf8_lossy(&output.stderr).into_owned()
+        + &String::from_utf8_lossy(&output.stdout);
+    text.lines()
+        .find(|l| l.contains("error") || l.contains("cannot find"))
+        .or_else(|| text.lines().next())
+        .unwrap_or("")
+        .trim()
+        .to_string()
+}
+
+/// Check `requires_headers` and `requires_libs`, failing with every missing
+/// one and what to do about it.
+pub fn check(
+    config: &ProjectConfig,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> Result<(), BuildError> {
+    if config.requires_headers.is_empty() && config.requires_libs.is_empty() {
+        return Ok(());
+    }
+    let dir = probe_dir(config);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+    let probes = probes(config, profile, extra_flags, &dir)?;
+
+    let mut hasher = Sha256::new();
+    for probe in &probes {
+        hasher.update_field(&shell::command_line(&probe.program, &probe.args));
+    }
+    let key = hasher.finish_hex();
+    let stamp = dir.join(match profile {
+        BuildProfile::Debug => "debug.stamp",
+        BuildProfile::Release => "release.stamp",
+    });
+    if std::fs::read_to_string(&stamp).ok().as_deref() == Some(key.as_str()) {
+        return Ok(());
+    }
+
+    let count = config.requires_headers.len() + config.requires_libs.len();
+    ui::line(&format!("  \x1b[36mProbing\x1b[0m {} system requirement(s)", count));
+    let mut missing = Vec::new();
+    for probe in &probes {
+        if config.verbose {
+            let command = shell::command_line(&probe.program, &probe.args);
+            ui::line(&format!("  \x1b[2m$ {}\x1b[0m", command));
+        }
+        let output = std::process::Command::new(&probe.program)
+            .args(&probe.args)
+            .output()
+            .map_err(|e| BuildError::IoError(format!("Failed to run {}: {}", probe.program, e)))?;
+        if !output.status.success() {
+            missing.push(format!(
+                "  {}: {}\n    {}",
+                probe.what,
+                probe.hint,
+                first_error(&output)
+            ));
+            // Without a program to link, every library would fail too
+            if probe.what.starts_with("a program") {
+                break;
+            }
+        }
+    }
+    if !missing.is_empty() {
+        let _ = std::fs::remove_file(&stamp);
+        return Err(BuildError::ConfigError(format!(
+            "missing system requirements:\n{}",
+            missing.join("\n")
+        )));
+    }
+    write(&stamp, &key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_language() {
+        assert_eq!(header_language("zlib.h"), Language::C);
+        assert_eq!(header_language("sys/socket.h"), Language::C);
+        assert_eq!(header_language("vector"), Language::Cpp);
+        assert_eq!(header_language("boost/asio.hpp"), Language::Cpp);
+    }
+
+    #[test]
+    fn test_probes() {
+        let dir = std::env::temp_dir().join("drakkar_test_probe_commands");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let config = ProjectConfig {
+            requires_headers: vec!["zlib.h".to_string()],
+            requires_libs: vec!["z".to_string(), "-lcurl".to_string()],
+            ld_flags: vec!["-L/opt/curl/lib".to_string()],
+            link_libs: vec!["unrelated".to_string()],
+            ..Default::default()
+        };
+        let probes = probes(&config, &BuildProfile::Debug, &[], &dir).unwrap();
+        let what: Vec<&str> = probes.iter().map(|p| p.what.as_str()).collect();
+        assert_eq!(
+            what,
+            ["a program without libraries", "header <zlib.h>", "library -lz", "library -lcurl"]
+        );
+        let header = std::fs::read_to_string(dir.join("header0.c")).unwrap();
+        assert!(header.starts_with("#include <zlib.h>\n"));
+        let link = &probes[3].args;
+        assert!(link.contains(&"-lcurl".to_string()));
+        assert!(link.contains(&"-L/opt/curl/lib".to_string()));
+        assert!(!link.contains(&"-lz".to_string()) && !link.contains(&"-lunrelated".to_string()));
+        assert!(probes[3].hint.contains("libcurl-dev"));
+        assert!(probes[1].hint.contains("(zlib1g-dev on Debian/Ubuntu)"), "{}", probes[1].hint);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
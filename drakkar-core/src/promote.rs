@@ -0,0 +1,300 @@
+//! `drakkar promote --from <channel> --to <channel>`: copy a build's
+//! artifacts into a release channel, a directory under `channels_dir`.
+//!
+//! Every channel holds its artifacts next to a `SHA256SUMS` file (the
+//! `sha256sum -c` format) and a `manifest.json` saying where they came
+//! from. Artifacts are checked against their checksums before they are
+//! copied and again once copied, so a channel never holds a file that
+//! doesn't match its manifest. A lock file in the target channel keeps two
+//! promotions, from this machine or another sharing the directory, from
+//! interleaving.
+
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::hash::Sha256;
+use crate::json::Json;
+
+/// The checksums of a channel's artifacts, in the `sha256sum -c` format.
+pub const CHECKSUMS: &str = "SHA256SUMS";
+/// What was promoted into a channel, when and from which commit.
+pub const MANIFEST: &str = "manifest.json";
+const LOCK: &str = ".promote.lock";
+
+/// A file to promote, with the checksum it must have.
+#[derive(Debug, Clone)]
+pub struct Artifact {
+    /// File name in the channel.
+    pub name: String,
+    /// Where it is now.
+    pub path: PathBuf,
+    /// Its SHA-256, in hex.
+    pub sha256: String,
+    /// Its size in bytes.
+    pub size: u64,
+}
+
+/// The directory of channel `name`.
+pub fn channel_dir(config: &ProjectConfig, name: &str) -> Result<PathBuf, BuildError> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\']) {
+        return Err(BuildError::ConfigError(format!(
+            "'{}' is not a channel name; use a plain name such as release-candidate",
+            name
+        )));
+    }
+    Ok(config.channels_dir.join(name))
+}
+
+/// Freshly built files, checksummed as they are now.
+pub fn built_artifacts(paths: &[PathBuf]) -> Result<Vec<Artifact>, BuildError> {
+    let mut artifacts = Vec::with_capacity(paths.len());
+    for path in paths {
+        let (sha256, size) = file_sha256(path)?;
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        if let Some(other) = artifacts.iter().find(|a: &&Artifact| a.name == name) {
+            return Err(BuildError::ConfigError(format!(
+                "{:?} and {:?} would both be '{}' in the channel",
+                other.path, path, name
+            )));
+        }
+        artifacts.push(Artifact { name, path: path.clone(), sha256, size });
+    }
+    Ok(artifacts)
+}
+
+/// The artifacts of an existing channel, each checked against its
+/// `SHA256SUMS` entry.
+pub fn channel_artifacts(dir: &Path) -> Result<Vec<Artifact>, BuildError> {
+    let sums_path = dir.join(CHECKSUMS);
+    if !sums_path.is_file() {
+        return Err(BuildError::ConfigError(format!(
+            "channel {:?} has no {}; promote a build into it first",
+            dir, CHECKSUMS
+        )));
+    }
+    let mut artifacts = Vec::new();
+    for (sha256, name) in read_checksums(&sums_path)? {
+        let path = dir.join(&name);
+        let (actual, size) = file_sha256(&path)?;
+        if actual != sha256 {
+            return Err(BuildError::IoError(format!(
+                "{:?} does not match {}: expected {}, found {}",
+                path, CHECKSUMS, sha256, actual
+            )));
+        }
+        artifacts.push(Artifact { name, path, sha256, size });
+    }
+    Ok(artifacts)
+}
+
+/// Copy `artifacts` into the channel `to` at `dir`, replacing what it
+/// held, and write its checksums and manifest. `from` is recorded as the
+/// source.
+pub fn promote(artifacts: &[Artifact], from: &str, to: &str, dir: &Path) -> Result<(), BuildError> {
+    let _lock = ChannelLock::acquire(dir)?;
+    let previous = read_checksums(&dir.join(CHECKSUMS)).unwrap_or_default();
+
+    for artifact in artifacts {
+        let partial = dir.join(format!(".{}.partial", artifact.name));
+        std::fs::copy(&artifact.path, &partial).map_err(|e| {
+            BuildError::IoError(format!("Cannot copy {:?} to {:?}: {}", artifact.path, partial, e))
+        })?;
+        // Check the copy, not just the source: the file may have changed
+        // since it was checksummed
+        let (copied, _) = file_sha256(&partial)?;
+        if copied != artifact.sha256 {
+            let _ = std::fs::remove_file(&partial);
+            return Err(BuildError::IoError(format!(
+                "{:?} changed while it was promoted: expected {}, copied {}",
+                artifact.path, artifact.sha256, copied
+            )));
+        }
+        rename(&partial, &dir.join(&artifact.name))?;
+    }
+
+    let names: HashSet<&str> = artifacts.iter().map(|a| a.name.as_str()).collect();
+    for (_, name) in &previous {
+        if !names.contains(name.as_str()) {
+            let _ = std::fs::remove_file(dir.join(name));
+        }
+    }
+
+    let sums: String = artifacts.iter().map(|a| format!("{}  {}\n", a.sha256, a.name)).collect();
+    write_atomic(&dir.join(CHECKSUMS), sums.as_bytes())?;
+    let manifest = manifest_json(artifacts, from, to, unix_now());
+    write_atomic(&dir.join(MANIFEST), format!("{}\n", manifest).as_bytes())
+}
+
+fn manifest_json(artifacts: &[Artifact], from: &str, to: &str, now: u64) -> Json {
+    Json::object()
+        .field("channel", to)
+        .field("from", from)
+        .field("promoted_at", now as i64)
+        .field(
+            "artifacts",
+            Json::Array(
+                artifacts
+                    .iter()
+                    .map(|a| {
+                        Json::object()
+                            .field("path", a.name.as_str())
+                            .field("sha256", a.sha256.as_str())
+                            .field("size", a.size as usize)
+                    })
+                    .collect(),
+            ),
+        )
+}
+
+/// `SHA256SUMS` as (checksum, file name) pairs; a `*` before the name
+/// (binary mode) is accepted.
+fn read_checksums(path: &Path) -> Result<Vec<(String, String)>, BuildError> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e)))?;
+    parse_checksums(&content).map_err(|line| {
+        BuildError::ParseError(format!("{:?}: malformed line '{}'", path, line))
+    })
+}
+
+/// `SHA256SUMS` text as (checksum, file name) pairs; Err holds the first
+/// malformed line.
+pub fn parse_checksums(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut entries = Vec::new();
+    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+        let (sum, name) = line.split_once(' ').ok_or_else(|| line.to_string())?;
+        let name = name.trim_start_matches(' ').trim_start_matches('*');
+        let valid_name = !name.is_empty() && !name.starts_with('.') && !name.contains(['/', '\\']);
+        if sum.len() != 64 || !sum.bytes().all(|b| b.is_ascii_hexdigit()) || !valid_name {
+            return Err(line.to_string());
+        }
+        entries.push((sum.to_ascii_lowercase(), name.to_string()));
+    }
+    Ok(entries)
+}
+
+fn file_sha256(path: &Path) -> Result<(String, u64), BuildError> {
+    let content = std::fs::read(path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read artifact {:?}: {}", path, e)))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+    Ok((hasher.finish_hex(), content.len() as u64))
+}
+
+fn write_atomic(path: &Path, content: &[u8]) -> Result<(), BuildError> {
+    let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.partial", name));
+    std::fs::write(&tmp, content)
+        .map_err(|e| BuildError::IoError(format!("Cannot write {:?}: {}", tmp, e)))?;
+    rename(&tmp, path)
+}
+
+fn rename(from: &Path, to: &Path) -> Result<(), BuildError> {
+    std::fs::rename(from, to)
+        .map_err(|e| BuildError::IoError(format!("Cannot move {:?} to {:?}: {}", from, to, e)))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Held while a channel is written; the file names its holder.
+struct ChannelLock(PathBuf);
+
+impl ChannelLock {
+    fn acquire(dir: &Path) -> Result<Self, BuildError> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+        let path = dir.join(LOCK);
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                let holder = std::fs::read_to_string(&path).unwrap_or_default();
+                return Err(BuildError::IoError(format!(
+                    "another promotion into {:?} is in progress ({}); delete {:?} if it was \
+                     interrupted",
+                    dir,
+                    holder.trim(),
+                    path
+                )));
+            }
+            Err(e) => {
+                return Err(BuildError::IoError(format!("Cannot create {:?}: {}", path, e)))
+            }
+        };
+        let host = std::env::var("HOSTNAME")
+            .or_else(|_| std::env::var("COMPUTERNAME"))
+            .unwrap_or_else(|_| "unknown host".to_string());
+        let _ = writeln!(file, "pid {} on {}, since {}", std::process::id(), host, unix_now());
+        Ok(ChannelLock(path))
+    }
+}
+
+impl Drop for ChannelLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_checksums() {
+        let sum = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let content = format!("{}  app\n{} *libmath.a\n\n", sum, sum.to_uppercase());
+        let entries = parse_checksums(&content).unwrap();
+        assert_eq!(entries[0], (sum.to_string(), "app".to_string()));
+        assert_eq!(entries[1], (sum.to_string(), "libmath.a".to_string()));
+        assert!(parse_checksums("abc  app\n").is_err());
+        assert!(parse_checksums(&format!("{}  ../etc/passwd\n", sum)).is_err());
+    }
+
+    #[test]
+    fn test_promote_between_channels() {
+        let dir = std::env::temp_dir().join("drakkar_test_promote");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("out")).unwrap();
+        let exe = dir.join("out/app");
+        std::fs::write(&exe, b"abc").unwrap();
+
+        let rc = dir.join("channels/rc");
+        std::fs::create_dir_all(&rc).unwrap();
+        std::fs::write(rc.join("stale"), b"x").unwrap();
+        let stale_sum = "2d711642b726b04401627ca9fbac32f5c8530fb1903cc4db02258717921a4881";
+        std::fs::write(rc.join(CHECKSUMS), format!("{}  stale\n", stale_sum)).unwrap();
+
+        let artifacts = built_artifacts(&[exe]).unwrap();
+        promote(&artifacts, "debug", "rc", &rc).unwrap();
+        assert_eq!(std::fs::read(rc.join("app")).unwrap(), b"abc");
+        assert!(!rc.join("stale").exists(), "files of the previous promotion are replaced");
+        assert!(!rc.join(LOCK).exists());
+        let manifest = std::fs::read_to_string(rc.join(MANIFEST)).unwrap();
+        assert!(manifest.contains(r#""from":"debug""#), "{}", manifest);
+        assert!(manifest.contains(
+            r#""sha256":"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad""#
+        ));
+
+        let verified = channel_artifacts(&rc).unwrap();
+        assert_eq!(verified.len(), 1);
+        std::fs::write(rc.join("app"), b"tampered").unwrap();
+        let err = channel_artifacts(&rc).unwrap_err().to_string();
+        assert!(err.contains("does not match SHA256SUMS"), "{}", err);
+
+        let held = ChannelLock::acquire(&rc).unwrap();
+        let err = promote(&artifacts, "debug", "rc", &rc).unwrap_err().to_string();
+        assert!(err.contains("another promotion"), "{}", err);
+        drop(held);
+
+        let config = ProjectConfig::default();
+        assert!(channel_dir(&config, "../out").is_err());
+        assert_eq!(channel_dir(&config, "rc").unwrap(), Path::new("channels/rc"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
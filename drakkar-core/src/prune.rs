@@ -0,0 +1,206 @@
+//! Keeping temp_dir and the object cache from growing without bound.
+//!
+//! With `temp_max_size_mb` set, a successful build evicts the least recently
+//! used objects of temp_dir, with their depfiles, until it fits; objects the
+//! build itself used are never evicted. An evicted object is simply
+//! compiled again when something needs it. `drakkar cache prune
+//! --older-than 30d` removes objects unused for that long from temp_dir, and
+//! entries that old from the object cache.
+//!
+//! "Used" is the later of a file's access and modification times, so with
+//! `relatime` mounts it is accurate to about a day.
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::error::BuildError;
+
+/// Files removed and the bytes they took.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Pruned {
+    /// Files removed.
+    pub files: usize,
+    /// Their total size.
+    pub bytes: u64,
+}
+
+/// A file, or an object with its depfile, that can go as one.
+struct Entry {
+    paths: Vec<PathBuf>,
+    bytes: u64,
+    used: SystemTime,
+}
+
+fn last_used(meta: &std::fs::Metadata) -> SystemTime {
+    let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+    meta.accessed().map(|a| a.max(modified)).unwrap_or(modified)
+}
+
+fn walk(root: &Path, f: &mut dyn FnMut(PathBuf, std::fs::Metadata)) {
+    let mut dirs = vec![root.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            match entry.metadata() {
+                Ok(meta) if meta.is_dir() => dirs.push(entry.path()),
+                Ok(meta) => f(entry.path(), meta),
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+fn is_object(path: &Path) -> bool {
+    matches!(path.extension().and_then(|e| e.to_str()), Some("o" | "obj"))
+}
+
+/// The objects under `root`, each with its depfile, and the bytes every
+/// file under `root` takes.
+fn objects(root: &Path) -> (Vec<Entry>, u64) {
+    let mut entries = Vec::new();
+    let mut total = 0;
+    walk(root, &mut |path, meta| {
+        total += meta.len();
+        if !is_object(&path) {
+            return;
+        }
+        let mut entry = Entry { paths: vec![], bytes: meta.len(), used: last_used(&meta) };
+        let dep = path.with_extension("d");
+        if let Ok(dep_meta) = std::fs::metadata(&dep) {
+            entry.bytes += dep_meta.len();
+            entry.used = entry.used.max(last_used(&dep_meta));
+            entry.paths.push(dep);
+        }
+        entry.paths.insert(0, path);
+        entries.push(entry);
+    });
+    (entries, total)
+}
+
+fn remove(entry: &Entry, pruned: &mut Pruned) {
+    // The object goes first: without it, a leftover depfile is harmless
+    if entry.paths.iter().all(|p| std::fs::remove_file(p).is_ok()) {
+        pruned.files += 1;
+        pruned.bytes += entry.bytes;
+    }
+}
+
+/// Evict the least recently used objects under `temp_dir` until it takes
+/// at most `max_bytes`, never one in `keep`.
+pub fn enforce_limit(temp_dir: &Path, max_bytes: u64, keep: &HashSet<PathBuf>) -> Pruned {
+    let (mut entries, mut total) = objects(temp_dir);
+    let mut pruned = Pruned::default();
+    if total <= max_bytes {
+        return pruned;
+    }
+    entries.retain(|e| !keep.contains(&e.paths[0]));
+    entries.sort_by_key(|e| e.used);
+    for entry in &entries {
+        if total <= max_bytes {
+            break;
+        }
+        remove(entry, &mut pruned);
+        total = total.saturating_sub(entry.bytes);
+    }
+    pruned
+}
+
+/// Remove the objects under `temp_dir` last used before `cutoff`.
+pub fn prune_objects(temp_dir: &Path, cutoff: SystemTime) -> Pruned {
+    let mut pruned = Pruned::default();
+    for entry in objects(temp_dir).0.iter().filter(|e| e.used < cutoff) {
+        remove(entry, &mut pruned);
+    }
+    pruned
+}
+
+/// Remove every object cache file last used before `cutoff`, except the
+/// hit and miss totals.
+pub fn prune_cache(root: &Path, cutoff: SystemTime) -> Pruned {
+    let mut pruned = Pruned::default();
+    walk(root, &mut |path, meta| {
+        let stats = path.parent() == Some(root) && path.ends_with(crate::cache::STATS_FILE);
+        if !stats && last_used(&meta) < cutoff {
+            let entry = Entry { bytes: meta.len(), used: last_used(&meta), paths: vec![path] };
+            remove(&entry, &mut pruned);
+        }
+    });
+    pruned
+}
+
+/// Parse an age such as `30d`, `12h`, `2w` or `90m`.
+pub fn parse_age(s: &str) -> Result<Duration, BuildError> {
+    let invalid = || {
+        BuildError::ParseError(format!(
+            "invalid age {:?}: expected a number and a unit (m, h, d or w), e.g. 30d",
+            s
+        ))
+    };
+    let split = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let n: u64 = s[..split].parse().map_err(|_| invalid())?;
+    let unit = match &s[split..] {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(n.checked_mul(unit).ok_or_else(invalid)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_age() {
+        assert_eq!(parse_age("30d").unwrap(), Duration::from_secs(30 * 86400));
+        assert_eq!(parse_age("2w").unwrap(), Duration::from_secs(14 * 86400));
+        assert_eq!(parse_age("90m").unwrap(), Duration::from_secs(5400));
+        for bad in ["30", "d", "30 days", "-1d", "", "999999999999999999d"] {
+            assert!(parse_age(bad).is_err(), "{:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_enforce_limit_evicts_oldest() {
+        let dir = std::env::temp_dir().join("drakkar_test_prune_limit");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("tool")).unwrap();
+        let now = SystemTime::now();
+        for (name, age_days) in [("old.o", 9), ("tool/mid.o", 5), ("new.o", 1), ("kept.o", 30)] {
+            let path = dir.join(name);
+            std::fs::write(&path, [0u8; 1000]).unwrap();
+            std::fs::write(path.with_extension("d"), [0u8; 100]).unwrap();
+            let used = now - Duration::from_secs(age_days * 86400);
+            let stamp = used.duration_since(SystemTime::UNIX_EPOCH).unwrap().as_secs();
+            let touched = std::process::Command::new("touch")
+                .arg("-d")
+                .arg(format!("@{}", stamp))
+                .arg(&path)
+                .arg(path.with_extension("d"))
+                .status()
+                .unwrap();
+            assert!(touched.success());
+        }
+        std::fs::write(dir.join(".drakkar_state"), [0u8; 200]).unwrap();
+
+        // 4400 bytes of objects and depfiles plus the state file
+        let keep: HashSet<PathBuf> = [dir.join("kept.o")].into_iter().collect();
+        let pruned = enforce_limit(&dir, 2500, &keep);
+        assert_eq!(pruned, Pruned { files: 2, bytes: 2200 });
+        assert!(!dir.join("old.o").exists() && !dir.join("old.d").exists());
+        assert!(!dir.join("tool/mid.o").exists());
+        assert!(dir.join("new.o").exists() && dir.join("kept.o").exists());
+        assert_eq!(enforce_limit(&dir, 2500, &keep), Pruned::default());
+
+        let pruned = prune_objects(&dir, now - Duration::from_secs(7 * 86400));
+        assert_eq!(pruned.files, 1);
+        assert!(!dir.join("kept.o").exists() && dir.join("new.o").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,462 @@
+//! `remote_cache`: an object cache shared by a team and its CI.
+//!
+//! Entries use the `shared_temp_dir` scheme — a manifest per compile
+//! command and source, and the object per header contents — stored under
+//! the same names (see `shared::entry_name`) through a [`Backend`]: a
+//! directory, typically a network mount, or an HTTP server that answers
+//! GET, HEAD and PUT on `<url>/<name>` (nginx with WebDAV, bazel-remote,
+//! a bucket behind a proxy). Keys don't include the project directory, so
+//! checkouts at different paths share entries.
+//!
+//! Only plain `http://` is spoken; `DRAKKAR_CACHE_TOKEN`, when set, is
+//! sent as a bearer token.
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::build::{headers_content_hash, ObjectFile};
+use crate::depfile::{parse_depfile, parse_depfile_text};
+use crate::error::BuildError;
+use crate::shared::{entry_key, entry_name};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const IO_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Where `remote_cache` points.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CacheLocation {
+    /// A directory, typically a network mount.
+    Dir(PathBuf),
+    /// An HTTP server taking GET, HEAD and PUT below `path`.
+    Http {
+        /// Host name or address.
+        host: String,
+        /// TCP port, 80 unless the URL gives one.
+        port: u16,
+        /// URL path of the cache root, without a trailing slash.
+        path: String,
+    },
+}
+
+impl CacheLocation {
+    /// An `http://host[:port]/path` URL, or else a directory.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s.starts_with("https://") {
+            return Err("https is not supported; use http:// (e.g. through a local TLS proxy) or a directory".to_string());
+        }
+        let rest = match s.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => return Ok(CacheLocation::Dir(PathBuf::from(s))),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|_| format!("invalid port in '{}'", s))?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in '{}'", s));
+        }
+        Ok(CacheLocation::Http {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn backend(&self) -> Box<dyn Backend> {
+        match self {
+            CacheLocation::Dir(root) => Box::new(DirBackend { root: root.clone() }),
+            CacheLocation::Http { host, port, path } => Box::new(HttpBackend {
+                host: host.clone(),
+                port: *port,
+                path: path.clone(),
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for CacheLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CacheLocation::Dir(root) => write!(f, "{}", root.display()),
+            CacheLocation::Http { host, port: 80, path } => write!(f, "http://{}{}", host, path),
+            CacheLocation::Http { host, port, path } => write!(f, "http://{}:{}{}", host, port, path),
+        }
+    }
+}
+
+/// Storage for cache entries, addressed by relative names such as
+/// `ab/ab12….o`.
+pub trait Backend: Send + Sync {
+    /// The entry's content, or None when the cache doesn't have it.
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, BuildError>;
+
+    /// Whether the cache has the entry, without fetching it.
+    fn has(&self, name: &str) -> Result<bool, BuildError>;
+
+    /// Store the entry, replacing any older one.
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), BuildError>;
+}
+
+/// A cache in a directory.
+pub struct DirBackend {
+    root: PathBuf,
+}
+
+impl Backend for DirBackend {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, BuildError> {
+        let path = self.root.join(name);
+        match std::fs::read(&path) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(BuildError::IoError(format!("Cannot read {:?}: {}", path, e))),
+        }
+    }
+
+    fn has(&self, name: &str) -> Result<bool, BuildError> {
+        Ok(self.root.join(name).is_file())
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), BuildError> {
+        let path = self.root.join(name);
+        let io_err = |e: std::io::Error| BuildError::IoError(format!("Cannot write {:?}: {}", path, e));
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(io_err)?;
+        }
+        // Renamed into place so readers on other machines never see half a file
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(format!(".{}.tmp", std::process::id()));
+        std::fs::write(&tmp, data).map_err(io_err)?;
+        std::fs::rename(&tmp, &path).map_err(io_err)
+    }
+}
+
+/// A cache behind a plain HTTP server.
+pub struct HttpBackend {
+    host: String,
+    port: u16,
+    /// URL path of the cache root, without a trailing slash.
+    path: String,
+}
+
+impl HttpBackend {
+    fn request(&self, method: &str, name: &str, body: &[u8]) -> Result<(u16, Vec<u8>), BuildError> {
+        let url = format!("http://{}:{}{}/{}", self.host, self.port, self.path, name);
+        let io_err = |e: std::io::Error| BuildError::IoError(format!("remote cache {} {}: {}", method, url, e));
+
+        let addr = (self.host.as_str(), self.port)
+            .to_socket_addrs()
+            .map_err(io_err)?
+            .next()
+            .ok_or_else(|| BuildError::IoError(format!("remote cache: cannot resolve {}", self.host)))?;
+        let mut stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).map_err(io_err)?;
+        stream.set_read_timeout(Some(IO_TIMEOUT)).map_err(io_err)?;
+        stream.set_write_timeout(Some(IO_TIMEOUT)).map_err(io_err)?;
+
+        let host = match self.port {
+            80 => self.host.clone(),
+            port => format!("{}:{}", self.host, port),
+        };
+        let mut head = format!(
+            "{} {}/{} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            method,
+            self.path,
+            name,
+            host,
+            body.len()
+        );
+        if let Some(token) = std::env::var_os("DRAKKAR_CACHE_TOKEN") {
+            head.push_str(&format!("Authorization: Bearer {}\r\n", token.to_string_lossy()));
+        }
+        head.push_str("\r\n");
+        stream.write_all(head.as_bytes()).map_err(io_err)?;
+        stream.write_all(body).map_err(io_err)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).map_err(io_err)?;
+        parse_response(&response)
+            .ok_or_else(|| BuildError::IoError(format!("remote cache {} {}: malformed HTTP response", method, url)))
+    }
+
+    fn status_error(&self, method: &str, name: &str, status: u16) -> BuildError {
+        BuildError::IoError(format!(
+            "remote cache {} http://{}:{}{}/{}: HTTP {}",
+            method, self.host, self.port, self.path, name, status
+        ))
+    }
+}
+
+impl Backend for HttpBackend {
+    fn get(&self, name: &str) -> Result<Option<Vec<u8>>, BuildError> {
+        match self.request("GET", name, &[])? {
+            (200, body) => Ok(Some(body)),
+            (404, _) => Ok(None),
+            (status, _) => Err(self.status_error("GET", name, status)),
+        }
+    }
+
+    fn has(&self, name: &str) -> Result<bool, BuildError> {
+        match self.request("HEAD", name, &[])?.0 {
+            200 => Ok(true),
+            404 => Ok(false),
+            status => Err(self.status_error("HEAD", name, status)),
+        }
+    }
+
+    fn put(&self, name: &str, data: &[u8]) -> Result<(), BuildError> {
+        match self.request("PUT", name, data)?.0 {
+            200 | 201 | 204 => Ok(()),
+            status => Err(self.status_error("PUT", name, status)),
+        }
+    }
+}
+
+/// Status and body of an HTTP/1.1 response read to the end of the
+/// connection. Handles `Content-Length` and chunked bodies.
+fn parse_response(response: &[u8]) -> Option<(u16, Vec<u8>)> {
+    let split = response.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = std::str::from_utf8(&response[..split]).ok()?;
+    let mut body = response[split + 4..].to_vec();
+
+    let mut lines = head.split("\r\n");
+    let status = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+    for line in lines {
+        let (name, value) = line.split_once(':')?;
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("content-length") {
+            body.truncate(value.parse().ok()?);
+        } else if name.eq_ignore_ascii_case("transfer-encoding") && value.eq_ignore_ascii_case("chunked") {
+            body = dechunk(&body)?;
+        }
+    }
+    Some((status, body))
+}
+
+fn dechunk(mut data: &[u8]) -> Option<Vec<u8>> {
+    let mut body = Vec::new();
+    loop {
+        let line_end = data.windows(2).position(|w| w == b"\r\n")?;
+        let size_field = std::str::from_utf8(&data[..line_end]).ok()?;
+        let size = usize::from_str_radix(size_field.split(';').next()?.trim(), 16).ok()?;
+        if size == 0 {
+            return Some(body);
+        }
+        let start = line_end + 2;
+        body.extend_from_slice(data.get(start..start + size)?);
+        data = data.get(start + size + 2..)?;
+    }
+}
+
+/// The remote tier of the object cache.
+pub struct RemoteCache {
+    backend: Box<dyn Backend>,
+}
+
+impl RemoteCache {
+    /// The remote cache at `location`.
+    pub fn new(location: &CacheLocation) -> Self {
+        RemoteCache {
+            backend: location.backend(),
+        }
+    }
+
+    /// Download the entry built from the same command, source and headers
+    /// into `obj`'s paths. Ok(false) on a miss.
+    pub fn fetch(&self, obj: &ObjectFile, flags_hash: &str) -> Result<bool, BuildError> {
+        let key = match entry_key("", obj, flags_hash) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let manifest = match self.backend.get(&entry_name(&key, "d"))? {
+            Some(data) => String::from_utf8_lossy(&data).into_owned(),
+            None => return Ok(false),
+        };
+        let headers = match parse_depfile_text(&manifest, &obj.dep_path)
+            .ok()
+            .and_then(|deps| headers_content_hash(&obj.src.path, &deps.prerequisites))
+        {
+            Some(h) => h,
+            None => return Ok(false),
+        };
+        let entry = format!("{}-{}", key, headers);
+        let (object, depfile) = match (
+            self.backend.get(&entry_name(&entry, "o"))?,
+            self.backend.get(&entry_name(&entry, "d"))?,
+        ) {
+            (Some(object), Some(depfile)) => (object, depfile),
+            _ => return Ok(false),
+        };
+        let io_err = |path: &Path, e: std::io::Error| {
+            BuildError::IoError(format!("Cannot write {:?}: {}", path, e))
+        };
+        std::fs::write(&obj.dep_path, depfile).map_err(|e| io_err(&obj.dep_path, e))?;
+        std::fs::write(&obj.obj_path, object).map_err(|e| io_err(&obj.obj_path, e))?;
+        Ok(true)
+    }
+
+    /// Upload a freshly compiled `obj`, unless the remote already has it.
+    /// Returns whether anything was uploaded.
+    pub fn store(&self, obj: &ObjectFile, flags_hash: &str) -> Result<bool, BuildError> {
+        let key = match entry_key("", obj, flags_hash) {
+            Some(key) => key,
+            None => return Ok(false),
+        };
+        let headers = match parse_depfile(&obj.dep_path)
+            .ok()
+            .and_then(|deps| headers_content_hash(&obj.src.path, &deps.prerequisites))
+        {
+            Some(h) => h,
+            None => return Ok(false),
+        };
+        let entry = format!("{}-{}", key, headers);
+        if self.backend.has(&entry_name(&entry, "o"))? {
+            return Ok(false);
+        }
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e)))
+        };
+        let depfile = read(&obj.dep_path)?;
+        self.backend.put(&entry_name(&entry, "d"), &depfile)?;
+        self.backend.put(&entry_name(&entry, "o"), &read(&obj.obj_path)?)?;
+        // Last, so a manifest always points at a complete entry
+        self.backend.put(&entry_name(&key, "d"), &depfile)?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use std::collections::HashMap;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_parse_location_and_response() {
+        assert_eq!(
+            CacheLocation::parse("http://cache.lan:9090/drakkar/").unwrap(),
+            CacheLocation::Http {
+                host: "cache.lan".to_string(),
+                port: 9090,
+                path: "/drakkar".to_string()
+            }
+        );
+        assert_eq!(
+            CacheLocation::parse("/mnt/cache").unwrap(),
+            CacheLocation::Dir(PathBuf::from("/mnt/cache"))
+        );
+        assert!(CacheLocation::parse("https://cache.lan").is_err());
+        assert!(CacheLocation::parse("http://cache.lan:x/").is_err());
+        assert_eq!(CacheLocation::parse("http://cache.lan").unwrap().to_string(), "http://cache.lan");
+
+        let plain = b"HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nOBJ";
+        assert_eq!(parse_response(plain), Some((200, b"OBJ".to_vec())));
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nOB\r\n1\r\nJ\r\n0\r\n\r\n";
+        assert_eq!(parse_response(chunked), Some((200, b"OBJ".to_vec())));
+        assert_eq!(parse_response(b"HTTP/1.1 404 Not Found\r\n\r\n"), Some((404, vec![])));
+        assert_eq!(parse_response(b"garbage"), None);
+    }
+
+    /// A cache server keeping entries in memory, for one connection each.
+    fn serve(listener: TcpListener, entries: Arc<Mutex<HashMap<String, Vec<u8>>>>) {
+        for stream in listener.incoming() {
+            let mut stream = match stream {
+                Ok(s) => s,
+                Err(_) => return,
+            };
+            let mut reader = std::io::BufReader::new(stream.try_clone().unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            let mut length = 0;
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                if let Some(value) = line.strip_prefix("Content-Length: ") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let mut parts = request_line.split_whitespace();
+            let (method, path) = (parts.next().unwrap(), parts.next().unwrap().to_string());
+            let mut entries = entries.lock().unwrap();
+            let response = match (method, entries.get(&path)) {
+                ("PUT", _) => {
+                    entries.insert(path, body);
+                    b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n".to_vec()
+                }
+                ("GET", Some(data)) | ("HEAD", Some(data)) => {
+                    let mut r = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n", data.len()).into_bytes();
+                    if method == "GET" {
+                        r.extend_from_slice(data);
+                    }
+                    r
+                }
+                _ => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_vec(),
+            };
+            stream.write_all(&response).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_store_then_fetch_over_http_and_dir() {
+        let dir = std::env::temp_dir().join("drakkar_test_remote");
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["src", "ci", "dev"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join("src/a.c"), "#include \"a.h\"\n").unwrap();
+        std::fs::write(dir.join("src/a.h"), "int a(void);\n").unwrap();
+        let object = |checkout: &str| ObjectFile {
+            src: SourceFile {
+                path: dir.join("src/a.c"),
+                rel_path: PathBuf::from("a.c"),
+                language: Language::C,
+            },
+            obj_path: dir.join(checkout).join("a.o"),
+            dep_path: dir.join(checkout).join("a.d"),
+        };
+        let (ci, dev) = (object("ci"), object("dev"));
+        std::fs::write(&ci.obj_path, "OBJ").unwrap();
+        std::fs::write(
+            &ci.dep_path,
+            format!("a.o: {} {}\n", ci.src.path.display(), dir.join("src/a.h").display()),
+        )
+        .unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let entries = Arc::new(Mutex::new(HashMap::new()));
+        let served = Arc::clone(&entries);
+        std::thread::spawn(move || serve(listener, served));
+
+        let http = CacheLocation::parse(&format!("http://127.0.0.1:{}/cache", port)).unwrap();
+        let local = CacheLocation::Dir(dir.join("remote"));
+        for location in [http, local] {
+            let remote = RemoteCache::new(&location);
+            let _ = std::fs::remove_file(&dev.obj_path);
+            assert!(!remote.fetch(&dev, "flags").unwrap());
+            assert!(remote.store(&ci, "flags").unwrap());
+            assert!(!remote.store(&ci, "flags").unwrap(), "already uploaded");
+            assert!(!remote.fetch(&dev, "other flags").unwrap());
+            assert!(remote.fetch(&dev, "flags").unwrap());
+            assert_eq!(std::fs::read_to_string(&dev.obj_path).unwrap(), "OBJ");
+        }
+        assert_eq!(entries.lock().unwrap().len(), 3);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,137 @@
+//! Sandboxed compiles (`sandbox = "bwrap"`).
+//!
+//! Each compile runs under bubblewrap in a filesystem that only has the
+//! system directories, the compiler's install prefix, `source_dir`,
+//! `include_dirs` and `sandbox_paths` (read-only) and `temp_dir`. A header
+//! reached through an undeclared path, which the depfile would record but a
+//! clean checkout elsewhere wouldn't have, then fails the compile with
+//! "No such file" instead of working by accident. Linking and archiving
+//! are not sandboxed.
+
+use std::path::{Component, Path, PathBuf};
+
+use crate::config::{ProjectConfig, Sandbox, ToolCommand};
+use crate::error::BuildError;
+use crate::platform::find_executable;
+
+/// Mounted read-only when present: where compilers, their libraries and
+/// the system headers live.
+const SYSTEM_DIRS: &[&str] =
+    &["/usr", "/bin", "/sbin", "/lib", "/lib32", "/lib64", "/libx32", "/etc"];
+
+/// Fail early when the configured sandbox can't be used here.
+pub fn check(config: &ProjectConfig) -> Result<(), BuildError> {
+    match config.sandbox {
+        Sandbox::Off => Ok(()),
+        Sandbox::Bwrap if !cfg!(target_os = "linux") => Err(BuildError::ConfigError(
+            "sandbox = \"bwrap\" is only available on Linux".to_string(),
+        )),
+        Sandbox::Bwrap => ToolCommand::new("bwrap").validate("sandbox"),
+    }
+}
+
+/// The command `compiler` runs under, None with the sandbox off.
+pub fn command(config: &ProjectConfig, compiler: &str) -> Option<ToolCommand> {
+    match config.sandbox {
+        Sandbox::Off => None,
+        Sandbox::Bwrap => {
+            let cwd = std::env::current_dir().unwrap_or_default();
+            let compiler = find_executable(compiler).map(|path| absolute(&path, &cwd));
+            Some(ToolCommand {
+                program: "bwrap".to_string(),
+                args: bwrap_args(config, compiler.as_deref(), &cwd),
+            })
+        }
+    }
+}
+
+fn bwrap_args(config: &ProjectConfig, compiler: Option<&Path>, cwd: &Path) -> Vec<String> {
+    let mut args: Vec<String> = ["--unshare-all", "--die-with-parent", "--dev", "/dev"]
+        .iter()
+        .chain(&["--proc", "/proc", "--tmpfs", "/tmp", "--setenv", "TMPDIR", "/tmp"])
+        .map(|a| a.to_string())
+        .collect();
+    let mut mount = |option: &str, path: &Path| {
+        let path = path.to_string_lossy().into_owned();
+        args.extend([option.to_string(), path.clone(), path]);
+    };
+    for dir in SYSTEM_DIRS {
+        mount("--ro-bind-try", Path::new(dir));
+    }
+    // A toolchain unpacked elsewhere: /opt/gcc-14/bin/g++ needs /opt/gcc-14
+    if let Some(compiler) = compiler {
+        let system = SYSTEM_DIRS.iter().any(|dir| compiler.starts_with(dir));
+        let prefix = compiler.parent().and_then(Path::parent);
+        if let (false, Some(prefix)) = (system, prefix) {
+            mount("--ro-bind-try", prefix);
+        }
+    }
+    for dir in config.input_dirs().chain(&config.sandbox_paths) {
+        mount("--ro-bind-try", &absolute(dir, cwd));
+    }
+    mount("--bind", &absolute(&config.temp_dir, cwd));
+    let cwd = cwd.to_string_lossy().into_owned();
+    args.extend(["--dir".to_string(), cwd.clone(), "--chdir".to_string(), cwd]);
+    args
+}
+
+/// `path` as an absolute path without `.` or `..`, resolved lexically so
+/// a symlinked source dir is mounted where the compiler will look for it.
+fn absolute(path: &Path, cwd: &Path) -> PathBuf {
+    let mut absolute = PathBuf::new();
+    for component in cwd.join(path).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                absolute.pop();
+            }
+            other => absolute.push(other),
+        }
+    }
+    absolute
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bwrap_args() {
+        let cfg = ProjectConfig {
+            source_dirs: vec![PathBuf::from("src/")],
+            include_dirs: vec![PathBuf::from("./include"), PathBuf::from("../shared/include")],
+            vendor_dirs: vec![PathBuf::from("imgui")],
+            system_include_dirs: vec![PathBuf::from("/home/u/.conan2/p/zlib/include")],
+            sandbox_paths: vec![PathBuf::from("/opt/sdk")],
+            temp_dir: PathBuf::from("target"),
+            sandbox: Sandbox::Bwrap,
+            ..ProjectConfig::default()
+        };
+        let args = bwrap_args(&cfg, Some(Path::new("/opt/gcc-14/bin/g++")), Path::new("/work/app"));
+        let line = args.join(" ");
+        let mounted = |dir: &str| line.contains(&format!("--ro-bind-try {} {} ", dir, dir));
+        assert!(line.starts_with("--unshare-all --die-with-parent --dev /dev"), "{}", line);
+        let dirs = [
+            "/usr",
+            "/opt/gcc-14",
+            "/work/app/src",
+            "/work/app/imgui",
+            "/work/app/include",
+            "/home/u/.conan2/p/zlib/include",
+            "/opt/sdk",
+        ];
+        for dir in dirs {
+            assert!(mounted(dir), "{} in {}", dir, line);
+        }
+        assert!(mounted("/work/shared/include"), "{}", line);
+        assert!(line.ends_with(
+            "--bind /work/app/target /work/app/target --dir /work/app --chdir /work/app"
+        ), "{}", line);
+
+        // Compilers in the system dirs add nothing
+        let system = bwrap_args(&cfg, Some(Path::new("/usr/bin/g++")), Path::new("/work/app"));
+        assert_eq!(system.len(), args.len() - 3);
+
+        assert!(command(&ProjectConfig::default(), "g++").is_none());
+    }
+}
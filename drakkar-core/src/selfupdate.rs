@@ -0,0 +1,225 @@
+//! `drakkar self update`: replace the running drakkar with the latest
+//! release for this platform.
+//!
+//! A release is a directory of assets named `drakkar-<arch>-<os>` next to
+//! a `SHA256SUMS` (the `sha256sum -c` format), such as a GitHub release's
+//! `releases/latest/download`. Its URL is `DRAKKAR_RELEASE_URL`, set when
+//! drakkar runs or when it is built (release builds bake it in).
+//!
+//! Downloads go through `curl`, since std speaks no TLS, into a new
+//! directory next to the executable that only its owner can read. The
+//! binary must match its checksum when it arrives and again as the copy
+//! about to be renamed into place, and with `DRAKKAR_RELEASE_PUBKEY` set,
+//! `SHA256SUMS` must also carry a valid minisign signature
+//! (`SHA256SUMS.minisig`).
+
+use std::path::{Path, PathBuf};
+
+use crate::error::BuildError;
+use crate::hash::Sha256;
+use crate::promote::{parse_checksums, CHECKSUMS};
+
+/// What [`update`] did.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// The running binary already is the release's; its checksum.
+    UpToDate(String),
+    /// Replaced.
+    Updated {
+        /// The checksum of the binary that was running.
+        from: String,
+        /// The checksum of the release it was replaced with.
+        to: String,
+    },
+}
+
+/// The release asset for this platform: `drakkar-x86_64-linux`,
+/// `drakkar-aarch64-macos`, `drakkar-x86_64-windows.exe`.
+pub fn asset_name() -> String {
+    format!(
+        "drakkar-{}-{}{}",
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        std::env::consts::EXE_SUFFIX
+    )
+}
+
+/// `DRAKKAR_RELEASE_URL` from the environment, else from the build.
+pub fn release_url() -> Result<String, BuildError> {
+    std::env::var("DRAKKAR_RELEASE_URL")
+        .ok()
+        .or_else(|| option_env!("DRAKKAR_RELEASE_URL").map(str::to_string))
+        .filter(|url| !url.is_empty())
+        .ok_or_else(|| {
+            BuildError::ConfigError(
+                "this drakkar was built without a release URL; set DRAKKAR_RELEASE_URL to \
+                 where the release assets are, e.g. \
+                 https://github.com/<owner>/<repo>/releases/latest/download"
+                    .to_string(),
+            )
+        })
+}
+
+fn download(url: &str, dest: &Path) -> Result<(), BuildError> {
+    let output = std::process::Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--location", "--output"])
+        .arg(dest)
+        .arg(url)
+        .output()
+        .map_err(|e| BuildError::IoError(format!("Failed to run curl: {}", e)))?;
+    if !output.status.success() {
+        return Err(BuildError::IoError(format!(
+            "Cannot download {}: {}",
+            url,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(())
+}
+
+fn sha256_of(path: &Path) -> Result<String, BuildError> {
+    let content = std::fs::read(path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e)))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+    Ok(hasher.finish_hex())
+}
+
+/// The checksum `sums` (a `SHA256SUMS` file) lists for `asset`.
+pub fn expected_sha256(sums: &str, asset: &str) -> Result<String, BuildError> {
+    let entries = parse_checksums(sums).map_err(|line| {
+        BuildError::ParseError(format!("{}: malformed line '{}'", CHECKSUMS, line))
+    })?;
+    entries.into_iter().find(|(_, name)| name == asset).map(|(sum, _)| sum).ok_or_else(|| {
+        BuildError::ConfigError(format!("the release has no {} for this platform", asset))
+    })
+}
+
+fn verify_signature(sums: &Path, signature: &Path, pubkey: &str) -> Result<(), BuildError> {
+    let output = std::process::Command::new("minisign")
+        .arg("-Vm")
+        .arg(sums)
+        .arg("-x")
+        .arg(signature)
+        .args(["-P", pubkey])
+        .output()
+        .map_err(|e| BuildError::IoError(format!("Failed to run minisign: {}", e)))?;
+    if !output.status.success() {
+        return Err(BuildError::IoError(format!(
+            "{} of the release is not signed by DRAKKAR_RELEASE_PUBKEY:\n{}",
+            CHECKSUMS,
+            String::from_utf8_lossy(&output.stderr).trim_end()
+        )));
+    }
+    Ok(())
+}
+
+/// Put `new` in place of `exe`. The copy goes next to it first, so the
+/// rename stays on one filesystem and `exe` is never half-written, and is
+/// checked against `expected` once it is written. A running Windows
+/// executable can't be replaced, only renamed away.
+fn replace(exe: &Path, new: &Path, expected: &str) -> Result<(), BuildError> {
+    let name = exe.file_name().unwrap_or_default().to_string_lossy().into_owned();
+    let partial = exe.with_file_name(format!(".{}.partial", name));
+    let io = |what: &str, e: std::io::Error| {
+        let _ = std::fs::remove_file(&partial);
+        BuildError::IoError(format!("Cannot {} {:?}: {}", what, exe, e))
+    };
+    std::fs::copy(new, &partial).map_err(|e| io("write next to", e))?;
+    let permissions = std::fs::metadata(exe).map_err(|e| io("read", e))?.permissions();
+    std::fs::set_permissions(&partial, permissions).map_err(|e| io("copy permissions of", e))?;
+    let copied = sha256_of(&partial)?;
+    if copied != expected {
+        let _ = std::fs::remove_file(&partial);
+        return Err(BuildError::IoError(format!(
+            "{:?} changed before it replaced {:?}: expected {}, found {}",
+            partial, exe, expected, copied
+        )));
+    }
+    if cfg!(windows) {
+        let old = exe.with_file_name(format!("{}.old", name));
+        let _ = std::fs::remove_file(&old);
+        std::fs::rename(exe, &old).map_err(|e| io("move away", e))?;
+    }
+    std::fs::rename(&partial, exe).map_err(|e| io("replace", e))
+}
+
+/// Update the executable at `exe` from the release at `url`.
+pub fn update(exe: &Path, url: &str, pubkey: Option<&str>) -> Result<Outcome, BuildError> {
+    let url = url.trim_end_matches('/');
+    let asset = asset_name();
+    // A new directory, so nothing already there can stand in for a download
+    let dir = exe.with_file_name(format!(".drakkar-update-{}", std::process::id()));
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder
+        .create(&dir)
+        .map_err(|e| BuildError::IoError(format!("Cannot create directory {:?}: {}", dir, e)))?;
+    let result = (|| {
+        let sums_path = dir.join(CHECKSUMS);
+        download(&format!("{}/{}", url, CHECKSUMS), &sums_path)?;
+        if let Some(pubkey) = pubkey {
+            let signature = dir.join(format!("{}.minisig", CHECKSUMS));
+            download(&format!("{}/{}.minisig", url, CHECKSUMS), &signature)?;
+            verify_signature(&sums_path, &signature, pubkey)?;
+        }
+        let sums = std::fs::read_to_string(&sums_path)
+            .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", sums_path, e)))?;
+        let expected = expected_sha256(&sums, &asset)?;
+        let current = sha256_of(exe)?;
+        if current == expected {
+            return Ok(Outcome::UpToDate(current));
+        }
+
+        let new: PathBuf = dir.join(&asset);
+        download(&format!("{}/{}", url, asset), &new)?;
+        let downloaded = sha256_of(&new)?;
+        if downloaded != expected {
+            return Err(BuildError::IoError(format!(
+                "{} does not match {}: expected {}, downloaded {}",
+                asset, CHECKSUMS, expected, downloaded
+            )));
+        }
+        replace(exe, &new, &expected)?;
+        Ok(Outcome::Updated { from: current, to: downloaded })
+    })();
+    let _ = std::fs::remove_dir_all(&dir);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_sha256() {
+        let sum = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let other = "2d711642b726b04401627ca9fbac32f5c8530fb1903cc4db02258717921a4881";
+        let sums = format!("{}  drakkar-x86_64-linux\n{}  drakkar-aarch64-macos\n", other, sum);
+        assert_eq!(expected_sha256(&sums, "drakkar-aarch64-macos").unwrap(), sum);
+        let err = expected_sha256(&sums, "drakkar-riscv64-linux").unwrap_err().to_string();
+        assert!(err.contains("no drakkar-riscv64-linux for this platform"), "{}", err);
+        assert!(expected_sha256("not a checksum\n", "drakkar-x86_64-linux").is_err());
+    }
+
+    #[test]
+    fn test_replace() {
+        let dir = std::env::temp_dir().join("drakkar_test_selfupdate_replace");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let (exe, new) = (dir.join("drakkar"), dir.join("download"));
+        std::fs::write(&exe, b"old").unwrap();
+        std::fs::write(&new, b"new").unwrap();
+        let old_sum = sha256_of(&exe).unwrap();
+        let err = replace(&exe, &new, &old_sum).unwrap_err().to_string();
+        assert!(err.contains("changed before it replaced"), "{}", err);
+        assert_eq!(std::fs::read(&exe).unwrap(), b"old");
+        assert!(!dir.join(".drakkar.partial").exists());
+
+        replace(&exe, &new, &sha256_of(&new).unwrap()).unwrap();
+        assert_eq!(std::fs::read(&exe).unwrap(), b"new");
+        assert!(!dir.join(".drakkar.partial").exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
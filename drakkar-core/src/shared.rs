@@ -0,0 +1,239 @@
+//! `shared_temp_dir`: objects shared between checkouts of one project,
+//! typically several git worktrees of the same repository.
+//!
+//! Entries are keyed by content rather than by path. The compile command,
+//! the source path and the source content select a manifest — the depfile
+//! of the last compile stored under that key — and the contents of the
+//! headers it lists select the object. drakkar runs the compiler from the
+//! project root, so project headers appear in depfiles as relative paths
+//! and resolve against whichever checkout is building.
+//!
+//! Files are written under a temporary name and renamed into place, so a
+//! reader never sees a partial object and needs no lock. Writers of one
+//! entry are serialized by a `.lock` file next to it.
+
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::build::{file_hash, headers_content_hash, ObjectFile};
+use crate::depfile::parse_depfile;
+use crate::error::BuildError;
+use crate::hash::Sha256;
+
+/// A lock older than this was left behind by a killed build.
+const STALE_LOCK: Duration = Duration::from_secs(60);
+
+/// A content-addressed store of objects under one root.
+pub struct SharedDir {
+    root: PathBuf,
+    /// Mixed into every key, to keep users of one store apart.
+    salt: String,
+}
+
+impl SharedDir {
+    /// The store at `root`, created on first write.
+    pub fn new(root: &Path) -> Self {
+        SharedDir {
+            root: root.to_path_buf(),
+            salt: String::new(),
+        }
+    }
+
+    /// The same store with `salt` mixed into every key.
+    pub fn salted(self, salt: &str) -> Self {
+        SharedDir {
+            salt: salt.to_string(),
+            ..self
+        }
+    }
+
+    /// Copy the object and depfile built from the same command, source and
+    /// headers into `obj`'s paths. False on a miss or any read error.
+    pub fn fetch(&self, obj: &ObjectFile, flags_hash: &str) -> bool {
+        let key = match self.key(obj, flags_hash) {
+            Some(key) => key,
+            None => return false,
+        };
+        let manifest = self.path(&key, "d");
+        let headers = match parse_depfile(&manifest)
+            .ok()
+            .and_then(|deps| headers_content_hash(&obj.src.path, &deps.prerequisites))
+        {
+            Some(h) => h,
+            None => return false,
+        };
+        let entry = format!("{}-{}", key, headers);
+        let (shared_obj, shared_dep) = (self.path(&entry, "o"), self.path(&entry, "d"));
+        if !shared_obj.exists() || !shared_dep.exists() {
+            return false;
+        }
+        std::fs::copy(&shared_dep, &obj.dep_path).is_ok()
+            && std::fs::copy(&shared_obj, &obj.obj_path).is_ok()
+    }
+
+    /// Publish a freshly compiled `obj`. Returns Ok without storing when
+    /// another build holds the entry's lock: it is storing the same thing.
+    pub fn store(&self, obj: &ObjectFile, flags_hash: &str) -> Result<(), BuildError> {
+        let io_err = |path: &Path, e: std::io::Error| {
+            BuildError::IoError(format!("Cannot write shared object {:?}: {}", path, e))
+        };
+        let key = match self.key(obj, flags_hash) {
+            Some(key) => key,
+            None => return Ok(()),
+        };
+        let headers = parse_depfile(&obj.dep_path)
+            .ok()
+            .and_then(|deps| headers_content_hash(&obj.src.path, &deps.prerequisites));
+        let headers = match headers {
+            Some(h) => h,
+            None => return Ok(()),
+        };
+
+        let manifest = self.path(&key, "d");
+        if let Some(parent) = manifest.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| io_err(parent, e))?;
+        }
+        let _lock = match EntryLock::acquire(&self.path(&key, "lock")) {
+            Some(lock) => lock,
+            None => return Ok(()),
+        };
+        let entry = format!("{}-{}", key, headers);
+        publish(&obj.dep_path, &self.path(&entry, "d")).map_err(|e| io_err(&self.path(&entry, "d"), e))?;
+        publish(&obj.obj_path, &self.path(&entry, "o")).map_err(|e| io_err(&self.path(&entry, "o"), e))?;
+        // Last, so a manifest always points at a complete entry
+        publish(&obj.dep_path, &manifest).map_err(|e| io_err(&manifest, e))
+    }
+
+    fn key(&self, obj: &ObjectFile, flags_hash: &str) -> Option<String> {
+        entry_key(&self.salt, obj, flags_hash)
+    }
+
+    /// `<root>/<first two hex digits>/<name>.<ext>`, to keep directories small.
+    fn path(&self, name: &str, ext: &str) -> PathBuf {
+        self.root.join(entry_name(name, ext))
+    }
+}
+
+/// Key of `obj`'s entries: the compile command, source path and source
+/// content, plus `salt` if any. None when the source can't be read.
+pub fn entry_key(salt: &str, obj: &ObjectFile, flags_hash: &str) -> Option<String> {
+    let mut h = Sha256::new();
+    if !salt.is_empty() {
+        h.update_field(salt);
+    }
+    h.update_field(flags_hash);
+    h.update_field(&obj.src.path.to_string_lossy());
+    h.update_field(&file_hash(&obj.src.path)?);
+    Some(h.finish_hex())
+}
+
+/// Where an entry lives relative to the root of a store. Remote caches use
+/// the same layout.
+pub fn entry_name(name: &str, ext: &str) -> String {
+    format!("{}/{}.{}", &name[..2], name, ext)
+}
+
+/// Copy `from` next to `to` and rename it into place.
+fn publish(from: &Path, to: &Path) -> std::io::Result<()> {
+    let mut tmp = to.as_os_str().to_owned();
+    tmp.push(".tmp");
+    std::fs::copy(from, &tmp)?;
+    std::fs::rename(&tmp, to)
+}
+
+/// Exclusive ownership of one entry, released on drop.
+struct EntryLock {
+    path: PathBuf,
+}
+
+impl EntryLock {
+    /// None while another build holds a fresh lock.
+    fn acquire(path: &Path) -> Option<Self> {
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(_) => {
+                    return Some(EntryLock {
+                        path: path.to_path_buf(),
+                    })
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let stale = std::fs::metadata(path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|t| SystemTime::now().duration_since(t).ok())
+                        .map(|age| age > STALE_LOCK)
+                        .unwrap_or(false);
+                    if !stale {
+                        return None;
+                    }
+                    let _ = std::fs::remove_file(path);
+                }
+                Err(_) => return None,
+            }
+        }
+        None
+    }
+}
+
+impl Drop for EntryLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+
+    fn object(dir: &Path, checkout: &str) -> ObjectFile {
+        ObjectFile {
+            src: SourceFile {
+                path: dir.join("src/a.cpp"),
+                rel_path: PathBuf::from("a.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: dir.join(checkout).join("a.o"),
+            dep_path: dir.join(checkout).join("a.d"),
+        }
+    }
+
+    #[test]
+    fn test_store_then_fetch_and_lock() {
+        let dir = std::env::temp_dir().join("drakkar_test_shared");
+        let _ = std::fs::remove_dir_all(&dir);
+        for sub in ["src", "a", "b"] {
+            std::fs::create_dir_all(dir.join(sub)).unwrap();
+        }
+        std::fs::write(dir.join("src/a.cpp"), "#include \"a.h\"\n").unwrap();
+        std::fs::write(dir.join("src/a.h"), "int a();\n").unwrap();
+
+        let shared = SharedDir::new(&dir.join("shared"));
+        let (a, b) = (object(&dir, "a"), object(&dir, "b"));
+        std::fs::write(&a.obj_path, "OBJ").unwrap();
+        std::fs::write(
+            &a.dep_path,
+            format!("a.o: {} {}\n", a.src.path.display(), dir.join("src/a.h").display()),
+        )
+        .unwrap();
+
+        assert!(!shared.fetch(&b, "flags"));
+        shared.store(&a, "flags").unwrap();
+        assert!(!shared.fetch(&b, "other flags"));
+        assert!(shared.fetch(&b, "flags"));
+        assert_eq!(std::fs::read_to_string(&b.obj_path).unwrap(), "OBJ");
+
+        // A different header content is a miss
+        std::fs::write(dir.join("src/a.h"), "long a();\n").unwrap();
+        assert!(!shared.fetch(&b, "flags"));
+
+        let lock_path = shared.path(&shared.key(&a, "flags").unwrap(), "lock");
+        let lock = EntryLock::acquire(&lock_path).unwrap();
+        assert!(EntryLock::acquire(&lock_path).is_none());
+        drop(lock);
+        assert!(EntryLock::acquire(&lock_path).is_some());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
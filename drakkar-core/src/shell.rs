@@ -0,0 +1,93 @@
+//! Quoting arguments for display, so a command echoed by `--verbose` or
+//! `--explain-commands` can be copied into a shell and run as is.
+//!
+//! POSIX shells get single quotes; on Windows arguments are quoted the way
+//! `CommandLineToArgvW` (and so every MSVC-built program) splits them.
+
+/// `arg` as a single shell word, unchanged when nothing in it needs quoting.
+pub fn quote(arg: &str) -> String {
+    if cfg!(windows) {
+        quote_windows(arg)
+    } else {
+        quote_posix(arg)
+    }
+}
+
+/// `program` and `args` as one command line.
+pub fn command_line<S: AsRef<str>>(program: &str, args: &[S]) -> String {
+    let mut line = quote(program);
+    for arg in args {
+        line.push(' ');
+        line.push_str(&quote(arg.as_ref()));
+    }
+    line
+}
+
+fn quote_posix(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "@%+=:,./-_".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        return arg.to_string();
+    }
+    format!("'{}'", arg.replace('\'', r"'\''"))
+}
+
+fn quote_windows(arg: &str) -> String {
+    let special = |c: char| c.is_whitespace() || "\"&|<>^()%!".contains(c);
+    if !arg.is_empty() && !arg.chars().any(special) {
+        return arg.to_string();
+    }
+    let mut quoted = String::from("\"");
+    let mut backslashes = 0;
+    for c in arg.chars() {
+        match c {
+            '\\' => backslashes += 1,
+            '"' => {
+                // Backslashes before a quote escape each other, then the quote
+                quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+                backslashes = 0;
+            }
+            _ => {
+                quoted.push_str(&"\\".repeat(backslashes));
+                backslashes = 0;
+            }
+        }
+        if c != '\\' {
+            quoted.push(c);
+        }
+    }
+    // ...and so do the ones before the closing quote
+    quoted.push_str(&"\\".repeat(backslashes * 2));
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::shell_tokenize;
+
+    #[test]
+    fn test_quote_posix() {
+        assert_eq!(quote_posix("-DNAME=value"), "-DNAME=value");
+        assert_eq!(quote_posix("src/main.c"), "src/main.c");
+        assert_eq!(quote_posix(""), "''");
+        assert_eq!(quote_posix("-DMSG=\"hi there\""), "'-DMSG=\"hi there\"'");
+        assert_eq!(quote_posix("it's"), r"'it'\''s'");
+        assert_eq!(quote_posix("-I$HOME/inc"), "'-I$HOME/inc'");
+
+        // What the config tokenizer reads back is what was quoted
+        let args = ["-DMSG=\"a b\"", "it's", "x*y", "", "plain"];
+        let line = args.iter().map(|a| quote_posix(a)).collect::<Vec<_>>().join(" ");
+        assert_eq!(shell_tokenize(&line).unwrap(), args);
+    }
+
+    #[test]
+    fn test_quote_windows() {
+        assert_eq!(quote_windows("/Fo:out\\a.obj"), "/Fo:out\\a.obj");
+        assert_eq!(quote_windows(""), "\"\"");
+        assert_eq!(quote_windows("C:\\Program Files\\x"), "\"C:\\Program Files\\x\"");
+        assert_eq!(quote_windows("C:\\dir with space\\"), "\"C:\\dir with space\\\\\"");
+        assert_eq!(quote_windows("/DMSG=\"hi\""), "\"/DMSG=\\\"hi\\\"\"");
+        assert_eq!(quote_windows("a\\\"b"), "\"a\\\\\\\"b\"");
+    }
+}
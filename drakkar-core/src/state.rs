@@ -0,0 +1,332 @@
+//! Persisted incremental-build state.
+//!
+//! mtimes alone can't tell whether an object was built with the current
+//! command line, so per-object metadata is kept in `temp_dir/.drakkar_state`.
+//! The format is one tab-separated line per object, `<obj_path>` followed
+//! by `key=value` fields: `flags=<sha256>` and, once compiled, `ms=<compile
+//! time in milliseconds>`. Pipelined builds also record `pp=<sha256>` of the
+//! preprocessed source, whose codegen is skipped while it stays the same,
+//! and `rebuild_policy = "hash"` records `src=<sha256>` of the source and
+//! `hdr=<sha256>` of the headers it included. Linked
+//! executables get a line of their own whose `flags=` is the hash of the
+//! link command.
+//!
+//! Unknown fields are ignored on load so the file can grow new columns
+//! without invalidating older builds.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::depfile::parse_depfile;
+use crate::error::BuildError;
+use crate::json::Json;
+
+/// The state file, in temp_dir.
+pub const STATE_FILE_NAME: &str = ".drakkar_state";
+const HEADER: &str = "# drakkar incremental state v1";
+
+/// What was recorded about an object or executable when it was last built.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectState {
+    /// SHA-256 of the compiler and full argument list.
+    pub flags_hash: String,
+    /// Wall time of the last compile, used to estimate rebuild cost.
+    pub compile_ms: Option<u64>,
+    /// SHA-256 of the preprocessed source, when built with `pipeline`.
+    pub preprocessed_hash: Option<String>,
+    /// SHA-256 of the source content, under `rebuild_policy = "hash"`.
+    pub source_hash: Option<String>,
+    /// Combined SHA-256 of every other depfile entry's path and content.
+    pub headers_hash: Option<String>,
+}
+
+/// The recorded state of every object and executable, keyed by path.
+#[derive(Debug, Default)]
+pub struct BuildState {
+    path: PathBuf,
+    entries: HashMap<PathBuf, ObjectState>,
+}
+
+impl BuildState {
+    /// Load state from `temp_dir`. A missing or unreadable file yields an
+    /// empty state: the worst case is a conservative rebuild.
+    pub fn load(temp_dir: &Path) -> Self {
+        let path = temp_dir.join(STATE_FILE_NAME);
+        let mut entries = HashMap::new();
+
+        if let Ok(content) = std::fs::read_to_string(&path) {
+            for line in content.lines() {
+                if line.starts_with('#') || line.trim().is_empty() {
+                    continue;
+                }
+                let mut fields = line.split('\t');
+                let obj = match fields.next() {
+                    Some(o) if !o.is_empty() => PathBuf::from(o),
+                    _ => continue,
+                };
+                let mut state = ObjectState::default();
+                for field in fields {
+                    if let Some(v) = field.strip_prefix("flags=") {
+                        state.flags_hash = v.to_string();
+                    } else if let Some(v) = field.strip_prefix("ms=") {
+                        state.compile_ms = v.parse().ok();
+                    } else if let Some(v) = field.strip_prefix("pp=") {
+                        state.preprocessed_hash = Some(v.to_string());
+                    } else if let Some(v) = field.strip_prefix("src=") {
+                        state.source_hash = Some(v.to_string());
+                    } else if let Some(v) = field.strip_prefix("hdr=") {
+                        state.headers_hash = Some(v.to_string());
+                    }
+                }
+                entries.insert(obj, state);
+            }
+        }
+
+        BuildState { path, entries }
+    }
+
+    /// The state recorded for `obj_path`, if any.
+    pub fn get(&self, obj_path: &Path) -> Option<&ObjectState> {
+        self.entries.get(obj_path)
+    }
+
+    /// Record `state` for `obj_path`, replacing what was there.
+    pub fn set(&mut self, obj_path: &Path, state: ObjectState) {
+        self.entries.insert(obj_path.to_path_buf(), state);
+    }
+
+    /// The state file this was loaded from.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Every recorded object and executable, sorted, with the inputs of its
+    /// depfile and when it was last written, for `drakkar state`.
+    pub fn inspect(&self) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = self
+            .entries
+            .iter()
+            .map(|(path, state)| Entry {
+                path: path.clone(),
+                state: state.clone(),
+                inputs: parse_depfile(&path.with_extension("d"))
+                    .map(|d| d.prerequisites)
+                    .unwrap_or_default(),
+                built: std::fs::metadata(path).and_then(|m| m.modified()).ok(),
+            })
+            .collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        entries
+    }
+
+    /// Write the state file back, entries sorted by path.
+    pub fn save(&self) -> Result<(), BuildError> {
+        let mut keys: Vec<_> = self.entries.keys().collect();
+        keys.sort();
+
+        let mut out = String::from(HEADER);
+        out.push('\n');
+        for key in keys {
+            let state = &self.entries[key];
+            out.push_str(&format!("{}\tflags={}", key.display(), state.flags_hash));
+            if let Some(ms) = state.compile_ms {
+                out.push_str(&format!("\tms={}", ms));
+            }
+            if let Some(pp) = &state.preprocessed_hash {
+                out.push_str(&format!("\tpp={}", pp));
+            }
+            if let Some(src) = &state.source_hash {
+                out.push_str(&format!("\tsrc={}", src));
+            }
+            if let Some(hdr) = &state.headers_hash {
+                out.push_str(&format!("\thdr={}", hdr));
+            }
+            out.push('\n');
+        }
+
+        std::fs::write(&self.path, out).map_err(|e| {
+            BuildError::IoError(format!("Cannot write build state {:?}: {}", self.path, e))
+        })
+    }
+}
+
+/// One line of the state file, as `drakkar state` shows it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// The object, or the executable for a link entry.
+    pub path: PathBuf,
+    /// What was recorded for it.
+    pub state: ObjectState,
+    /// What the depfile next to it lists; empty for executables and
+    /// objects without one.
+    pub inputs: Vec<PathBuf>,
+    /// When the file was last written; None once it was deleted.
+    pub built: Option<SystemTime>,
+}
+
+/// `5m ago`: how long before `now` the file was written.
+fn ago(built: SystemTime, now: SystemTime) -> String {
+    let secs = now.duration_since(built).map(|d| d.as_secs()).unwrap_or(0);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+/// Hashes are shortened in the human listing, like git does.
+fn short(hash: &str) -> &str {
+    hash.get(..12).unwrap_or(hash)
+}
+
+/// `entries` as indented text, one block per entry.
+pub fn to_text(entries: &[Entry], now: SystemTime) -> Vec<String> {
+    let mut lines = Vec::new();
+    for entry in entries {
+        lines.push(entry.path.display().to_string());
+        let state = &entry.state;
+        lines.push(format!("  flags    {}", short(&state.flags_hash)));
+        let built = match entry.built {
+            Some(built) => ago(built, now),
+            None => "missing".to_string(),
+        };
+        match state.compile_ms {
+            Some(ms) => lines.push(format!("  built    {} (took {} ms)", built, ms)),
+            None => lines.push(format!("  built    {}", built)),
+        }
+        let hashes = [
+            ("source", &state.source_hash),
+            ("headers", &state.headers_hash),
+            ("preproc", &state.preprocessed_hash),
+        ];
+        for (name, hash) in hashes {
+            if let Some(hash) = hash {
+                lines.push(format!("  {:8} {}", name, short(hash)));
+            }
+        }
+        for (i, input) in entry.inputs.iter().enumerate() {
+            let label = if i == 0 { "inputs" } else { "" };
+            lines.push(format!("  {:8} {}", label, input.display()));
+        }
+    }
+    lines
+}
+
+/// `entries` as JSON, with full hashes and `built_at` in Unix seconds.
+pub fn to_json(entries: &[Entry]) -> String {
+    let entries = entries.iter().map(|entry| {
+        let state = &entry.state;
+        let built_at = entry
+            .built
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs() as i64);
+        let inputs: Vec<String> =
+            entry.inputs.iter().map(|p| p.to_string_lossy().into_owned()).collect();
+        Json::object()
+            .field("path", entry.path.to_string_lossy().into_owned())
+            .field("flags_hash", state.flags_hash.as_str())
+            .field("compile_ms", state.compile_ms.map(|ms| ms as i64))
+            .field("source_hash", state.source_hash.clone())
+            .field("headers_hash", state.headers_hash.clone())
+            .field("preprocessed_hash", state.preprocessed_hash.clone())
+            .field("built_at", built_at)
+            .field("inputs", inputs)
+    });
+    Json::Array(entries.collect()).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = std::env::temp_dir().join("drakkar_test_state_roundtrip");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let mut state = BuildState::load(&dir);
+        assert!(state.get(Path::new("target/a.o")).is_none());
+        state.set(
+            Path::new("target/a.o"),
+            ObjectState {
+                flags_hash: "abc".to_string(),
+                compile_ms: Some(420),
+                preprocessed_hash: Some("def".to_string()),
+                source_hash: Some("5a".to_string()),
+                headers_hash: Some("7b".to_string()),
+            },
+        );
+        state.save().unwrap();
+
+        let loaded = BuildState::load(&dir);
+        let entry = loaded.get(Path::new("target/a.o")).unwrap();
+        assert_eq!(entry.flags_hash, "abc");
+        assert_eq!(entry.compile_ms, Some(420));
+        assert_eq!(entry.preprocessed_hash.as_deref(), Some("def"));
+        assert_eq!(entry.source_hash.as_deref(), Some("5a"));
+        assert_eq!(entry.headers_hash.as_deref(), Some("7b"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_unknown_fields_ignored() {
+        let dir = std::env::temp_dir().join("drakkar_test_state_unknown");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join(STATE_FILE_NAME), "target/a.o\tfuture=1\tflags=f00\n").unwrap();
+
+        let loaded = BuildState::load(&dir);
+        assert_eq!(loaded.get(Path::new("target/a.o")).unwrap().flags_hash, "f00");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_inspect() {
+        let dir = std::env::temp_dir().join("drakkar_test_state_inspect");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let obj = dir.join("a.o");
+        std::fs::write(&obj, "").unwrap();
+        std::fs::write(dir.join("a.d"), "a.o: src/a.c src/a.h\n").unwrap();
+
+        let mut state = BuildState::load(&dir);
+        state.set(
+            &obj,
+            ObjectState {
+                flags_hash: "0123456789abcdef".to_string(),
+                compile_ms: Some(420),
+                source_hash: Some("5a".to_string()),
+                ..ObjectState::default()
+            },
+        );
+        state.set(&dir.join("app"), ObjectState::default());
+        let entries = state.inspect();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].inputs, vec![PathBuf::from("src/a.c"), PathBuf::from("src/a.h")]);
+        assert!(entries[0].built.is_some());
+        assert_eq!(entries[1].built, None);
+
+        let built = entries[0].built.unwrap();
+        let text = to_text(&entries[..1], built + std::time::Duration::from_secs(150));
+        assert_eq!(
+            text[1..],
+            [
+                "  flags    0123456789ab",
+                "  built    2m ago (took 420 ms)",
+                "  source   5a",
+                "  inputs   src/a.c",
+                "           src/a.h",
+            ]
+        );
+        let json = to_json(&entries[1..]);
+        assert!(json.contains("\"compile_ms\":null,"), "{}", json);
+        assert!(json.ends_with("\"built_at\":null,\"inputs\":[]}]"), "{}", json);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
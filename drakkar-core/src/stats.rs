@@ -0,0 +1,297 @@
+//! `drakkar stats sources`: how big the codebase is, from the same source
+//! and header scan the build uses. Files per language and directory, line
+//! totals, headers per source file and the largest files.
+
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::build::{collect_sources, Language};
+use crate::config::ProjectConfig;
+use crate::error::BuildError;
+use crate::headers::find_headers;
+use crate::json::Json;
+
+/// What a file is, for grouping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Kind {
+    /// C sources.
+    C,
+    /// C++ sources.
+    Cpp,
+    /// Headers of either language.
+    Header,
+}
+
+impl Kind {
+    /// The group's name in the report: `C`, `C++` or `headers`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Kind::C => "C",
+            Kind::Cpp => "C++",
+            Kind::Header => "headers",
+        }
+    }
+}
+
+/// One counted file.
+#[derive(Debug, Clone)]
+pub struct FileStats {
+    /// Where it is.
+    pub path: PathBuf,
+    /// What it is.
+    pub kind: Kind,
+    /// Its line count, a last line without a newline included.
+    pub lines: usize,
+}
+
+/// Files, lines and header count of a group of files.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Totals {
+    /// Files in the group.
+    pub files: usize,
+    /// Their lines.
+    pub lines: usize,
+    /// How many of them are headers.
+    pub headers: usize,
+}
+
+impl Totals {
+    fn add(&mut self, file: &FileStats) {
+        self.files += 1;
+        self.lines += file.lines;
+        if file.kind == Kind::Header {
+            self.headers += 1;
+        }
+    }
+
+    /// Files that aren't headers.
+    pub fn sources(&self) -> usize {
+        self.files - self.headers
+    }
+
+    /// Headers per source file; None without sources.
+    pub fn header_ratio(&self) -> Option<f64> {
+        match self.sources() {
+            0 => None,
+            n => Some(self.headers as f64 / n as f64),
+        }
+    }
+}
+
+/// Every source and header of `units` (the project, or each of its
+/// targets), once each. Generated sources aren't part of the codebase and
+/// are left out.
+pub fn collect(units: &[ProjectConfig]) -> Result<Vec<FileStats>, BuildError> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for unit in units {
+        for src in collect_sources(unit)?.sources {
+            if src.path.starts_with(&unit.temp_dir) || !seen.insert(src.path.clone()) {
+                continue;
+            }
+            let kind = match src.language {
+                Language::C => Kind::C,
+                Language::Cpp => Kind::Cpp,
+            };
+            files.push(FileStats { lines: count_lines(&src.path)?, path: src.path, kind });
+        }
+        for header in find_headers(unit)? {
+            if seen.insert(header.path.clone()) {
+                let lines = count_lines(&header.path)?;
+                files.push(FileStats { path: header.path, kind: Kind::Header, lines });
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn count_lines(path: &Path) -> Result<usize, BuildError> {
+    let content = std::fs::read(path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read {:?}: {}", path, e)))?;
+    let newlines = content.iter().filter(|&&b| b == b'\n').count();
+    Ok(newlines + usize::from(content.last().map(|&b| b != b'\n').unwrap_or(false)))
+}
+
+/// The totals over `files`.
+pub fn total(files: &[FileStats]) -> Totals {
+    let mut totals = Totals::default();
+    files.iter().for_each(|f| totals.add(f));
+    totals
+}
+
+/// Totals per kind, C and C++ before headers.
+pub fn by_kind(files: &[FileStats]) -> Vec<(Kind, Totals)> {
+    let mut groups: BTreeMap<Kind, Totals> = BTreeMap::new();
+    files.iter().for_each(|f| groups.entry(f.kind).or_default().add(f));
+    groups.into_iter().collect()
+}
+
+/// Totals per directory the files are directly in, by path.
+pub fn by_directory(files: &[FileStats]) -> Vec<(PathBuf, Totals)> {
+    let mut groups: BTreeMap<PathBuf, Totals> = BTreeMap::new();
+    for file in files {
+        let dir = match file.path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        groups.entry(dir).or_default().add(file);
+    }
+    groups.into_iter().collect()
+}
+
+/// The `n` files with the most lines, largest first.
+pub fn largest(files: &[FileStats], n: usize) -> Vec<&FileStats> {
+    let mut sorted: Vec<&FileStats> = files.iter().collect();
+    sorted.sort_by(|a, b| b.lines.cmp(&a.lines).then_with(|| a.path.cmp(&b.path)));
+    sorted.truncate(n);
+    sorted
+}
+
+fn ratio(totals: &Totals) -> String {
+    totals.header_ratio().map(|r| format!("{:.2}", r)).unwrap_or_else(|| "-".to_string())
+}
+
+/// The report as aligned text tables.
+pub fn to_text(files: &[FileStats], top: usize) -> Vec<String> {
+    let all = total(files);
+    let mut lines = vec![format!(
+        "{} file(s), {} line(s): {} source(s), {} header(s), {} header(s) per source",
+        all.files,
+        all.lines,
+        all.sources(),
+        all.headers,
+        ratio(&all)
+    )];
+
+    lines.push(String::new());
+    lines.push(format!("  {:<10} {:>7} {:>9}", "language", "files", "lines"));
+    for (kind, totals) in by_kind(files) {
+        lines.push(format!("  {:<10} {:>7} {:>9}", kind.name(), totals.files, totals.lines));
+    }
+
+    let dirs = by_directory(files);
+    let width = dirs.iter().map(|(d, _)| d.display().to_string().len()).max().unwrap_or(0);
+    let width = width.max("directory".len());
+    lines.push(String::new());
+    lines.push(format!(
+        "  {:<w$} {:>7} {:>7} {:>9} {:>6}",
+        "directory",
+        "sources",
+        "headers",
+        "lines",
+        "h/src",
+        w = width
+    ));
+    for (dir, totals) in &dirs {
+        lines.push(format!(
+            "  {:<w$} {:>7} {:>7} {:>9} {:>6}",
+            dir.display(),
+            totals.sources(),
+            totals.headers,
+            totals.lines,
+            ratio(totals),
+            w = width
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("  largest files".to_string());
+    for file in largest(files, top) {
+        lines.push(format!("  {:>9}  {}", file.lines, file.path.display()));
+    }
+    lines
+}
+
+/// The report as one JSON object, for dashboards.
+pub fn to_json(files: &[FileStats], top: usize) -> String {
+    let totals_json = |json: Json, totals: &Totals| {
+        json.field("files", totals.files)
+            .field("sources", totals.sources())
+            .field("headers", totals.headers)
+            .field("lines", totals.lines)
+            .field("header_ratio", totals.header_ratio())
+    };
+    let kinds = by_kind(files)
+        .iter()
+        .map(|(kind, totals)| {
+            Json::object()
+                .field("language", kind.name())
+                .field("files", totals.files)
+                .field("lines", totals.lines)
+        })
+        .collect();
+    let dirs = by_directory(files)
+        .iter()
+        .map(|(dir, totals)| {
+            let dir = Json::object().field("directory", dir.to_string_lossy().into_owned());
+            totals_json(dir, totals)
+        })
+        .collect();
+    let largest = largest(files, top)
+        .iter()
+        .map(|f| {
+            Json::object()
+                .field("path", f.path.to_string_lossy().into_owned())
+                .field("language", f.kind.name())
+                .field("lines", f.lines)
+        })
+        .collect();
+    totals_json(Json::object(), &total(files))
+        .field("languages", Json::Array(kinds))
+        .field("directories", Json::Array(dirs))
+        .field("largest", Json::Array(largest))
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str, kind: Kind, lines: usize) -> FileStats {
+        FileStats { path: PathBuf::from(path), kind, lines }
+    }
+
+    #[test]
+    fn test_groups() {
+        let files = vec![
+            file("src/main.cpp", Kind::Cpp, 40),
+            file("src/net/socket.cpp", Kind::Cpp, 300),
+            file("src/net/socket.h", Kind::Header, 60),
+            file("src/net/http.h", Kind::Header, 20),
+            file("src/legacy.c", Kind::C, 120),
+            file("include/api.h", Kind::Header, 30),
+        ];
+        let all = total(&files);
+        assert_eq!((all.files, all.lines, all.headers, all.sources()), (6, 570, 3, 3));
+        assert_eq!(all.header_ratio(), Some(1.0));
+
+        let kinds: Vec<(&str, usize)> =
+            by_kind(&files).iter().map(|(k, t)| (k.name(), t.lines)).collect();
+        assert_eq!(kinds, [("C", 120), ("C++", 340), ("headers", 110)]);
+
+        let dirs = by_directory(&files);
+        let names: Vec<String> = dirs.iter().map(|(d, _)| d.display().to_string()).collect();
+        assert_eq!(names, ["include", "src", "src/net"]);
+        assert_eq!(dirs[0].1.header_ratio(), None);
+        assert_eq!(dirs[2].1.header_ratio(), Some(2.0));
+
+        let top: Vec<&Path> = largest(&files, 2).iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(top, [Path::new("src/net/socket.cpp"), Path::new("src/legacy.c")]);
+
+        let json = to_json(&files, 1);
+        assert!(json.contains(r#""directory":"src/net""#), "{}", json);
+        assert!(json.contains(r#""largest":[{"path":"src/net/socket.cpp""#), "{}", json);
+    }
+
+    #[test]
+    fn test_count_lines() {
+        let dir = std::env::temp_dir().join("drakkar_test_stats_lines");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, content, lines) in [("a", "x\ny\n", 2), ("b", "x\ny", 2), ("c", "", 0)] {
+            std::fs::write(dir.join(name), content).unwrap();
+            assert_eq!(count_lines(&dir.join(name)).unwrap(), lines, "{:?}", content);
+        }
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,204 @@
+//! `--emit-status <path>`: a small JSON summary of the last build for
+//! dashboards and commit-status bots.
+//!
+//! The file is written after every build, successful or not, so a bot can
+//! always read the outcome of the most recent run:
+//!
+//! ```json
+//! {"result": "success", "profile": "debug", "duration_secs": 1.42,
+//!  "warnings": 3, "errors": 0, "compiled": 2, "up_to_date": 10,
+//!  "artifacts": [{"path": "out/myapp", "sha256": "…", "size": 18232}]}
+//! ```
+//!
+//! A failed build has `"result": "failure"`, an empty `artifacts` list and
+//! an `error` object in the `--message-format=json` shape. Its warning and
+//! compile counts are `null`: the build stopped before they were complete.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::config::BuildProfile;
+use crate::error::BuildError;
+use crate::hash::Sha256;
+use crate::json::Json;
+
+/// What a successful build did, beyond the executables it produced.
+#[derive(Debug, Default)]
+pub struct BuildSummary {
+    /// Executables linked or already up to date.
+    pub executables: Vec<PathBuf>,
+    /// Archives of `static_lib` targets, and plugin modules.
+    pub libraries: Vec<PathBuf>,
+    /// Objects compiled, or fetched from a shared dir or cache.
+    pub compiled: usize,
+    /// Objects that were already current.
+    pub up_to_date: usize,
+    /// Compiler warnings, replayed ones included.
+    pub warnings: usize,
+    /// Objects the object cache had.
+    pub cache_hits: usize,
+    /// Objects it didn't have.
+    pub cache_misses: usize,
+    /// Objects the build compiled or found up to date.
+    pub objects: Vec<PathBuf>,
+}
+
+impl BuildSummary {
+    /// Fold in the summary of another target or project built in the same run.
+    pub fn add(&mut self, other: BuildSummary) {
+        self.executables.extend(other.executables);
+        self.libraries.extend(other.libraries);
+        self.compiled += other.compiled;
+        self.up_to_date += other.up_to_date;
+        self.warnings += other.warnings;
+        self.cache_hits += other.cache_hits;
+        self.cache_misses += other.cache_misses;
+        self.objects.extend(other.objects);
+    }
+}
+
+/// Count `file:line:col: warning: ...` lines in compiler output, and
+/// MSVC's `file(line): warning C4100: ...`.
+pub fn count_warnings(diagnostics: &str) -> usize {
+    diagnostics
+        .lines()
+        .filter(|l| l.contains(": warning: ") || l.contains("): warning C"))
+        .count()
+}
+
+/// Number of individual failures an error stands for.
+fn error_count(err: &BuildError) -> usize {
+    match err {
+        BuildError::MultipleErrors(errs) => errs.iter().map(error_count).sum(),
+        _ => 1,
+    }
+}
+
+fn artifact(path: &Path) -> Result<Json, BuildError> {
+    let content = std::fs::read(path)
+        .map_err(|e| BuildError::IoError(format!("Cannot read artifact {:?}: {}", path, e)))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&content);
+    Ok(Json::object()
+        .field("path", path.to_string_lossy().into_owned())
+        .field("sha256", hasher.finish_hex())
+        .field("size", content.len()))
+}
+
+/// Build the status document for a finished build.
+pub fn status_json(
+    outcome: &Result<BuildSummary, BuildError>,
+    profile: &BuildProfile,
+    duration: Duration,
+) -> Result<Json, BuildError> {
+    let profile = match profile {
+        BuildProfile::Debug => "debug",
+        BuildProfile::Release => "release",
+    };
+    let json = Json::object()
+        .field(
+            "result",
+            if outcome.is_ok() { "success" } else { "failure" },
+        )
+        .field("profile", profile)
+        .field("duration_secs", duration.as_secs_f64());
+
+    Ok(match outcome {
+        Ok(summary) => json
+            .field("warnings", summary.warnings)
+            .field("errors", 0)
+            .field("compiled", summary.compiled)
+            .field("up_to_date", summary.up_to_date)
+            .field(
+                "artifacts",
+                Json::Array(
+                    summary
+                        .executables
+                        .iter()
+                        .chain(&summary.libraries)
+                        .map(|p| artifact(p))
+                        .collect::<Result<_, _>>()?,
+                ),
+            ),
+        Err(err) => json
+            .field("warnings", Json::Null)
+            .field("errors", error_count(err))
+            .field("compiled", Json::Null)
+            .field("up_to_date", Json::Null)
+            .field("artifacts", Json::Array(Vec::new()))
+            .field("error", err.to_json()),
+    })
+}
+
+/// Write the status document to `path`, creating parent directories.
+pub fn write_status(path: &Path, status: &Json) -> Result<(), BuildError> {
+    if let Some(parent) = path.parent() {
+        if !parent.as_os_str().is_empty() {
+            std::fs::create_dir_all(parent).map_err(|e| {
+                BuildError::IoError(format!("Cannot create directory {:?}: {}", parent, e))
+            })?;
+        }
+    }
+    std::fs::write(path, format!("{}\n", status))
+        .map_err(|e| BuildError::IoError(format!("Cannot write status file {:?}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_warnings() {
+        let stderr = "src/a.cpp: In function 'int main()':\n\
+                      src/a.cpp:3:9: warning: unused variable 'x' [-Wunused-variable]\n\
+                      \x20   3 |     int x;\n\
+                      src/a.cpp:4:1: warning: no return statement [-Wreturn-type]\n";
+        assert_eq!(count_warnings(stderr), 2);
+        assert_eq!(count_warnings("src\\a.cpp(3): warning C4101: 'x': unreferenced local variable\n"), 1);
+        assert_eq!(count_warnings(""), 0);
+    }
+
+    #[test]
+    fn test_status_success_and_failure() {
+        let dir = std::env::temp_dir().join("drakkar_test_status");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let exe = dir.join("app");
+        std::fs::write(&exe, b"abc").unwrap();
+
+        let ok = Ok(BuildSummary {
+            executables: vec![exe],
+            libraries: vec![],
+            compiled: 1,
+            up_to_date: 2,
+            warnings: 3,
+            ..Default::default()
+        });
+        let json = status_json(&ok, &BuildProfile::Release, Duration::from_millis(1500))
+            .unwrap()
+            .to_string();
+        assert!(json.contains(r#""result":"success""#));
+        assert!(json.contains(r#""profile":"release""#));
+        assert!(json.contains(r#""warnings":3"#));
+        assert!(json.contains(
+            r#""sha256":"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad""#
+        ));
+
+        let failed: Result<BuildSummary, _> = Err(BuildError::MultipleErrors(vec![
+            BuildError::Cancelled,
+            BuildError::ConfigError("x".to_string()),
+        ]));
+        let json = status_json(&failed, &BuildProfile::Debug, Duration::ZERO)
+            .unwrap()
+            .to_string();
+        assert!(json.contains(r#""result":"failure""#));
+        assert!(json.contains(r#""errors":2"#));
+        assert!(json.contains(r#""artifacts":[]"#));
+
+        let path = dir.join("nested/status.json");
+        write_status(&path, &Json::object().field("result", "success")).unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().ends_with("}\n"));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,181 @@
+//! `{{var}}` template rendering for `drakkar create`.
+//!
+//! Both the built-in project skeleton and user template directories
+//! (`--from-template`) go through the same renderer, which substitutes
+//! file contents *and* file/directory names.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::error::BuildError;
+
+/// Template variables, e.g. `name` → `myapp`.
+pub type TemplateVars = HashMap<String, String>;
+
+/// Replace every `{{ident}}` (surrounding spaces allowed) with its value.
+///
+/// Only identifier-shaped placeholders are considered, so C++ brace
+/// initializers such as `v{{1, 2}}` pass through untouched. An identifier
+/// that has no value is an error, which catches typos in templates.
+pub fn render(text: &str, vars: &TemplateVars) -> Result<String, BuildError> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = match after.find("}}") {
+            Some(e) => e,
+            None => {
+                out.push_str(&rest[start..]);
+                return Ok(out);
+            }
+        };
+        let ident = after[..end].trim();
+        if is_identifier(ident) {
+            let value = vars.get(ident).ok_or_else(|| {
+                BuildError::ConfigError(format!(
+                    "Template variable '{{{{{}}}}}' has no value (pass --var {}=...)",
+                    ident, ident
+                ))
+            })?;
+            out.push_str(value);
+        } else {
+            out.push_str(&rest[start..start + 2 + end + 2]);
+        }
+        rest = &after[end + 2..];
+    }
+
+    out.push_str(rest);
+    Ok(out)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() || c == '_' => {}
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parse a `key=value` pair from `--var`.
+pub fn parse_var(arg: &str) -> Result<(String, String), BuildError> {
+    let eq = arg.find('=').ok_or_else(|| {
+        BuildError::ParseError(format!("--var: expected key=value, got '{}'", arg))
+    })?;
+    let key = arg[..eq].trim();
+    if !is_identifier(key) {
+        return Err(BuildError::ParseError(format!(
+            "--var: '{}' is not a valid variable name",
+            key
+        )));
+    }
+    Ok((key.to_string(), arg[eq + 1..].to_string()))
+}
+
+/// Resolve `--from-template` to a directory. Bare names that don't exist
+/// as paths are looked up in `~/.config/drakkar/templates/<name>`.
+pub fn resolve_template_dir(spec: &str) -> Result<PathBuf, BuildError> {
+    let home = std::env::var_os("HOME").or_else(|| std::env::var_os("USERPROFILE"));
+
+    let path = match (spec.strip_prefix("~/"), &home) {
+        (Some(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => PathBuf::from(spec),
+    };
+    if path.is_dir() {
+        return Ok(path);
+    }
+
+    if !spec.contains('/') && !spec.contains('\\') {
+        if let Some(home) = home {
+            let named = PathBuf::from(home)
+                .join(".config")
+                .join("drakkar")
+                .join("templates")
+                .join(spec);
+            if named.is_dir() {
+                return Ok(named);
+            }
+        }
+    }
+
+    Err(BuildError::IoError(format!(
+        "Template directory {:?} does not exist",
+        path
+    )))
+}
+
+/// Recursively copy `src` into `dst`, rendering names and UTF-8 contents.
+/// Files that aren't valid UTF-8 (images, fonts) are copied verbatim.
+pub fn render_dir(src: &Path, dst: &Path, vars: &TemplateVars) -> Result<(), BuildError> {
+    std::fs::create_dir_all(dst)?;
+
+    let entries = std::fs::read_dir(src).map_err(|e| {
+        BuildError::IoError(format!("Cannot read template directory {:?}: {}", src, e))
+    })?;
+
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let name = file_name.to_string_lossy();
+        if name == ".git" {
+            continue;
+        }
+        let target = dst.join(render(&name, vars)?);
+        let path = entry.path();
+
+        if path.is_dir() {
+            render_dir(&path, &target, vars)?;
+        } else {
+            let bytes = std::fs::read(&path)?;
+            match String::from_utf8(bytes) {
+                Ok(text) => std::fs::write(&target, render(&text, vars)?)?,
+                Err(e) => std::fs::write(&target, e.into_bytes())?,
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> TemplateVars {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn test_render_substitutes_and_skips_braces() {
+        let v = vars(&[("name", "demo")]);
+        let out = render("app {{name}} / {{ name }} / int a[]{{1, 2}};", &v).unwrap();
+        assert_eq!(out, "app demo / demo / int a[]{{1, 2}};");
+    }
+
+    #[test]
+    fn test_render_unknown_variable_errors() {
+        assert!(render("{{nope}}", &vars(&[])).is_err());
+    }
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(parse_var("author=Jo Doe").unwrap(), ("author".to_string(), "Jo Doe".to_string()));
+        assert!(parse_var("novalue").is_err());
+        assert!(parse_var("1x=y").is_err());
+    }
+
+    #[test]
+    fn test_render_dir_renames_files() {
+        let root = std::env::temp_dir().join("drakkar_test_template_dir");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("tpl/src")).unwrap();
+        std::fs::write(root.join("tpl/src/{{name}}.cpp"), "// {{name}}\n").unwrap();
+
+        render_dir(&root.join("tpl"), &root.join("out"), &vars(&[("name", "app")])).unwrap();
+        assert_eq!(std::fs::read_to_string(root.join("out/src/app.cpp")).unwrap(), "// app\n");
+
+        let _ = std::fs::remove_dir_all(&root);
+    }
+}
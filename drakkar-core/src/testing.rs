@@ -0,0 +1,154 @@
+//! `drakkar test`: every C/C++ file under `tests_dir` is a test program.
+//!
+//! Each one is linked on its own against the project's objects except the
+//! entry points (`main.*`, `*_main.*`, `bin/`) — or, for a project with
+//! named targets, against its static libraries — and run. A test passes
+//! when it exits with status 0.
+
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::build::{reserved_dir, resolve_targets, static_lib_path, ObjectFile};
+use crate::config::{CompileCommands, ProjectConfig, TargetType};
+use crate::error::BuildError;
+
+/// The config the tests are compiled with: the project's, with
+/// `tests_dir` as the only source root, the project's sources and library
+/// include dirs on the include path, and a temp_dir of their own, so
+/// `tests/check.cpp` and the project's `src/tests/check.cpp` don't share
+/// an object.
+pub fn test_config(config: &ProjectConfig) -> Result<ProjectConfig, BuildError> {
+    let mut cfg = config.clone();
+    cfg.app_name = format!("{} tests", config.app_name);
+    cfg.source_dirs = vec![config.tests_dir.clone()];
+    cfg.temp_dir = reserved_dir(config).join("tests");
+    cfg.include_dirs.extend(config.source_dirs.iter().cloned());
+
+    // Dependents before their dependencies, as the linker wants them
+    let mut libs = resolve_targets(config, None)?;
+    libs.retain(|t| t.target_type == TargetType::StaticLib);
+    libs.reverse();
+    for lib in &libs {
+        cfg.include_dirs.extend(lib.include_dirs.iter().cloned());
+        cfg.link_libs.extend(lib.link_libs.iter().cloned());
+    }
+    let archives = libs.iter().map(|lib| static_lib_path(config, lib.output_name()));
+    cfg.extra_objects.splice(0..0, archives);
+
+    cfg.bin_discovery = false;
+    cfg.targets = Vec::new();
+    cfg.compile_commands = CompileCommands::Off;
+    Ok(cfg)
+}
+
+/// A test's name: its path under `tests_dir` without the extension.
+pub fn test_name(obj: &ObjectFile) -> String {
+    obj.src
+        .rel_path
+        .with_extension("")
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy().into_owned())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Where a test program is linked, under the tests' temp_dir.
+pub fn test_exe_path(test_config: &ProjectConfig, obj: &ObjectFile) -> PathBuf {
+    let mut path = test_config.temp_dir.join("bin").join(obj.src.rel_path.with_extension(""));
+    if test_config.target_os() == "windows" {
+        path.set_extension("exe");
+    }
+    path
+}
+
+/// How one test program ran.
+pub struct TestOutcome {
+    /// See [`test_name`].
+    pub name: String,
+    /// Whether it exited with status 0.
+    pub passed: bool,
+    /// How long it ran.
+    pub duration: Duration,
+    /// Combined stdout and stderr, shown for failed tests.
+    pub output: String,
+}
+
+/// Run one test program from the project root, under `run_wrapper` if
+/// one is set.
+pub fn run_test(name: &str, exe: &Path, config: &ProjectConfig) -> TestOutcome {
+    let mut command = match &config.run_wrapper {
+        Some(wrapper) => {
+            let mut command = std::process::Command::new(&wrapper.program);
+            command.args(&wrapper.args).arg(exe);
+            command
+        }
+        None => std::process::Command::new(exe),
+    };
+    let started = Instant::now();
+    let (passed, output) = match command.output() {
+        Ok(out) => {
+            let mut text = String::from_utf8_lossy(&out.stdout).into_owned();
+            text.push_str(&String::from_utf8_lossy(&out.stderr));
+            if !out.status.success() {
+                text.push_str(&match out.status.code() {
+                    Some(code) => format!("exit status {}\n", code),
+                    None => "terminated by a signal\n".to_string(),
+                });
+            }
+            (out.status.success(), text)
+        }
+        Err(e) => (false, format!("cannot run {}: {}\n", exe.display(), e)),
+    };
+    TestOutcome {
+        name: name.to_string(),
+        passed,
+        duration: started.elapsed(),
+        output,
+    }
+}
+
+/// `test result: ok. 3 passed; 0 failed; finished in 0.42s`
+pub fn summary(outcomes: &[TestOutcome], elapsed: Duration) -> String {
+    let failed = outcomes.iter().filter(|o| !o.passed).count();
+    let result = if failed == 0 {
+        "\x1b[32mok\x1b[0m"
+    } else {
+        "\x1b[31mFAILED\x1b[0m"
+    };
+    format!(
+        "test result: {}. {} passed; {} failed; finished in {:.2}s",
+        result,
+        outcomes.len() - failed,
+        failed,
+        elapsed.as_secs_f64()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+
+    #[test]
+    fn test_names_and_paths_follow_tests_dir() {
+        let obj = ObjectFile {
+            src: SourceFile {
+                path: PathBuf::from("tests/math/vec_test.cpp"),
+                rel_path: PathBuf::from("math/vec_test.cpp"),
+                language: Language::Cpp,
+            },
+            obj_path: PathBuf::from("target/.drakkar/tests/math/vec_test.o"),
+            dep_path: PathBuf::from("target/.drakkar/tests/math/vec_test.d"),
+        };
+        let cfg = test_config(&ProjectConfig::default()).unwrap();
+        assert_eq!(cfg.source_dirs, vec![PathBuf::from("tests/")]);
+        assert!(cfg.include_dirs.contains(&PathBuf::from("src/")));
+        assert_eq!(test_name(&obj), "math/vec_test");
+        if !cfg!(windows) {
+            assert_eq!(
+                test_exe_path(&cfg, &obj),
+                PathBuf::from("target/.drakkar/tests/bin/math/vec_test")
+            );
+        }
+    }
+}
@@ -0,0 +1,387 @@
+//! Minimal TOML reader for `drakkar.toml`.
+//!
+//! Covers what a build config needs: `[table]` and `[dotted.table]`
+//! headers, bare, quoted and dotted keys, basic and literal strings,
+//! integers, booleans and arrays of those (which may span lines). Inline
+//! tables, arrays of tables, floats and dates are rejected with a parse
+//! error rather than misread.
+
+use crate::error::BuildError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Integer(i64),
+    Bool(bool),
+    Array(Vec<Value>),
+}
+
+/// One `key = value` line, with its table and dotted key flattened into
+/// a single path (`[profile.release]` + `strip` → `profile.release.strip`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub line_no: usize,
+    pub path: Vec<String>,
+    pub value: Value,
+}
+
+pub fn parse(content: &str) -> Result<Vec<Entry>, BuildError> {
+    let mut entries: Vec<Entry> = Vec::new();
+    let mut table: Vec<String> = Vec::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((idx, raw)) = lines.next() {
+        let line_no = idx + 1;
+        let err = |msg: &str| BuildError::ParseError(format!("Line {}: {}", line_no, msg));
+        let line = raw.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if line.starts_with("[[") {
+            return Err(err("arrays of tables are not supported"));
+        }
+        if let Some(rest) = line.strip_prefix('[') {
+            let mut p = Parser::new(rest);
+            let path = p.key()?;
+            p.skip_ws();
+            if !p.eat(']') || !p.at_end_or_comment() {
+                return Err(err("expected ']' to close the table header"));
+            }
+            table = path;
+            continue;
+        }
+
+        // Multi-line arrays: keep reading until the brackets balance
+        let mut text = raw.to_string();
+        while !brackets_balanced(&text) {
+            match lines.next() {
+                Some((_, more)) => {
+                    text.push('\n');
+                    text.push_str(more);
+                }
+                None => return Err(err("unterminated array")),
+            }
+        }
+
+        let mut p = Parser::new(&text);
+        let key = p.key().map_err(|e| prefix_line(e, line_no))?;
+        p.skip_ws();
+        if !p.eat('=') {
+            return Err(err("expected 'key = value'"));
+        }
+        let value = p.value().map_err(|e| prefix_line(e, line_no))?;
+        p.skip_ws();
+        if !p.at_end_or_comment() {
+            return Err(err("unexpected text after value"));
+        }
+
+        let mut path = table.clone();
+        path.extend(key);
+        if entries.iter().any(|e| e.path == path) {
+            return Err(err(&format!("duplicate key '{}'", path.join("."))));
+        }
+        entries.push(Entry {
+            line_no,
+            path,
+            value,
+        });
+    }
+    Ok(entries)
+}
+
+fn prefix_line(e: BuildError, line_no: usize) -> BuildError {
+    match e {
+        BuildError::ParseError(msg) => BuildError::ParseError(format!("Line {}: {}", line_no, msg)),
+        other => other,
+    }
+}
+
+/// True once every `[` outside strings and comments has its `]`.
+fn brackets_balanced(text: &str) -> bool {
+    let mut depth = 0i32;
+    let mut quote: Option<char> = None;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some('"'), '\\') => {
+                chars.next();
+            }
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (None, '#') => {
+                // Comment runs to the end of its line
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            (None, '[') => depth += 1,
+            (None, ']') => depth -= 1,
+            _ => {}
+        }
+    }
+    depth <= 0
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(s: &'a str) -> Self {
+        Parser {
+            chars: s.chars().peekable(),
+        }
+    }
+
+    fn err(msg: &str) -> BuildError {
+        BuildError::ParseError(msg.to_string())
+    }
+
+    /// Skip spaces and tabs (not newlines).
+    fn skip_ws(&mut self) {
+        while matches!(self.chars.peek(), Some(' ') | Some('\t')) {
+            self.chars.next();
+        }
+    }
+
+    /// Skip whitespace, newlines and comments inside arrays.
+    fn skip_ws_multiline(&mut self) {
+        loop {
+            match self.chars.peek() {
+                Some(' ') | Some('\t') | Some('\n') | Some('\r') => {
+                    self.chars.next();
+                }
+                Some('#') => {
+                    for c in self.chars.by_ref() {
+                        if c == '\n' {
+                            break;
+                        }
+                    }
+                }
+                _ => return,
+            }
+        }
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.chars.peek() == Some(&c) {
+            self.chars.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn at_end_or_comment(&mut self) -> bool {
+        matches!(self.chars.peek(), None | Some('#'))
+    }
+
+    /// A dotted key: `a`, `a.b`, `"quoted key".c`.
+    fn key(&mut self) -> Result<Vec<String>, BuildError> {
+        let mut parts = Vec::new();
+        loop {
+            self.skip_ws();
+            let part = match self.chars.peek() {
+                Some('"') => {
+                    self.chars.next();
+                    self.basic_string()?
+                }
+                Some('\'') => {
+                    self.chars.next();
+                    self.literal_string()?
+                }
+                _ => {
+                    let mut s = String::new();
+                    while let Some(&c) = self.chars.peek() {
+                        if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                            s.push(c);
+                            self.chars.next();
+                        } else {
+                            break;
+                        }
+                    }
+                    if s.is_empty() {
+                        return Err(Self::err("expected a key"));
+                    }
+                    s
+                }
+            };
+            parts.push(part);
+            self.skip_ws();
+            if !self.eat('.') {
+                return Ok(parts);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, BuildError> {
+        self.skip_ws();
+        match self.chars.peek() {
+            Some('"') => {
+                self.chars.next();
+                Ok(Value::String(self.basic_string()?))
+            }
+            Some('\'') => {
+                self.chars.next();
+                Ok(Value::String(self.literal_string()?))
+            }
+            Some('[') => {
+                self.chars.next();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_ws_multiline();
+                    if self.eat(']') {
+                        return Ok(Value::Array(items));
+                    }
+                    items.push(self.value()?);
+                    self.skip_ws_multiline();
+                    if self.eat(']') {
+                        return Ok(Value::Array(items));
+                    }
+                    if !self.eat(',') {
+                        return Err(Self::err("expected ',' or ']' in array"));
+                    }
+                }
+            }
+            Some('{') => Err(Self::err("inline tables are not supported")),
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = self.chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '+' || c == '.' || c == ':' {
+                        word.push(c);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                match word.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "" => Err(Self::err("expected a value")),
+                    _ => word
+                        .replace('_', "")
+                        .parse::<i64>()
+                        .map(Value::Integer)
+                        .map_err(|_| {
+                            BuildError::ParseError(format!(
+                                "unsupported value '{}' (use a quoted string, integer, boolean or array)",
+                                word
+                            ))
+                        }),
+                }
+            }
+        }
+    }
+
+    /// Body of a `"..."` string; the opening quote is already consumed.
+    fn basic_string(&mut self) -> Result<String, BuildError> {
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(s),
+                Some('\\') => match self.chars.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('n') => s.push('\n'),
+                    Some('t') => s.push('\t'),
+                    Some('r') => s.push('\r'),
+                    Some(c) => {
+                        return Err(BuildError::ParseError(format!(
+                            "unknown escape '\\{}' (use '...' for backslash paths)",
+                            c
+                        )))
+                    }
+                    None => return Err(Self::err("unterminated string")),
+                },
+                Some('\n') | None => return Err(Self::err("unterminated string")),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    /// Body of a `'...'` string; no escapes.
+    fn literal_string(&mut self) -> Result<String, BuildError> {
+        let mut s = String::new();
+        loop {
+            match self.chars.next() {
+                Some('\'') => return Ok(s),
+                Some('\n') | None => return Err(Self::err("unterminated string")),
+                Some(c) => s.push(c),
+            }
+        }
+    }
+}
+
+/// Quote `s` as a TOML basic string.
+pub fn quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(p: &str) -> Vec<String> {
+        p.split('.').map(String::from).collect()
+    }
+
+    #[test]
+    fn test_tables_and_values() {
+        let doc = r#"
+# comment
+app_name = "demo"   # trailing comment
+[build]
+parallel_jobs = 8
+incremental = false
+cxx_flags = ["-Wall", '-DPATH="C:\x"',
+             "-O2", ]  # multi-line
+[profile.release]
+strip = "separate"
+[libs.socket]
+"windows" = "ws2_32"
+"#;
+        let entries = parse(doc).unwrap();
+        let find = |p: &str| entries.iter().find(|e| e.path == path(p)).map(|e| e.value.clone());
+        assert_eq!(find("app_name"), Some(Value::String("demo".into())));
+        assert_eq!(find("build.parallel_jobs"), Some(Value::Integer(8)));
+        assert_eq!(find("build.incremental"), Some(Value::Bool(false)));
+        assert_eq!(
+            find("build.cxx_flags"),
+            Some(Value::Array(vec![
+                Value::String("-Wall".into()),
+                Value::String(r#"-DPATH="C:\x""#.into()),
+                Value::String("-O2".into()),
+            ]))
+        );
+        assert_eq!(find("profile.release.strip"), Some(Value::String("separate".into())));
+        assert_eq!(find("libs.socket.windows"), Some(Value::String("ws2_32".into())));
+        assert_eq!(entries.iter().find(|e| e.path == path("build.cxx_flags")).unwrap().line_no, 7);
+    }
+
+    #[test]
+    fn test_rejects_unsupported() {
+        assert!(parse("a = {b = 1}\n").is_err());
+        assert!(parse("[[bin]]\n").is_err());
+        assert!(parse("a = 1.5\n").is_err());
+        assert!(parse("a = \"C:\\path\"\n").is_err());
+        assert!(parse("a = 1\na = 2\n").is_err());
+        assert!(parse("a = [1, 2\n").is_err());
+        assert_eq!(quote("say \"hi\" \\"), r#""say \"hi\" \\""#);
+    }
+}
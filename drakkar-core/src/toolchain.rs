@@ -0,0 +1,314 @@
+//! Compiler families.
+//!
+//! A toolchain supplies the default compiler commands when `gcc_path` /
+//! `gpp_path` aren't set, and knows how its family spells the flags drakkar
+//! adds on its own: dependency generation, per-profile optimization,
+//! language standard and cross-compilation target. User flag strings are
+//! passed through untouched.
+
+use crate::config::{BuildProfile, Lto, ToolCommand};
+use crate::error::BuildError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Toolchain {
+    /// gcc / g++ (and anything command-line compatible, such as clang).
+    Gcc,
+    /// `zig cc` / `zig c++`: clang with bundled libcs for cross-compiling.
+    Zig,
+    /// Intel oneAPI `icx` / `icpx`.
+    Intel,
+    /// NVIDIA HPC SDK `nvc` / `nvc++`.
+    Nvhpc,
+    /// Arm Compiler for Embedded / Linux `armclang`.
+    Armclang,
+    /// Microsoft `cl` / `link` / `lib`, from a Visual Studio developer
+    /// prompt. Headers come from `/showIncludes` instead of a depfile.
+    Msvc,
+}
+
+impl Toolchain {
+    pub fn parse(s: &str, line_no: usize) -> Result<Self, BuildError> {
+        match s {
+            "gcc" => Ok(Toolchain::Gcc),
+            "zig" => Ok(Toolchain::Zig),
+            "intel" => Ok(Toolchain::Intel),
+            "nvhpc" => Ok(Toolchain::Nvhpc),
+            "armclang" => Ok(Toolchain::Armclang),
+            "msvc" => Ok(Toolchain::Msvc),
+            _ => Err(BuildError::ParseError(format!(
+                "Line {}: unknown toolchain '{}' (expected gcc, zig, intel, nvhpc, armclang or msvc)",
+                line_no, s
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Toolchain::Gcc => "gcc",
+            Toolchain::Zig => "zig",
+            Toolchain::Intel => "intel",
+            Toolchain::Nvhpc => "nvhpc",
+            Toolchain::Armclang => "armclang",
+            Toolchain::Msvc => "msvc",
+        }
+    }
+
+    /// Default C compiler command.
+    pub fn c_compiler(&self) -> ToolCommand {
+        match self {
+            Toolchain::Gcc => ToolCommand::new("gcc"),
+            Toolchain::Zig => ToolCommand {
+                program: "zig".to_string(),
+                args: vec!["cc".to_string()],
+            },
+            Toolchain::Intel => ToolCommand::new("icx"),
+            Toolchain::Nvhpc => ToolCommand::new("nvc"),
+            Toolchain::Armclang => ToolCommand::new("armclang"),
+            Toolchain::Msvc => ToolCommand::new("cl"),
+        }
+    }
+
+    /// Default C++ compiler command, also used to link except with MSVC.
+    pub fn cxx_compiler(&self) -> ToolCommand {
+        match self {
+            Toolchain::Gcc => ToolCommand::new("g++"),
+            Toolchain::Zig => ToolCommand {
+                program: "zig".to_string(),
+                args: vec!["c++".to_string()],
+            },
+            Toolchain::Intel => ToolCommand::new("icpx"),
+            Toolchain::Nvhpc => ToolCommand::new("nvc++"),
+            Toolchain::Armclang => ToolCommand::new("armclang"),
+            Toolchain::Msvc => ToolCommand::new("cl"),
+        }
+    }
+
+    /// Whether `target_triple` can be passed straight to the compiler.
+    /// gcc selects its target by binary (`aarch64-linux-gnu-gcc`) instead.
+    pub fn supports_target_triple(&self) -> bool {
+        matches!(self, Toolchain::Zig | Toolchain::Armclang)
+    }
+
+    /// Flags selecting `triple`, for both compiling and linking.
+    pub fn target_flags(&self, triple: &str) -> Vec<String> {
+        match self {
+            Toolchain::Zig => vec!["-target".to_string(), triple.to_string()],
+            Toolchain::Armclang => vec![format!("--target={}", triple)],
+            Toolchain::Gcc | Toolchain::Intel | Toolchain::Nvhpc | Toolchain::Msvc => vec![],
+        }
+    }
+
+    /// Compile and link flags enabling OpenMP, or None if unsupported.
+    pub fn openmp_flags(&self) -> Option<(Vec<String>, Vec<String>)> {
+        let flag = match self {
+            Toolchain::Gcc | Toolchain::Armclang => "-fopenmp",
+            Toolchain::Intel => "-qopenmp",
+            Toolchain::Nvhpc => "-mp",
+            // zig doesn't ship libomp
+            Toolchain::Zig => return None,
+            // cl adds the runtime library on its own
+            Toolchain::Msvc => return Some((vec!["/openmp".to_string()], vec![])),
+        };
+        Some((vec![flag.to_string()], vec![flag.to_string()]))
+    }
+
+    /// Compile and link flags for POSIX threads.
+    pub fn thread_flags(&self) -> (Vec<String>, Vec<String>) {
+        match self {
+            Toolchain::Nvhpc => (vec![], vec!["-lpthread".to_string()]),
+            // Win32 threads need no flags
+            Toolchain::Msvc => (vec![], vec![]),
+            _ => (vec!["-pthread".to_string()], vec!["-pthread".to_string()]),
+        }
+    }
+
+    /// Write a make-style depfile to `dep_path` listing non-system headers.
+    /// With MSVC the depfile is written from the `/showIncludes` output.
+    pub fn dep_flags(&self, dep_path: &str) -> Vec<String> {
+        if *self == Toolchain::Msvc {
+            return vec!["/showIncludes".to_string()];
+        }
+        let mut flags = vec!["-MMD".to_string()];
+        // nvc/nvc++ don't emit the phony header targets; drakkar doesn't need them
+        if *self != Toolchain::Nvhpc {
+            flags.push("-MP".to_string());
+        }
+        flags.push("-MF".to_string());
+        flags.push(dep_path.to_string());
+        flags
+    }
+
+    /// Debug info and optimization level for `profile`.
+    pub fn profile_flags(&self, profile: &BuildProfile) -> Vec<String> {
+        let flags: &[&str] = match (self, profile) {
+            // /Z7 keeps debug info in each object: /Zi would have parallel
+            // compiles contend for one PDB
+            (Toolchain::Msvc, BuildProfile::Debug) => &["/Z7", "/Od"],
+            (Toolchain::Msvc, BuildProfile::Release) => &["/O2"],
+            (_, BuildProfile::Debug) => &["-g", "-O0"],
+            // icx defaults to -fp-model=fast; keep release results comparable to gcc
+            (Toolchain::Intel, BuildProfile::Release) => &["-O2", "-fp-model=precise"],
+            (_, BuildProfile::Release) => &["-O2"],
+        };
+        flags.iter().map(|f| f.to_string()).collect()
+    }
+
+    /// Spell a `c_standard` / `cxx_standard` value such as `c++17`.
+    pub fn std_flag(&self, std: &str) -> Vec<String> {
+        if *self == Toolchain::Msvc {
+            // No GNU dialects; `gnu++20` is taken as `c++20`
+            return match std.strip_prefix("gnu") {
+                Some(version) => vec![format!("/std:c{}", version)],
+                None => vec![format!("/std:{}", std)],
+            };
+        }
+        if *self == Toolchain::Nvhpc {
+            // nvc/nvc++ spell standards as --c++17 / -c11, GNU dialects via --gnu_extensions
+            if let Some(version) = std.strip_prefix("gnu++") {
+                return vec![format!("--c++{}", version), "--gnu_extensions".to_string()];
+            }
+            if std.starts_with("c++") {
+                return vec![format!("--{}", std)];
+            }
+            if std.starts_with('c') && std[1..].chars().all(|c| c.is_ascii_digit()) {
+                return vec![format!("-{}", std)];
+            }
+        }
+        vec![format!("-std={}", std)]
+    }
+
+    /// Compile and link flags for `lto`, or None if unsupported. The
+    /// parallelism goes on the link step only, where the optimization
+    /// happens, so changing `parallel_jobs` doesn't recompile everything.
+    pub fn lto_flags(&self, lto: Lto, jobs: usize) -> Option<(Vec<String>, Vec<String>)> {
+        let (compile, link): (&[&str], Vec<String>) = match (self, lto) {
+            (_, Lto::Off) => return Some((vec![], vec![])),
+            (Toolchain::Nvhpc, _) => return None,
+            (Toolchain::Gcc, Lto::Thin) => (&["-flto"], vec![format!("-flto={}", jobs)]),
+            (Toolchain::Gcc, Lto::Full) => (
+                &["-flto"],
+                vec![format!("-flto={}", jobs), "-flto-partition=one".to_string()],
+            ),
+            (Toolchain::Msvc, Lto::Thin) => (&["/GL"], vec!["/LTCG:INCREMENTAL".to_string()]),
+            (Toolchain::Msvc, Lto::Full) => (&["/GL"], vec!["/LTCG".to_string()]),
+            // clang-based: zig, icx, armclang
+            (_, Lto::Thin) => (
+                &["-flto=thin"],
+                vec!["-flto=thin".to_string(), format!("-flto-jobs={}", jobs)],
+            ),
+            (_, Lto::Full) => (&["-flto=full"], vec!["-flto=full".to_string()]),
+        };
+        Some((compile.iter().map(|f| f.to_string()).collect(), link))
+    }
+
+    /// Turn off every warning, for third-party sources.
+    pub fn no_warnings_flag(&self) -> String {
+        match self {
+            Toolchain::Msvc => "/w".to_string(),
+            _ => "-w".to_string(),
+        }
+    }
+
+    /// Put `dir` on the include path with warnings in its headers silenced.
+    pub fn system_include_flags(&self, dir: &str) -> Vec<String> {
+        match self {
+            Toolchain::Msvc => vec![format!("/external:I{}", dir), "/external:W0".to_string()],
+            _ => vec!["-isystem".to_string(), dir.to_string()],
+        }
+    }
+
+    /// Flags writing the object to `obj_path`.
+    pub fn output_flags(&self, obj_path: &str) -> Vec<String> {
+        match self {
+            Toolchain::Msvc => vec![format!("/Fo{}", obj_path)],
+            _ => vec!["-o".to_string(), obj_path.to_string()],
+        }
+    }
+
+    /// Flags forcing the source's language (`c` or `c++`) whatever its
+    /// extension.
+    pub fn language_flags(&self, lang: &str) -> Vec<String> {
+        match self {
+            Toolchain::Msvc => vec![if lang == "c" { "/TC" } else { "/TP" }.to_string()],
+            _ => vec!["-x".to_string(), lang.to_string()],
+        }
+    }
+
+    /// Extension of object files, as the family's linker expects it.
+    pub fn object_extension(&self) -> &'static str {
+        match self {
+            Toolchain::Msvc => "obj",
+            _ => "o",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zig_commands_and_target() {
+        let zig = Toolchain::parse("zig", 1).unwrap();
+        assert_eq!(zig.c_compiler().to_string(), "zig cc");
+        assert_eq!(zig.cxx_compiler().to_string(), "zig c++");
+        assert_eq!(
+            zig.target_flags("x86_64-windows-gnu"),
+            vec!["-target", "x86_64-windows-gnu"]
+        );
+        assert!(Toolchain::parse("borland", 3).is_err());
+    }
+
+    #[test]
+    fn test_vendor_spellings() {
+        let nv = Toolchain::parse("nvhpc", 1).unwrap();
+        assert_eq!(nv.cxx_compiler().to_string(), "nvc++");
+        assert_eq!(nv.std_flag("c++17"), vec!["--c++17"]);
+        assert_eq!(nv.std_flag("gnu++20"), vec!["--c++20", "--gnu_extensions"]);
+        assert_eq!(nv.std_flag("c11"), vec!["-c11"]);
+        assert_eq!(nv.dep_flags("a.d"), vec!["-MMD", "-MF", "a.d"]);
+
+        assert_eq!(Toolchain::Gcc.std_flag("c++17"), vec!["-std=c++17"]);
+        assert_eq!(Toolchain::Gcc.dep_flags("a.d"), vec!["-MMD", "-MP", "-MF", "a.d"]);
+        assert!(Toolchain::Intel
+            .profile_flags(&BuildProfile::Release)
+            .contains(&"-fp-model=precise".to_string()));
+        assert_eq!(nv.openmp_flags().unwrap().1, vec!["-mp"]);
+        assert!(Toolchain::Zig.openmp_flags().is_none());
+        assert_eq!(
+            Toolchain::Armclang.target_flags("aarch64-arm-none-eabi"),
+            vec!["--target=aarch64-arm-none-eabi"]
+        );
+    }
+
+    #[test]
+    fn test_msvc_spellings() {
+        let msvc = Toolchain::parse("msvc", 1).unwrap();
+        assert_eq!(msvc.cxx_compiler().to_string(), "cl");
+        assert_eq!(msvc.std_flag("gnu++20"), vec!["/std:c++20"]);
+        assert_eq!(msvc.dep_flags("a.d"), vec!["/showIncludes"]);
+        assert_eq!(msvc.output_flags("target/a.obj"), vec!["/Fotarget/a.obj"]);
+        assert_eq!(msvc.language_flags("c++"), vec!["/TP"]);
+        assert_eq!(msvc.profile_flags(&BuildProfile::Debug), vec!["/Z7", "/Od"]);
+        assert_eq!(msvc.openmp_flags().unwrap(), (vec!["/openmp".to_string()], vec![]));
+        assert_eq!(msvc.system_include_flags("vendor"), vec!["/external:Ivendor", "/external:W0"]);
+        assert_eq!(Toolchain::Gcc.output_flags("a.o"), vec!["-o", "a.o"]);
+        assert_eq!(Toolchain::Gcc.system_include_flags("vendor"), vec!["-isystem", "vendor"]);
+    }
+
+    #[test]
+    fn test_lto_flags() {
+        let (compile, link) = Toolchain::Gcc.lto_flags(Lto::Full, 8).unwrap();
+        assert_eq!(compile, vec!["-flto"]);
+        assert_eq!(link, vec!["-flto=8", "-flto-partition=one"]);
+        let (compile, link) = Toolchain::Zig.lto_flags(Lto::Thin, 4).unwrap();
+        assert_eq!(compile, vec!["-flto=thin"]);
+        assert_eq!(link, vec!["-flto=thin", "-flto-jobs=4"]);
+        assert_eq!(
+            Toolchain::Msvc.lto_flags(Lto::Thin, 4).unwrap().1,
+            vec!["/LTCG:INCREMENTAL"]
+        );
+        assert!(Toolchain::Nvhpc.lto_flags(Lto::Full, 4).is_none());
+        assert_eq!(Toolchain::Nvhpc.lto_flags(Lto::Off, 4), Some((vec![], vec![])));
+    }
+}
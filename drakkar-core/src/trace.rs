@@ -0,0 +1,117 @@
+//! Build profile in Chrome's trace event format (`--trace-out`).
+//!
+//! Every compile and link is recorded as a complete (`"ph":"X"`) event on
+//! the thread that ran it, so about://tracing or Perfetto shows one row per
+//! worker and makes the files that hold a build up easy to spot.
+
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::error::BuildError;
+use crate::json::Json;
+
+/// Row of link and archive jobs; worker `n` is row `n + 1`.
+pub const MAIN_THREAD: usize = 0;
+
+struct Trace {
+    started: Instant,
+    events: Vec<Json>,
+    /// Highest row used, to name each one.
+    rows: usize,
+}
+
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+/// Start recording, discarding anything recorded before.
+pub fn start() {
+    if let Ok(mut trace) = TRACE.lock() {
+        *trace = Some(Trace {
+            started: Instant::now(),
+            events: Vec::new(),
+            rows: 0,
+        });
+    }
+}
+
+/// Record `name` running on `row` from `started` until now. Does nothing
+/// unless recording.
+pub fn record(name: &str, category: &str, row: usize, started: Instant) {
+    let ended = Instant::now();
+    if let Ok(mut guard) = TRACE.lock() {
+        if let Some(trace) = guard.as_mut() {
+            let micros = |t: Instant| t.saturating_duration_since(trace.started).as_micros() as i64;
+            let event = Json::object()
+                .field("name", name)
+                .field("cat", category)
+                .field("ph", "X")
+                .field("ts", micros(started))
+                .field("dur", micros(ended) - micros(started))
+                .field("pid", 1)
+                .field("tid", row);
+            trace.events.push(event);
+            trace.rows = trace.rows.max(row);
+        }
+    }
+}
+
+/// The recording so far as a trace document.
+fn document(trace: &Trace) -> Json {
+    let names = (0..=trace.rows).map(|row| {
+        let name = match row {
+            MAIN_THREAD => "main".to_string(),
+            n => format!("worker {}", n),
+        };
+        Json::object()
+            .field("name", "thread_name")
+            .field("ph", "M")
+            .field("pid", 1)
+            .field("tid", row)
+            .field("args", Json::object().field("name", name))
+    });
+    Json::object()
+        .field("traceEvents", Json::Array(names.chain(trace.events.iter().cloned()).collect()))
+        .field("displayTimeUnit", "ms")
+}
+
+/// Write what was recorded since `start` to `path`.
+pub fn write(path: &Path) -> Result<(), BuildError> {
+    let text = match TRACE.lock().ok().as_ref().and_then(|t| t.as_ref()) {
+        Some(trace) => document(trace).to_string(),
+        None => return Ok(()),
+    };
+    std::fs::write(path, text + "\n")
+        .map_err(|e| BuildError::IoError(format!("Cannot write trace {:?}: {}", path, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace_document() {
+        let started = Instant::now();
+        let event = |name: &str, row: usize, from_ms: u64, to_ms: u64| {
+            Json::object()
+                .field("name", name)
+                .field("cat", "compile")
+                .field("ph", "X")
+                .field("ts", (from_ms * 1000) as i64)
+                .field("dur", ((to_ms - from_ms) * 1000) as i64)
+                .field("pid", 1)
+                .field("tid", row)
+        };
+        let trace = Trace {
+            started,
+            events: vec![event("a.c", 1, 0, 40), event("b.c", 2, 5, 15)],
+            rows: 2,
+        };
+        let text = document(&trace).to_string();
+        assert!(text.starts_with(
+            r#"{"traceEvents":[{"name":"thread_name","ph":"M","pid":1,"tid":0,"args":{"name":"main"}},"#
+        ));
+        assert!(text.contains(r#"{"name":"thread_name","ph":"M","pid":1,"tid":2,"args":{"name":"worker 2"}}"#));
+        assert!(text.contains(r#"{"name":"b.c","cat":"compile","ph":"X","ts":5000,"dur":10000,"pid":1,"tid":2}"#));
+        assert!(text.ends_with(r#"],"displayTimeUnit":"ms"}"#));
+    }
+}
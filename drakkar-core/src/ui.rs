@@ -0,0 +1,596 @@
+//! Shared progress output.
+//!
+//! Build progress lines go through [`line()`] so cross-cutting presentation
+//! options (such as `--show-timestamps`) apply uniformly, whether the line
+//! is printed from the main thread or from a worker. With the JSON event
+//! stream on they go to stderr, leaving stdout to the events.
+//!
+//! On a terminal a [`StatusBoard`] is redrawn in place while compiling:
+//! a one-line progress bar, or with `status_board = "true"` one line per
+//! worker. Other output is printed above it.
+//!
+//! Paths are shown relative to the project root (see [`path`]), or
+//! absolute with `--absolute-paths`, so output reads the same wherever
+//! drakkar was started from.
+
+use std::io::Write;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::error::BuildError;
+use crate::events::{self, EventFormat};
+use crate::messages::{tr, Msg};
+use crate::platform;
+
+static SHOW_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+static TERMINAL_TITLE: AtomicBool = AtomicBool::new(false);
+static START: Mutex<Option<Instant>> = Mutex::new(None);
+static ABSOLUTE_PATHS: AtomicBool = AtomicBool::new(false);
+static ROOT: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Reset the output clock and configure line prefixes.
+pub fn init(show_timestamps: bool) {
+    SHOW_TIMESTAMPS.store(show_timestamps, Ordering::Relaxed);
+    if let Ok(mut start) = START.lock() {
+        *start = Some(Instant::now());
+    }
+}
+
+/// Whether progress lines carry a timestamp (`--show-timestamps`).
+pub fn show_timestamps() -> bool {
+    SHOW_TIMESTAMPS.load(Ordering::Relaxed)
+}
+
+/// Show paths relative to `root` (the project directory), or absolute.
+pub fn set_paths(root: &Path, absolute: bool) {
+    ABSOLUTE_PATHS.store(absolute, Ordering::Relaxed);
+    if let Ok(mut r) = ROOT.lock() {
+        *r = Some(root.to_path_buf());
+    }
+}
+
+fn root() -> Option<PathBuf> {
+    ROOT.lock().ok().and_then(|r| r.clone())
+}
+
+/// `p` (relative to the current directory) as it should be shown: relative
+/// to the project root, or absolute with `--absolute-paths`. Paths outside
+/// the project stay absolute.
+pub fn path(p: &Path) -> String {
+    match (root(), std::env::current_dir()) {
+        (Some(root), Ok(cwd)) => {
+            display_path(p, &cwd, &root, ABSOLUTE_PATHS.load(Ordering::Relaxed))
+        }
+        _ => p.display().to_string(),
+    }
+}
+
+fn display_path(p: &Path, cwd: &Path, root: &Path, absolute: bool) -> String {
+    let full = cwd.join(p);
+    let mut normal = PathBuf::new();
+    for component in full.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if normal.parent().is_some() => {
+                normal.pop();
+            }
+            other => normal.push(other),
+        }
+    }
+    if absolute {
+        return normal.display().to_string();
+    }
+    match normal.strip_prefix(root) {
+        Ok(rel) if rel.as_os_str().is_empty() => ".".to_string(),
+        Ok(rel) => rel.display().to_string(),
+        Err(_) => normal.display().to_string(),
+    }
+}
+
+/// `text` with absolute paths into the project shortened to project-relative
+/// ones, for compiler output and command lines. Left alone with
+/// `--absolute-paths`.
+pub fn relative_text(text: &str) -> String {
+    match root() {
+        Some(root) if !ABSOLUTE_PATHS.load(Ordering::Relaxed) => strip_root(text, &root),
+        _ => text.to_string(),
+    }
+}
+
+fn strip_root(text: &str, root: &Path) -> String {
+    let prefix = root.display().to_string();
+    let prefix = prefix.trim_end_matches(std::path::MAIN_SEPARATOR);
+    if prefix.is_empty() {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find(prefix) {
+        out.push_str(&rest[..idx]);
+        let after = &rest[idx + prefix.len()..];
+        let mut chars = after.chars();
+        match chars.next() {
+            // "/root/proj/src/a.c" → "src/a.c", but not "/root/project2/…"
+            Some(c) if std::path::is_separator(c) => rest = chars.as_str(),
+            None | Some(' ' | '"' | '\'' | ':' | '\n') => {
+                out.push('.');
+                rest = after;
+            }
+            Some(_) => {
+                out.push_str(prefix);
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Time since `init` (zero if it was never called).
+pub fn elapsed() -> Duration {
+    START
+        .lock()
+        .ok()
+        .and_then(|s| s.map(|t| t.elapsed()))
+        .unwrap_or_default()
+}
+
+/// Print one progress line to stdout.
+pub fn line(msg: &str) {
+    let mut board = BOARD.lock().ok();
+    if let Some(Some(b)) = board.as_deref_mut() {
+        b.erase();
+    }
+    let msg = relative_text(msg);
+    let msg = if show_timestamps() {
+        format!("\x1b[2m[{:>8.3}s]\x1b[0m {}", elapsed().as_secs_f64(), msg)
+    } else {
+        msg
+    };
+    if events::format() == EventFormat::Json {
+        eprintln!("{}", msg);
+    } else {
+        println!("{}", msg);
+    }
+}
+
+/// Print a non-fatal error to stderr and carry on.
+pub fn report_warning(err: &BuildError) {
+    let text = relative_text(&err.to_string());
+    eprintln!("\x1b[33m{}[{}]:\x1b[0m {}", tr(Msg::Warning), err.code(), text);
+}
+
+/// Print compiler diagnostics to stderr, above the status board if one
+/// is showing. In the VS Code format `events` prints them normalized
+/// instead.
+pub fn diagnostics(text: &str) {
+    if events::format() == EventFormat::Vscode {
+        return;
+    }
+    let mut board = BOARD.lock().ok();
+    if let Some(Some(b)) = board.as_deref_mut() {
+        b.erase();
+    }
+    eprint!("{}", relative_text(text));
+}
+
+// ─────────────────────────────────────────────
+// Status board
+// ─────────────────────────────────────────────
+
+const BOARD_REFRESH: Duration = Duration::from_millis(100);
+const BOARD_PATH_WIDTH: usize = 60;
+const BAR_WIDTH: usize = 24;
+const BAR_PATH_WIDTH: usize = 30;
+
+static BOARD: Mutex<Option<Board>> = Mutex::new(None);
+
+/// What a [`StatusBoard`] shows.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BoardStyle {
+    /// One line: a bar with the percentage, time left, failures and the
+    /// files being compiled.
+    Bar,
+    /// A header line, then one line per worker.
+    Workers,
+}
+
+struct Board {
+    style: BoardStyle,
+    started: Instant,
+    /// What each worker is compiling and since when; None while idle.
+    slots: Vec<Option<(String, Instant)>>,
+    done: usize,
+    failed: usize,
+    total: usize,
+    eta: Option<Duration>,
+    /// Lines currently on screen, to move back over on the next draw.
+    drawn: usize,
+}
+
+impl Board {
+    fn erase(&mut self) {
+        if self.drawn > 0 {
+            print!("\x1b[{}F\x1b[J", self.drawn);
+            let _ = std::io::stdout().flush();
+            self.drawn = 0;
+        }
+    }
+
+    fn draw(&mut self) {
+        let text = match self.style {
+            BoardStyle::Bar => render_bar(self),
+            BoardStyle::Workers => render(self, Instant::now()),
+        };
+        self.erase();
+        // Autowrap off, so a long line can't push the board out of step
+        print!("\x1b[?7l{}\x1b[?7h", text);
+        let _ = std::io::stdout().flush();
+        self.drawn = text.lines().count();
+    }
+}
+
+/// `Compiling [=========>     ] 42% 21/50 · ~12s left · 1 failed · a.cpp, b.cpp`
+fn render_bar(board: &Board) -> String {
+    let filled = (BAR_WIDTH * board.done).checked_div(board.total).unwrap_or(BAR_WIDTH);
+    let bar = match filled {
+        0 => " ".repeat(BAR_WIDTH),
+        n if n < BAR_WIDTH => format!("{}>{}", "=".repeat(n - 1), " ".repeat(BAR_WIDTH - n)),
+        _ => "=".repeat(BAR_WIDTH),
+    };
+    let mut text = format!(
+        "\x1b[1m{}\x1b[0m [{}] {:>3}% {}/{}",
+        tr(Msg::Compiling),
+        bar,
+        (board.done * 100).checked_div(board.total).unwrap_or(100),
+        board.done,
+        board.total
+    );
+    if let Some(eta) = board.eta {
+        text.push_str(&format!(" \x1b[2m· {}\x1b[0m", eta_text(eta)));
+    }
+    if board.failed > 0 {
+        text.push_str(&format!(" · \x1b[31m{} {}\x1b[0m", board.failed, tr(Msg::Failed)));
+    }
+    // Longest-running first: those are the ones holding the build up
+    let mut active: Vec<&(String, Instant)> = board.slots.iter().flatten().collect();
+    active.sort_by_key(|(_, since)| *since);
+    if !active.is_empty() {
+        let files: Vec<String> = active.iter().map(|(f, _)| shorten(f, BAR_PATH_WIDTH)).collect();
+        text.push_str(&format!(" · \x1b[36m{}\x1b[0m", files.join(", ")));
+    }
+    text.push('\n');
+    text
+}
+
+fn render(board: &Board, now: Instant) -> String {
+    let mut text = format!(
+        "\x1b[1m{}\x1b[0m [{}/{}] \x1b[2m{:.1}s",
+        tr(Msg::Compiling),
+        board.done,
+        board.total,
+        now.duration_since(board.started).as_secs_f64()
+    );
+    if let Some(eta) = board.eta {
+        text.push_str(&format!(" · {}", eta_text(eta)));
+    }
+    text.push_str("\x1b[0m\n");
+    for (i, slot) in board.slots.iter().enumerate() {
+        match slot {
+            Some((file, since)) => text.push_str(&format!(
+                "  \x1b[36m#{:<2}\x1b[0m {:<width$} \x1b[2m{:>5.1}s\x1b[0m\n",
+                i + 1,
+                shorten(file, BOARD_PATH_WIDTH),
+                now.duration_since(*since).as_secs_f64(),
+                width = BOARD_PATH_WIDTH
+            )),
+            None => text.push_str(&format!("  \x1b[2m#{:<2} —\x1b[0m\n", i + 1)),
+        }
+    }
+    text
+}
+
+/// Keep the end of a long path, which is the part that tells files apart.
+fn shorten(path: &str, width: usize) -> String {
+    let len = path.chars().count();
+    if len <= width {
+        return path.to_string();
+    }
+    let tail: String = path.chars().skip(len - (width - 1)).collect();
+    format!("…{}", tail)
+}
+
+/// Live compile progress, redrawn every 100 ms. Removed from the screen
+/// when dropped.
+pub struct StatusBoard {
+    stop: Arc<AtomicBool>,
+    refresher: Option<JoinHandle<()>>,
+}
+
+impl StatusBoard {
+    /// Start a board for `workers` workers, or None when stdout isn't a
+    /// terminal or is read by a tool (the plain "Compiling" lines are
+    /// printed instead).
+    pub fn start(style: BoardStyle, workers: usize, total: usize) -> Option<StatusBoard> {
+        if !platform::stdout_is_terminal() || events::format() != EventFormat::Off {
+            return None;
+        }
+        if let Ok(mut board) = BOARD.lock() {
+            *board = Some(Board {
+                style,
+                started: Instant::now(),
+                slots: vec![None; workers],
+                done: 0,
+                failed: 0,
+                total,
+                eta: None,
+                drawn: 0,
+            });
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&stop);
+        let refresher = thread::spawn(move || {
+            while !flag.load(Ordering::Relaxed) {
+                if let Ok(mut board) = BOARD.lock() {
+                    if let Some(b) = board.as_mut() {
+                        b.draw();
+                    }
+                }
+                thread::sleep(BOARD_REFRESH);
+            }
+        });
+        Some(StatusBoard {
+            stop,
+            refresher: Some(refresher),
+        })
+    }
+}
+
+impl Drop for StatusBoard {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.refresher.take() {
+            let _ = handle.join();
+        }
+        if let Ok(mut board) = BOARD.lock() {
+            if let Some(b) = board.as_mut() {
+                b.erase();
+            }
+            *board = None;
+        }
+    }
+}
+
+/// Whether a status board is showing; workers then skip their
+/// "Compiling" lines.
+pub fn board_active() -> bool {
+    BOARD.lock().map(|b| b.is_some()).unwrap_or(false)
+}
+
+/// Show worker `slot` as compiling `file`, or as idle with None.
+pub fn board_slot(slot: usize, file: Option<&str>) {
+    if let Ok(mut board) = BOARD.lock() {
+        if let Some(entry) = board.as_mut().and_then(|b| b.slots.get_mut(slot)) {
+            *entry = file.map(|f| (f.to_string(), Instant::now()));
+        }
+    }
+}
+
+/// Update the board's count of finished files (`failed` of them with
+/// errors) and the time left.
+pub fn board_done(done: usize, failed: usize, eta: Option<Duration>) {
+    if let Ok(mut board) = BOARD.lock() {
+        if let Some(b) = board.as_mut() {
+            b.done = done;
+            b.failed = failed;
+            b.eta = eta;
+        }
+    }
+}
+
+/// `~1m05s left`
+pub fn eta_text(eta: Duration) -> String {
+    let secs = eta.as_secs();
+    if secs >= 60 {
+        format!("~{}m{:02}s {}", secs / 60, secs % 60, tr(Msg::Left))
+    } else {
+        format!("~{}s {}", secs.max(1), tr(Msg::Left))
+    }
+}
+
+/// Mirror build progress into the terminal title (`terminal_title` key).
+pub fn enable_terminal_title(enabled: bool) {
+    TERMINAL_TITLE.store(enabled, Ordering::Relaxed);
+}
+
+/// Show "drakkar: 42% building myapp" in the title and as taskbar progress.
+/// Written to stderr so piped stdout stays clean.
+pub fn title_progress(app: &str, done: usize, total: usize) {
+    if TERMINAL_TITLE.load(Ordering::Relaxed) {
+        eprint!("{}", progress_sequence(app, done, total));
+    }
+}
+
+/// Final title once the build ends; clears the taskbar progress.
+pub fn title_finished(app: &str, ok: bool) {
+    if TERMINAL_TITLE.load(Ordering::Relaxed) {
+        let status = if ok { "finished" } else { "failed" };
+        eprint!("\x1b]0;drakkar: {} {}\x07\x1b]9;4;0;0\x07", status, app);
+    }
+}
+
+/// OSC 0 sets the title; OSC 9;4 is the progress extension understood by
+/// Windows Terminal and ConEmu (other terminals ignore it).
+fn progress_sequence(app: &str, done: usize, total: usize) -> String {
+    let pct = (done * 100).checked_div(total).unwrap_or(100);
+    format!(
+        "\x1b]0;drakkar: {}% building {}\x07\x1b]9;4;1;{}\x07",
+        pct, app, pct
+    )
+}
+
+/// `1.5 MiB`
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// Wall-clock time spent in each phase of a build.
+#[derive(Debug, Default, Clone)]
+pub struct PhaseTimings {
+    /// Collecting sources and preparing the build dirs.
+    pub collect: Duration,
+    /// Deciding what is stale.
+    pub dep_check: Duration,
+    /// Compiling.
+    pub compile: Duration,
+    /// Linking.
+    pub link: Duration,
+}
+
+impl PhaseTimings {
+    /// Run `f` and add its wall time to the phase selected by `slot`.
+    pub fn measure<T>(slot: &mut Duration, f: impl FnOnce() -> T) -> T {
+        let start = Instant::now();
+        let result = f();
+        *slot += start.elapsed();
+        result
+    }
+
+    /// `collect 0.01s · dep-check 0.00s · compile 1.20s · link 0.10s`
+    pub fn summary(&self) -> String {
+        format!(
+            "collect {:.2}s · dep-check {:.2}s · compile {:.2}s · link {:.2}s",
+            self.collect.as_secs_f64(),
+            self.dep_check.as_secs_f64(),
+            self.compile.as_secs_f64(),
+            self.link.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_phase_summary_format() {
+        let t = PhaseTimings {
+            collect: Duration::from_millis(10),
+            dep_check: Duration::from_millis(0),
+            compile: Duration::from_millis(1200),
+            link: Duration::from_millis(100),
+        };
+        assert_eq!(
+            t.summary(),
+            "collect 0.01s · dep-check 0.00s · compile 1.20s · link 0.10s"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_display_path() {
+        let root = Path::new("/work/app");
+        assert_eq!(display_path(Path::new("src/main.c"), root, root, false), "src/main.c");
+        assert_eq!(display_path(Path::new("./out/app"), root, root, false), "out/app");
+        let header = Path::new("/work/app/include/a.h");
+        assert_eq!(display_path(header, root, root, false), "include/a.h");
+        assert_eq!(display_path(Path::new("/work/app"), root, root, false), ".");
+        assert_eq!(display_path(Path::new("../shared/a.h"), root, root, false), "/work/shared/a.h");
+        assert_eq!(display_path(Path::new("/usr/include"), root, root, false), "/usr/include");
+        assert_eq!(display_path(Path::new("./out/app"), root, root, true), "/work/app/out/app");
+        let member = Path::new("/work/app/libs/math");
+        assert_eq!(display_path(Path::new("src/a.c"), member, root, false), "libs/math/src/a.c");
+
+        assert_eq!(
+            strip_root("g++ -I/work/app/include -c /work/app/src/a.c -I/work/app2 --chdir /work/app", root),
+            "g++ -Iinclude -c src/a.c -I/work/app2 --chdir ."
+        );
+        assert_eq!(
+            strip_root("/work/app/src/a.c:3:1: error: x\n", root),
+            "src/a.c:3:1: error: x\n"
+        );
+    }
+
+    #[test]
+    fn test_progress_sequence() {
+        assert_eq!(
+            progress_sequence("myapp", 21, 50),
+            "\x1b]0;drakkar: 42% building myapp\x07\x1b]9;4;1;42\x07"
+        );
+    }
+
+    #[test]
+    fn test_status_board_render() {
+        let started = Instant::now();
+        let board = Board {
+            style: BoardStyle::Workers,
+            started,
+            slots: vec![Some(("src/net/socket.cpp".to_string(), started)), None],
+            done: 3,
+            failed: 0,
+            total: 10,
+            eta: Some(Duration::from_secs(65)),
+            drawn: 0,
+        };
+        let text = render(&board, started + Duration::from_millis(1500));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].contains("[3/10]"));
+        assert!(lines[0].contains("1.5s · ~1m05s left"));
+        assert!(lines[1].contains("src/net/socket.cpp"));
+        assert!(lines[1].ends_with("  1.5s\x1b[0m"));
+        assert!(lines[2].contains("#2  —"));
+        assert_eq!(shorten("abcdefgh", 5), "…efgh");
+    }
+
+    #[test]
+    fn test_progress_bar_render() {
+        let started = Instant::now();
+        let mut board = Board {
+            style: BoardStyle::Bar,
+            started,
+            slots: vec![
+                Some(("src/b.cpp".to_string(), started + Duration::from_secs(1))),
+                None,
+                Some(("src/a.cpp".to_string(), started)),
+            ],
+            done: 5,
+            failed: 1,
+            total: 10,
+            eta: Some(Duration::from_secs(12)),
+            drawn: 0,
+        };
+        let text = render_bar(&board);
+        assert_eq!(text.lines().count(), 1);
+        assert!(text.contains("[===========>            ]  50% 5/10"));
+        assert!(text.contains("~12s left"));
+        assert!(text.contains("1 failed"));
+        assert!(text.contains("src/a.cpp, src/b.cpp"));
+
+        board.done = 10;
+        board.slots = vec![None; 3];
+        assert!(render_bar(&board).contains(&format!("[{}] 100% 10/10", "=".repeat(BAR_WIDTH))));
+    }
+
+    #[test]
+    fn test_measure_accumulates() {
+        let mut slot = Duration::from_millis(5);
+        let v = PhaseTimings::measure(&mut slot, || 42);
+        assert_eq!(v, 42);
+        assert!(slot >= Duration::from_millis(5));
+    }
+}
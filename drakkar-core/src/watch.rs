@@ -0,0 +1,183 @@
+//! `drakkar watch`: rebuild whenever a source, header or config file
+//! changes.
+//!
+//! Changes are found by polling modification times, which needs nothing
+//! beyond std and behaves the same on every platform and on network
+//! mounts. A burst of writes — an editor saving several files, a branch
+//! switch — is collapsed into one rebuild by waiting until the tree has
+//! been quiet for `DEBOUNCE`.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use crate::build::language_for;
+use crate::config::ProjectConfig;
+use crate::glob;
+use crate::headers::is_header;
+use crate::platform::is_interrupted;
+
+/// How often the tree is scanned while idle.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the tree must stay unchanged before a rebuild starts.
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// Modification time of every watched file.
+pub type Snapshot = BTreeMap<PathBuf, SystemTime>;
+
+/// Scan the source, vendor and include roots of the project and of its
+/// named targets, plus `config_files` (missing ones are simply absent, so
+/// creating one counts as a change).
+pub fn snapshot(config: &ProjectConfig, config_files: &[PathBuf]) -> Snapshot {
+    let mut snap = Snapshot::new();
+    let target_roots = config
+        .targets
+        .iter()
+        .flat_map(|t| t.source_dirs.iter().chain(&t.vendor_dirs).chain(&t.include_dirs));
+    let roots = config
+        .source_dirs
+        .iter()
+        .chain(&config.vendor_dirs)
+        .chain(&config.include_dirs)
+        .chain(target_roots);
+    for root in roots {
+        walk(config, root, &mut snap);
+    }
+    for file in config_files {
+        if let Ok(modified) = std::fs::metadata(file).and_then(|m| m.modified()) {
+            snap.insert(file.clone(), modified);
+        }
+    }
+    snap
+}
+
+fn walk(config: &ProjectConfig, dir: &Path, snap: &mut Snapshot) {
+    // A directory vanishing mid-scan is just another change
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if glob::matches_any(&config.ignore_patterns, &name) {
+            continue;
+        }
+        if path.is_dir() {
+            if name.starts_with('.') || name == "target" || name == "out" {
+                continue;
+            }
+            walk(config, &path, snap);
+        } else if is_watched(&path, config) {
+            if let Ok(modified) = entry.metadata().and_then(|m| m.modified()) {
+                snap.insert(path, modified);
+            }
+        }
+    }
+}
+
+/// Sources, headers and `.inl` files; build outputs written next to them
+/// (objects, `compile_commands.json`) must not trigger another build.
+fn is_watched(path: &Path, config: &ProjectConfig) -> bool {
+    language_for(path, config).is_some()
+        || is_header(path)
+        || matches!(path.extension().and_then(|e| e.to_str()), Some(e) if e.eq_ignore_ascii_case("inl"))
+}
+
+/// Files added, removed or modified between two snapshots.
+pub fn changes(old: &Snapshot, new: &Snapshot) -> Vec<PathBuf> {
+    let mut changed: Vec<PathBuf> = new
+        .iter()
+        .filter(|(path, modified)| old.get(*path) != Some(modified))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(old.keys().filter(|path| !new.contains_key(*path)).cloned());
+    changed.sort();
+    changed
+}
+
+/// Block until something differs from `baseline` and the tree has settled.
+/// Returns the changed files, or None once Ctrl+C was pressed.
+pub fn wait_for_change(
+    config: &ProjectConfig,
+    config_files: &[PathBuf],
+    baseline: &Snapshot,
+) -> Option<Vec<PathBuf>> {
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if is_interrupted() {
+            return None;
+        }
+        let mut latest = snapshot(config, config_files);
+        if changes(baseline, &latest).is_empty() {
+            continue;
+        }
+        loop {
+            std::thread::sleep(DEBOUNCE);
+            if is_interrupted() {
+                return None;
+            }
+            let again = snapshot(config, config_files);
+            if again == latest {
+                break;
+            }
+            latest = again;
+        }
+        let changed = changes(baseline, &latest);
+        // Saved and then reverted within the debounce window
+        if !changed.is_empty() {
+            return Some(changed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snapshot_tracks_sources_headers_and_config() {
+        let dir = std::env::temp_dir().join("drakkar_watch_unit");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("src/target")).unwrap();
+        for file in ["src/main.cpp", "src/util.h", "src/impl.inl", "src/notes.txt", "src/main.o"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        std::fs::write(dir.join("src/target/gen.cpp"), "").unwrap();
+        std::fs::create_dir_all(dir.join("imgui")).unwrap();
+        std::fs::write(dir.join("imgui/imgui.cpp"), "").unwrap();
+        std::fs::write(dir.join("config.txt"), "").unwrap();
+
+        let config = ProjectConfig {
+            source_dirs: vec![dir.join("src")],
+            vendor_dirs: vec![dir.join("imgui")],
+            ..ProjectConfig::default()
+        };
+        let config_files = [dir.join("config.txt"), dir.join("config.local.txt")];
+        let before = snapshot(&config, &config_files);
+        let mut watched: Vec<&Path> = before.keys().map(|p| p.strip_prefix(&dir).unwrap()).collect();
+        watched.sort();
+        assert_eq!(
+            watched,
+            [
+                Path::new("config.txt"),
+                Path::new("imgui/imgui.cpp"),
+                Path::new("src/impl.inl"),
+                Path::new("src/main.cpp"),
+                Path::new("src/util.h"),
+            ]
+        );
+
+        std::fs::write(dir.join("config.local.txt"), "").unwrap();
+        std::fs::remove_file(dir.join("src/util.h")).unwrap();
+        let after = snapshot(&config, &config_files);
+        assert_eq!(
+            changes(&before, &after),
+            vec![dir.join("config.local.txt"), dir.join("src/util.h")]
+        );
+        assert!(changes(&after, &after).is_empty());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,736 @@
+//! Parallel worker pool for concurrent compilation.
+//!
+//! Uses `std::sync::mpsc` + `std::thread` — no external crates.
+//!
+//! Design:
+//! - N worker threads receive tasks over a channel.
+//! - Each worker checks the global cancel token before/after each task.
+//! - Results are returned over a separate channel.
+//! - On FailFast: the first compile error causes immediate cancellation of all workers.
+//! - On aggregate mode: all errors are collected and returned together.
+//! - With `pipeline = "true"` (experimental) an extra `jobs / 2` threads run
+//!   the preprocessor and hand `.i`/`.ii` files to the N codegen workers,
+//!   so IO-bound preprocessing overlaps CPU-bound code generation. An
+//!   object whose preprocessed output hashes the same as last time, with
+//!   the same flags, skips codegen.
+//!
+//! Compiles go through a `Compiler` (see `compiler`), normally the
+//! configured toolchain; `--simulate` and the tests swap in stand-ins.
+//!
+//! Child process tracking:
+//! - Each child process pid is registered in `ActiveChildren` (`Arc<Mutex<HashSet>>`).
+//! - On cancellation, the main thread kills all active children.
+
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, mpsc};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+
+use crate::build::{keep_object, warnings_path, ObjectFile};
+use crate::compiler::{Compiler, ProcessCompiler};
+use crate::config::{ProjectConfig, BuildProfile};
+use crate::error::BuildError;
+use crate::estimate;
+use crate::events;
+use crate::messages::{tr, Msg};
+use crate::platform::{is_cancelled, cancel, kill_process_group};
+use crate::trace;
+use crate::ui;
+
+// ─────────────────────────────────────────────
+// ActiveChildren — process pid registry
+// ─────────────────────────────────────────────
+
+/// Tracks all active compiler child process PIDs so they can be killed on cancellation.
+#[derive(Clone, Default)]
+pub struct ActiveChildren {
+    inner: Arc<Mutex<HashSet<u32>>>,
+    process_groups: bool,
+}
+
+impl ActiveChildren {
+    /// No children yet; only pids are killed.
+    pub fn new() -> Self {
+        ActiveChildren {
+            inner: Arc::new(Mutex::new(HashSet::new())),
+            process_groups: false,
+        }
+    }
+
+    /// Children spawned with `set_process_group` lead their own group
+    /// (pgid == pid), so the whole group is killed instead of just the pid.
+    pub fn with_process_groups(process_groups: bool) -> Self {
+        ActiveChildren {
+            process_groups,
+            ..ActiveChildren::new()
+        }
+    }
+
+    /// Track a child just spawned.
+    pub fn add(&self, pid: u32) {
+        if let Ok(mut guard) = self.inner.lock() {
+            guard.insert(pid);
+        }
+    }
+
+    /// Stop tracking a child that has exited.
+    pub fn remove(&self, pid: u32) {
+        if let Ok(mut guard) = self.inner.lock() {
+            guard.remove(&pid);
+        }
+    }
+
+    /// Kill all tracked children (best-effort, ignores errors).
+    pub fn kill_all(&self) {
+        if let Ok(guard) = self.inner.lock() {
+            for &pid in guard.iter() {
+                if self.process_groups {
+                    kill_process_group(pid);
+                } else {
+                    kill_pid(pid);
+                }
+            }
+        }
+    }
+}
+
+fn kill_pid(pid: u32) {
+    #[cfg(unix)]
+    {
+        extern "C" {
+            fn kill(pid: i32, sig: i32) -> i32;
+        }
+        unsafe {
+            kill(pid as i32, 9); // SIGKILL
+        }
+    }
+
+    #[cfg(windows)]
+    {
+        // Use TerminateProcess via OpenProcess
+        extern "system" {
+            fn OpenProcess(access: u32, inherit: i32, pid: u32) -> *mut std::ffi::c_void;
+            fn TerminateProcess(handle: *mut std::ffi::c_void, code: u32) -> i32;
+            fn CloseHandle(handle: *mut std::ffi::c_void) -> i32;
+        }
+        const PROCESS_TERMINATE: u32 = 0x0001;
+        unsafe {
+            let handle = OpenProcess(PROCESS_TERMINATE, 0, pid);
+            if !handle.is_null() {
+                TerminateProcess(handle, 1);
+                CloseHandle(handle);
+            }
+        }
+    }
+}
+
+// ─────────────────────────────────────────────
+// Worker pool
+// ─────────────────────────────────────────────
+
+/// A successfully compiled object and what the build learned doing it.
+pub struct CompiledObject {
+    /// The object compiled.
+    pub obj: ObjectFile,
+    /// How long compiling it took.
+    pub duration: Duration,
+    /// SHA-256 of the preprocessed source (pipelined builds only).
+    pub preprocessed_hash: Option<String>,
+    /// Compiler stderr from the successful compile, usually warnings.
+    pub diagnostics: String,
+}
+
+/// Compiles objects on `parallel_jobs` threads, reporting progress as they
+/// finish.
+pub struct WorkerPool {
+    config: Arc<ProjectConfig>,
+    compiler: Arc<dyn Compiler>,
+    aggregate: bool,
+    active_children: ActiveChildren,
+    /// Predicted compile time per object, for the ETA.
+    estimates: HashMap<PathBuf, Duration>,
+    /// Preprocessed hash each existing object was generated from, for
+    /// objects whose flags haven't changed since.
+    preprocessed: Arc<HashMap<PathBuf, String>>,
+}
+
+impl WorkerPool {
+    /// A pool compiling with the configured toolchain.
+    pub fn new(
+        config: Arc<ProjectConfig>,
+        profile: BuildProfile,
+        extra_flags: Vec<String>,
+        verbose: bool,
+        aggregate: bool,
+    ) -> Self {
+        let active_children = ActiveChildren::with_process_groups(config.use_process_groups);
+        let compiler = ProcessCompiler::new(Arc::clone(&config), profile, extra_flags, verbose);
+        WorkerPool {
+            config,
+            compiler: Arc::new(compiler),
+            aggregate,
+            active_children,
+            estimates: HashMap::new(),
+            preprocessed: Arc::new(HashMap::new()),
+        }
+    }
+
+    /// Show an ETA based on these predictions (see `estimate`).
+    pub fn with_estimates(self, estimates: HashMap<PathBuf, Duration>) -> Self {
+        WorkerPool { estimates, ..self }
+    }
+
+    /// Skip codegen for pipelined objects whose preprocessed output hashes
+    /// to the value recorded here for their object path.
+    pub fn with_preprocessed(self, preprocessed: HashMap<PathBuf, String>) -> Self {
+        WorkerPool { preprocessed: Arc::new(preprocessed), ..self }
+    }
+
+    /// Compile with `compiler` instead of the configured toolchain.
+    pub fn with_compiler(self, compiler: Arc<dyn Compiler>) -> Self {
+        WorkerPool { compiler, ..self }
+    }
+
+    /// Compile the given (already dependency-checked) objects in parallel.
+    /// Returns the compiled objects, or Err on failure.
+    pub fn run(&self, to_compile: Vec<ObjectFile>) -> Result<Vec<CompiledObject>, BuildError> {
+        let num_workers = self.config.parallel_jobs.max(1);
+        let compile_count = to_compile.len();
+
+        if compile_count == 0 {
+            return Ok(Vec::new());
+        }
+
+        let total_to_compile = compile_count;
+        let counter = Arc::new(AtomicUsize::new(0));
+        // Predicted compile time of the files not finished yet; 0 hides the ETA
+        let predicted: Option<Duration> = to_compile
+            .iter()
+            .map(|obj| self.estimates.get(&obj.obj_path).copied())
+            .sum();
+        let remaining_ms = Arc::new(AtomicU64::new(
+            predicted.map(|d| d.as_millis() as u64).unwrap_or(0),
+        ));
+
+        // Task channel: sender sends ObjectFile tasks to workers
+        let (task_tx, task_rx) = mpsc::channel::<ObjectFile>();
+        let task_rx = Arc::new(Mutex::new(task_rx));
+
+        // Result channel: workers send results back
+        let (res_tx, res_rx) = mpsc::channel::<Result<CompiledObject, BuildError>>();
+
+        // Pipelined mode: preprocessors feed codegen workers over a second
+        // channel. Failures in either stage go straight to the result channel.
+        let (codegen_tx, codegen_rx) = mpsc::channel::<(ObjectFile, Instant, String, String)>();
+        let codegen_rx = Arc::new(Mutex::new(codegen_rx));
+        let pipeline = self.config.pipeline;
+        let num_preprocessors = if pipeline { (num_workers / 2).max(1) } else { 0 };
+        let num_codegen = num_workers.min(compile_count);
+
+        let style = if self.config.status_board {
+            Some(ui::BoardStyle::Workers)
+        } else if self.config.progress_bar {
+            Some(ui::BoardStyle::Bar)
+        } else {
+            None
+        };
+        let _board = style.and_then(|style| {
+            ui::StatusBoard::start(
+                style,
+                num_codegen + num_preprocessors.min(compile_count),
+                compile_count,
+            )
+        });
+
+        // Spawn workers
+        let mut handles = Vec::new();
+        for slot in 0..num_codegen {
+            let task_rx = Arc::clone(&task_rx);
+            let codegen_rx = Arc::clone(&codegen_rx);
+            let res_tx = res_tx.clone();
+            let config = Arc::clone(&self.config);
+            let compiler = Arc::clone(&self.compiler);
+            let active_children = self.active_children.clone();
+            let counter = Arc::clone(&counter);
+            let remaining_ms = Arc::clone(&remaining_ms);
+            let preprocessed = Arc::clone(&self.preprocessed);
+
+            let handle = thread::spawn(move || {
+                loop {
+                    // Check cancellation
+                    if is_cancelled() {
+                        break;
+                    }
+
+                    if pipeline {
+                        let (obj, started, hash, pp_diagnostics) = match next_task(&codegen_rx) {
+                            Some(t) => t,
+                            None => break,
+                        };
+                        if !hash.is_empty() && preprocessed.get(&obj.obj_path) == Some(&hash) {
+                            if config.verbose {
+                                ui::line(&format!(
+                                    "  \x1b[2m{}: preprocessed output unchanged, codegen \
+                                     skipped\x1b[0m",
+                                    ui::path(&obj.src.path)
+                                ));
+                            }
+                            // Codegen would see the same input: same warnings as last time
+                            let result = keep_object(&obj, &config).map(|()| {
+                                let warnings = std::fs::read_to_string(warnings_path(&obj));
+                                warnings.unwrap_or(pp_diagnostics)
+                            });
+                            let _ = res_tx.send(result.map(|diagnostics| CompiledObject {
+                                obj,
+                                duration: started.elapsed(),
+                                preprocessed_hash: Some(hash),
+                                diagnostics,
+                            }));
+                            continue;
+                        }
+                        ui::board_slot(slot, Some(&ui::path(&obj.src.path)));
+                        let codegen_started = Instant::now();
+                        let result = compiler.compile_preprocessed(&obj, &active_children);
+                        trace::record(&trace_name(&obj), "codegen", slot + 1, codegen_started);
+                        ui::board_slot(slot, None);
+                        let _ = res_tx.send(result.map(|diagnostics| CompiledObject {
+                            obj,
+                            duration: started.elapsed(),
+                            preprocessed_hash: Some(hash),
+                            diagnostics: pp_diagnostics + &diagnostics,
+                        }));
+                        continue;
+                    }
+
+                    // Try to get a task
+                    let obj = match next_task(&task_rx) {
+                        Some(o) => o,
+                        None => break, // Channel closed
+                    };
+
+                    if is_cancelled() {
+                        break;
+                    }
+
+                    announce(&counter, total_to_compile, &obj, &config, slot, &remaining_ms);
+
+                    let started = Instant::now();
+                    let result = compiler.compile(&obj, &active_children);
+                    trace::record(&trace_name(&obj), "compile", slot + 1, started);
+                    ui::board_slot(slot, None);
+
+                    match result {
+                        Ok(diagnostics) => {
+                            let _ = res_tx.send(Ok(CompiledObject {
+                                obj,
+                                duration: started.elapsed(),
+                                preprocessed_hash: None,
+                                diagnostics,
+                            }));
+                        }
+                        Err(e) => {
+                            let _ = res_tx.send(Err(e));
+                        }
+                    }
+                }
+            });
+            handles.push(handle);
+        }
+
+        for slot in num_codegen..num_codegen + num_preprocessors.min(compile_count) {
+            let task_rx = Arc::clone(&task_rx);
+            let codegen_tx = codegen_tx.clone();
+            let res_tx = res_tx.clone();
+            let config = Arc::clone(&self.config);
+            let compiler = Arc::clone(&self.compiler);
+            let active_children = self.active_children.clone();
+            let counter = Arc::clone(&counter);
+            let remaining_ms = Arc::clone(&remaining_ms);
+
+            handles.push(thread::spawn(move || {
+                while !is_cancelled() {
+                    let obj = match next_task(&task_rx) {
+                        Some(o) => o,
+                        None => break,
+                    };
+                    announce(&counter, total_to_compile, &obj, &config, slot, &remaining_ms);
+
+                    let started = Instant::now();
+                    let result = compiler.preprocess(&obj, &active_children);
+                    trace::record(&trace_name(&obj), "preprocess", slot + 1, started);
+                    ui::board_slot(slot, None);
+                    match result {
+                        Ok((hash, diagnostics)) => {
+                            let _ = codegen_tx.send((obj, started, hash, diagnostics));
+                        }
+                        Err(e) => {
+                            let _ = res_tx.send(Err(e));
+                        }
+                    }
+                }
+            }));
+        }
+        // Codegen workers exit once every preprocessor has dropped its sender
+        drop(codegen_tx);
+        // Workers that stop on cancellation send nothing; with every sender
+        // gone the collector below stops waiting for them
+        drop(res_tx);
+
+        // Send all tasks
+        for obj in to_compile {
+            if task_tx.send(obj).is_err() {
+                break;
+            }
+        }
+        drop(task_tx); // Signal workers: no more tasks
+
+        // Collect results
+        let mut errors: Vec<BuildError> = Vec::new();
+        let mut compiled_objects: Vec<CompiledObject> = Vec::new();
+        let mut received = 0;
+
+        while received < compile_count {
+            match res_rx.recv() {
+                Ok(Ok(compiled)) => {
+                    // Printed here rather than in the worker so warnings
+                    // from different sources don't interleave
+                    if !compiled.diagnostics.is_empty() {
+                        ui::diagnostics(&compiled.diagnostics);
+                    }
+                    events::compile_finish(
+                        &compiled.obj.src.path,
+                        &compiled.diagnostics,
+                        true,
+                        Some(compiled.duration),
+                    );
+                    let ms = self.estimates.get(&compiled.obj.obj_path).map(|d| d.as_millis() as u64);
+                    let left = remaining_ms.load(Ordering::Relaxed).saturating_sub(ms.unwrap_or(0));
+                    remaining_ms.store(left, Ordering::Relaxed);
+                    compiled_objects.push(compiled);
+                    received += 1;
+                    ui::board_done(
+                        received,
+                        errors.len(),
+                        time_left(&remaining_ms, compile_count - received, num_workers),
+                    );
+                }
+                Ok(Err(e)) => {
+                    received += 1;
+                    events::compile_failed(&e);
+                    ui::board_done(
+                        received,
+                        errors.len() + 1,
+                        time_left(&remaining_ms, compile_count - received, num_workers),
+                    );
+                    if !self.aggregate {
+                        // Fail-fast: cancel all workers and kill children
+                        cancel();
+                        self.active_children.kill_all();
+                        errors.push(e);
+                        break;
+                    } else {
+                        errors.push(e);
+                    }
+                }
+                Err(_) => {
+                    // All senders dropped (workers panicked or done)
+                    break;
+                }
+            }
+        }
+
+        // Wait for all worker threads to finish
+        for h in handles {
+            let _ = h.join();
+        }
+
+        if is_cancelled() && errors.is_empty() {
+            return Err(BuildError::Cancelled);
+        }
+
+        if !errors.is_empty() {
+            if errors.len() == 1 {
+                return Err(errors.remove(0));
+            } else {
+                return Err(BuildError::MultipleErrors(errors));
+            }
+        }
+
+        Ok(compiled_objects)
+    }
+}
+
+/// Take the next task, or None once the channel is closed.
+fn next_task<T>(rx: &Mutex<mpsc::Receiver<T>>) -> Option<T> {
+    rx.lock().ok()?.recv().ok()
+}
+
+fn announce(
+    counter: &AtomicUsize,
+    total: usize,
+    obj: &ObjectFile,
+    config: &ProjectConfig,
+    slot: usize,
+    remaining_ms: &AtomicU64,
+) {
+    let n = counter.fetch_add(1, Ordering::Relaxed) + 1;
+    ui::title_progress(&config.app_name, n - 1, total);
+    events::compile_start(obj, n, total);
+    if ui::board_active() {
+        ui::board_slot(slot, Some(&ui::path(&obj.src.path)));
+        return;
+    }
+    let eta = match time_left(remaining_ms, total + 1 - n, config.parallel_jobs) {
+        Some(eta) => format!(" \x1b[2m({})\x1b[0m", ui::eta_text(eta)),
+        None => String::new(),
+    };
+    ui::line(&format!(
+        "\x1b[36m{}\x1b[0m [{}/{}] {}{}",
+        tr(Msg::Compiling),
+        n,
+        total,
+        ui::path(&obj.src.path),
+        eta
+    ));
+}
+
+fn trace_name(obj: &ObjectFile) -> String {
+    obj.src.rel_path.display().to_string()
+}
+
+/// Wall time left for the predicted compile time still outstanding, or
+/// None when there are no predictions.
+fn time_left(remaining_ms: &AtomicU64, files_left: usize, jobs: usize) -> Option<Duration> {
+    match remaining_ms.load(Ordering::Relaxed) {
+        0 => None,
+        ms => Some(estimate::eta(Duration::from_millis(ms), files_left, jobs.max(1))),
+    }
+}
+
+// ─────────────────────────────────────────────
+// Tests
+// ─────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::build::{Language, SourceFile};
+    use crate::compiler::Simulated;
+    use crate::platform::reset_cancel;
+    use std::time::SystemTime;
+
+    /// The cancel token is global: pool tests take turns.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Takes `delay` per object, fails the sources named in `fail` and
+    /// cancels the build on `cancel_on`.
+    struct Fake {
+        delay: Duration,
+        fail: Vec<&'static str>,
+        cancel_on: Option<&'static str>,
+        running: AtomicUsize,
+        peak: AtomicUsize,
+        compiled: AtomicUsize,
+        codegen: AtomicUsize,
+    }
+
+    impl Fake {
+        fn new(delay_ms: u64) -> Self {
+            Fake {
+                delay: Duration::from_millis(delay_ms),
+                fail: vec![],
+                cancel_on: None,
+                running: AtomicUsize::new(0),
+                peak: AtomicUsize::new(0),
+                compiled: AtomicUsize::new(0),
+                codegen: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl Compiler for Fake {
+        fn compile(&self, obj: &ObjectFile, _: &ActiveChildren) -> Result<String, BuildError> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.peak.fetch_max(running, Ordering::SeqCst);
+            let name = obj.src.rel_path.to_string_lossy().into_owned();
+            if self.cancel_on == Some(name.as_str()) {
+                cancel();
+            }
+            let started = Instant::now();
+            while started.elapsed() < self.delay && !is_cancelled() {
+                thread::sleep(Duration::from_millis(2));
+            }
+            self.running.fetch_sub(1, Ordering::SeqCst);
+            if is_cancelled() {
+                return Err(BuildError::Cancelled);
+            }
+            if self.fail.contains(&name.as_str()) {
+                return Err(BuildError::CompileError {
+                    src: obj.src.path.clone(),
+                    stderr: format!("{}: error", name),
+                    code: Some(1),
+                });
+            }
+            self.compiled.fetch_add(1, Ordering::SeqCst);
+            Ok(String::new())
+        }
+
+        fn preprocess(
+            &self,
+            obj: &ObjectFile,
+            children: &ActiveChildren,
+        ) -> Result<(String, String), BuildError> {
+            let hash = format!("pp of {}", obj.src.rel_path.display());
+            self.compile(obj, children).map(|d| (hash, d))
+        }
+
+        fn compile_preprocessed(
+            &self,
+            _: &ObjectFile,
+            _: &ActiveChildren,
+        ) -> Result<String, BuildError> {
+            self.codegen.fetch_add(1, Ordering::SeqCst);
+            Ok(String::new())
+        }
+    }
+
+    fn objects(names: &[&str]) -> Vec<ObjectFile> {
+        names
+            .iter()
+            .map(|name| ObjectFile {
+                src: SourceFile {
+                    path: PathBuf::from("src").join(name),
+                    rel_path: PathBuf::from(name),
+                    language: Language::C,
+                },
+                obj_path: PathBuf::from("target").join(name).with_extension("o"),
+                dep_path: PathBuf::from("target").join(name).with_extension("d"),
+            })
+            .collect()
+    }
+
+    fn pool(jobs: usize, aggregate: bool, pipeline: bool, compiler: &Arc<Fake>) -> WorkerPool {
+        let config = ProjectConfig {
+            parallel_jobs: jobs,
+            pipeline,
+            ..ProjectConfig::default()
+        };
+        WorkerPool::new(Arc::new(config), BuildProfile::Debug, vec![], false, aggregate)
+            .with_compiler(Arc::clone(compiler) as Arc<dyn Compiler>)
+    }
+
+    #[test]
+    fn test_pool_uses_every_worker() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        reset_cancel();
+        let fake = Arc::new(Fake::new(30));
+        let compiled = pool(3, false, false, &fake)
+            .run(objects(&["a.c", "b.c", "c.c", "d.c", "e.c", "f.c"]))
+            .unwrap();
+        assert_eq!(compiled.len(), 6);
+        assert_eq!(fake.peak.load(Ordering::SeqCst), 3);
+        assert!(compiled.iter().all(|c| c.duration >= Duration::from_millis(30)));
+
+        let fake = Arc::new(Fake::new(1));
+        let compiled = pool(4, false, true, &fake).run(objects(&["a.c", "b.c", "c.c"])).unwrap();
+        assert!(compiled.iter().all(|c| c.preprocessed_hash.is_some()));
+
+        // a.c preprocesses to what its object was built from: only b.c and
+        // c.c get codegen, and a.o is marked current
+        let dir = std::env::temp_dir().join("drakkar_test_pool_preprocessed");
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut objs = objects(&["a.c", "b.c", "c.c"]);
+        for obj in &mut objs {
+            obj.obj_path = dir.join(obj.obj_path.file_name().unwrap());
+            std::fs::write(&obj.obj_path, b"object").unwrap();
+        }
+        let old = SystemTime::now() - Duration::from_secs(3600);
+        let a = std::fs::File::options().write(true).open(dir.join("a.o")).unwrap();
+        a.set_modified(old).unwrap();
+        drop(a);
+        let previous = HashMap::from([
+            (dir.join("a.o"), "pp of a.c".to_string()),
+            (dir.join("b.o"), "pp of an older b.c".to_string()),
+        ]);
+        let fake = Arc::new(Fake::new(1));
+        let compiled =
+            pool(2, false, true, &fake).with_preprocessed(previous).run(objs).unwrap();
+        assert_eq!(compiled.len(), 3);
+        assert_eq!(fake.codegen.load(Ordering::SeqCst), 2);
+        let touched = std::fs::metadata(dir.join("a.o")).unwrap().modified().unwrap();
+        assert!(touched > old + Duration::from_secs(60));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        // Two 200ms compiles at 10x on two workers
+        let started = Instant::now();
+        let simulated = Simulated::new(HashMap::new(), Duration::from_millis(200), 10);
+        let compiled = pool(2, false, false, &fake)
+            .with_compiler(Arc::new(simulated))
+            .run(objects(&["a.c", "b.c"]))
+            .unwrap();
+        assert_eq!(compiled.len(), 2);
+        let took = started.elapsed();
+        assert!(took >= Duration::from_millis(20) && took < Duration::from_millis(200), "{:?}", took);
+    }
+
+    #[test]
+    fn test_pool_fails_fast_or_aggregates() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let names = ["a.c", "bad.c", "c.c", "worse.c", "e.c", "f.c", "g.c", "h.c"];
+
+        reset_cancel();
+        let fake = Arc::new(Fake {
+            fail: vec!["bad.c", "worse.c"],
+            ..Fake::new(20)
+        });
+        match pool(2, false, false, &fake).run(objects(&names)) {
+            Err(BuildError::CompileError { stderr, .. }) => assert_eq!(stderr, "bad.c: error"),
+            other => panic!("expected bad.c to fail the build, got {:?}", other.err()),
+        }
+        // The workers stopped instead of compiling the rest
+        assert!(fake.compiled.load(Ordering::SeqCst) < 6);
+
+        reset_cancel();
+        match pool(2, true, false, &fake).run(objects(&names)) {
+            Err(BuildError::MultipleErrors(errors)) => assert_eq!(errors.len(), 2),
+            other => panic!("expected both failures, got {:?}", other.err()),
+        }
+        reset_cancel();
+    }
+
+    #[test]
+    fn test_pool_cancelled_mid_build() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        reset_cancel();
+        let fake = Arc::new(Fake {
+            cancel_on: Some("c.c"),
+            ..Fake::new(10_000)
+        });
+        let started = Instant::now();
+        let result = pool(3, true, false, &fake).run(objects(&["a.c", "b.c", "c.c", "d.c"]));
+        assert!(matches!(result, Err(BuildError::Cancelled) | Err(BuildError::MultipleErrors(_))));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        reset_cancel();
+    }
+
+    #[test]
+    fn test_active_children_add_remove() {
+        let ac = ActiveChildren::new();
+        ac.add(1234);
+        ac.add(5678);
+        {
+            let guard = ac.inner.lock().unwrap();
+            assert!(guard.contains(&1234));
+            assert!(guard.contains(&5678));
+        }
+        ac.remove(1234);
+        {
+            let guard = ac.inner.lock().unwrap();
+            assert!(!guard.contains(&1234));
+            assert!(guard.contains(&5678));
+        }
+    }
+}
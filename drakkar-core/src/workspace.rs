@@ -0,0 +1,291 @@
+//! Workspaces: a root config whose `[workspace]` section lists member
+//! project directories (`members = "libs/math app"`), each with its own
+//! config. `drakkar build` at the root builds every member, the members a
+//! project names in its `deps` first.
+//!
+//! `deps` also works outside a workspace: each dependency is another
+//! drakkar project, built before the project. Its `static_lib` targets are
+//! linked into the project's executables, with their include dirs added.
+
+use std::path::{Path, PathBuf};
+
+use crate::build::{resolve_targets, static_lib_path};
+use crate::config::{find_config, read_config, ProjectConfig, TargetType};
+use crate::error::BuildError;
+
+/// The members to build, dependencies first: all of them, or with
+/// `package` the one of that name (its dir, or the dir's last component)
+/// and the members it depends on.
+pub fn build_order(
+    config: &ProjectConfig,
+    package: Option<&str>,
+) -> Result<Vec<PathBuf>, BuildError> {
+    let members = &config.workspace_members;
+    let ids: Vec<PathBuf> = members
+        .iter()
+        .map(|dir| {
+            if !find_config(dir).is_file() {
+                return Err(BuildError::ConfigError(format!(
+                    "workspace member '{}' has no drakkar.toml or config.txt",
+                    dir.display()
+                )));
+            }
+            Ok(dir.canonicalize().unwrap_or_else(|_| dir.clone()))
+        })
+        .collect::<Result<_, _>>()?;
+
+    // Dependencies on projects outside the workspace don't affect the order
+    let mut deps = Vec::with_capacity(members.len());
+    for (dir, id) in members.iter().zip(&ids) {
+        let member = read_config(&find_config(dir))?;
+        let indices: Vec<usize> = member
+            .deps
+            .iter()
+            .filter_map(|dep| {
+                let dep = id.join(dep).canonicalize().ok()?;
+                ids.iter().position(|other| *other == dep)
+            })
+            .collect();
+        deps.push(indices);
+    }
+
+    let roots: Vec<usize> = match package {
+        None => (0..members.len()).collect(),
+        Some(name) => vec![find_member(members, name)?],
+    };
+    let mut order = Vec::new();
+    let mut visiting = Vec::new();
+    for root in roots {
+        visit(root, &deps, members, &mut visiting, &mut order)?;
+    }
+    Ok(order.into_iter().map(|i| members[i].clone()).collect())
+}
+
+fn find_member(members: &[PathBuf], name: &str) -> Result<usize, BuildError> {
+    let found = members.iter().position(|dir| {
+        dir == Path::new(name) || dir.file_name().map(|n| n == name).unwrap_or(false)
+    });
+    found.ok_or_else(|| {
+        let names: Vec<String> = members.iter().map(|d| d.display().to_string()).collect();
+        BuildError::ConfigError(format!(
+            "no workspace member named '{}' (members: {})",
+            name,
+            names.join(", ")
+        ))
+    })
+}
+
+/// Depth-first: `member` goes into `order` after everything it depends on.
+fn visit(
+    member: usize,
+    deps: &[Vec<usize>],
+    members: &[PathBuf],
+    visiting: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), BuildError> {
+    if order.contains(&member) {
+        return Ok(());
+    }
+    if let Some(start) = visiting.iter().position(|&m| m == member) {
+        let cycle: Vec<String> = visiting[start..]
+            .iter()
+            .chain([&member])
+            .map(|&m| members[m].display().to_string())
+            .collect();
+        return Err(BuildError::ConfigError(format!(
+            "workspace members depend on each other in a cycle: {}",
+            cycle.join(" → ")
+        )));
+    }
+    visiting.push(member);
+    for &dep in &deps[member] {
+        visit(dep, deps, members, visiting, order)?;
+    }
+    visiting.pop();
+    order.push(member);
+    Ok(())
+}
+
+/// A project another one depends on, directly or through other projects.
+#[derive(Debug)]
+pub struct ProjectDep {
+    /// Its directory, relative to the dependent's.
+    pub dir: PathBuf,
+    /// Its config as read; paths in it are relative to `dir`.
+    pub config: ProjectConfig,
+}
+
+/// Every project `config` depends on, each after its own dependencies.
+pub fn project_deps(config: &ProjectConfig) -> Result<Vec<ProjectDep>, BuildError> {
+    let mut deps = Vec::new();
+    let mut ids = Vec::new();
+    let mut visiting = Vec::new();
+    for dep in &config.deps {
+        visit_dep(dep.clone(), &mut visiting, &mut ids, &mut deps)?;
+    }
+    Ok(deps)
+}
+
+fn visit_dep(
+    dir: PathBuf,
+    visiting: &mut Vec<(PathBuf, PathBuf)>,
+    ids: &mut Vec<PathBuf>,
+    deps: &mut Vec<ProjectDep>,
+) -> Result<(), BuildError> {
+    let config_path = find_config(&dir);
+    if !config_path.is_file() {
+        return Err(BuildError::ConfigError(format!(
+            "dependency '{}' has no drakkar.toml or config.txt",
+            dir.display()
+        )));
+    }
+    let id = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+    if ids.contains(&id) {
+        return Ok(());
+    }
+    if let Some(start) = visiting.iter().position(|(other, _)| *other == id) {
+        let cycle: Vec<String> = visiting[start..]
+            .iter()
+            .map(|(_, d)| d.display().to_string())
+            .chain([dir.display().to_string()])
+            .collect();
+        return Err(BuildError::ConfigError(format!(
+            "project dependencies form a cycle: {}",
+            cycle.join(" → ")
+        )));
+    }
+    let config = read_config(&config_path)?;
+    visiting.push((id.clone(), dir.clone()));
+    for dep in &config.deps {
+        visit_dep(dir.join(dep), visiting, ids, deps)?;
+    }
+    visiting.pop();
+    ids.push(id);
+    deps.push(ProjectDep { dir, config });
+    Ok(())
+}
+
+/// Compile `config` against its project dependencies, as returned by
+/// [`project_deps`]: their include dirs (`include/` when they set none),
+/// their `link_libs`, and the archives of their `static_lib` targets.
+/// Dependencies without a library are only built first.
+pub fn link_project_deps(
+    config: &mut ProjectConfig,
+    deps: &[ProjectDep],
+) -> Result<(), BuildError> {
+    // Dependents before their dependencies, as the linker wants them
+    for dep in deps.iter().rev() {
+        let mut libs = resolve_targets(&dep.config, None)?;
+        libs.retain(|t| t.target_type == TargetType::StaticLib);
+        if libs.is_empty() {
+            continue;
+        }
+        let mut include_dirs = dep.config.include_dirs.clone();
+        include_dirs.extend(libs.iter().flat_map(|t| t.include_dirs.iter().cloned()));
+        if include_dirs.is_empty() && dep.dir.join("include").is_dir() {
+            include_dirs.push(PathBuf::from("include"));
+        }
+        config.include_dirs.extend(include_dirs.iter().map(|d| dep.dir.join(d)));
+        config.link_libs.extend(dep.config.link_libs.iter().cloned());
+        config.link_libs.extend(libs.iter().flat_map(|t| t.link_libs.iter().cloned()));
+        let archives = libs.iter().rev().map(|t| static_lib_path(&dep.config, t.output_name()));
+        config.extra_objects.extend(archives.map(|a| dep.dir.join(a)));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_order() {
+        let dir = std::env::temp_dir().join("drakkar_test_workspace");
+        let _ = std::fs::remove_dir_all(&dir);
+        let project = |name: &str, deps: &str| {
+            std::fs::create_dir_all(dir.join(name)).unwrap();
+            let config = format!("app_name = \"{}\"\ndeps = \"{}\"\n", name, deps);
+            std::fs::write(dir.join(name).join("config.txt"), config).unwrap();
+        };
+        project("app", "../libs/net ../libs/math");
+        project("libs/net", "../math");
+        project("libs/math", "");
+        project("tool", "../outside");
+
+        let members = ["app", "libs/net", "libs/math", "tool"];
+        let config = ProjectConfig {
+            workspace_members: members.iter().map(|m| dir.join(m)).collect(),
+            ..ProjectConfig::default()
+        };
+        let names = |order: Vec<PathBuf>| -> Vec<String> {
+            order.iter().map(|p| p.strip_prefix(&dir).unwrap().display().to_string()).collect()
+        };
+        let all = build_order(&config, None).unwrap();
+        assert_eq!(names(all), ["libs/math", "libs/net", "app", "tool"]);
+        assert_eq!(names(build_order(&config, Some("net")).unwrap()), ["libs/math", "libs/net"]);
+        let err = build_order(&config, Some("web")).unwrap_err().to_string();
+        assert!(err.contains("no workspace member named 'web'"), "{}", err);
+
+        project("libs/math", "../../app");
+        let err = build_order(&config, None).unwrap_err().to_string();
+        assert!(err.contains("cycle"), "{}", err);
+        assert!(err.ends_with("app"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_project_deps() {
+        let dir = std::env::temp_dir().join("drakkar_test_project_deps");
+        let _ = std::fs::remove_dir_all(&dir);
+        let project = |name: &str, config: &str| {
+            std::fs::create_dir_all(dir.join(name).join("include")).unwrap();
+            std::fs::write(dir.join(name).join("config.txt"), config).unwrap();
+        };
+        let lib = |name: &str, deps: &str| {
+            format!(
+                "deps = \"{}\"\nlink_libs = \"m\"\n[target.{}]\ntarget_type = \"static_lib\"\n\
+                 source_dir = \"src\"\n",
+                deps, name
+            )
+        };
+        project("math", &lib("math", ""));
+        project("geo", &lib("geo", "../math"));
+        project("tool", "app_name = \"tool\"\n");
+
+        let mut config = ProjectConfig {
+            deps: vec![dir.join("geo"), dir.join("math")],
+            ..ProjectConfig::default()
+        };
+        let deps = project_deps(&config).unwrap();
+        let names: Vec<&str> = deps.iter().map(|d| d.config.targets[0].name.as_str()).collect();
+        assert_eq!(names, ["math", "geo"]);
+        assert_eq!(deps[0].dir, dir.join("geo/../math"));
+
+        link_project_deps(&mut config, &deps).unwrap();
+        assert_eq!(
+            config.extra_objects,
+            vec![dir.join("geo/out/libgeo.a"), dir.join("geo/../math/out/libmath.a")]
+        );
+        assert_eq!(
+            config.include_dirs,
+            vec![dir.join("geo/include"), dir.join("geo/../math/include")]
+        );
+        assert_eq!(config.link_libs, ["m", "m"]);
+
+        let mut tool_user = ProjectConfig {
+            deps: vec![dir.join("tool")],
+            ..ProjectConfig::default()
+        };
+        let deps = project_deps(&tool_user).unwrap();
+        link_project_deps(&mut tool_user, &deps).unwrap();
+        assert!(tool_user.extra_objects.is_empty() && tool_user.include_dirs.is_empty());
+
+        project("math", &lib("math", "../geo"));
+        config.deps = vec![dir.join("geo")];
+        let err = project_deps(&config).unwrap_err().to_string();
+        assert!(err.contains("cycle"), "{}", err);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
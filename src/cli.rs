@@ -1,13 +1,51 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use crate::build::{
-    collect_sources, create_project, link_objects, object_path_for, prepare_build_dirs,
+use drakkar_core::build::{
+    build_compile_args, build_link_args, check_clean_dir, clean_dir, collect_sources,
+    compile_flags_hash, create_project, executable_path, is_entry_point, link_objects,
+    object_path_for, plan_executables, prepare_build_dirs, rebuild_reason, relink_needed,
+    CleanStats, Language, ObjectFile,
 };
-use crate::config::{read_config, BuildProfile, ProjectConfig};
-use crate::error::BuildError;
-use crate::platform::register_ctrlc_handler;
-use crate::worker::WorkerPool;
+use drakkar_core::cache::{self, CacheStats, ObjectCache};
+use drakkar_core::compare;
+use drakkar_core::compdb;
+use drakkar_core::compiler::Simulated;
+use drakkar_core::conan;
+use drakkar_core::config::{
+    add_include_dirs, find_config, local_override_path, read_config, BuildProfile, ProjectConfig,
+    TargetType, TEXT_CONFIG, TOML_CONFIG,
+};
+use drakkar_core::diagnostics::{hints_for, include_dir_suggestions};
+use drakkar_core::engine::{build_project, build_units, compile_unit, write_compdb, CompiledUnit};
+use drakkar_core::error::BuildError;
+use drakkar_core::estimate;
+use drakkar_core::events::{self, EventFormat};
+use drakkar_core::fetch;
+use drakkar_core::hash::Sha256;
+use drakkar_core::graph::Graph;
+use drakkar_core::headers::{check_guards, find_headers, standalone_unit};
+use drakkar_core::impact;
+use drakkar_core::install;
+use drakkar_core::messages::{localize_help, tr, Msg};
+use drakkar_core::metrics::{self, BuildMetrics, MetricsOutput};
+use drakkar_core::migrate::{config_to_toml, migrate_config, CURRENT_CONFIG_VERSION};
+use drakkar_core::platform::{self, register_ctrlc_handler};
+use drakkar_core::promote;
+use drakkar_core::prune;
+use drakkar_core::remote::RemoteCache;
+use drakkar_core::selfupdate;
+use drakkar_core::state::{self, BuildState, ObjectState};
+use drakkar_core::stats;
+use drakkar_core::status::{status_json, write_status, BuildSummary};
+use drakkar_core::template::{parse_var, resolve_template_dir};
+use drakkar_core::ui::{self, report_warning, PhaseTimings};
+use drakkar_core::testing;
+use drakkar_core::trace;
+use drakkar_core::watch;
+use drakkar_core::worker::WorkerPool;
+use drakkar_core::workspace;
 
 const HELP_TEXT: &str = r#"drakkar — C/C++ build system
 
@@ -16,24 +54,108 @@ USAGE:
 
 COMMANDS:
     create <name>          Create a new project skeleton
-    build [debug|release]  Build the project (default: debug)
-    run   [debug|release]  Build and run the project
+           [--var key=value]... [--from-template <dir|name>]
+    build [debug|release] [<target>]
+                           Build the project, or one [target.<name>] and its deps
+                           (default: debug)
+    run   [debug|release] [<target>]
+                           Build and run an executable (default: default_target,
+                           then app_name or the first executable target)
+          --list           List the executables `run` can launch
+    clean [--all]          Remove build intermediates from temp_dir
+                           (--all also empties output_dir)
+    migrate [--dry-run]    Upgrade the config to the current schema version
+            [--to-toml]    Convert config.txt (and config.local.txt) to drakkar.toml
+    impact <header>        Show which sources a header change would rebuild
+    graph [<target>] [--json]
+                           Print the source→header graph of the last build as
+                           Graphviz DOT (or JSON)
+    state [<target>] [--json]
+                           Show the recorded incremental state of each object:
+                           flags hash, content hashes, inputs, last build
+    check-headers          Check include guards and compile public headers standalone
+    compdb                 Write compile_commands.json without compiling
+    cache push|pull [debug|release] [<target>]
+                           Upload up-to-date objects to remote_cache, or
+                           download stale ones into the local object cache
+    cache stats            Show object cache size, hits and misses
+    cache prune --older-than <age>
+                           Remove objects unused for <age> (30d, 12h, 2w) from
+                           temp_dir and the object cache
+    stats sources [--json] Count files and lines per language and directory,
+                           headers per source and the largest files
+    fetch                  Check out the [deps] git dependencies at the commits
+                           in drakkar.lock, locking tags not pinned yet
+    install [debug|release] [<target>] [--prefix <dir>]
+                           Build (release unless debug is given), then copy
+                           executables, libraries and their headers into the
+                           prefix (install_prefix, else /usr/local; $DESTDIR
+                           is honored)
+    promote --from <channel> --to <channel>
+                           Copy artifacts into a channel under channels_dir with
+                           checksums and a manifest; --from debug or release
+                           builds that profile first
+    test  [debug|release] [<filter>]
+                           Build and run every test program in tests_dir
+    watch [debug|release] [<target>]
+                           Build, then rebuild whenever a source, header or
+                           the config changes (Ctrl+C to stop)
+    self update            Replace drakkar with the latest release for this
+                           platform, checked against its SHA256SUMS
     help                   Show this help message
 
 OPTIONS:
     --parallel <n>         Override number of parallel jobs
     --verbose, -v          Print compiler commands
+    --show-timestamps      Prefix output with elapsed time and print phase durations
+    --absolute-paths       Print absolute paths instead of paths relative to the
+                           project root
     --aggregate-errors     Collect all compile errors instead of failing fast
+    --replay-warnings      Also show cached warnings of up-to-date sources
+    --explain-commands     Print each compiler command with notes on its flags
+    --save-ice-repro       When the compiler crashes, bundle the preprocessed source,
+                           flags and compiler version into temp_dir/ice/
+    --fallback-temp        Use a per-user cache dir if temp_dir isn't writable
+    --fix                  With build/run: add include_dirs for headers found in
+                           the project when a compile can't find them
+    --simulate             With build: schedule a full rebuild using recorded
+                           compile times, without running the compiler
+    --message-format <fmt> Output format: human (default), json for build
+                           events on stdout and errors as JSON on stderr, or
+                           vscode for problem-matcher friendly diagnostics
+                           (also --output-format)
+    --emit-status <path>   Write a JSON build summary (result, counts, artifact hashes)
+    --trace-out <path>     Write a Chrome trace of every compile and link job
+    --metrics prometheus:<path>
+                           Write build metrics (duration, jobs, cache hit rate,
+                           failure counters) in Prometheus textfile format
+    --compiler <cmd>       Compile with this compiler instead of gcc_path/gpp_path
+                           (either of a pair: gcc-13 or g++-13)
+    -p, --package <member> In a workspace: build this member and the members it
+                           depends on
+    --compare <cmd>        With build: build from scratch with both compilers and
+                           compare build time, warnings and binary sizes
     --                     Pass remaining flags to the compiler
 
 EXAMPLES:
     drakkar create myapp
+    drakkar create mygame --from-template sdl --var author=me
     drakkar build
     drakkar build release
+    drakkar build release server
     drakkar run debug
+    drakkar run release mytool
+    drakkar test
+    drakkar watch
     drakkar build -- -fsanitize=address
+    drakkar build --compiler clang++-18 --compare gcc-13
+    drakkar impact include/core.h
+    drakkar graph | dot -Tsvg -o includes.svg
+    drakkar promote --from release --to release-candidate
+    drakkar clean --all
 
-The project must have a config.txt in the current directory.
+The project must have a drakkar.toml or config.txt in the current directory
+or one of its parents; paths are printed relative to that directory.
 Run `drakkar create <name>` to generate a new project with a template config.
 "#;
 
@@ -44,6 +166,71 @@ pub struct CliArgs {
     pub parallel_override: Option<usize>,
     pub verbose: bool,
     pub aggregate_errors: bool,
+    pub replay_warnings: bool,
+    pub explain_commands: bool,
+    /// Use a per-user cache dir when temp_dir isn't writable.
+    pub fallback_temp: bool,
+    /// `build --simulate`: replay compile times instead of compiling.
+    pub simulate: bool,
+    /// `build --fix`: add suggested include_dirs to the config and retry.
+    pub fix: bool,
+    /// Show absolute paths instead of project-relative ones.
+    pub absolute_paths: bool,
+    /// Bundle a repro when the compiler crashes.
+    pub save_ice_repro: bool,
+    /// `build -p <member>`: the workspace member to build.
+    pub package: Option<String>,
+    pub message_format: MessageFormat,
+    pub dry_run: bool,
+    pub template_vars: Vec<(String, String)>,
+    pub template_dir: Option<String>,
+    pub show_timestamps: bool,
+    /// Executable or `[target.<name>]` named after `run` / `build`.
+    pub target: Option<String>,
+    pub list: bool,
+    pub all: bool,
+    pub to_toml: bool,
+    pub emit_status: Option<PathBuf>,
+    /// Chrome trace of the build's jobs.
+    pub trace_out: Option<PathBuf>,
+    /// `--metrics prometheus:<path>`.
+    pub metrics: Option<MetricsOutput>,
+    /// Compiler to use instead of gcc_path / gpp_path.
+    pub compiler: Option<String>,
+    /// `build --compare`: the compiler to compare against.
+    pub compare: Option<String>,
+    /// `graph --json` instead of DOT, `state --json` instead of text.
+    pub json: bool,
+    /// `promote --from`: a channel, or a profile to build.
+    pub from: Option<String>,
+    /// `promote --to`: the channel to promote into.
+    pub to: Option<String>,
+    /// `cache prune --older-than`.
+    pub older_than: Option<Duration>,
+    /// `install --prefix`.
+    pub prefix: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MessageFormat {
+    Human,
+    Json,
+    /// Human output, with diagnostics in a fixed shape for VS Code.
+    Vscode,
+}
+
+impl MessageFormat {
+    fn parse(s: &str) -> Result<Self, BuildError> {
+        match s {
+            "human" => Ok(MessageFormat::Human),
+            "json" => Ok(MessageFormat::Json),
+            "vscode" => Ok(MessageFormat::Vscode),
+            other => Err(BuildError::ParseError(format!(
+                "--message-format: expected 'human', 'json' or 'vscode', got '{}'",
+                other
+            ))),
+        }
+    }
 }
 
 pub enum Command {
@@ -51,6 +238,28 @@ pub enum Command {
     Help,
     Build,
     Run,
+    Migrate,
+    Clean,
+    CheckHeaders,
+    Compdb,
+    Impact(PathBuf),
+    Graph,
+    State,
+    Test,
+    Watch,
+    Cache(CacheAction),
+    Promote,
+    StatsSources,
+    Fetch,
+    Install,
+    SelfUpdate,
+}
+
+pub enum CacheAction {
+    Push,
+    Pull,
+    Stats,
+    Prune,
 }
 
 // ─────────────────────────────────────────────
@@ -68,15 +277,69 @@ pub fn parse_cli_args() -> Result<CliArgs, BuildError> {
             parallel_override: None,
             verbose: false,
             aggregate_errors: false,
+            replay_warnings: false,
+            explain_commands: false,
+            fallback_temp: false,
+            simulate: false,
+            fix: false,
+            absolute_paths: false,
+            save_ice_repro: false,
+            package: None,
+            message_format: MessageFormat::Human,
+            dry_run: false,
+            template_vars: vec![],
+            template_dir: None,
+            show_timestamps: false,
+            target: None,
+            list: false,
+            all: false,
+            to_toml: false,
+            emit_status: None,
+            trace_out: None,
+            metrics: None,
+            compiler: None,
+            compare: None,
+            json: false,
+            from: None,
+            to: None,
+            older_than: None,
+            prefix: None,
         });
     }
 
     let mut command: Option<Command> = None;
-    let mut profile = BuildProfile::Debug;
+    let mut profile: Option<BuildProfile> = None;
     let mut extra_flags: Vec<String> = Vec::new();
     let mut parallel_override: Option<usize> = None;
     let mut verbose = false;
     let mut aggregate_errors = false;
+    let mut replay_warnings = false;
+    let mut explain_commands = false;
+    let mut fallback_temp = false;
+    let mut simulate = false;
+    let mut fix = false;
+    let mut absolute_paths = false;
+    let mut save_ice_repro = false;
+    let mut package: Option<String> = None;
+    let mut message_format = MessageFormat::Human;
+    let mut dry_run = false;
+    let mut template_vars: Vec<(String, String)> = Vec::new();
+    let mut template_dir: Option<String> = None;
+    let mut show_timestamps = false;
+    let mut target: Option<String> = None;
+    let mut list = false;
+    let mut all = false;
+    let mut to_toml = false;
+    let mut emit_status: Option<PathBuf> = None;
+    let mut trace_out: Option<PathBuf> = None;
+    let mut metrics: Option<MetricsOutput> = None;
+    let mut compiler: Option<String> = None;
+    let mut compare: Option<String> = None;
+    let mut json = false;
+    let mut from: Option<String> = None;
+    let mut to: Option<String> = None;
+    let mut older_than: Option<Duration> = None;
+    let mut prefix: Option<PathBuf> = None;
     let mut after_dashdash = false;
     let mut i = 0;
 
@@ -102,6 +365,159 @@ pub fn parse_cli_args() -> Result<CliArgs, BuildError> {
             "--aggregate-errors" => {
                 aggregate_errors = true;
             }
+            "--replay-warnings" => {
+                replay_warnings = true;
+            }
+            "--explain-commands" => {
+                explain_commands = true;
+            }
+            "--fallback-temp" => {
+                fallback_temp = true;
+            }
+            "--simulate" => {
+                simulate = true;
+            }
+            "--fix" => {
+                fix = true;
+            }
+            "--absolute-paths" => {
+                absolute_paths = true;
+            }
+            "--save-ice-repro" => {
+                save_ice_repro = true;
+            }
+            "--show-timestamps" => {
+                show_timestamps = true;
+            }
+            "--message-format" | "--output-format" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(format!(
+                        "{} requires a value (human|json|vscode)",
+                        args[i - 1]
+                    )));
+                }
+                message_format = MessageFormat::parse(&args[i])?;
+            }
+            other if other.starts_with("--message-format=") => {
+                message_format = MessageFormat::parse(&other["--message-format=".len()..])?;
+            }
+            other if other.starts_with("--output-format=") => {
+                message_format = MessageFormat::parse(&other["--output-format=".len()..])?;
+            }
+            "--emit-status" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--emit-status requires a file path".to_string(),
+                    ));
+                }
+                emit_status = Some(PathBuf::from(&args[i]));
+            }
+            other if other.starts_with("--emit-status=") => {
+                emit_status = Some(PathBuf::from(&other["--emit-status=".len()..]));
+            }
+            "--trace-out" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--trace-out requires a file path".to_string(),
+                    ));
+                }
+                trace_out = Some(PathBuf::from(&args[i]));
+            }
+            other if other.starts_with("--trace-out=") => {
+                trace_out = Some(PathBuf::from(&other["--trace-out=".len()..]));
+            }
+            "--metrics" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--metrics requires prometheus:<path>".to_string(),
+                    ));
+                }
+                metrics = Some(MetricsOutput::parse(&args[i])?);
+            }
+            other if other.starts_with("--metrics=") => {
+                metrics = Some(MetricsOutput::parse(&other["--metrics=".len()..])?);
+            }
+            "--older-than" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--older-than requires an age, e.g. 30d".to_string(),
+                    ));
+                }
+                older_than = Some(prune::parse_age(&args[i])?);
+            }
+            other if other.starts_with("--older-than=") => {
+                older_than = Some(prune::parse_age(&other["--older-than=".len()..])?);
+            }
+            "--prefix" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--prefix requires a directory".to_string(),
+                    ));
+                }
+                prefix = Some(PathBuf::from(&args[i]));
+            }
+            other if other.starts_with("--prefix=") => {
+                prefix = Some(PathBuf::from(&other["--prefix=".len()..]));
+            }
+            "--compiler" | "--compare" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(format!(
+                        "{} requires a compiler command",
+                        args[i - 1]
+                    )));
+                }
+                if args[i - 1] == "--compiler" {
+                    compiler = Some(args[i].clone());
+                } else {
+                    compare = Some(args[i].clone());
+                }
+            }
+            "--package" | "-p" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(format!(
+                        "{} requires a workspace member name",
+                        args[i - 1]
+                    )));
+                }
+                package = Some(args[i].clone());
+            }
+            "--from" | "--to" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(format!(
+                        "{} requires a channel name",
+                        args[i - 1]
+                    )));
+                }
+                if args[i - 1] == "--from" {
+                    from = Some(args[i].clone());
+                } else {
+                    to = Some(args[i].clone());
+                }
+            }
+            other if other.starts_with("--from=") => {
+                from = Some(other["--from=".len()..].to_string());
+            }
+            other if other.starts_with("--to=") => {
+                to = Some(other["--to=".len()..].to_string());
+            }
+            other if other.starts_with("--package=") => {
+                package = Some(other["--package=".len()..].to_string());
+            }
+            other if other.starts_with("--compiler=") => {
+                compiler = Some(other["--compiler=".len()..].to_string());
+            }
+            other if other.starts_with("--compare=") => {
+                compare = Some(other["--compare=".len()..].to_string());
+            }
             "--parallel" | "-j" => {
                 i += 1;
                 if i >= args.len() {
@@ -134,16 +550,139 @@ pub fn parse_cli_args() -> Result<CliArgs, BuildError> {
             "run" => {
                 command = Some(Command::Run);
             }
+            "migrate" => {
+                command = Some(Command::Migrate);
+            }
+            "clean" => {
+                command = Some(Command::Clean);
+            }
+            "check-headers" => {
+                command = Some(Command::CheckHeaders);
+            }
+            "compdb" => {
+                command = Some(Command::Compdb);
+            }
+            "test" => {
+                command = Some(Command::Test);
+            }
+            "graph" => {
+                command = Some(Command::Graph);
+            }
+            "state" => {
+                command = Some(Command::State);
+            }
+            "watch" => {
+                command = Some(Command::Watch);
+            }
+            "promote" => {
+                command = Some(Command::Promote);
+            }
+            "fetch" => {
+                command = Some(Command::Fetch);
+            }
+            "install" => {
+                command = Some(Command::Install);
+            }
+            "self" => {
+                i += 1;
+                if args.get(i).map(String::as_str) != Some("update") {
+                    return Err(BuildError::ParseError(
+                        "'self' requires update".to_string(),
+                    ));
+                }
+                command = Some(Command::SelfUpdate);
+            }
+            "stats" => {
+                i += 1;
+                if args.get(i).map(String::as_str) != Some("sources") {
+                    return Err(BuildError::ParseError(
+                        "'stats' requires sources".to_string(),
+                    ));
+                }
+                command = Some(Command::StatsSources);
+            }
+            "cache" => {
+                i += 1;
+                let action = match args.get(i).map(String::as_str) {
+                    Some("push") => CacheAction::Push,
+                    Some("pull") => CacheAction::Pull,
+                    Some("stats") => CacheAction::Stats,
+                    Some("prune") => CacheAction::Prune,
+                    _ => {
+                        return Err(BuildError::ParseError(
+                            "'cache' requires push, pull, stats or prune".to_string(),
+                        ))
+                    }
+                };
+                command = Some(Command::Cache(action));
+            }
+            "impact" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "'impact' requires a header path".to_string(),
+                    ));
+                }
+                command = Some(Command::Impact(PathBuf::from(&args[i])));
+            }
+            "--dry-run" => {
+                dry_run = true;
+            }
+            "--to-toml" => {
+                to_toml = true;
+            }
+            "--list" => {
+                list = true;
+            }
+            "--json" => {
+                json = true;
+            }
+            "--all" => {
+                all = true;
+            }
+            "--var" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--var requires key=value".to_string(),
+                    ));
+                }
+                template_vars.push(parse_var(&args[i])?);
+            }
+            "--from-template" => {
+                i += 1;
+                if i >= args.len() {
+                    return Err(BuildError::ParseError(
+                        "--from-template requires a directory".to_string(),
+                    ));
+                }
+                template_dir = Some(args[i].clone());
+            }
             "debug" => {
-                profile = BuildProfile::Debug;
+                profile = Some(BuildProfile::Debug);
             }
             "release" => {
-                profile = BuildProfile::Release;
+                profile = Some(BuildProfile::Release);
             }
             other => {
                 // Could be a flag starting with '-' (e.g. -DFOO) or unknown command
                 if other.starts_with('-') {
                     extra_flags.push(other.to_string());
+                } else if matches!(
+                    command,
+                    Some(Command::Run)
+                        | Some(Command::Build)
+                        | Some(Command::Cache(CacheAction::Push))
+                        | Some(Command::Cache(CacheAction::Pull))
+                        | Some(Command::Test)
+                        | Some(Command::Watch)
+                        | Some(Command::Graph)
+                        | Some(Command::State)
+                        | Some(Command::Install)
+                )
+                    && target.is_none()
+                {
+                    target = Some(other.to_string());
                 } else {
                     return Err(BuildError::ParseError(format!(
                         "Unknown command or option: '{}'. Run `drakkar help`.",
@@ -157,6 +696,44 @@ pub fn parse_cli_args() -> Result<CliArgs, BuildError> {
     }
 
     let command = command.unwrap_or(Command::Help);
+    if simulate && !matches!(command, Command::Build) {
+        return Err(BuildError::ParseError(
+            "--simulate only works with `drakkar build`".to_string(),
+        ));
+    }
+    if compare.is_some() && (simulate || !matches!(command, Command::Build)) {
+        return Err(BuildError::ParseError(
+            "--compare only works with `drakkar build`".to_string(),
+        ));
+    }
+
+    if matches!(command, Command::Promote) && (from.is_none() || to.is_none()) {
+        return Err(BuildError::ParseError(
+            "'promote' requires --from <channel> and --to <channel>".to_string(),
+        ));
+    }
+    let prune = matches!(command, Command::Cache(CacheAction::Prune));
+    if prune && older_than.is_none() {
+        return Err(BuildError::ParseError(
+            "'cache prune' requires --older-than <age>, e.g. 30d".to_string(),
+        ));
+    }
+    if !prune && older_than.is_some() {
+        return Err(BuildError::ParseError(
+            "--older-than only works with `drakkar cache prune`".to_string(),
+        ));
+    }
+    if prefix.is_some() && !matches!(command, Command::Install) {
+        return Err(BuildError::ParseError(
+            "--prefix only works with `drakkar install`".to_string(),
+        ));
+    }
+
+    // What gets installed should be optimized unless asked otherwise
+    let profile = profile.unwrap_or(match command {
+        Command::Install => BuildProfile::Release,
+        _ => BuildProfile::Debug,
+    });
 
     Ok(CliArgs {
         command,
@@ -165,48 +742,363 @@ pub fn parse_cli_args() -> Result<CliArgs, BuildError> {
         parallel_override,
         verbose,
         aggregate_errors,
+        replay_warnings,
+        explain_commands,
+        fallback_temp,
+        simulate,
+        fix,
+        absolute_paths,
+        save_ice_repro,
+        package,
+        message_format,
+        dry_run,
+        template_vars,
+        template_dir,
+        show_timestamps,
+        target,
+        list,
+        all,
+        to_toml,
+        emit_status,
+        trace_out,
+        metrics,
+        compiler,
+        compare,
+        json,
+        from,
+        to,
+        older_than,
+        prefix,
     })
 }
 
+/// Print a fatal error to stderr in the requested format.
+pub fn report_error(err: &BuildError, format: MessageFormat) {
+    let hints: Vec<String> = hints_for(err).iter().map(|h| ui::relative_text(h)).collect();
+    let text = match (format, err) {
+        // Compiler output was already printed one diagnostic per line
+        (
+            MessageFormat::Vscode,
+            BuildError::CompileError { .. } | BuildError::MultipleErrors(_),
+        ) => err.summary(),
+        _ => err.to_string(),
+    };
+    let text = ui::relative_text(&text);
+    match format {
+        MessageFormat::Human | MessageFormat::Vscode => {
+            eprintln!("\x1b[31m{}[{}]:\x1b[0m {}", tr(Msg::Error), err.code(), text);
+            for hint in &hints {
+                eprintln!("  \x1b[36m{}:\x1b[0m {}", tr(Msg::Hint), hint);
+            }
+        }
+        MessageFormat::Json => {
+            eprintln!("{}", err.to_json_line(&hints));
+        }
+    }
+}
+
 // ─────────────────────────────────────────────
 // Main run() entrypoint
 // ─────────────────────────────────────────────
 
-pub fn run() -> Result<i32, BuildError> {
-    let mut cli = parse_cli_args()?;
-
+pub fn run(cli: CliArgs) -> Result<i32, BuildError> {
     match &cli.command {
         Command::Help => {
-            print!("{}", HELP_TEXT);
+            print!("{}", localize_help(HELP_TEXT));
             return Ok(0);
         }
         Command::Create(name) => {
             let name = name.clone();
-            create_project(&name)?;
+            let template_dir = match &cli.template_dir {
+                Some(spec) => Some(resolve_template_dir(spec)?),
+                None => None,
+            };
+            create_project(&name, template_dir.as_deref(), &cli.template_vars)?;
             println!(
                 "\x1b[32mProject \"{}\" created.\x1b[0m Edit {}/config.txt and add sources into {}/src/",
                 name, name, name
             );
             return Ok(0);
         }
-        Command::Build | Command::Run => {}
+        Command::SelfUpdate => return run_self_update(),
+        Command::Migrate => {
+            enter_project_root(cli.absolute_paths)?;
+            if cli.to_toml {
+                return run_migrate_to_toml(Path::new(TEXT_CONFIG), cli.dry_run);
+            }
+            return run_migrate(&find_config(Path::new(".")), cli.dry_run);
+        }
+        Command::Build
+        | Command::Run
+        | Command::Clean
+        | Command::CheckHeaders
+        | Command::Compdb
+        | Command::Impact(_)
+        | Command::Graph
+        | Command::State
+        | Command::Test
+        | Command::Watch
+        | Command::Cache(_)
+        | Command::Promote
+        | Command::StatsSources
+        | Command::Fetch
+        | Command::Install => {}
     }
 
+    enter_project_root(cli.absolute_paths)?;
     // Register Ctrl+C handler for build/run commands
     register_ctrlc_handler();
+    ui::init(cli.show_timestamps);
+    events::set_format(match cli.message_format {
+        MessageFormat::Human => EventFormat::Off,
+        MessageFormat::Json => EventFormat::Json,
+        MessageFormat::Vscode => EventFormat::Vscode,
+    });
 
     // Read config
-    let config_path = PathBuf::from("config.txt");
+    let config_path = find_config(Path::new("."));
     if !config_path.exists() {
         return Err(BuildError::ConfigError(
-            "No config.txt found in current directory. Run `drakkar create <name>` first."
+            "No drakkar.toml or config.txt found in this directory or its parents. Run `drakkar create <name>` first."
                 .to_string(),
         ));
     }
+    if config_path.ends_with(TOML_CONFIG) && Path::new(TEXT_CONFIG).exists() {
+        eprintln!(
+            "\x1b[33mwarning:\x1b[0m both {} and {} exist; using {}",
+            TOML_CONFIG, TEXT_CONFIG, TOML_CONFIG
+        );
+    }
+
+    let config = load_config(&cli, &config_path)?;
+
+    if !config.workspace_members.is_empty() {
+        return run_workspace(&cli, &config);
+    }
+    if cli.package.is_some() {
+        return Err(BuildError::ConfigError(format!(
+            "-p selects a workspace member, but {} has no [workspace] members",
+            ui::path(&config_path)
+        )));
+    }
+
+    if let Command::Impact(header) = &cli.command {
+        return run_impact(header, &config);
+    }
+
+    if let Command::Clean = &cli.command {
+        return run_clean(&config, cli.all);
+    }
+
+    if let Command::Cache(action) = &cli.command {
+        return run_cache(action, &Arc::new(config), &cli);
+    }
+
+    if let Command::Promote = &cli.command {
+        return run_promote(&cli, &Arc::new(config));
+    }
 
-    let mut config = read_config(&config_path)?;
+    if let Command::Install = &cli.command {
+        return run_install(&cli, &Arc::new(config));
+    }
 
-    // Apply CLI overrides
+    if let Command::StatsSources = &cli.command {
+        return run_stats_sources(&Arc::new(config), cli.json);
+    }
+
+    if let Command::Fetch = &cli.command {
+        return run_fetch(&config);
+    }
+
+    if let Command::Compdb = &cli.command {
+        return run_compdb(&config, &cli.profile, &cli.extra_flags);
+    }
+
+    if let Command::CheckHeaders = &cli.command {
+        return run_check_headers(Arc::new(config), &cli.profile, &cli.extra_flags);
+    }
+
+    if let Command::Run = &cli.command {
+        if cli.list {
+            return list_executables(&config);
+        }
+    }
+
+    let mut config = Arc::new(config);
+
+    if let Command::Graph = &cli.command {
+        return run_graph(&config, cli.target.as_deref(), cli.json);
+    }
+    if let Command::State = &cli.command {
+        return run_state(&config, cli.target.as_deref(), cli.json);
+    }
+    if cli.simulate {
+        return run_simulate(&config, cli.target.as_deref());
+    }
+    if let Some(other) = &cli.compare {
+        return run_compare(&cli, &config, other);
+    }
+    ui::enable_terminal_title(config.terminal_title);
+    if let Command::Watch = &cli.command {
+        return run_watch(&cli, config, &config_path);
+    }
+    if let Command::Test = &cli.command {
+        return run_tests(&cli, &config);
+    }
+    let target = match &cli.command {
+        Command::Run if !config.targets.is_empty() => {
+            Some(run_target_name(&config, cli.target.as_deref())?)
+        }
+        Command::Run => None,
+        _ => cli.target.clone(),
+    };
+    let exe_paths = match build_fixing_includes(&cli, &mut config, &config_path, target.as_deref())? {
+        Some(summary) => summary.executables,
+        None => return Ok(1),
+    };
+
+    if let Command::Run = &cli.command {
+        let exe_path = if config.targets.is_empty() {
+            select_run_target(&config, &exe_paths, cli.target.as_deref())?
+        } else {
+            // Only the selected target and its libraries were built
+            exe_paths[0].clone()
+        };
+        if config.run_wrapper.is_none() && !config.runs_on_host() {
+            println!(
+                "\x1b[33mNot running\x1b[0m {}: built for {}; set run_wrapper (e.g. an emulator such as qemu) to run it here",
+                ui::path(&exe_path),
+                config.target_triple.as_deref().unwrap_or_default()
+            );
+            return Ok(0);
+        }
+        let mut command = match &config.run_wrapper {
+            Some(wrapper) => {
+                wrapper.validate("run_wrapper")?;
+                let exe = ui::path(&exe_path);
+                println!("\x1b[32m{}\x1b[0m {} under {}", tr(Msg::Running), exe, wrapper);
+                let mut command = std::process::Command::new(&wrapper.program);
+                command.args(&wrapper.args).arg(&exe_path);
+                command
+            }
+            None => {
+                println!("\x1b[32m{}\x1b[0m {}", tr(Msg::Running), ui::path(&exe_path));
+                std::process::Command::new(&exe_path)
+            }
+        };
+        let status = command
+            .status()
+            .map_err(|e| BuildError::IoError(format!("Cannot run {:?}: {}", exe_path, e)))?;
+
+        return Ok(status.code().unwrap_or(1));
+    }
+
+    Ok(0)
+}
+
+/// `drakkar test`: compile the code under test and every program in
+/// `tests_dir`, link each test on its own, run them and summarize.
+/// Exits with 1 when any test failed.
+fn run_tests(cli: &CliArgs, config: &Arc<ProjectConfig>) -> Result<i32, BuildError> {
+    if config.run_wrapper.is_none() && !config.runs_on_host() {
+        return Err(BuildError::ConfigError(format!(
+            "tests built for {} cannot run on this host; set run_wrapper (e.g. an emulator such as qemu)",
+            config.target_triple.as_deref().unwrap_or_default()
+        )));
+    }
+    if !config.tests_dir.is_dir() {
+        return Err(BuildError::ConfigError(format!(
+            "tests_dir {:?} does not exist; add one test program per file there",
+            config.tests_dir
+        )));
+    }
+    let profile = &cli.profile;
+    let mut timings = PhaseTimings::default();
+    build_project_deps(cli, config, &[])?;
+
+    // With named targets the tests link against the static libraries
+    let library_objects: Vec<ObjectFile> = if config.targets.is_empty() {
+        compile_unit(config, profile, &cli.extra_flags, &mut timings)?
+            .objects
+            .into_iter()
+            .filter(|o| !is_entry_point(&o.src.rel_path))
+            .collect()
+    } else {
+        build_project(config, profile, &cli.extra_flags, None)?;
+        Vec::new()
+    };
+
+    let test_config = Arc::new(testing::test_config(config)?);
+    let CompiledUnit {
+        objects, mut state, ..
+    } = compile_unit(&test_config, profile, &cli.extra_flags, &mut timings)?;
+
+    let mut tests = Vec::new();
+    for obj in objects {
+        let name = testing::test_name(&obj);
+        if cli.target.as_deref().map(|filter| name.contains(filter)).unwrap_or(true) {
+            tests.push((name, obj));
+        }
+    }
+    tests.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let mut programs = Vec::new();
+    for (name, obj) in &tests {
+        let exe = testing::test_exe_path(&test_config, obj);
+        let mut link_objs = vec![obj.clone()];
+        link_objs.extend(library_objects.iter().cloned());
+        let (linker, args) = build_link_args(&link_objs, &exe, &test_config, profile, &cli.extra_flags);
+        let link_hash = compile_flags_hash(&linker, &args, &test_config);
+        if relink_needed(&link_objs, &exe, &test_config, &state, &link_hash) {
+            PhaseTimings::measure(&mut timings.link, || {
+                link_objects(&link_objs, &exe, &test_config, profile, &cli.extra_flags, config.verbose)
+            })?;
+            state.set(
+                &exe,
+                ObjectState {
+                    flags_hash: link_hash,
+                    ..Default::default()
+                },
+            );
+            state.save()?;
+        }
+        programs.push((name, exe));
+    }
+
+    ui::line(&format!("\n\x1b[1mRunning\x1b[0m {} test(s)", programs.len()));
+    let started = std::time::Instant::now();
+    let mut outcomes = Vec::new();
+    for (name, exe) in programs {
+        let outcome = testing::run_test(name, &exe, config);
+        let verdict = if outcome.passed {
+            "\x1b[32mok\x1b[0m"
+        } else {
+            "\x1b[31mFAILED\x1b[0m"
+        };
+        ui::line(&format!(
+            "test {} ... {} ({:.2}s)",
+            name,
+            verdict,
+            outcome.duration.as_secs_f64()
+        ));
+        outcomes.push(outcome);
+    }
+
+    let failed: Vec<&testing::TestOutcome> = outcomes.iter().filter(|o| !o.passed).collect();
+    for outcome in &failed {
+        ui::line(&format!("\n---- {} output ----", outcome.name));
+        for line in outcome.output.lines() {
+            ui::line(line);
+        }
+    }
+    ui::line("");
+    ui::line(&testing::summary(&outcomes, started.elapsed()));
+    Ok(if failed.is_empty() { 0 } else { 1 })
+}
+
+/// Read the config and apply the command-line overrides.
+fn load_config(cli: &CliArgs, config_path: &Path) -> Result<ProjectConfig, BuildError> {
+    let mut config = read_config(config_path)?;
     if let Some(jobs) = cli.parallel_override {
         config.parallel_jobs = jobs;
     }
@@ -216,114 +1108,1102 @@ pub fn run() -> Result<i32, BuildError> {
     if cli.aggregate_errors {
         config.aggregate_errors = true;
     }
+    if cli.replay_warnings {
+        config.replay_warnings = true;
+    }
+    if cli.explain_commands {
+        config.explain_commands = true;
+    }
+    if cli.save_ice_repro {
+        config.save_ice_repro = true;
+    }
+    if let Some(command) = &cli.compiler {
+        config.use_compiler(command)?;
+    }
+    // Other projects' headers aren't part of this one's statistics
+    if !matches!(cli.command, Command::Clean | Command::StatsSources | Command::Fetch) {
+        fetch::fetch(&config)?;
+        fetch::wire(&mut config);
+        let deps = workspace::project_deps(&config)?;
+        workspace::link_project_deps(&mut config, &deps)?;
+    }
+    // impact only reads temp_dir; clean shouldn't create it, nor a
+    // workspace root, whose members have their own
+    let writes_temp = config.workspace_members.is_empty() && !matches!(
+        cli.command,
+        Command::Impact(_)
+            | Command::Graph
+            | Command::State
+            | Command::Clean
+            | Command::Cache(CacheAction::Stats)
+            | Command::Cache(CacheAction::Prune)
+            | Command::StatsSources
+            | Command::Fetch
+    );
+    if writes_temp || cli.fallback_temp {
+        check_temp_dir(&mut config, cli.fallback_temp)?;
+    }
+    if writes_temp {
+        conan::resolve(&mut config, &cli.profile)?;
+    }
+    Ok(config)
+}
 
-    let config = Arc::new(config);
+/// Fail early with a clear error when temp_dir can't be written (read-only
+/// mounts, sandboxes), or with `--fallback-temp` move it to a per-project
+/// directory under the user's cache dir.
+fn check_temp_dir(config: &mut ProjectConfig, fallback: bool) -> Result<(), BuildError> {
+    let err = match platform::check_writable(&config.temp_dir) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    let fallback_dir = platform::user_cache_dir().map(|cache| {
+        let project = std::fs::canonicalize(".").unwrap_or_default().join(&config.temp_dir);
+        let mut hasher = Sha256::new();
+        hasher.update_field(&project.to_string_lossy());
+        cache.join("temp").join(&hasher.finish_hex()[..16])
+    });
+    match fallback_dir {
+        Some(dir) if fallback => {
+            platform::check_writable(&dir).map_err(|e| {
+                BuildError::IoError(format!("Cannot use fallback temp dir {:?}: {}", dir, e))
+            })?;
+            eprintln!(
+                "\x1b[33mwarning:\x1b[0m temp_dir {:?} is not writable ({}); using {:?}",
+                config.temp_dir, err, dir
+            );
+            config.temp_dir = dir;
+            Ok(())
+        }
+        Some(dir) => Err(BuildError::ConfigError(format!(
+            "temp_dir {:?} is not writable ({}); set temp_dir to a writable directory, \
+             or pass --fallback-temp to use {:?}",
+            config.temp_dir, err, dir
+        ))),
+        None => Err(BuildError::ConfigError(format!(
+            "temp_dir {:?} is not writable ({}); set temp_dir to a writable directory",
+            config.temp_dir, err
+        ))),
+    }
+}
 
-    let exe_path = build_project(&config, &cli.profile, &cli.extra_flags)?;
+/// Run from the nearest directory at or above the current one that has a
+/// config, so drakkar works from anywhere in the project, and show paths
+/// relative to it.
+fn enter_project_root(absolute_paths: bool) -> Result<(), BuildError> {
+    let cwd = std::env::current_dir()?;
+    let root = cwd.ancestors().find(|dir| find_config(dir).is_file()).unwrap_or(&cwd);
+    if root != cwd {
+        std::env::set_current_dir(root)?;
+    }
+    ui::set_paths(root, absolute_paths);
+    Ok(())
+}
 
-    if let Command::Run = &cli.command {
-        println!("\x1b[32mRunning\x1b[0m {:?}", exe_path);
-        let status = std::process::Command::new(&exe_path)
-            .status()
-            .map_err(|e| BuildError::IoError(format!("Cannot run {:?}: {}", exe_path, e)))?;
+/// Build, update the terminal title and write `--emit-status`.
+fn build_and_report(
+    cli: &CliArgs,
+    config: &Arc<ProjectConfig>,
+    target: Option<&str>,
+) -> Result<BuildSummary, BuildError> {
+    report_build(cli, config, || {
+        build_project_deps(cli, config, &[])?;
+        build_project(config, &cli.profile, &cli.extra_flags, target)
+    })
+}
 
-        return Ok(status.code().unwrap_or(1));
+/// Build the projects `config` lists in `deps`, and theirs, dependencies
+/// first, each from its own directory; `skip` holds the canonical dirs of
+/// ones already built.
+fn build_project_deps(
+    cli: &CliArgs,
+    config: &ProjectConfig,
+    skip: &[PathBuf],
+) -> Result<(), BuildError> {
+    let root = std::env::current_dir()?;
+    for dep in workspace::project_deps(config)? {
+        if skip.iter().any(|s| dep.dir.canonicalize().map(|d| d == *s).unwrap_or(false)) {
+            continue;
+        }
+        ui::line(&format!("\x1b[1mDependency\x1b[0m {}", ui::path(&dep.dir)));
+        std::env::set_current_dir(&dep.dir)?;
+        let built = load_config(cli, &find_config(Path::new("."))).and_then(|mut dep_config| {
+            dep_config.parallel_jobs = config.parallel_jobs;
+            build_project(&Arc::new(dep_config), &cli.profile, &cli.extra_flags, None)
+        });
+        std::env::set_current_dir(&root)?;
+        built?;
+    }
+    Ok(())
+}
+
+/// Run `build` as one build for the events, trace, title, status file and
+/// metrics.
+fn report_build(
+    cli: &CliArgs,
+    config: &ProjectConfig,
+    build: impl FnOnce() -> Result<BuildSummary, BuildError>,
+) -> Result<BuildSummary, BuildError> {
+    let started = std::time::Instant::now();
+    events::build_started(config);
+    if cli.trace_out.is_some() {
+        trace::start();
+    }
+    let built = build();
+    ui::title_finished(&config.app_name, built.is_ok());
+    if let Some(path) = &cli.trace_out {
+        if let Err(e) = trace::write(path) {
+            report_warning(&e);
+        }
     }
+    events::done(&built, started.elapsed());
+    if let Some(path) = &cli.emit_status {
+        // A status file problem must not mask the build's own result
+        let written = status_json(&built, &cli.profile, started.elapsed())
+            .and_then(|status| write_status(path, &status));
+        if let Err(e) = written {
+            report_warning(&e);
+        }
+    }
+    if let Some(output) = &cli.metrics {
+        let build = BuildMetrics {
+            outcome: &built,
+            profile: &cli.profile,
+            duration: started.elapsed(),
+            jobs: config.parallel_jobs,
+        };
+        if let Err(e) = metrics::write(output, &build) {
+            report_warning(&e);
+        }
+    }
+    built
+}
+
+/// Build, and when a compile fails on a header that exists somewhere in
+/// the project, say which directory to add to include_dirs. With `--fix`
+/// the directory is added to the config and the build retried instead.
+/// None when the error was reported here along with the suggestions.
+fn build_fixing_includes(
+    cli: &CliArgs,
+    config: &mut Arc<ProjectConfig>,
+    config_path: &Path,
+    target: Option<&str>,
+) -> Result<Option<BuildSummary>, BuildError> {
+    let mut added: Vec<PathBuf> = Vec::new();
+    loop {
+        let err = match build_and_report(cli, config, target) {
+            Ok(summary) => return Ok(Some(summary)),
+            Err(e) => e,
+        };
+        let suggestions = include_dir_suggestions(&err, config);
+        let fresh = suggestions.iter().any(|(_, dir)| !added.contains(dir));
+        if !fresh || cli.message_format == MessageFormat::Json {
+            return Err(err);
+        }
+        if !cli.fix {
+            report_error(&err, cli.message_format);
+            for (header, dir) in &suggestions {
+                let hint = format!(
+                    "'{}' is in {}: add it to include_dirs, or rebuild with --fix",
+                    header,
+                    ui::path(dir)
+                );
+                eprintln!("  \x1b[36m{}:\x1b[0m {}", tr(Msg::Hint), hint);
+            }
+            return Ok(None);
+        }
+        let dirs: Vec<PathBuf> = suggestions.into_iter().map(|(_, dir)| dir).collect();
+        add_include_dirs(config_path, &dirs)?;
+        let names: Vec<String> = dirs.iter().map(|d| ui::path(d)).collect();
+        ui::line(&format!(
+            "  \x1b[36mFixed\x1b[0m added {} to include_dirs in {}",
+            names.join(", "),
+            ui::path(config_path)
+        ));
+        added.extend(dirs);
+        *config = Arc::new(load_config(cli, config_path)?);
+        platform::reset_cancel();
+    }
+}
 
+/// `drakkar build` (or `clean`) at a workspace root: every member, or the
+/// `-p` one, in dependency order, from its own directory. Members build one
+/// at a time with the workspace's parallel_jobs, so the jobs limit holds
+/// for the whole workspace.
+fn run_workspace(cli: &CliArgs, config: &ProjectConfig) -> Result<i32, BuildError> {
+    if !matches!(cli.command, Command::Build | Command::Clean) {
+        return Err(BuildError::ConfigError(
+            "this is a workspace root: only build and clean work here; run other commands \
+             in a member's directory"
+                .to_string(),
+        ));
+    }
+    let members = workspace::build_order(config, cli.package.as_deref())?;
+    let names: Vec<String> = members.iter().map(|m| ui::path(m)).collect();
+    ui::line(&format!(
+        "\x1b[1mWorkspace\x1b[0m {} member(s): {}",
+        members.len(),
+        names.join(", ")
+    ));
+    let root = std::env::current_dir()?;
+    let in_member = |dir: &Path, f: &mut dyn FnMut(ProjectConfig) -> Result<(), BuildError>| {
+        std::env::set_current_dir(dir)?;
+        let result = load_config(cli, &find_config(Path::new("."))).and_then(|mut member| {
+            member.parallel_jobs = config.parallel_jobs;
+            f(member)
+        });
+        std::env::set_current_dir(&root)?;
+        result
+    };
+
+    if let Command::Clean = cli.command {
+        for dir in &members {
+            in_member(dir, &mut |member| run_clean(&member, cli.all).map(|_| ()))?;
+        }
+        return Ok(0);
+    }
+    // Members are built in order already, only other dependencies need a build
+    let member_ids: Vec<PathBuf> = config
+        .workspace_members
+        .iter()
+        .filter_map(|dir| dir.canonicalize().ok())
+        .collect();
+    report_build(cli, config, || {
+        let mut summary = BuildSummary::default();
+        for dir in &members {
+            in_member(dir, &mut |member| {
+                build_project_deps(cli, &member, &member_ids)?;
+                let member = Arc::new(member);
+                let mut built = build_project(&member, &cli.profile, &cli.extra_flags, None)?;
+                // Relative to the workspace root, where the status file reads them
+                for path in built.executables.iter_mut().chain(&mut built.libraries) {
+                    *path = dir.join(&*path);
+                }
+                summary.add(built);
+                Ok(())
+            })?;
+        }
+        Ok(summary)
+    })?;
     Ok(0)
 }
 
-// ─────────────────────────────────────────────
-// Core build pipeline
-// ─────────────────────────────────────────────
+/// `drakkar watch`: build, then rebuild after every change until Ctrl+C.
+/// Build errors are reported and watching goes on; config edits are
+/// picked up before the next build.
+fn run_watch(
+    cli: &CliArgs,
+    mut config: Arc<ProjectConfig>,
+    config_path: &Path,
+) -> Result<i32, BuildError> {
+    loop {
+        let mut config_files = vec![config_path.to_path_buf(), local_override_path(config_path)];
+        config_files.extend(config.included_configs.iter().cloned());
+        // Taken before building so edits made during the build count
+        let baseline = watch::snapshot(&config, &config_files);
+        // A fail-fast error in the previous build left the workers cancelled
+        platform::reset_cancel();
+        match build_and_report(cli, &config, cli.target.as_deref()) {
+            Err(_) if platform::is_interrupted() => break,
+            Err(e) => report_error(&e, cli.message_format),
+            Ok(_) => {}
+        }
+        ui::line(&format!(
+            "\x1b[1mWatching\x1b[0m {} file(s) for changes (Ctrl+C to stop)",
+            baseline.len()
+        ));
 
-pub fn build_project(
-    config: &Arc<ProjectConfig>,
+        let changed = match watch::wait_for_change(&config, &config_files, &baseline) {
+            Some(changed) => changed,
+            None => break,
+        };
+        let more = match changed.len() {
+            1 => String::new(),
+            n => format!(" and {} more", n - 1),
+        };
+        ui::line(&format!(
+            "\x1b[36mChanged\x1b[0m {}{}",
+            changed[0].display(),
+            more
+        ));
+        match load_config(cli, config_path) {
+            Ok(reloaded) => config = Arc::new(reloaded),
+            Err(e) => {
+                report_error(&e, cli.message_format);
+                ui::line("  Keeping the previous config until it is fixed");
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Empty temp_dir (and output_dir with `--all`). Preprocessed `.i`/`.ii`
+/// files survive only when the config sets `preserve_temp = "true"`.
+fn run_clean(config: &ProjectConfig, all: bool) -> Result<i32, BuildError> {
+    let preserve = config.preserve_temp == Some(true);
+    let keep_temp = move |p: &Path| {
+        preserve && matches!(p.extension().and_then(|e| e.to_str()), Some("i" | "ii"))
+    };
+
+    check_clean_dir(&config.temp_dir, "temp_dir", config)?;
+    if all {
+        check_clean_dir(&config.output_dir, "output_dir", config)?;
+    }
+    report_clean(&config.temp_dir, clean_dir(&config.temp_dir, &keep_temp)?);
+    if all {
+        report_clean(&config.output_dir, clean_dir(&config.output_dir, &|_| false)?);
+    }
+    Ok(0)
+}
+
+fn report_clean(dir: &Path, stats: CleanStats) {
+    if stats.files == 0 && stats.kept == 0 {
+        ui::line(&format!("  {} is already clean", ui::path(dir)));
+        return;
+    }
+    ui::line(&format!(
+        "  \x1b[32m{}\x1b[0m {} {}, {} from {}",
+        tr(Msg::Removed),
+        stats.files,
+        tr(Msg::Files),
+        ui::human_size(stats.bytes),
+        ui::path(dir)
+    ));
+    if stats.kept > 0 {
+        ui::line(&format!(
+            "  Kept {} preprocessed file(s) (preserve_temp = \"true\")",
+            stats.kept
+        ));
+    }
+}
+
+/// Regenerate compile_commands.json from the current sources. Writes to the
+/// project root when `compile_commands = "off"`, since it was asked for.
+fn run_compdb(
+    config: &ProjectConfig,
     profile: &BuildProfile,
     extra_flags: &[String],
-) -> Result<PathBuf, BuildError> {
-    let t_start = std::time::Instant::now();
+) -> Result<i32, BuildError> {
+    let path = compdb::output_path(config).unwrap_or_else(|| PathBuf::from(compdb::FILE_NAME));
+    let count = write_compdb(&path, config, profile, extra_flags)?;
+    ui::line(&format!(
+        "\x1b[32mWrote\x1b[0m {} ({} entries)",
+        ui::path(&path),
+        count
+    ));
+    Ok(0)
+}
 
-    println!(
-        "\x1b[1mBuilding\x1b[0m {} [{:?}]",
-        config.app_name,
-        profile
+/// Report include guard problems, then compile every public header on its
+/// own. Returns 1 if any guard is missing or shared.
+fn run_check_headers(
+    config: Arc<ProjectConfig>,
+    profile: &BuildProfile,
+    extra_flags: &[String],
+) -> Result<i32, BuildError> {
+    let headers = find_headers(&config)?;
+    ui::line(&format!("\x1b[1mChecking\x1b[0m {} header(s)", headers.len()));
+
+    let issues = check_guards(&headers)?;
+    for issue in &issues {
+        eprintln!("\x1b[33mwarning:\x1b[0m {}", issue);
+    }
+
+    let sources = collect_sources(&config)?.sources;
+    let c_project = !sources.iter().any(|s| s.language == Language::Cpp);
+    if c_project {
+        config.c_compiler().validate("gcc_path")?;
+    } else {
+        config.cxx_compiler().validate("gpp_path")?;
+    }
+    let units = headers
+        .iter()
+        .filter(|h| h.public)
+        .map(|h| standalone_unit(h, &config, c_project))
+        .collect::<Result<Vec<_>, _>>()?;
+    let unit_count = units.len();
+
+    // Collect every failing header rather than stopping at the first
+    let pool = WorkerPool::new(
+        Arc::clone(&config),
+        profile.clone(),
+        extra_flags.to_vec(),
+        config.verbose,
+        true,
     );
+    pool.run(units)?;
+
+    ui::line(&format!(
+        "\x1b[32m{}\x1b[0m {} header(s) compile standalone, {} guard issue(s)",
+        tr(Msg::Finished),
+        unit_count,
+        issues.len()
+    ));
+    Ok(if issues.is_empty() { 0 } else { 1 })
+}
 
-    // Collect sources
-    let source_dir = &config.source_dir;
-    if !source_dir.exists() {
-        return Err(BuildError::IoError(format!(
-            "source_dir {:?} does not exist",
-            source_dir
+/// Pick the executable `drakkar run` launches: the one named on the command
+/// line, else `default_target`, else `app_name`, else the first one built.
+fn select_run_target(
+    config: &ProjectConfig,
+    exe_paths: &[PathBuf],
+    requested: Option<&str>,
+) -> Result<PathBuf, BuildError> {
+    let wanted = requested.or(config.default_target.as_deref());
+    if let Some(name) = wanted {
+        let path = executable_path(config, name);
+        if exe_paths.contains(&path) {
+            return Ok(path);
+        }
+        let available: Vec<String> = exe_paths
+            .iter()
+            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().into_owned()))
+            .collect();
+        return Err(BuildError::ConfigError(format!(
+            "no executable named '{}' (available: {})",
+            name,
+            available.join(", ")
         )));
     }
 
-    let sources = collect_sources(source_dir)?;
+    // Prefer the app_name executable when bin_discovery produced several
+    let primary = executable_path(config, &config.app_name);
+    if exe_paths.contains(&primary) {
+        Ok(primary)
+    } else {
+        Ok(exe_paths[0].clone())
+    }
+}
 
-    if sources.is_empty() {
-        return Err(BuildError::IoError(format!(
-            "No source files found in {:?}",
-            source_dir
-        )));
+/// The `[target.<name>]` `drakkar run` builds and launches: the named one,
+/// else `default_target`, else the first executable target.
+fn run_target_name(config: &ProjectConfig, requested: Option<&str>) -> Result<String, BuildError> {
+    let wanted = requested.or(config.default_target.as_deref());
+    let target = match wanted {
+        Some(name) => config.targets.iter().find(|t| t.name == name),
+        None => config
+            .targets
+            .iter()
+            .find(|t| t.target_type == TargetType::Executable),
+    };
+    match target {
+        Some(t) if t.target_type == TargetType::Executable => Ok(t.name.clone()),
+        Some(t) => Err(BuildError::ConfigError(format!(
+            "target '{}' is a {} and cannot be run",
+            t.name,
+            t.target_type.name()
+        ))),
+        None => {
+            let names: Vec<&str> = config
+                .targets
+                .iter()
+                .filter(|t| t.target_type == TargetType::Executable)
+                .map(|t| t.name.as_str())
+                .collect();
+            Err(BuildError::ConfigError(format!(
+                "no executable target named '{}' (available: {})",
+                wanted.unwrap_or(""),
+                names.join(", ")
+            )))
+        }
+    }
+}
+
+/// `drakkar run --list`: the executables a build would produce, without
+/// building them.
+fn list_executables(config: &ProjectConfig) -> Result<i32, BuildError> {
+    if !config.targets.is_empty() {
+        let default = run_target_name(config, None).ok();
+        for target in &config.targets {
+            if target.target_type != TargetType::Executable {
+                continue;
+            }
+            let path = executable_path(config, target.output_name());
+            let marker = if Some(&target.name) == default.as_ref() { " (default)" } else { "" };
+            let built = if path.exists() { "" } else { "  \x1b[2m[not built]\x1b[0m" };
+            println!("{}{}{}", target.name, marker, built);
+        }
+        return Ok(0);
     }
+    let objects: Vec<ObjectFile> = collect_sources(config)?
+        .sources
+        .iter()
+        .map(|src| object_path_for(src, config))
+        .collect();
+    let names: Vec<String> = plan_executables(objects, config)?
+        .into_iter()
+        .map(|exe| exe.name)
+        .collect();
+    let default = config
+        .default_target
+        .clone()
+        .filter(|t| names.contains(t))
+        .or_else(|| names.iter().find(|n| **n == config.app_name).cloned())
+        .or_else(|| names.first().cloned());
 
-    println!("  Found {} source file(s)", sources.len());
+    for name in &names {
+        let path = executable_path(config, name);
+        let marker = if Some(name) == default.as_ref() { " (default)" } else { "" };
+        let built = if path.exists() { "" } else { "  \x1b[2m[not built]\x1b[0m" };
+        println!("{}{}{}", name, marker, built);
+    }
+    Ok(0)
+}
 
-    // Compute object paths
-    let objects: Vec<_> = sources
+/// Print the translation units that include `header`, based on the
+/// depfiles and compile times from the last build.
+fn run_impact(header: &Path, config: &ProjectConfig) -> Result<i32, BuildError> {
+    let objects: Vec<ObjectFile> = collect_sources(config)?
+        .sources
         .iter()
         .map(|src| object_path_for(src, config))
         .collect();
+    let state = BuildState::load(&config.temp_dir);
+    let impact = impact::analyze(header, &objects, &state)?;
 
-    // Create directories
-    prepare_build_dirs(config, &objects)?;
+    println!(
+        "\x1b[1m{}\x1b[0m is included by {} of {} translation unit(s)",
+        header.display(),
+        impact.affected.len(),
+        impact.total_units
+    );
+    for unit in &impact.affected {
+        match unit.compile_time {
+            Some(t) => println!("  {}  ({:.2}s)", unit.rel_path.display(), t.as_secs_f64()),
+            None => println!("  {}  (no timing yet)", unit.rel_path.display()),
+        }
+    }
 
-    // Parallel compilation
-    let pool = WorkerPool::new(
-        Arc::clone(config),
-        profile.clone(),
-        extra_flags.to_vec(),
-        config.verbose,
-        config.aggregate_errors,
+    if !impact.affected.is_empty() {
+        let jobs = config.parallel_jobs.max(1);
+        println!(
+            "Estimated rebuild: {:.2}s CPU, ~{:.2}s wall with {} job(s)",
+            impact.cpu_time().as_secs_f64(),
+            impact.wall_estimate(jobs).as_secs_f64(),
+            jobs
+        );
+        if impact.untimed() > 0 {
+            println!(
+                "  \x1b[2m{} unit(s) have no recorded compile time and are not counted\x1b[0m",
+                impact.untimed()
+            );
+        }
+    }
+    if impact.unknown > 0 {
+        println!(
+            "\x1b[33mnote:\x1b[0m {} source(s) have not been built yet; run `drakkar build` for a complete answer",
+            impact.unknown
+        );
+    }
+    Ok(0)
+}
+
+/// Upgrade the config in place, keeping a `.bak` copy of the original.
+fn run_migrate(config_path: &Path, dry_run: bool) -> Result<i32, BuildError> {
+    let content = std::fs::read_to_string(config_path).map_err(|e| {
+        BuildError::ConfigError(format!("Cannot read {:?}: {}", config_path, e))
+    })?;
+
+    let result = migrate_config(&content)?;
+    if result.changes.is_empty() {
+        println!(
+            "\x1b[32m{}\x1b[0m is already at config_version {}",
+            config_path.display(),
+            CURRENT_CONFIG_VERSION
+        );
+        return Ok(0);
+    }
+
+    println!(
+        "\x1b[1mMigrating\x1b[0m {} (config_version {} -> {})",
+        config_path.display(),
+        result.from_version,
+        CURRENT_CONFIG_VERSION
     );
+    for change in &result.changes {
+        println!("  {}", change);
+    }
 
-    let (compiled_objects, compiled_count) = pool.run(objects)?;
+    if dry_run {
+        println!("  (dry run — no files written)");
+        return Ok(0);
+    }
 
-    if compiled_count == 0 {
-        println!("  \x1b[32mAll up-to-date\x1b[0m — nothing to recompile.");
-    } else {
+    let backup = bak_path(config_path);
+    std::fs::copy(config_path, &backup)?;
+    std::fs::write(config_path, result.content)?;
+    println!(
+        "\x1b[32mMigrated\x1b[0m {} (original saved as {})",
+        config_path.display(),
+        backup.display()
+    );
+    Ok(0)
+}
+
+/// `config.txt` → `config.txt.bak`.
+fn bak_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(".bak");
+    PathBuf::from(name)
+}
+
+/// Convert config.txt and config.local.txt to drakkar.toml and
+/// drakkar.local.toml. The originals are renamed to `.bak`, so the
+/// project keeps a single source of truth.
+fn run_migrate_to_toml(config_path: &Path, dry_run: bool) -> Result<i32, BuildError> {
+    let toml_path = config_path.with_file_name(TOML_CONFIG);
+    if toml_path.exists() {
+        return Err(BuildError::ConfigError(format!(
+            "{} already exists; remove it to convert {} again",
+            toml_path.display(),
+            config_path.display()
+        )));
+    }
+    let content = std::fs::read_to_string(config_path).map_err(|e| {
+        BuildError::ConfigError(format!("Cannot read {:?}: {}", config_path, e))
+    })?;
+    // Bring the schema up to date first so renamed keys convert cleanly
+    let migrated = migrate_config(&content)?;
+
+    let mut conversions = vec![(config_path.to_path_buf(), toml_path, migrated.content)];
+    let local = local_override_path(config_path);
+    if local.is_file() {
+        let content = std::fs::read_to_string(&local).map_err(|e| {
+            BuildError::ConfigError(format!("Cannot read {:?}: {}", local, e))
+        })?;
+        let local_toml = local_override_path(&conversions[0].1);
+        conversions.push((local, local_toml, content));
+    }
+
+    let mut converted = Vec::new();
+    for (from, to, content) in conversions {
+        let text = config_to_toml(&content).map_err(|e| match e {
+            BuildError::ParseError(msg) => {
+                BuildError::ParseError(format!("{}: {}", from.display(), msg))
+            }
+            other => other,
+        })?;
+        converted.push((from, to, text));
+    }
+
+    for (from, to, text) in &converted {
+        if dry_run {
+            println!("\x1b[1m{}\x1b[0m would become {}:\n", from.display(), to.display());
+            print!("{}", text);
+            println!();
+            continue;
+        }
+        std::fs::write(to, text)?;
+        let backup = bak_path(from);
+        std::fs::rename(from, &backup)?;
         println!(
-            "  \x1b[32mCompiled\x1b[0m {} file(s)",
-            compiled_count
+            "\x1b[32mConverted\x1b[0m {} to {} (original saved as {})",
+            from.display(),
+            to.display(),
+            backup.display()
         );
     }
+    if dry_run {
+        println!("  (dry run — no files written)");
+    }
+    Ok(0)
+}
 
-    // Link
-    let exe_name = if cfg!(windows) {
-        format!("{}.exe", config.app_name)
-    } else {
-        config.app_name.clone()
+// ─────────────────────────────────────────────
+// Object cache and build simulation
+// ─────────────────────────────────────────────
+
+/// `drakkar promote --from <channel> --to <channel>`. `debug` and
+/// `release` aren't channels: they build that profile and promote what it
+/// produced.
+fn run_promote(cli: &CliArgs, config: &Arc<ProjectConfig>) -> Result<i32, BuildError> {
+    let from = cli.from.as_deref().unwrap_or_default();
+    let to = cli.to.as_deref().unwrap_or_default();
+    let profile = match from {
+        "debug" => Some(BuildProfile::Debug),
+        "release" => Some(BuildProfile::Release),
+        _ => None,
+    };
+    if to == "debug" || to == "release" {
+        return Err(BuildError::ConfigError(format!(
+            "'{}' is a build profile; promote into a channel such as release-candidate",
+            to
+        )));
+    }
+    if from == to {
+        return Err(BuildError::ConfigError(format!("cannot promote '{}' into itself", to)));
+    }
+    let artifacts = match profile {
+        Some(profile) => {
+            build_project_deps(cli, config, &[])?;
+            let summary = build_project(config, &profile, &cli.extra_flags, None)?;
+            let mut paths = summary.executables;
+            paths.extend(summary.libraries);
+            promote::built_artifacts(&paths)?
+        }
+        None => promote::channel_artifacts(&promote::channel_dir(config, from)?)?,
     };
-    let out_exe = config.output_dir.join(&exe_name);
+    if artifacts.is_empty() {
+        return Err(BuildError::ConfigError(format!("'{}' has no artifacts to promote", from)));
+    }
+    let dir = promote::channel_dir(config, to)?;
+    promote::promote(&artifacts, from, to, &dir)?;
+    for artifact in &artifacts {
+        ui::line(&format!(
+            "  {}  {}  {}",
+            &artifact.sha256[..16],
+            artifact.name,
+            ui::human_size(artifact.size)
+        ));
+    }
+    ui::line(&format!(
+        "\x1b[32mPromoted\x1b[0m {} artifact(s) from {} to {} ({})",
+        artifacts.len(),
+        from,
+        to,
+        ui::path(&dir)
+    ));
+    Ok(0)
+}
 
-    println!("  \x1b[36mLinking\x1b[0m {}", out_exe.display());
-    link_objects(
-        &compiled_objects,
-        &out_exe,
-        config,
-        profile,
-        extra_flags,
-        config.verbose,
-    )?;
+/// `drakkar install`: build, then copy the artifacts into the prefix and
+/// record them in the install manifest.
+fn run_install(cli: &CliArgs, config: &Arc<ProjectConfig>) -> Result<i32, BuildError> {
+    let prefix = cli
+        .prefix
+        .clone()
+        .or_else(|| config.install_prefix.clone())
+        .unwrap_or_else(|| PathBuf::from(install::DEFAULT_PREFIX));
+    let destdir = std::env::var_os("DESTDIR").map(PathBuf::from);
+    // The manifest should name the files wherever it is read from
+    let root = std::env::current_dir()
+        .map_err(|e| BuildError::IoError(format!("Cannot read the current directory: {}", e)))?
+        .join(install::install_root(&prefix, destdir.as_deref()));
+
+    build_project_deps(cli, config, &[])?;
+    let summary = build_project(config, &cli.profile, &cli.extra_flags, cli.target.as_deref())?;
+    let items = install::plan(config, &summary, cli.target.as_deref())?;
+    if items.is_empty() {
+        return Err(BuildError::ConfigError("the build has nothing to install".to_string()));
+    }
+    let installed = install::install(&items, &root)?;
+    for path in &installed {
+        ui::line(&format!("  {}", path.display()));
+    }
+    let manifest = install::write_manifest(config, &installed)?;
+    ui::line(&format!(
+        "\x1b[32mInstalled\x1b[0m {} file(s) into {} (listed in {})",
+        installed.len(),
+        root.display(),
+        ui::path(&manifest)
+    ));
+    Ok(0)
+}
+
+/// `drakkar self update`: works outside any project.
+fn run_self_update() -> Result<i32, BuildError> {
+    let exe = std::env::current_exe()
+        .map_err(|e| BuildError::IoError(format!("Cannot find the drakkar executable: {}", e)))?;
+    let url = selfupdate::release_url()?;
+    let pubkey = std::env::var("DRAKKAR_RELEASE_PUBKEY").ok().filter(|k| !k.is_empty());
+    ui::line(&format!("  \x1b[36mChecking\x1b[0m {} for {}", url, selfupdate::asset_name()));
+    match selfupdate::update(&exe, &url, pubkey.as_deref())? {
+        selfupdate::Outcome::UpToDate(sha) => {
+            ui::line(&format!("\x1b[32mUp to date\x1b[0m ({})", &sha[..16]));
+        }
+        selfupdate::Outcome::Updated { from, to } => ui::line(&format!(
+            "\x1b[32mUpdated\x1b[0m {} ({} → {})",
+            exe.display(),
+            &from[..16],
+            &to[..16]
+        )),
+    }
+    Ok(0)
+}
+
+/// `drakkar cache push|pull|stats|prune`.
+///
+/// `push` uploads every up-to-date object to `remote_cache`, e.g. from CI
+/// after a build; `pull` downloads the stale ones the remote has into the
+/// local object cache, so later builds find them even offline.
+fn run_cache(action: &CacheAction, config: &Arc<ProjectConfig>, cli: &CliArgs) -> Result<i32, BuildError> {
+    match action {
+        CacheAction::Stats => return cache_stats(config),
+        CacheAction::Prune => return cache_prune(config, cli.older_than.unwrap_or_default()),
+        _ => {}
+    }
+    let location = config.remote_cache.as_ref().ok_or_else(|| {
+        BuildError::ConfigError("no remote_cache configured; set it to a directory or an http:// URL".to_string())
+    })?;
+    let remote = RemoteCache::new(location);
+    let mut cache = ObjectCache::open(config);
+
+    let (mut moved, mut already, mut skipped) = (0, 0, 0);
+    for unit in build_units(config, cli.target.as_deref())? {
+        let objects: Vec<ObjectFile> = collect_sources(&unit)?
+            .sources
+            .iter()
+            .map(|src| object_path_for(src, &unit))
+            .collect();
+        let state = BuildState::load(&unit.temp_dir);
+        if let CacheAction::Pull = action {
+            prepare_build_dirs(&unit, &objects)?;
+        }
+        for obj in &objects {
+            let (compiler, args) = build_compile_args(obj, &unit, &cli.profile, &cli.extra_flags);
+            let flags_hash = compile_flags_hash(&compiler, &args, &unit);
+            let stale = rebuild_reason(obj, &unit, &state, &flags_hash).is_some();
+            match action {
+                CacheAction::Push if stale => skipped += 1,
+                CacheAction::Push if remote.store(obj, &flags_hash)? => moved += 1,
+                CacheAction::Pull if stale => {
+                    let cache = match cache.as_mut() {
+                        Some(cache) => cache,
+                        None => continue,
+                    };
+                    let before = (cache.hits(), cache.remote_hits());
+                    cache.fetch(obj, &flags_hash);
+                    if let Some(e) = cache.take_remote_error() {
+                        return Err(e);
+                    }
+                    match (cache.hits() - before.0, cache.remote_hits() - before.1) {
+                        (_, 1) => moved += 1,
+                        (1, _) => already += 1,
+                        _ => skipped += 1,
+                    }
+                }
+                _ => already += 1,
+            }
+        }
+    }
+
+    match action {
+        CacheAction::Push => println!(
+            "\x1b[32mPushed\x1b[0m {} object(s) to {} ({} already there, {} not built yet)",
+            moved, location, already, skipped
+        ),
+        _ => println!(
+            "\x1b[32mPulled\x1b[0m {} object(s) from {} ({} up to date or cached, {} not in the remote cache)",
+            moved, location, already, skipped
+        ),
+    }
+    Ok(0)
+}
 
-    let elapsed = t_start.elapsed();
+/// `drakkar cache prune --older-than <age>`: objects of every target unused
+/// for `age` go from temp_dir, entries that old from the object cache.
+fn cache_prune(config: &ProjectConfig, age: Duration) -> Result<i32, BuildError> {
+    let cutoff = SystemTime::now().checked_sub(age).unwrap_or(SystemTime::UNIX_EPOCH);
+    let objects = prune::prune_objects(&config.temp_dir, cutoff);
+    let cached = match cache::local_root(config) {
+        Some(root) if root.is_dir() => prune::prune_cache(&root, cutoff),
+        _ => prune::Pruned::default(),
+    };
     println!(
-        "\x1b[32mFinished\x1b[0m {:?} in {:.2}s → {}",
-        profile,
-        elapsed.as_secs_f64(),
-        out_exe.display()
+        "\x1b[32mPruned\x1b[0m {} object(s), {} from temp_dir; {} file(s), {} from the \
+         object cache",
+        objects.files,
+        ui::human_size(objects.bytes),
+        cached.files,
+        ui::human_size(cached.bytes)
     );
+    Ok(0)
+}
+
+fn cache_stats(config: &ProjectConfig) -> Result<i32, BuildError> {
+    let root = cache::local_root(config).ok_or_else(|| {
+        BuildError::ConfigError("no home directory for the object cache; set object_cache_dir".to_string())
+    })?;
+    let enabled = if config.object_cache || config.remote_cache.is_some() {
+        "on"
+    } else if !config.vendor_dirs.is_empty() {
+        "vendor_dirs only"
+    } else {
+        "off"
+    };
+    let (objects, bytes) = cache::usage(&root);
+    let stats = CacheStats::load(&root);
+    println!("\x1b[1mObject cache\x1b[0m {} ({})", root.display(), enabled);
+    println!("  {} object(s), {}", objects, ui::human_size(bytes));
+    let lookups = stats.hits + stats.remote_hits + stats.misses;
+    println!(
+        "  {} {} ({} {}), {} {} over all builds{}",
+        stats.hits + stats.remote_hits,
+        tr(Msg::Hits),
+        stats.remote_hits,
+        tr(Msg::FromRemoteCache),
+        stats.misses,
+        tr(Msg::Misses),
+        match lookups {
+            0 => String::new(),
+            n => format!(", {:.0}% hit rate", (stats.hits + stats.remote_hits) as f64 * 100.0 / n as f64),
+        }
+    );
+    match &config.remote_cache {
+        Some(location) => println!(
+            "\x1b[1mRemote cache\x1b[0m {} (push {})",
+            location,
+            if config.remote_cache_push { "on" } else { "off" }
+        ),
+        None => println!("\x1b[1mRemote cache\x1b[0m none"),
+    }
+    Ok(0)
+}
+
+/// How much faster than recorded `--simulate` replays compile times.
+const SIMULATE_SPEEDUP: u32 = 10;
+
+/// Compile time assumed for sources with nothing to predict from.
+const SIMULATE_DEFAULT: Duration = Duration::from_secs(1);
+
+/// `drakkar build --simulate`: schedule a full rebuild of every unit on
+/// `parallel_jobs` workers as the real build would, each compile taking
+/// its predicted time (sped up SIMULATE_SPEEDUP times), and report how
+/// long it would take. Runs no compiler and writes nothing.
+fn run_simulate(config: &Arc<ProjectConfig>, target: Option<&str>) -> Result<i32, BuildError> {
+    let units = build_units(config, target)?;
+
+    let mut wall = Duration::ZERO;
+    let mut busy = Duration::ZERO;
+    let mut count = 0;
+    let mut guessed = 0;
+    for unit in &units {
+        let mut objects: Vec<ObjectFile> = collect_sources(unit)?
+            .sources
+            .iter()
+            .map(|src| object_path_for(src, unit))
+            .collect();
+        let state = BuildState::load(&unit.temp_dir);
+        let estimates = estimate::longest_first(&mut objects, &state);
+        let compiler = Simulated::new(estimates.clone(), SIMULATE_DEFAULT, SIMULATE_SPEEDUP);
+        busy += objects.iter().map(|obj| compiler.time(obj)).sum::<Duration>();
+        guessed += objects.len() - estimates.len();
+        count += objects.len();
+
+        let pool = WorkerPool::new(Arc::clone(unit), BuildProfile::Debug, vec![], false, false)
+            .with_estimates(estimates)
+            .with_compiler(Arc::new(compiler));
+        let started = Instant::now();
+        pool.run(objects)?;
+        wall += started.elapsed() * SIMULATE_SPEEDUP;
+    }
+
+    if guessed > 0 {
+        ui::line(&format!(
+            "  \x1b[2m{} source(s) have no compile time to go by; assumed {}s each\x1b[0m",
+            guessed,
+            SIMULATE_DEFAULT.as_secs()
+        ));
+    }
+    let jobs = config.parallel_jobs.max(1).min(count.max(1));
+    ui::line(&format!(
+        "\x1b[32mSimulated\x1b[0m {} compile(s) on {} worker(s): {:.1}s (compile time {:.1}s, workers busy {:.0}%)",
+        count,
+        jobs,
+        wall.as_secs_f64(),
+        busy.as_secs_f64(),
+        busy.as_secs_f64() * 100.0 / (wall.as_secs_f64() * jobs as f64).max(f64::EPSILON)
+    ));
+    Ok(0)
+}
 
-    Ok(out_exe)
+/// `drakkar graph`: the include graph from the last build's depfiles, on
+/// stdout so it can be piped into `dot`.
+fn run_graph(config: &Arc<ProjectConfig>, target: Option<&str>, json: bool) -> Result<i32, BuildError> {
+    let mut objects: Vec<ObjectFile> = Vec::new();
+    for unit in build_units(config, target)? {
+        let sources = collect_sources(&unit)?.sources;
+        objects.extend(sources.iter().map(|src| object_path_for(src, &unit)));
+    }
+    let root = std::fs::canonicalize(".").unwrap_or_default();
+    let graph = Graph::from_depfiles(&objects, &root);
+    if json {
+        println!("{}", graph.to_json());
+    } else {
+        print!("{}", graph.to_dot());
+    }
+    if graph.unbuilt > 0 {
+        eprintln!(
+            "\x1b[33mnote:\x1b[0m {} source(s) have not been built yet and are left out; run `drakkar build` for a complete graph",
+            graph.unbuilt
+        );
+    }
+    Ok(0)
+}
+
+/// `drakkar state`: what the last builds recorded for each object and
+/// executable, to see why something is (or isn't) rebuilt.
+fn run_state(config: &Arc<ProjectConfig>, target: Option<&str>, json: bool) -> Result<i32, BuildError> {
+    let mut entries = Vec::new();
+    for unit in build_units(config, target)? {
+        entries.extend(BuildState::load(&unit.temp_dir).inspect());
+    }
+    if json {
+        println!("{}", state::to_json(&entries));
+        return Ok(0);
+    }
+    if entries.is_empty() {
+        println!("No recorded state yet; run `drakkar build` first");
+    }
+    for line in state::to_text(&entries, SystemTime::now()) {
+        println!("{}", line);
+    }
+    Ok(0)
+}
+
+/// `drakkar fetch`: check out the git dependencies and show where each is.
+fn run_fetch(config: &ProjectConfig) -> Result<i32, BuildError> {
+    let fetched = fetch::fetch(config)?;
+    if fetched.is_empty() {
+        ui::line("No git dependencies in [deps]");
+    }
+    for dep in &fetched {
+        let state = if dep.updated { "Fetched" } else { "Fresh" };
+        ui::line(&format!(
+            "  \x1b[32m{}\x1b[0m {} {} ({})",
+            state,
+            dep.name,
+            dep.tag,
+            &dep.commit[..dep.commit.len().min(12)]
+        ));
+    }
+    Ok(0)
+}
+
+/// `drakkar stats sources`: the size of the codebase, over every target.
+fn run_stats_sources(config: &Arc<ProjectConfig>, json: bool) -> Result<i32, BuildError> {
+    let units: Vec<ProjectConfig> =
+        build_units(config, None)?.iter().map(|unit| (**unit).clone()).collect();
+    let files = stats::collect(&units)?;
+    if json {
+        println!("{}", stats::to_json(&files, 10));
+        return Ok(0);
+    }
+    for line in stats::to_text(&files, 10) {
+        println!("{}", line);
+    }
+    Ok(0)
+}
+
+/// `drakkar build --compare`: build with the configured (or `--compiler`)
+/// compiler and with `other`, then show the differences. Exits with 1 when
+/// either build failed.
+fn run_compare(cli: &CliArgs, config: &ProjectConfig, other: &str) -> Result<i32, BuildError> {
+    let sides = compare::run(
+        config,
+        [cli.compiler.as_deref(), Some(other)],
+        &cli.profile,
+        &cli.extra_flags,
+        cli.target.as_deref(),
+    )?;
+    for side in &sides {
+        if let Err(e) = &side.outcome {
+            report_error(e, cli.message_format);
+        }
+    }
+    ui::line("");
+    for line in compare::report(&sides) {
+        ui::line(&line);
+    }
+    Ok(if sides.iter().all(|s| s.outcome.is_ok()) { 0 } else { 1 })
 }
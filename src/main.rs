@@ -1,19 +1,30 @@
+//! The `drakkar` command line. Everything but argument parsing and
+//! dispatch lives in the `drakkar_core` library.
+
 mod cli;
-mod config;
-mod build;
-mod worker;
-mod error;
-mod depfile;
-mod platform;
 
 use std::process;
 
+use cli::MessageFormat;
+use drakkar_core::{messages, ui};
+
 fn main() {
-    let result = cli::run();
-    match result {
+    if let Err(e) = messages::init() {
+        ui::report_warning(&e);
+    }
+    let args = match cli::parse_cli_args() {
+        Ok(args) => args,
+        Err(e) => {
+            cli::report_error(&e, MessageFormat::Human);
+            process::exit(1);
+        }
+    };
+    let format = args.message_format;
+
+    match cli::run(args) {
         Ok(code) => process::exit(code),
         Err(e) => {
-            eprintln!("\x1b[31merror:\x1b[0m {}", e);
+            cli::report_error(&e, format);
             process::exit(1);
         }
     }
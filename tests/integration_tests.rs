@@ -1,7 +1,7 @@
-/// Drakkar integration tests.
-/// These tests run the full build pipeline using real gcc/g++.
-/// Run with: cargo test --test integration_tests
-/// Requires gcc and g++ to be installed.
+//! Drakkar integration tests.
+//! These tests run the full build pipeline using real gcc/g++.
+//! Run with: cargo test --test integration_tests
+//! Requires gcc and g++ to be installed.
 
 use std::path::PathBuf;
 use std::fs;
@@ -42,7 +42,7 @@ fn test_create_project_skeleton() {
     let workspace = temp_workspace("create");
 
     let out = Command::new(drakkar_bin())
-        .args(&["create", "demo"])
+        .args(["create", "demo"])
         .current_dir(&workspace)
         .output()
         .unwrap();
@@ -388,9 +388,2840 @@ parallel_jobs = "8"
 
     // Run and verify output
     let run_out = Command::new(workspace.join("out/parallel_test")).output().unwrap();
-    let expected: i32 = (0..n).map(|i| i as i32).sum();
+    let expected: i32 = (0..n).sum();
     let actual: i32 = String::from_utf8_lossy(&run_out.stdout).trim().parse().unwrap_or(-1);
     assert_eq!(actual, expected, "Parallel build produced wrong result");
 
     let _ = fs::remove_dir_all(&workspace);
 }
+
+// ─────────────────────────────────────────────
+// Test 8: bin_discovery links one executable per entry point
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_bin_discovery_multiple_executables() {
+    let workspace = temp_workspace("bin_discovery");
+
+    fs::create_dir_all(workspace.join("src/bin")).unwrap();
+
+    fs::write(workspace.join("src/greet.cpp"), "const char* greet() { return \"hi\"; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), r#"
+#include <cstdio>
+const char* greet();
+int main() { std::printf("app %s\n", greet()); return 0; }
+"#).unwrap();
+    fs::write(workspace.join("src/dump_main.cpp"), r#"
+#include <cstdio>
+const char* greet();
+int main() { std::printf("dump %s\n", greet()); return 0; }
+"#).unwrap();
+    fs::write(workspace.join("src/bin/fmt.cpp"), r#"
+#include <cstdio>
+const char* greet();
+int main() { std::printf("fmt %s\n", greet()); return 0; }
+"#).unwrap();
+
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "suite"
+source_dir = "src/"
+output_dir = "out/"
+temp_dir = "target/"
+bin_discovery = "true"
+parallel_jobs = "2"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(
+        out.status.success(),
+        "bin_discovery build failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    for (exe, expected) in [("suite", "app hi"), ("dump", "dump hi"), ("fmt", "fmt hi")] {
+        let run_out = Command::new(workspace.join("out").join(exe)).output().unwrap();
+        let stdout = String::from_utf8_lossy(&run_out.stdout);
+        assert_eq!(stdout.trim(), expected, "unexpected output from {}", exe);
+    }
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 9: pipelined preprocess/codegen build
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_pipeline_build() {
+    let workspace = temp_workspace("pipeline");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/value.h"), "#define VALUE 1\n").unwrap();
+    fs::write(workspace.join("src/util.c"), "#include \"value.h\"\nint value(void) { return VALUE; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), r#"
+#include <cstdio>
+extern "C" int value(void);
+int main() { std::printf("%d\n", value()); return 0; }
+"#).unwrap();
+
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "piped"
+source_dir = "src/"
+output_dir = "out/"
+temp_dir = "target/"
+pipeline = "true"
+preserve_temp = "false"
+parallel_jobs = "2"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(
+        out.status.success(),
+        "pipelined build failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let run_out = Command::new(workspace.join("out/piped")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&run_out.stdout).trim(), "1");
+    assert!(!workspace.join("target/util.i").exists(), "preprocessed file not cleaned up");
+
+    let state = fs::read_to_string(workspace.join("target/.drakkar_state")).unwrap();
+    assert!(state.contains("\tpp="), "preprocessed hash not recorded:\n{}", state);
+
+    // Depfiles come from the preprocess stage, so header changes still rebuild
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(workspace.join("src/value.h"), "#define VALUE 2\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("util.c"), "header change not picked up:\n{}", stdout);
+    let run_out = Command::new(workspace.join("out/piped")).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&run_out.stdout).trim(), "2");
+
+    // Rewritten without changes: preprocessed again, but codegen is skipped
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    let util = fs::read(workspace.join("src/util.c")).unwrap();
+    fs::write(workspace.join("src/util.c"), util).unwrap();
+    let out = run_drakkar(&["build", "--verbose"], &workspace);
+    assert!(out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("codegen skipped"), "codegen not skipped:\n{}", stdout);
+    let out = run_drakkar(&["build"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("nothing to recompile"), "kept object not current:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 10: compiler wrapper command with embedded arguments
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_compiler_wrapper_command() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("wrapper");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+
+    // A ccache-style wrapper: logs each call, then runs the real compiler
+    let wrapper = workspace.join("wrap.sh");
+    fs::write(&wrapper, "#!/bin/sh\necho \"$1\" >> wrap.log\nexec \"$@\"\n").unwrap();
+    fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o755)).unwrap();
+
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "wrapped"
+gpp_path = "./wrap.sh g++"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(
+        out.status.success(),
+        "wrapped build failed: {}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    let log = fs::read_to_string(workspace.join("wrap.log")).unwrap();
+    assert_eq!(log.lines().collect::<Vec<_>>(), ["g++", "g++"], "compile + link via wrapper");
+
+    // A wrapper that isn't executable is rejected before compiling
+    fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o644)).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 1; }\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("gpp_path"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 11: run target selection and --list
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_run_target_selection() {
+    let workspace = temp_workspace("run_target");
+
+    fs::create_dir_all(workspace.join("src/bin")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 10; }\n").unwrap();
+    fs::write(workspace.join("src/bin/tool.cpp"), "int main() { return 11; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "app"
+bin_discovery = "true"
+"#).unwrap();
+
+    let out = run_drakkar(&["run", "--list"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("app (default)"), "unexpected --list output:\n{}", stdout);
+    assert!(stdout.contains("tool"), "unexpected --list output:\n{}", stdout);
+    assert!(!workspace.join("out/app").exists(), "--list must not build");
+
+    assert_eq!(run_drakkar(&["run"], &workspace).status.code(), Some(10));
+    assert_eq!(run_drakkar(&["run", "tool"], &workspace).status.code(), Some(11));
+    assert_eq!(run_drakkar(&["run", "release", "tool"], &workspace).status.code(), Some(11));
+
+    let out = run_drakkar(&["run", "nope"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("available: app, tool"));
+
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "app"
+bin_discovery = "true"
+default_target = "tool"
+"#).unwrap();
+    assert_eq!(run_drakkar(&["run"], &workspace).status.code(), Some(11));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 12: --emit-status writes a build summary
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_emit_status() {
+    let workspace = temp_workspace("emit_status");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "int main() { int unused; return 0; }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "statusapp"
+cxx_flags = "-Wall"
+"#).unwrap();
+
+    let out = run_drakkar(&["build", "--emit-status", "out/status.json"], &workspace);
+    assert!(out.status.success(), "build failed:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(
+        String::from_utf8_lossy(&out.stderr).contains("unused variable"),
+        "warnings from successful compiles should be shown"
+    );
+    let status = fs::read_to_string(workspace.join("out/status.json")).unwrap();
+    assert!(status.contains(r#""result":"success""#), "{}", status);
+    assert!(status.contains(r#""warnings":1"#), "{}", status);
+    assert!(status.contains(r#""sha256":""#), "{}", status);
+
+    fs::write(workspace.join("src/main.cpp"), "int main() { return }\n").unwrap();
+    let out = run_drakkar(&["build", "--emit-status=out/status.json"], &workspace);
+    assert!(!out.status.success());
+    let status = fs::read_to_string(workspace.join("out/status.json")).unwrap();
+    assert!(status.contains(r#""result":"failure""#), "{}", status);
+    assert!(status.contains(r#""errors":1"#), "{}", status);
+    assert!(status.contains(r#""code":"E0101""#), "{}", status);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 13: run_wrapper launches the executable through a command
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_run_wrapper() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("run_wrapper");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 3; }\n").unwrap();
+    // Records its arguments, drops the leading marker and runs the rest so
+    // the exit code passes through
+    let wrapper = workspace.join("wrap.sh");
+    fs::write(&wrapper, "#!/bin/sh\necho \"$@\" > wrapped.txt\nshift\nexec \"$@\"\n").unwrap();
+    fs::set_permissions(&wrapper, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), format!(r#"
+app_name = "wrapped"
+run_wrapper = "{} marker"
+"#, wrapper.display())).unwrap();
+
+    let out = run_drakkar(&["run"], &workspace);
+    assert_eq!(out.status.code(), Some(3), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let recorded = fs::read_to_string(workspace.join("wrapped.txt")).unwrap();
+    assert!(recorded.starts_with("marker "), "wrapper args not passed: {}", recorded);
+    assert!(recorded.trim_end().ends_with("wrapped"), "exe not appended: {}", recorded);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 14: clean removes temp_dir, and output_dir with --all
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_clean() {
+    let workspace = temp_workspace("clean");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "cleanme"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "build failed:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("target/main.o").exists());
+
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Removed"));
+    assert!(!workspace.join("target/main.o").exists());
+    assert!(!workspace.join("target/.drakkar_state").exists());
+    assert!(workspace.join("out/cleanme").exists(), "plain clean must keep output_dir");
+
+    let out = run_drakkar(&["clean", "--all"], &workspace);
+    assert!(out.status.success());
+    assert!(!workspace.join("out/cleanme").exists());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("already clean"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 15: --replay-warnings re-shows warnings of up-to-date sources
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_replay_warnings() {
+    let workspace = temp_workspace("replay_warnings");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "int main() { int unused; return 0; }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "replay"
+cxx_flags = "-Wall"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "build failed:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("unused variable"));
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("unused variable"));
+
+    let out = run_drakkar(&["build", "--replay-warnings"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("All up-to-date"));
+    assert!(
+        String::from_utf8_lossy(&out.stderr).contains("unused variable"),
+        "cached warning not replayed:\n{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 16: check-headers reports guards and non-self-contained headers
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_check_headers() {
+    let workspace = temp_workspace("check_headers");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("include")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "#include \"ok.hpp\"\nint main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("include/ok.hpp"), "#pragma once\n#include <string>\ninline std::string ok() { return \"ok\"; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "hdrs"
+include_dirs = "include"
+"#).unwrap();
+
+    let out = run_drakkar(&["check-headers"], &workspace);
+    assert!(out.status.success(), "check failed:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    // Relies on its includer for <string>, and has no guard
+    fs::write(workspace.join("include/bad.hpp"), "inline std::string bad() { return \"\"; }\n").unwrap();
+    let out = run_drakkar(&["check-headers"], &workspace);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("bad.hpp: no include guard"), "stderr:\n{}", stderr);
+    assert!(stderr.contains("E0101"), "standalone compile should fail:\n{}", stderr);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 17: compile_commands.json from build and compdb
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_compile_commands() {
+    let workspace = temp_workspace("compdb");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "compdb"
+cxx_flags = "-DFROM_CONFIG"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "build failed:\n{}", String::from_utf8_lossy(&out.stderr));
+    let db = fs::read_to_string(workspace.join("compile_commands.json")).unwrap();
+    assert!(db.contains(r#""file":"src/main.cpp""#), "{}", db);
+    assert!(db.contains(r#""-DFROM_CONFIG""#), "{}", db);
+
+    fs::remove_dir_all(workspace.join("target")).unwrap();
+    fs::remove_file(workspace.join("compile_commands.json")).unwrap();
+    let out = run_drakkar(&["compdb"], &workspace);
+    assert!(out.status.success());
+    assert!(workspace.join("compile_commands.json").exists());
+    assert!(!workspace.join("target/main.o").exists(), "compdb must not compile");
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 18: interdependent static archives link in any order
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_static_archive_group() {
+    let workspace = temp_workspace("archive_group");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("lib")).unwrap();
+    // a needs b and b needs a: no single order satisfies a one-pass linker
+    fs::write(workspace.join("lib/a.c"), "int b1(void); int a1(void) { return b1(); } int a2(void) { return 2; }\n").unwrap();
+    fs::write(workspace.join("lib/b.c"), "int a2(void); int b1(void) { return a2(); }\n").unwrap();
+    for name in ["a", "b"] {
+        let status = Command::new("gcc")
+            .args(["-c", &format!("{}.c", name), "-o", &format!("{}.o", name)])
+            .current_dir(workspace.join("lib"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let status = Command::new("ar")
+            .args(["rcs", &format!("lib{}.a", name), &format!("{}.o", name)])
+            .current_dir(workspace.join("lib"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    }
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "extern \"C\" int a1(void);\nint main() { return a1() == 2 ? 0 : 1; }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "grouped"
+link_libs = "lib/libb.a lib/liba.a"
+"#).unwrap();
+
+    let out = run_drakkar(&["run"], &workspace);
+    assert_eq!(out.status.code(), Some(0), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 19: extra_objects are linked and trigger relinks; no-op builds skip linking
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_extra_objects_and_relink_skip() {
+    let workspace = temp_workspace("extra_objects");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("prebuilt")).unwrap();
+    let compile_prebuilt = |value: i32| {
+        fs::write(
+            workspace.join("prebuilt/answer.c"),
+            format!("int answer(void) {{ return {}; }}\n", value),
+        ).unwrap();
+        let status = Command::new("gcc")
+            .args(["-c", "answer.c", "-o", "answer.o"])
+            .current_dir(workspace.join("prebuilt"))
+            .status()
+            .unwrap();
+        assert!(status.success());
+    };
+    compile_prebuilt(5);
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "extern \"C\" int answer(void);\nint main() { return answer(); }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "prebuilt"
+extra_objects = "prebuilt/answer.o"
+"#).unwrap();
+
+    let out = run_drakkar(&["run"], &workspace);
+    assert_eq!(out.status.code(), Some(5), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Up-to-date"), "no-op build relinked:\n{}", stdout);
+    assert!(!stdout.contains("Linking"), "no-op build relinked:\n{}", stdout);
+
+    // Make sure the new object's mtime is observably newer
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    compile_prebuilt(6);
+    let out = run_drakkar(&["run"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Linking"));
+    assert_eq!(out.status.code(), Some(6));
+
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "prebuilt"
+extra_objects = "prebuilt/missing.o"
+"#).unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("missing.o"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 20: migrate --to-toml converts config.txt and the result builds
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_migrate_to_toml() {
+    let workspace = temp_workspace("to_toml");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "#include <cstring>\nint main() { return std::strcmp(GREETING, \"hi there\") == 0 ? 7 : 1; }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"# toml test
+app_name = "tomlapp"
+cxx_flags = "-Wall '-DGREETING="hi there"'"
+release.strip = "full"
+"#).unwrap();
+    fs::write(workspace.join("config.local.txt"), "parallel_jobs = \"2\"\n").unwrap();
+
+    let out = run_drakkar(&["migrate", "--to-toml", "--dry-run"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("[profile.release]"));
+    assert!(!workspace.join("drakkar.toml").exists());
+
+    let out = run_drakkar(&["migrate", "--to-toml"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("drakkar.toml").is_file());
+    assert!(workspace.join("drakkar.local.toml").is_file());
+    assert!(workspace.join("config.txt.bak").is_file());
+    assert!(!workspace.join("config.txt").exists());
+
+    let out = run_drakkar(&["run"], &workspace);
+    assert_eq!(out.status.code(), Some(7), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    fs::write(workspace.join("drakkar.toml"), "[build]\napp_name = {name = \"x\"}\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("drakkar.toml: Line 2"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 21: named targets share a static library and build selectively
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_named_targets() {
+    let workspace = temp_workspace("named_targets");
+
+    fs::create_dir_all(workspace.join("util/include")).unwrap();
+    fs::create_dir_all(workspace.join("apps/server")).unwrap();
+    fs::create_dir_all(workspace.join("apps/client")).unwrap();
+    fs::write(workspace.join("util/include/util.h"), "#pragma once\nint twice(int x);\n").unwrap();
+    fs::write(workspace.join("util/util.cpp"), "#include \"util.h\"\nint twice(int x) { return 2 * x; }\n").unwrap();
+    fs::write(
+        workspace.join("apps/server/main.cpp"),
+        "#include \"util.h\"\nint main() { return twice(SERVER_BASE); }\n",
+    ).unwrap();
+    fs::write(
+        workspace.join("apps/client/main.cpp"),
+        "#include \"util.h\"\nint main() { return twice(4); }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "suite"
+
+[target.util]
+target_type = "static_lib"
+source_dir = "util"
+include_dirs = "util/include"
+
+[target.server]
+source_dir = "apps/server"
+cxx_flags = "-DSERVER_BASE=10"
+deps = "util"
+
+[target.client]
+source_dir = "apps/client"
+output_name = "suite-client"
+deps = "util"
+"#).unwrap();
+
+    let out = run_drakkar(&["build", "client"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("out/libutil.a").is_file());
+    assert!(workspace.join("out/suite-client").is_file());
+    assert!(!workspace.join("out/server").exists(), "only the selected target is built");
+
+    let out = run_drakkar(&["run", "--list"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("server (default)"), "unexpected --list output:\n{}", stdout);
+    assert!(!stdout.contains("util"), "libraries are not runnable:\n{}", stdout);
+
+    assert_eq!(run_drakkar(&["run"], &workspace).status.code(), Some(20));
+    assert_eq!(run_drakkar(&["run", "client"], &workspace).status.code(), Some(8));
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Up-to-date"), "no-op build relinked:\n{}", stdout);
+    assert!(!stdout.contains("Archiving"), "no-op build re-archived:\n{}", stdout);
+    let compdb = fs::read_to_string(workspace.join("compile_commands.json")).unwrap();
+    assert_eq!(compdb.matches("\"file\"").count(), 3);
+
+    let out = run_drakkar(&["run", "util"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("static_lib"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 22: link_map reports which library satisfied each symbol
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_link_map_report() {
+    let workspace = temp_workspace("link_map");
+
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("lib")).unwrap();
+    fs::write(workspace.join("lib/answer.c"), "int answer(void) { return 3; }\n").unwrap();
+    let status = Command::new("gcc")
+        .args(["-c", "answer.c", "-o", "answer.o"])
+        .current_dir(workspace.join("lib"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let status = Command::new("ar")
+        .args(["rcs", "libanswer.a", "answer.o"])
+        .current_dir(workspace.join("lib"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "extern \"C\" int answer(void);\nint main() { return answer(); }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "mapped"
+link_libs = "lib/libanswer.a"
+link_map = "true"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Symbols resolved from libraries"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("lib/libanswer.a(answer.o): answer"), "stdout:\n{}", stdout);
+    assert!(workspace.join("target/mapped.map").is_file());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 23: plugin targets export only their allow-list
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_plugin_target() {
+    let workspace = temp_workspace("plugin");
+
+    fs::create_dir_all(workspace.join("greet")).unwrap();
+    fs::create_dir_all(workspace.join("host")).unwrap();
+    fs::write(
+        workspace.join("greet/greet.c"),
+        "int hidden_helper(void) { return 40; }\nint greet_value(void) { return hidden_helper() + 2; }\n",
+    ).unwrap();
+    fs::write(
+        workspace.join("host/main.c"),
+        r#"#include <dlfcn.h>
+#include <stdio.h>
+int main(void) {
+    void *h = dlopen("out/plugins/greet.so", RTLD_NOW);
+    if (!h) { printf("dlopen: %s\n", dlerror()); return 1; }
+    int (*greet)(void) = (int (*)(void))dlsym(h, "greet_value");
+    printf("greet=%d hidden=%s\n", greet ? greet() : -1,
+           dlsym(h, "hidden_helper") ? "visible" : "none");
+    return 0;
+}
+"#,
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "plugtest"
+plugins_dir = "plugins"
+
+[target.greet]
+target_type = "plugin"
+source_dir = "greet"
+exports = "greet_value"
+
+[target.host]
+source_dir = "host"
+link_libs = "dl"
+deps = "greet"
+"#).unwrap();
+
+    let out = run_drakkar(&["build", "host"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("out/plugins/greet.so").is_file());
+
+    let run = Command::new(workspace.join("out/host"))
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&run.stdout);
+    assert!(stdout.contains("greet=42 hidden=none"), "stdout:\n{}", stdout);
+
+    // A plugin is not something `drakkar run` can start
+    let out = run_drakkar(&["run", "greet"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("is a plugin"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 24: compress_binary packs release executables and reports sizes
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_compress_binary() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("compress");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+
+    // Stand-in for upx: records its arguments and shrinks the file
+    let fake_upx = workspace.join("fake-upx");
+    fs::write(&fake_upx, "#!/bin/sh\necho \"$@\" > upx-args.txt\nfor f; do :; done\nhead -c 100 \"$f\" > \"$f.tmp\" && cat \"$f.tmp\" > \"$f\" && rm \"$f.tmp\"\n").unwrap();
+    fs::set_permissions(&fake_upx, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), format!(r#"
+app_name = "packed"
+compress_binary = "true"
+upx_path = "{} --best"
+"#, fake_upx.display())).unwrap();
+
+    // Debug builds are left alone
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!workspace.join("upx-args.txt").exists());
+
+    let out = run_drakkar(&["build", "release"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Compressed\x1b[0m out/packed:"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("→ 100 B"), "stdout:\n{}", stdout);
+    let args = fs::read_to_string(workspace.join("upx-args.txt")).unwrap();
+    assert_eq!(args.trim(), "--best -q out/packed");
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 25: watch rebuilds after changes, including after a failed build
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_watch_rebuilds_on_change() {
+    use std::io::{BufRead, BufReader};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let workspace = temp_workspace("watch");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"watched\"\n").unwrap();
+
+    let mut child = Command::new(drakkar_bin())
+        .arg("watch")
+        .current_dir(&workspace)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || {
+        for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+    let wait_for = |needle: &str| {
+        while let Ok(line) = rx.recv_timeout(Duration::from_secs(20)) {
+            if line.contains(needle) {
+                return line;
+            }
+        }
+        panic!("drakkar watch never printed {:?}", needle);
+    };
+
+    wait_for("Finished");
+    wait_for("Watching");
+
+    // A compile error is reported and watching continues
+    fs::write(workspace.join("src/main.cpp"), "int main() { return }\n").unwrap();
+    assert!(wait_for("Changed").contains("main.cpp"));
+    wait_for("Watching");
+
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 1; }\n").unwrap();
+    wait_for("Changed");
+    wait_for("Finished");
+
+    let _ = child.kill();
+    let _ = child.wait();
+    let status = Command::new(workspace.join("out/watched")).status().unwrap();
+    assert_eq!(status.code(), Some(1));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 26: post_link steps rerun only when the binary or the steps change
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_post_link_pipeline() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("post_link");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    let tag = workspace.join("tag-step");
+    fs::write(&tag, "#!/bin/sh\necho \"$@\" >> post-link.log\n").unwrap();
+    fs::set_permissions(&tag, fs::Permissions::from_mode(0o755)).unwrap();
+    let config = |steps: &str| {
+        fs::write(
+            workspace.join("config.txt"),
+            format!("app_name = \"tagged\"\npost_link = \"strip; {} {}\"\n", tag.display(), steps),
+        )
+        .unwrap();
+    };
+    let log = || fs::read_to_string(workspace.join("post-link.log")).unwrap_or_default();
+
+    config("first {exe} done");
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(log(), "first out/tagged done\n");
+    assert!(workspace.join("target/linked/tagged").is_file());
+    // `strip` ran on the copy in out/, not on the linked binary
+    let linked = fs::metadata(workspace.join("target/linked/tagged")).unwrap().len();
+    assert!(fs::metadata(workspace.join("out/tagged")).unwrap().len() < linked);
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Up-to-date"), "stdout:\n{}", stdout);
+    assert_eq!(log().lines().count(), 1);
+
+    // New steps rerun on the existing link
+    config("second");
+    let out = run_drakkar(&["build"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Post-processing"), "stdout:\n{}", stdout);
+    assert!(!stdout.contains("Linking"), "stdout:\n{}", stdout);
+    assert!(log().ends_with("second out/tagged\n"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 27: drakkar test links each tests/ file against the project
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_test_command() {
+    let workspace = temp_workspace("test_cmd");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("tests/math")).unwrap();
+    fs::write(workspace.join("src/add.h"), "int add(int a, int b);\n").unwrap();
+    fs::write(workspace.join("src/add.cpp"), "int add(int a, int b) { return a + b; }\n").unwrap();
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "#include \"add.h\"\nint main() { return add(1, 2) == 3 ? 0 : 1; }\n",
+    ).unwrap();
+    fs::write(
+        workspace.join("tests/add_test.cpp"),
+        "#include \"add.h\"\nint main() { return add(2, 2) == 4 ? 0 : 1; }\n",
+    ).unwrap();
+    fs::write(
+        workspace.join("tests/math/broken_test.cpp"),
+        "#include <cstdio>\n#include \"add.h\"\nint main() { std::puts(\"expected 5\"); return add(2, 2) == 5 ? 0 : 1; }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"tested\"\n").unwrap();
+
+    let out = run_drakkar(&["test"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert_eq!(out.status.code(), Some(1), "stdout:\n{}", stdout);
+    assert!(stdout.contains("test add_test ... \x1b[32mok"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("test math/broken_test ... \x1b[31mFAILED"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("---- math/broken_test output ----\nexpected 5"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("1 passed; 1 failed"), "stdout:\n{}", stdout);
+    // The application itself is not linked
+    assert!(!workspace.join("out/tested").exists());
+
+    let out = run_drakkar(&["test", "add"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "stdout:\n{}", stdout);
+    assert!(stdout.contains("1 passed; 0 failed"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 28: release binaries are signed last with sign_identity
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_sign_release_binary() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("sign");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    let signer = workspace.join("fake-sign");
+    fs::write(&signer, "#!/bin/sh\necho \"$@\" >> sign.log\n").unwrap();
+    fs::set_permissions(&signer, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), format!(r#"
+app_name = "signed"
+post_link = "true"
+sign_command = "{} --key {{identity}}"
+sign_identity = "release.pem"
+"#, signer.display())).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!workspace.join("sign.log").exists());
+
+    let out = run_drakkar(&["build", "release"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Signed\x1b[0m out/signed"), "stdout:\n{}", stdout);
+    let log = fs::read_to_string(workspace.join("sign.log")).unwrap();
+    assert_eq!(log, "--key release.pem out/signed\n");
+
+    // A failing signer fails the build
+    fs::write(&signer, "#!/bin/sh\necho 'no such identity' >&2\nexit 1\n").unwrap();
+    fs::write(workspace.join("config.txt"), format!(r#"
+app_name = "signed"
+sign_command = "{}"
+sign_identity = "other.pem"
+"#, signer.display())).unwrap();
+    let out = run_drakkar(&["build", "release"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no such identity"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 29: rebuild_policy = "hash" ignores timestamps of unchanged files
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_hash_rebuild_policy() {
+    let workspace = temp_workspace("hash_policy");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.h"), "#define ANSWER 42\n").unwrap();
+    fs::write(workspace.join("src/util.cpp"), "#include \"util.h\"\nint answer() { return ANSWER; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int answer();\nint main() { return answer() == 42 ? 0 : 1; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "hashed"
+rebuild_policy = "hash"
+"#).unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    // A cache restore: same content, fresh timestamps
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    for file in ["src/util.h", "src/util.cpp", "src/main.cpp"] {
+        let content = fs::read(workspace.join(file)).unwrap();
+        fs::write(workspace.join(file), content).unwrap();
+    }
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("All up-to-date"), "stdout:\n{}", stdout);
+
+    fs::write(workspace.join("src/util.h"), "#define ANSWER 43\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("1 rebuilt (1 header changed)"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 30: checkouts sharing shared_temp_dir reuse each other's objects
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_shared_temp_dir_across_checkouts() {
+    let workspace = temp_workspace("shared_temp");
+    let shared = workspace.join("shared");
+    for checkout in ["main", "feature"] {
+        let root = workspace.join(checkout);
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/util.h"), "#define ANSWER 42\n").unwrap();
+        fs::write(root.join("src/util.cpp"), "#include \"util.h\"\nint answer() { return ANSWER; }\n").unwrap();
+        fs::write(root.join("src/main.cpp"), "int answer();\nint main() { return answer() == 42 ? 0 : 1; }\n").unwrap();
+        fs::write(root.join("config.txt"), format!(r#"
+app_name = "shared"
+shared_temp_dir = "{}"
+"#, shared.display())).unwrap();
+    }
+
+    let out = run_drakkar(&["build"], &workspace.join("main"));
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Compiled\x1b[0m 2 file(s)"));
+
+    let out = run_drakkar(&["build"], &workspace.join("feature"));
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Reused\x1b[0m 2 object(s) from shared_temp_dir"), "stdout:\n{}", stdout);
+    let status = Command::new(workspace.join("feature/out/shared")).status().unwrap();
+    assert!(status.success());
+
+    // The branch changes a header: only its includer is compiled
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(workspace.join("feature/src/util.h"), "#define ANSWER 42 // same value\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace.join("feature"));
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Compiled\x1b[0m 1 file(s)"), "stdout:\n{}", stdout);
+    assert!(!stdout.contains("Reused"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 31: executor = "slurm" submits each compile through srun
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_slurm_executor() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("slurm");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("bin")).unwrap();
+    fs::write(workspace.join("src/util.cpp"), "int answer() { return 42; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int answer();\nint main() { return answer() == 42 ? 0 : 1; }\n").unwrap();
+    // Logs its arguments and runs the command after the options
+    let srun = workspace.join("bin/srun");
+    fs::write(&srun, format!(
+        "#!/bin/sh\necho \"$@\" >> {}\nwhile [ \"${{1#-}}\" != \"$1\" ]; do shift; done\nexec \"$@\"\n",
+        workspace.join("srun.log").display()
+    )).unwrap();
+    fs::set_permissions(&srun, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "batch"
+executor = "slurm"
+executor_args = "--partition=build"
+"#).unwrap();
+
+    let path = format!("{}:{}", workspace.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+    let build = || {
+        Command::new(drakkar_bin())
+            .arg("build")
+            .current_dir(&workspace)
+            .env("PATH", &path)
+            .output()
+            .unwrap()
+    };
+
+    let out = build();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let log = fs::read_to_string(workspace.join("srun.log")).unwrap();
+    assert_eq!(log.lines().count(), 2, "log:\n{}", log);
+    assert!(log.lines().all(|l| l.starts_with("--quiet --ntasks=1 --partition=build g++ -c ")), "log:\n{}", log);
+    assert!(Command::new(workspace.join("out/batch")).status().unwrap().success());
+
+    // Depfiles come back from the job, so nothing is resubmitted
+    let out = build();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("All up-to-date"));
+    assert_eq!(fs::read_to_string(workspace.join("srun.log")).unwrap(), log);
+
+    // Compile errors from the job are reported as usual
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(workspace.join("src/util.cpp"), "int answer() { return undefined_name; }\n").unwrap();
+    let out = build();
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("undefined_name"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 32: target = "<triple>" uses prefixed gcc and skips run
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_cross_target() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("cross_target");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("bin")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    // Stands in for a cross compiler by logging and calling the host g++
+    let cross = workspace.join("bin/riscv64-linux-gnu-g++");
+    fs::write(&cross, format!(
+        "#!/bin/sh\necho \"$@\" >> {}\nexec g++ \"$@\"\n",
+        workspace.join("cross.log").display()
+    )).unwrap();
+    fs::set_permissions(&cross, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), r#"
+app_name = "cross"
+target = "riscv64-linux-gnu"
+"#).unwrap();
+
+    let path = format!("{}:{}", workspace.join("bin").display(), std::env::var("PATH").unwrap_or_default());
+    let out = Command::new(drakkar_bin())
+        .arg("run")
+        .current_dir(&workspace)
+        .env("PATH", &path)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(stdout.contains("Not running"), "stdout:\n{}", stdout);
+    assert!(workspace.join("target/riscv64-linux-gnu/main.o").exists());
+    // Compile and link both went through the prefixed compiler
+    let log = fs::read_to_string(workspace.join("cross.log")).unwrap();
+    assert_eq!(log.lines().count(), 2, "log:\n{}", log);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 33: --explain-commands annotates compile and link commands
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_explain_commands() {
+    let workspace = temp_workspace("explain");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"explained\"\ncxx_flags = \"-Wall\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--explain-commands"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("$ g++ -c src/main.cpp"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("enable the common warnings"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("-MF target/main.d"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("-o out/explained"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 34: DRAKKAR_LANG translates progress and error labels
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_drakkar_lang() {
+    let workspace = temp_workspace("lang");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"sprache\"\n").unwrap();
+
+    let out = Command::new(drakkar_bin())
+        .arg("build")
+        .env("DRAKKAR_LANG", "de_DE.UTF-8")
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Kompiliere\x1b[0m [1/1] src/main.cpp"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("Fertig\x1b[0m"), "stdout:\n{}", stdout);
+
+    fs::write(workspace.join("config.txt"), "app_name = \"sprache\"\ntoolchain = \"borland\"\n").unwrap();
+    let out = Command::new(drakkar_bin())
+        .arg("build")
+        .env("DRAKKAR_LANG", "de")
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("Fehler[E0002]"), "stderr:\n{}", stderr);
+
+    let out = Command::new(drakkar_bin())
+        .arg("help")
+        .env("DRAKKAR_LANG", "tlh")
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    assert!(String::from_utf8_lossy(&out.stdout).contains("USAGE:"));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("not a supported language"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 35: status_board falls back to plain lines when piped
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_status_board_not_a_terminal() {
+    let workspace = temp_workspace("status_board");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"board\"\nstatus_board = \"true\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Compiling\x1b[0m [1/1] src/main.cpp"), "stdout:\n{}", stdout);
+    assert!(!stdout.contains("\x1b[?7l"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 36: recorded compile times order the next build and give an ETA
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_compile_time_eta() {
+    let workspace = temp_workspace("eta");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"eta\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains(" left)"));
+
+    // New flags rebuild everything, now with a recorded time to go by
+    fs::write(workspace.join("config.txt"), "app_name = \"eta\"\ncxx_flags = \"-Wall\"\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("main.cpp \x1b[2m(~"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("s left)"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 37: lto applies matching flags to compile and link
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_lto_profile() {
+    let workspace = temp_workspace("lto");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.cpp"), "int twice(int x) { return 2 * x; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int twice(int);\nint main() { return twice(0); }\n").unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"lto\"\nparallel_jobs = \"3\"\nlto = \"full\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build", "release", "-v"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let compiles: Vec<&str> = stdout.lines().filter(|l| l.contains("$ g++ -c")).collect();
+    assert_eq!(compiles.len(), 2, "stdout:\n{}", stdout);
+    assert!(compiles.iter().all(|l| l.contains(" -flto ")), "stdout:\n{}", stdout);
+    assert!(stdout.contains("-flto=3 -flto-partition=one"), "stdout:\n{}", stdout);
+
+    // Debug stays without LTO
+    let out = run_drakkar(&["build", "debug", "-v"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("-flto"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 38: unwritable temp_dir errors, or falls back with --fallback-temp
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_fallback_temp() {
+    let workspace = temp_workspace("fallback_temp");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() { return 0; }\n").unwrap();
+    // A file where a directory should be: unwritable even for root
+    fs::write(workspace.join("blocker"), "").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"ro\"\ntemp_dir = \"blocker/tmp\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("\"blocker/tmp\" is not writable"), "stderr:\n{}", stderr);
+    assert!(stderr.contains("--fallback-temp"), "stderr:\n{}", stderr);
+
+    let cache = workspace.join("cache");
+    let out = Command::new(drakkar_bin())
+        .args(["build", "--fallback-temp"])
+        .env("XDG_CACHE_HOME", &cache)
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let temp_dirs: Vec<_> = fs::read_dir(cache.join("drakkar/temp")).unwrap().collect();
+    assert_eq!(temp_dirs.len(), 1);
+    assert!(workspace.join("out/ro").exists());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 39: object_cache restores objects after clean
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_object_cache() {
+    let workspace = temp_workspace("object_cache");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.cpp"), "int twice(int x) { return 2 * x; }\n").unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int twice(int);\nint main() { return twice(0); }\n").unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"cached\"\nobject_cache = \"true\"\nobject_cache_dir = \"cache\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Cache\x1b[0m 0 hit(s), 2 miss(es)"), "stdout:\n{}", stdout);
+
+    assert!(run_drakkar(&["clean"], &workspace).status.success());
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Cache\x1b[0m 2 hit(s), 0 miss(es)"), "stdout:\n{}", stdout);
+    assert!(!stdout.contains("Compiling"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 40: build --simulate schedules without compiling
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_simulate() {
+    let workspace = temp_workspace("simulate");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("src/util.c"), "int util(void) { return 1; }\n").unwrap();
+    // A compiler that doesn't exist: the simulation must not need one
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"sim\"\ngcc_path = \"no-such-gcc\"\ngpp_path = \"no-such-g++\"\nparallel_jobs = \"2\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build", "--simulate"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Simulated\x1b[0m 2 compile(s) on 2 worker(s)"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("assumed 1s each"), "stdout:\n{}", stdout);
+    assert!(!workspace.join("target/main.o").exists());
+    assert!(!workspace.join("target/.drakkar_state").exists());
+    assert!(!workspace.join("out/sim").exists());
+
+    let out = run_drakkar(&["run", "--simulate"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--simulate only works with"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 41: remote_cache shares objects between checkouts
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_remote_cache() {
+    let root = temp_workspace("remote_cache");
+    let remote = root.join("remote");
+    let checkout = |name: &str, push: bool| {
+        let dir = root.join(name);
+        fs::create_dir_all(dir.join("src")).unwrap();
+        fs::write(dir.join("src/util.c"), "int twice(int x) { return 2 * x; }\n").unwrap();
+        fs::write(dir.join("src/main.c"), "int twice(int);\nint main(void) { return twice(0); }\n").unwrap();
+        fs::write(
+            dir.join("config.txt"),
+            format!(
+                "app_name = \"shared\"\nobject_cache_dir = \"cache\"\nremote_cache = \"{}\"\nremote_cache_push = \"{}\"\n",
+                remote.display(),
+                push
+            ),
+        )
+        .unwrap();
+        dir
+    };
+    let (ci, dev, laptop) = (checkout("ci", true), checkout("dev", false), checkout("laptop", false));
+
+    let out = run_drakkar(&["build"], &ci);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let out = run_drakkar(&["cache", "push"], &ci);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Pushed\x1b[0m 0 object(s)") && stdout.contains("2 already there"), "stdout:\n{}", stdout);
+
+    // Another checkout path compiles nothing
+    let out = run_drakkar(&["build"], &dev);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("Cache\x1b[0m 2 hit(s) (2 from the remote cache), 0 miss(es)"),
+        "stdout:\n{}",
+        stdout
+    );
+    let out = run_drakkar(&["cache", "stats"], &dev);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("2 object(s)"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("2 hit(s) (2 from the remote cache), 0 miss(es)"), "stdout:\n{}", stdout);
+
+    let out = run_drakkar(&["cache", "pull"], &laptop);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Pulled\x1b[0m 2 object(s)"), "stdout:\n{}", stdout);
+
+    fs::write(root.join("config.txt"), "app_name = \"local\"\n").unwrap();
+    let out = run_drakkar(&["cache", "push"], &root);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no remote_cache configured"));
+
+    let _ = fs::remove_dir_all(&root);
+}
+
+// ─────────────────────────────────────────────
+// Test 42: --message-format=json streams build events on stdout
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_json_events() {
+    let workspace = temp_workspace("json_events");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.c"), "int helper(void) { int unused; return 1; }\n").unwrap();
+    fs::write(workspace.join("src/main.c"), "int helper(void);\nint main(void) { return helper() - 1; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"events\"\nc_flags = \"-Wall\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--message-format=json"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let events: Vec<&str> = stdout.lines().collect();
+    assert!(events.iter().all(|e| e.starts_with("{\"event\":\"") && e.ends_with('}')), "stdout:\n{}", stdout);
+    let count = |name: &str| events.iter().filter(|e| e.starts_with(&format!("{{\"event\":\"{}\"", name))).count();
+    assert_eq!((count("compile-start"), count("compile-finish"), count("link")), (2, 2, 1), "stdout:\n{}", stdout);
+    assert!(stdout.contains("\"file\":\"src/util.c\",\"line\":1,\"col\":24,\"severity\":\"warning\""), "stdout:\n{}", stdout);
+    assert!(events.last().unwrap().starts_with("{\"event\":\"done\",\"success\":true"));
+    // Progress still shows, on stderr
+    assert!(String::from_utf8_lossy(&out.stderr).contains("Linking"));
+
+    fs::write(workspace.join("src/util.c"), "int helper(void) { return nope; }\n").unwrap();
+    let out = run_drakkar(&["build", "--message-format=json"], &workspace);
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("\"severity\":\"error\""), "stdout:\n{}", stdout);
+    assert!(stdout.contains("{\"event\":\"compile-finish\",\"src\":\"src/util.c\",\"success\":false"));
+    assert!(stdout.lines().last().unwrap().contains("\"success\":false,"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 43: --output-format=vscode normalizes diagnostics
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_vscode_output() {
+    let workspace = temp_workspace("vscode_output");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { int unused; return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"vs\"\nc_flags = \"-Wall\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--output-format=vscode"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(lines.first(), Some(&"[drakkar] build started"));
+    assert_eq!(lines.last(), Some(&"[drakkar] build finished"));
+    assert!(lines.contains(&"src/main.c(1,22): warning: unused variable 'unused' [-Wunused-variable]"), "stdout:\n{}", stdout);
+    // The compiler's own rendering is not repeated
+    assert!(!String::from_utf8_lossy(&out.stderr).contains("main.c:1:22"));
+
+    fs::write(workspace.join("src/main.c"), "int main(void) { return nope; }\n").unwrap();
+    let out = run_drakkar(&["build", "--message-format", "vscode"], &workspace);
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("src/main.c(1,25): error: 'nope' undeclared"), "stdout:\n{}", stdout);
+    assert_eq!(stdout.lines().last(), Some("[drakkar] build failed"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 44: --trace-out writes a Chrome trace of the build
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_trace_out() {
+    let workspace = temp_workspace("trace_out");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.c"), "int twice(int x) { return 2 * x; }\n").unwrap();
+    fs::write(workspace.join("src/main.c"), "int twice(int);\nint main(void) { return twice(0); }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"traced\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--trace-out", "build.json"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let trace = fs::read_to_string(workspace.join("build.json")).unwrap();
+    assert!(trace.starts_with("{\"traceEvents\":["), "{}", trace);
+    for name in ["main.c", "util.c"] {
+        assert!(trace.contains(&format!("{{\"name\":\"{}\",\"cat\":\"compile\",\"ph\":\"X\"", name)), "{}", trace);
+    }
+    assert!(trace.contains("\"cat\":\"link\""), "{}", trace);
+    assert!(trace.contains("\"args\":{\"name\":\"worker 1\"}"), "{}", trace);
+
+    // Rewritten on the next build, with no compiles this time
+    let out = run_drakkar(&["build", "--trace-out=build.json"], &workspace);
+    assert!(out.status.success());
+    let trace = fs::read_to_string(workspace.join("build.json")).unwrap();
+    assert!(!trace.contains("\"cat\":\"compile\""), "{}", trace);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 45: build --compare builds with both compilers and compares them
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_compare_compilers() {
+    let workspace = temp_workspace("compare_compilers");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"ab\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--compiler", "gcc", "--compare", "gcc"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(stdout.contains("Comparing\x1b[0m gcc (1/2)"), "{}", stdout);
+    assert!(stdout.contains("Comparing\x1b[0m gcc (2/2)"), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("build time ")), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("warnings ")), "{}", stdout);
+    assert!(stdout.lines().any(|l| l.starts_with("ab ") && l.ends_with("+0.0%")), "{}", stdout);
+
+    // Both sides build apart from the regular build
+    let compare_dir = workspace.join("target/compare");
+    assert!(compare_dir.join("1/out/ab").exists());
+    assert!(compare_dir.join("2/out/ab").exists());
+    assert!(!workspace.join("out/ab").exists());
+
+    let out = run_drakkar(&["run", "--compare", "gcc"], &workspace);
+    assert!(!out.status.success());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 46: Conflicting flags warn and the last one is used
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_flag_conflicts() {
+    let workspace = temp_workspace("flag_conflicts");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    // Only compiles as C++17 or later
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "#include <optional>\nint main() { std::optional<int> x; return x.value_or(0); }\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"flags\"\ncxx_flags = \"-Wall -std=c++11 -Wall\"\ncxx_standard = \"c++17\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build", "--verbose"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "stderr:\n{}", stderr);
+    assert!(
+        stderr.contains("conflicting flags '-std=c++11' and '-std=c++17'; using '-std=c++17'"),
+        "{}",
+        stderr
+    );
+    assert_eq!(stderr.matches("conflicting flags").count(), 1, "{}", stderr);
+    let command = stdout.lines().find(|l| l.contains("main.cpp") && l.contains("-std=")).unwrap();
+    assert!(!command.contains("-std=c++11"), "{}", command);
+    assert_eq!(command.matches("-Wall").count(), 1, "{}", command);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 47: --verbose quotes arguments so the command can be re-run
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_verbose_quoting() {
+    let workspace = temp_workspace("verbose_quoting");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include <string.h>\nint main(void) { return strcmp(MSG, \"hello world\") != 0; }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"quoted\"\n").unwrap();
+
+    let out = run_drakkar(&["build", "--verbose", "--", "-DMSG=\"hello world\""], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let command = stdout.lines().find(|l| l.contains("$ ") && l.contains("main.c")).unwrap();
+    assert!(command.contains(" '-DMSG=\"hello world\"'"), "{}", command);
+
+    // The echoed command compiles the same object
+    let echoed = command.split("$ ").nth(1).unwrap().trim_end_matches("\x1b[0m");
+    let status = Command::new("sh").arg("-c").arg(echoed).current_dir(&workspace).status().unwrap();
+    assert!(status.success(), "{}", echoed);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 48: graph prints the include graph from the depfiles
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_include_graph() {
+    let workspace = temp_workspace("include_graph");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("include")).unwrap();
+    fs::write(workspace.join("include/common.h"), "#pragma once\n#define ANSWER 42\n").unwrap();
+    fs::write(workspace.join("include/extra.h"), "#pragma once\n").unwrap();
+    fs::write(workspace.join("src/util.c"), "#include \"common.h\"\nint answer(void) { return ANSWER; }\n").unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"common.h\"\n#include \"extra.h\"\nint answer(void);\nint main(void) { return answer() - ANSWER; }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"graph\"\ninclude_dirs = \"include\"\n").unwrap();
+
+    // Nothing built yet
+    let out = run_drakkar(&["graph"], &workspace);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("2 source(s) have not been built yet"));
+
+    assert!(run_drakkar(&["build"], &workspace).status.success());
+    let out = run_drakkar(&["graph"], &workspace);
+    let dot = String::from_utf8_lossy(&out.stdout);
+    assert!(out.status.success());
+    assert!(dot.starts_with("digraph includes {"), "{}", dot);
+    assert!(dot.contains("\"src/main.c\" -> \"include/extra.h\";"), "{}", dot);
+    assert!(dot.contains("\"include/common.h\" [shape=ellipse, label=\"include/common.h\\n2 of 2\""), "{}", dot);
+    assert!(!dot.contains("\"src/util.c\" -> \"include/extra.h\""), "{}", dot);
+
+    let out = run_drakkar(&["graph", "--json"], &workspace);
+    let json = String::from_utf8_lossy(&out.stdout);
+    assert!(json.contains("{\"path\":\"include/common.h\",\"included_by\":2}"), "{}", json);
+    assert!(json.trim_end().ends_with("\"unbuilt\":0}"), "{}", json);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 49: Changing a tracked_env variable rebuilds
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_tracked_env() {
+    let workspace = temp_workspace("tracked_env");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"env\"\ntracked_env = \"DRAKKAR_TEST_SDK\"\n").unwrap();
+
+    let build = |sdk: Option<&str>| {
+        let mut command = Command::new(drakkar_bin());
+        command.args(["build", "--verbose"]).current_dir(&workspace);
+        match sdk {
+            Some(sdk) => command.env("DRAKKAR_TEST_SDK", sdk),
+            None => command.env_remove("DRAKKAR_TEST_SDK"),
+        };
+        let out = command.output().unwrap();
+        assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+
+    build(Some("13.0"));
+    assert!(build(Some("13.0")).contains("All up-to-date"));
+    let changed = build(Some("14.0"));
+    assert!(changed.contains("main.c: flags changed"), "{}", changed);
+    let unset = build(None);
+    assert!(unset.contains("main.c: flags changed"), "{}", unset);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 50: Generator rules write sources that are compiled and rerun on change
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_generator_rules() {
+    let workspace = temp_workspace("generator_rules");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("defs")).unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"answer.h\"\nint main(void) { return answer(); }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("defs/answer.def"), "3\n").unwrap();
+    fs::write(
+        workspace.join("gen.sh"),
+        "echo \"int $3(void) { return $(cat \"$1\"); }\" > \"$2/$3.c\"\n\
+         echo \"int $3(void);\" > \"$2/$3.h\"\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"generated\"\n\
+         generator.defs.inputs = \"defs/*.def\"\n\
+         generator.defs.command = \"sh gen.sh {input} {out_dir} {stem}\"\n\
+         generator.defs.outputs = \"{stem}.c {stem}.h\"\n",
+    )
+    .unwrap();
+
+    let build = || {
+        let out = run_drakkar(&["build"], &workspace);
+        assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    let run = || Command::new(workspace.join("out/generated")).status().unwrap().code();
+
+    let first = build();
+    assert!(first.contains("Generating\x1b[0m defs/answer.def (defs)"), "{}", first);
+    assert!(workspace.join("target/gen/defs/answer.c").is_file());
+    assert_eq!(run(), Some(3));
+
+    let second = build();
+    assert!(!second.contains("Generating"), "{}", second);
+    assert!(second.contains("All up-to-date"), "{}", second);
+
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(workspace.join("defs/answer.def"), "4\n").unwrap();
+    let third = build();
+    assert!(third.contains("defs/answer.def (defs)"), "{}", third);
+    assert_eq!(run(), Some(4));
+
+    fs::write(workspace.join("gen.sh"), "exit 1\n").unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(workspace.join("defs/answer.def"), "5\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("answer.def"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 51: `drakkar state` shows what the last build recorded
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_state_command() {
+    let workspace = temp_workspace("state_command");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/util.h"), "#define ANSWER 0\n").unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"util.h\"\nint main(void) { return ANSWER; }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"state\"\n").unwrap();
+
+    let out = run_drakkar(&["state"], &workspace);
+    assert!(out.status.success());
+    assert!(String::from_utf8_lossy(&out.stdout).contains("No recorded state yet"));
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+
+    let out = run_drakkar(&["state"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let text = String::from_utf8_lossy(&out.stdout);
+    assert!(text.contains("target/main.o\n  flags    "), "{}", text);
+    assert!(text.contains("src/util.h"), "{}", text);
+
+    let out = run_drakkar(&["state", "--json"], &workspace);
+    let json = String::from_utf8_lossy(&out.stdout);
+    assert!(json.starts_with("[{\"path\":\""), "{}", json);
+    assert!(json.contains("\"inputs\":[\"src/main.c\",\"src/util.h\"]"), "{}", json);
+    assert_eq!(json.matches("\"flags_hash\"").count(), 2, "{}", json);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 52: sandbox = "bwrap" hides undeclared headers
+// ─────────────────────────────────────────────
+
+#[cfg(target_os = "linux")]
+#[test]
+fn test_sandbox_bwrap() {
+    let workspace = temp_workspace("sandbox_bwrap");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("undeclared")).unwrap();
+    fs::write(workspace.join("undeclared/extra.h"), "#define EXTRA 0\n").unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        format!(
+            "#include \"{}\"\nint main(void) {{ return EXTRA; }}\n",
+            workspace.join("undeclared/extra.h").display()
+        ),
+    )
+    .unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"sandboxed\"\nsandbox = \"bwrap\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    let bwrap = Command::new("bwrap").args(["--ro-bind", "/", "/", "true"]).status();
+    if bwrap.map(|s| s.success()).unwrap_or(false) {
+        // Compiles, but can't see the header outside source_dir
+        assert!(stderr.contains("extra.h"), "{}", stderr);
+    } else {
+        assert!(stderr.contains("sandbox = \"bwrap\": 'bwrap' is not an executable"), "{}", stderr);
+    }
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 53: A header missing from include_dirs is found, and added with --fix
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_missing_header_fix() {
+    let workspace = temp_workspace("missing_header_fix");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("third_party/v/include")).unwrap();
+    fs::write(workspace.join("third_party/v/include/vendored.h"), "#define VENDORED 0\n").unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"vendored.h\"\nint main(void) { return VENDORED; }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"fixme\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(
+        stderr.contains("'vendored.h' is in third_party/v/include: add it to include_dirs"),
+        "{}",
+        stderr
+    );
+
+    let out = run_drakkar(&["build", "--fix"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("added third_party/v/include"));
+    let config = fs::read_to_string(workspace.join("config.txt")).unwrap();
+    assert!(config.contains("include_dirs = \"third_party/v/include\"\n"), "{}", config);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 54: Paths are project-relative from a subdirectory, or absolute
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_project_relative_paths() {
+    let workspace = temp_workspace("relative_paths");
+    fs::create_dir_all(workspace.join("src/net")).unwrap();
+    fs::write(workspace.join("src/net/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"paths\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace.join("src/net"));
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Compiling\x1b[0m [1/1] src/net/main.c"), "{}", stdout);
+    assert!(stdout.contains("Linking\x1b[0m out/paths"), "{}", stdout);
+    assert!(workspace.join("target").is_dir() && !workspace.join("src/net/target").exists());
+
+    fs::write(
+        workspace.join("src/net/main.c"),
+        format!("#include \"{}\"\nint main(void) {{ return 0; }}\n", workspace.join("gone.h").display()),
+    )
+    .unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(stderr.contains("fatal error: gone.h"), "{}", stderr);
+    assert!(!stderr.contains(&workspace.display().to_string()), "{}", stderr);
+
+    fs::write(workspace.join("src/net/main.c"), "int main(void) { return 1; }\n").unwrap();
+    let out = run_drakkar(&["build", "--absolute-paths"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let main = workspace.join("src/net/main.c");
+    assert!(stdout.contains(&format!("[1/1] {}", main.display())), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 55: on_event receives build events as JSON lines on stdin
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_on_event_hook() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("on_event");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    let hook = workspace.join("notify.sh");
+    fs::write(&hook, "#!/bin/sh\ncat >> events.log\n").unwrap();
+    fs::set_permissions(&hook, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"hooked\"\non_event = \"./notify.sh\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let log = fs::read_to_string(workspace.join("events.log")).unwrap();
+    let lines: Vec<&str> = log.lines().collect();
+    assert_eq!(lines.len(), 3, "{}", log);
+    assert_eq!(lines[0], "{\"event\":\"build_started\",\"app\":\"hooked\"}");
+    assert!(lines[1].starts_with("{\"event\":\"file_compiled\",\"src\":\"src/main.c\",\"success\":true"));
+    assert!(lines[2].starts_with("{\"event\":\"build_finished\""), "{}", log);
+
+    fs::write(workspace.join("src/main.c"), "int main(void) { return }\n").unwrap();
+    fs::remove_file(workspace.join("events.log")).unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    let log = fs::read_to_string(workspace.join("events.log")).unwrap();
+    assert!(log.contains("\"event\":\"file_compiled\",\"src\":\"src/main.c\",\"success\":false"), "{}", log);
+    assert!(log.lines().last().unwrap().starts_with("{\"event\":\"build_failed\""), "{}", log);
+
+    // What the hook prints stays out of the JSON event stream
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(&hook, "#!/bin/sh\nwhile read line; do echo got event; done\n").unwrap();
+    let out = run_drakkar(&["build", "--message-format=json"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.lines().all(|l| l.starts_with("{\"event\"")), "{}", stdout);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("got event"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 56: A compiler crash is recognized, and --save-ice-repro bundles it
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_save_ice_repro() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("ice_repro");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "#define N 1\nint main(void) { return N - 1; }\n").unwrap();
+    // Preprocesses and reports its version like gcc, crashes on -c
+    let crashing = workspace.join("crashcc");
+    fs::write(
+        &crashing,
+        "#!/bin/sh\ncase \" $* \" in *\" -E \"*|*\" --version \"*) exec gcc \"$@\";; esac\n\
+         echo 'src/main.c:2:1: internal compiler error: Segmentation fault' >&2\nexit 4\n",
+    )
+    .unwrap();
+    fs::set_permissions(&crashing, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"ice\"\ngcc_path = \"./crashcc\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(!out.status.success());
+    assert!(stderr.contains("the compiler crashed") && stderr.contains("--save-ice-repro"), "{}", stderr);
+    assert!(!workspace.join("target/ice").exists());
+
+    let out = run_drakkar(&["build", "--save-ice-repro"], &workspace);
+    assert!(!out.status.success());
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("repro for src/main.c to target/ice/main.tar"), "{}", stdout);
+    let bundle = fs::read(workspace.join("target/ice/main.tar")).unwrap();
+    let text = String::from_utf8_lossy(&bundle);
+    for name in ["main-ice/main.i", "main-ice/command.txt", "main-ice/version.txt", "main-ice/stderr.txt"] {
+        assert!(text.contains(name), "{} missing", name);
+    }
+    assert!(text.contains("return 1 - 1;"), "not preprocessed");
+    assert!(text.contains("-c main.i -o main.o"), "{}", text);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 57: A workspace builds its members in dependency order, or one with -p
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_workspace_members() {
+    let workspace = temp_workspace("workspace");
+    let project = |dir: &str, config: &str| {
+        fs::create_dir_all(workspace.join(dir).join("src")).unwrap();
+        fs::write(workspace.join(dir).join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+        fs::write(workspace.join(dir).join("config.txt"), config).unwrap();
+    };
+    project("app", "app_name = \"app\"\ndeps = \"../libs/math\"\n");
+    project("libs/math", "app_name = \"math\"\n");
+    project("tools/gen", "app_name = \"gen\"\n");
+    fs::write(
+        workspace.join("config.txt"),
+        "[workspace]\nmembers = \"app libs/math tools/gen\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("3 member(s): libs/math, app, tools/gen"), "{}", stdout);
+    assert!(stdout.contains("Compiling\x1b[0m [1/1] libs/math/src/main.c"), "{}", stdout);
+    for exe in ["app/out/app", "libs/math/out/math", "tools/gen/out/gen"] {
+        assert!(workspace.join(exe).is_file(), "{} not built", exe);
+    }
+    assert!(!workspace.join("target").exists());
+
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let out = run_drakkar(&["build", "-p", "app"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("2 member(s): libs/math, app"), "{}", stdout);
+    assert!(!stdout.contains("tools/gen"), "{}", stdout);
+
+    let out = run_drakkar(&["build", "-p", "web"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("no workspace member named 'web'"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 58: promote copies a build into a channel, then between channels
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_promote_channels() {
+    let workspace = temp_workspace("promote");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"app\"\n").unwrap();
+
+    let out = run_drakkar(&["promote", "--from", "debug", "--to", "rc"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(
+        stdout.contains("Promoted\x1b[0m 1 artifact(s) from debug to rc (channels/rc)"),
+        "{}",
+        stdout
+    );
+    let rc = workspace.join("channels/rc");
+    assert_eq!(fs::read(rc.join("app")).unwrap(), fs::read(workspace.join("out/app")).unwrap());
+    let sums = fs::read_to_string(rc.join("SHA256SUMS")).unwrap();
+    assert!(sums.ends_with("  app\n"), "{}", sums);
+    let manifest = fs::read_to_string(rc.join("manifest.json")).unwrap();
+    assert!(manifest.contains(r#""channel":"rc","from":"debug""#), "{}", manifest);
+
+    let out = run_drakkar(&["promote", "--from", "rc", "--to", "stable"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(fs::read_to_string(workspace.join("channels/stable/SHA256SUMS")).unwrap(), sums);
+
+    // A channel whose files no longer match their checksums isn't promoted
+    fs::write(rc.join("app"), "tampered").unwrap();
+    let out = run_drakkar(&["promote", "--from", "rc", "--to", "stable"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("does not match SHA256SUMS"));
+
+    let out = run_drakkar(&["promote", "--from", "rc"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("requires --from <channel> and --to"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 59: A project dependency is built first and linked in, and relinks
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_project_deps_link() {
+    let workspace = temp_workspace("project_deps");
+    let libmath = workspace.join("libmath");
+    fs::create_dir_all(libmath.join("src")).unwrap();
+    fs::create_dir_all(libmath.join("include")).unwrap();
+    fs::write(libmath.join("include/mathlib.h"), "int answer(void);\n").unwrap();
+    fs::write(libmath.join("src/answer.c"), "int answer(void) { return 42; }\n").unwrap();
+    fs::write(
+        libmath.join("config.txt"),
+        "[target.math]\ntarget_type = \"static_lib\"\nsource_dir = \"src\"\n",
+    )
+    .unwrap();
+    let app = workspace.join("app");
+    fs::create_dir_all(app.join("src")).unwrap();
+    fs::write(
+        app.join("src/main.c"),
+        "#include \"mathlib.h\"\nint main(void) { return answer() == 42 ? 0 : 1; }\n",
+    )
+    .unwrap();
+    fs::write(app.join("config.txt"), "app_name = \"app\"\ndeps = \"../libmath\"\n").unwrap();
+
+    let out = run_drakkar(&["run"], &app);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    // Outside the project root, so shown with absolute paths
+    let dependency = format!("Dependency\x1b[0m {}", libmath.display());
+    assert!(stdout.contains(&dependency), "{}", stdout);
+    assert!(libmath.join("out/libmath.a").is_file());
+
+    // A change in the dependency rebuilds its library and relinks the app
+    std::thread::sleep(std::time::Duration::from_millis(1100));
+    fs::write(libmath.join("src/answer.c"), "int answer(void) { return 41; }\n").unwrap();
+    let out = run_drakkar(&["run"], &app);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Linking\x1b[0m out/app"), "{}", stdout);
+    assert_eq!(out.status.code(), Some(1));
+
+    fs::write(libmath.join("src/answer.c"), "int answer(void) { return 42 }\n").unwrap();
+    let out = run_drakkar(&["build"], &app);
+    assert!(!out.status.success());
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Building\x1b[0m app"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 60: stats sources counts files and lines per language and directory
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_stats_sources() {
+    let workspace = temp_workspace("stats_sources");
+    fs::create_dir_all(workspace.join("src/net")).unwrap();
+    fs::write(workspace.join("src/main.cpp"), "int main() {\n    return 0;\n}\n").unwrap();
+    fs::write(workspace.join("src/net/socket.c"), "int sock(void) { return 3; }\n").unwrap();
+    fs::write(workspace.join("src/net/socket.h"), "int sock(void);\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"app\"\n").unwrap();
+
+    let out = run_drakkar(&["stats", "sources"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.starts_with("3 file(s), 5 line(s): 2 source(s), 1 header(s)"), "{}", stdout);
+    assert!(stdout.contains("  src/net         1       1         2   1.00"), "{}", stdout);
+    assert!(stdout.contains("        3  src/main.cpp"), "{}", stdout);
+    assert!(!workspace.join("target").exists(), "stats doesn't create temp_dir");
+
+    let out = run_drakkar(&["stats", "sources", "--json"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains(r#""languages":[{"language":"C","files":1,"lines":1}"#), "{}", stdout);
+
+    let out = run_drakkar(&["stats"], &workspace);
+    assert!(!out.status.success());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 61: vendor_dirs compile without the project's warnings, stay cached and
+// pass include_check
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_vendor_dirs() {
+    let workspace = temp_workspace("vendor_dirs");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("third_party/stb")).unwrap();
+    fs::write(workspace.join("third_party/stb/stb.h"), "int stb_load(void);\n").unwrap();
+    // Warns under -Wall, which -Werror would turn into a failed build
+    fs::write(
+        workspace.join("third_party/stb/stb.c"),
+        "int stb_load(void) { int unused; return 0; }\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"stb.h\"\nint main(void) { return stb_load(); }\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"app\"\nc_flags = \"-Wall -Werror\"\nvendor_dirs = \"third_party/stb\"\n\
+         vendor_c_standard = \"c99\"\nobject_cache_dir = \"cache\"\ninclude_check = \"error\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Cache\x1b[0m 0 hit(s), 1 miss(es)"), "stdout:\n{}", stdout);
+    assert!(workspace.join("target/.drakkar/vendor/third_party/stb/stb.o").is_file());
+
+    // Only the vendored object comes back from the cache after a clean
+    assert!(run_drakkar(&["clean"], &workspace).status.success());
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Cache\x1b[0m 1 hit(s), 0 miss(es)"), "stdout:\n{}", stdout);
+    assert!(stdout.contains("src/main.c"), "stdout:\n{}", stdout);
+    assert!(!stdout.contains("stb.c"), "stdout:\n{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 62: conan_deps run conan install once and use the packages' flags
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_conan_deps() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("conan_deps");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::create_dir_all(workspace.join("pkgs/greet/include")).unwrap();
+    // Would warn under -Wall if it weren't a system header
+    fs::write(
+        workspace.join("pkgs/greet/include/greet.h"),
+        "static int greet(void) { int unused; return GREET_VALUE; }\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include <greet.h>\nint main(void) { return greet() == 7 ? 0 : 1; }\n",
+    )
+    .unwrap();
+
+    // Stand-in for conan: records its arguments and writes a .pc file into
+    // the output folder, as PkgConfigDeps does
+    let fake_conan = workspace.join("fake-conan");
+    fs::write(
+        &fake_conan,
+        format!(
+            "#!/bin/sh\necho \"$@\" >> conan.log\nfor a; do case \"$a\" in \
+             --output-folder=*) out=\"${{a#--output-folder=}}\";; esac; done\n\
+             printf 'prefix={}\\nName: greet\\nCflags: -I${{prefix}}/include -DGREET_VALUE=7\\n\
+             Libs: -lm\\n' > \"$out/greet.pc\"\n",
+            workspace.join("pkgs/greet").display()
+        ),
+    )
+    .unwrap();
+    fs::set_permissions(&fake_conan, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        format!(
+            "app_name = \"app\"\nc_flags = \"-Wall -Werror\"\nconan_deps = \"greet/1.0\"\n\
+             conan_path = \"{}\"\n",
+            fake_conan.display()
+        ),
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["run"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Installing\x1b[0m Conan packages (Debug)"), "stdout:\n{}", stdout);
+    let log = fs::read_to_string(workspace.join("conan.log")).unwrap();
+    assert!(log.starts_with("install target/conan/debug/conanfile.txt"), "{}", log);
+    assert!(log.contains("-s build_type=Debug"), "{}", log);
+    let conanfile = fs::read_to_string(workspace.join("target/conan/debug/conanfile.txt"));
+    assert!(conanfile.unwrap().contains("[requires]\ngreet/1.0\n"));
+
+    // Unchanged packages don't reinstall
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(fs::read_to_string(workspace.join("conan.log")).unwrap().lines().count(), 1);
+
+    // A conanfile.txt alongside conan_deps is ambiguous
+    fs::write(workspace.join("conanfile.txt"), "[requires]\ngreet/1.0\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("keep one of them"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 63: --metrics writes a Prometheus textfile and keeps counting builds
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_prometheus_metrics() {
+    let workspace = temp_workspace("prometheus_metrics");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"app\"\nparallel_jobs = \"3\"\n").unwrap();
+    let metrics = workspace.join("metrics/build.prom");
+
+    let out = run_drakkar(&["build", "--metrics", "prometheus:metrics/build.prom"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let text = fs::read_to_string(&metrics).unwrap();
+    assert!(text.contains("# TYPE drakkar_builds_total counter\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_builds_total 1\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_build_success{profile=\"debug\"} 1\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_build_jobs 3\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_build_compiled 1\n"), "{}", text);
+
+    fs::write(workspace.join("src/main.c"), "int main(void) { return }\n").unwrap();
+    let out = run_drakkar(&["build", "--metrics=prometheus:metrics/build.prom"], &workspace);
+    assert!(!out.status.success());
+    let text = fs::read_to_string(&metrics).unwrap();
+    assert!(text.contains("\ndrakkar_builds_total 2\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_build_failures_total 1\n"), "{}", text);
+    assert!(text.contains("\ndrakkar_build_success{profile=\"debug\"} 0\n"), "{}", text);
+    assert_eq!(fs::read_dir(workspace.join("metrics")).unwrap().count(), 1);
+
+    let out = run_drakkar(&["build", "--metrics", "statsd:localhost"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("expected prometheus:<path>"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 64: [deps] git dependencies are fetched, locked and built
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_git_deps() {
+    let workspace = temp_workspace("git_deps");
+    let git = |dir: &PathBuf, args: &[&str]| {
+        let out = std::process::Command::new("git")
+            .arg("-C")
+            .arg(dir)
+            .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(out.status.success(), "git {:?}: {}", args, String::from_utf8_lossy(&out.stderr));
+    };
+    let repo = |name: &str, files: &[(&str, &str)]| {
+        let dir = workspace.join("remotes").join(name);
+        for (path, content) in files {
+            fs::create_dir_all(dir.join(path).parent().unwrap()).unwrap();
+            fs::write(dir.join(path), content).unwrap();
+        }
+        git(&dir, &["init", "-q"]);
+        git(&dir, &["add", "."]);
+        git(&dir, &["commit", "-q", "-m", "v1"]);
+        git(&dir, &["tag", "v1"]);
+        dir
+    };
+    let libmath = repo(
+        "libmath",
+        &[
+            ("config.txt", "[target.math]\ntarget_type = \"static_lib\"\nsource_dir = \"src\"\n"),
+            ("include/mathlib.h", "int answer(void);\n"),
+            ("src/answer.c", "int answer(void) { return 40; }\n"),
+        ],
+    );
+    let ring = repo("ring", &[("include/ring.h", "#define RING_EXTRA 2\n")]);
+
+    let app = workspace.join("app");
+    fs::create_dir_all(app.join("src")).unwrap();
+    fs::write(
+        app.join("src/main.c"),
+        "#include \"mathlib.h\"\n#include \"ring.h\"\n\
+         int main(void) { return answer() + RING_EXTRA == 42 ? 0 : 1; }\n",
+    )
+    .unwrap();
+    fs::write(
+        app.join("config.txt"),
+        format!(
+            "app_name = \"app\"\n[deps]\nmath = \"file://{} v1\"\nring = \"file://{} v1\"\n",
+            libmath.display(),
+            ring.display()
+        ),
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["fetch"], &app);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Fetched\x1b[0m math v1 ("), "{}", stdout);
+    let lock = fs::read_to_string(app.join("drakkar.lock")).unwrap();
+    assert!(lock.contains(&format!("math file://{} v1 ", libmath.display())), "{}", lock);
+
+    // The tag moves upstream, but the lock keeps the build on the old commit
+    fs::write(libmath.join("src/answer.c"), "int answer(void) { return 0; }\n").unwrap();
+    git(&libmath, &["commit", "-q", "-am", "v2"]);
+    git(&libmath, &["tag", "-f", "v1"]);
+    fs::remove_dir_all(app.join("deps/math")).unwrap();
+    let out = run_drakkar(&["run"], &app);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Fetching\x1b[0m math v1"), "{}", stdout);
+    assert!(stdout.contains("Dependency\x1b[0m deps/math"), "{}", stdout);
+    assert_eq!(fs::read_to_string(app.join("drakkar.lock")).unwrap(), lock);
+
+    let out = run_drakkar(&["fetch"], &app);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Fresh\x1b[0m math v1"), "{}", stdout);
+    assert!(stdout.contains("Fresh\x1b[0m ring v1"), "{}", stdout);
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 65: temp_max_size_mb evicts unused objects; cache prune ages them out
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_temp_dir_pruning() {
+    let workspace = temp_workspace("temp_dir_pruning");
+    for name in ["one", "two"] {
+        fs::create_dir_all(workspace.join(name)).unwrap();
+        let main = "int util(void);\nint main(void) { return util(); }\n";
+        fs::write(workspace.join(name).join("main.c"), main).unwrap();
+        fs::write(workspace.join(name).join("util.c"), "int util(void) { return 0; }\n").unwrap();
+    }
+    let config = "app_name = \"app\"\n[target.one]\nsource_dir = \"one\"\n\
+                  [target.two]\nsource_dir = \"two\"\n";
+    fs::write(workspace.join("config.txt"), config).unwrap();
+    let objects = |dir: &str| -> Vec<PathBuf> {
+        let mut found = Vec::new();
+        let mut dirs = vec![workspace.join(dir)];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir).into_iter().flatten().flatten() {
+                let path = entry.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if path.extension() == Some("o".as_ref()) {
+                    found.push(path);
+                }
+            }
+        }
+        found
+    };
+
+    let out = run_drakkar(&["build", "one"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(objects("target/one").len(), 2);
+
+    // A cap below what one target needs: building `two` keeps its own
+    // objects and evicts those of `one`
+    let capped = format!("temp_max_size_mb = 0\n{}", config);
+    fs::write(workspace.join("config.txt"), capped).unwrap();
+    let out = run_drakkar(&["build", "two"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Evicted\x1b[0m 2 old object(s)"), "stdout:\n{}", stdout);
+    assert!(objects("target/one").is_empty());
+    assert_eq!(objects("target").len(), 2);
+
+    let out = run_drakkar(&["cache", "prune"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("requires --older-than"));
+
+    let out = run_drakkar(&["cache", "prune", "--older-than", "1d"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert_eq!(objects("target").len(), 2);
+
+    // Two days unused: 1d prunes them, with their depfiles
+    let old = std::time::SystemTime::now() - std::time::Duration::from_secs(2 * 86400);
+    let stamp = old.duration_since(std::time::UNIX_EPOCH).unwrap().as_secs();
+    for object in objects("target") {
+        let touched = std::process::Command::new("touch")
+            .arg("-d")
+            .arg(format!("@{}", stamp))
+            .arg(&object)
+            .arg(object.with_extension("d"))
+            .status()
+            .unwrap();
+        assert!(touched.success());
+    }
+    let out = run_drakkar(&["cache", "prune", "--older-than=1d"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Pruned\x1b[0m 2 object(s)"), "stdout:\n{}", stdout);
+    assert!(objects("target").is_empty());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 66: requires_headers / requires_libs are probed before compiling
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_system_requirements() {
+    let workspace = temp_workspace("system_requirements");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"app\"\nrequires_headers = \"stdio.h drakkar_missing.h\"\n\
+         requires_libs = \"m drakkar_missing\"\n",
+    )
+    .unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("missing system requirements"), "stderr:\n{}", stderr);
+    assert!(stderr.contains("header <drakkar_missing.h>: install"), "stderr:\n{}", stderr);
+    assert!(stderr.contains("library -ldrakkar_missing: install"), "stderr:\n{}", stderr);
+    assert!(!stderr.contains("<stdio.h>") && !stderr.contains("library -lm:"));
+    assert!(!workspace.join("target/main.o").exists());
+
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"app\"\nrequires_headers = \"stdio.h\"\nrequires_libs = \"m\"\n",
+    )
+    .unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    assert!(stdout.contains("Probing\x1b[0m 2 system requirement(s)"), "stdout:\n{}", stdout);
+    assert!(workspace.join("target/.drakkar/probe").is_dir());
+    assert!(!workspace.join("target/probe").exists());
+
+    // Passing probes aren't run again
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 1 - 1; }\n").unwrap();
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!String::from_utf8_lossy(&out.stdout).contains("Probing"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 67: install copies artifacts and headers into the prefix under DESTDIR,
+// building release unless debug is given
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_install() {
+    let workspace = temp_workspace("install");
+    for dir in ["core/include/core", "app", "plug"] {
+        fs::create_dir_all(workspace.join(dir)).unwrap();
+    }
+    fs::write(workspace.join("core/include/core/core.h"), "int answer(void);\n").unwrap();
+    fs::write(
+        workspace.join("core/core.c"),
+        "#include <core/core.h>\nint answer(void) { return 42; }\n",
+    )
+    .unwrap();
+    fs::write(
+        workspace.join("app/main.c"),
+        "#include <core/core.h>\n#ifdef NDEBUG\nint main(void) { return answer() - 42; }\n\
+         #else\nint main(void) { return 1; }\n#endif\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("plug/plug.c"), "int plug(void) { return 1; }\n").unwrap();
+    fs::write(
+        workspace.join("config.txt"),
+        "plugins_dir = \"plugins\"\ninstall_prefix = \"/opt/demo\"\n\
+         [target.core]\ntarget_type = \"static_lib\"\nsource_dir = \"core\"\n\
+         include_dirs = \"core/include\"\n\
+         [target.app]\nsource_dir = \"app\"\ndeps = \"core\"\n\
+         [target.plug]\ntarget_type = \"plugin\"\nsource_dir = \"plug\"\n",
+    )
+    .unwrap();
+
+    let stage = workspace.join("stage");
+    let out = std::process::Command::new(drakkar_bin())
+        .args(["install", "release"])
+        .env("DESTDIR", &stage)
+        .current_dir(&workspace)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let root = stage.join("opt/demo");
+    for file in ["bin/app", "lib/libcore.a", "lib/plugins/plug.so", "include/core/core.h"] {
+        assert!(root.join(file).is_file(), "{} not installed", file);
+    }
+    assert!(std::process::Command::new(root.join("bin/app")).status().unwrap().success());
+    let manifest = fs::read_to_string(workspace.join("out/install_manifest.txt")).unwrap();
+    assert_eq!(manifest.lines().count(), 4);
+    assert!(manifest.contains(&format!("{}\n", root.join("bin/app").display())), "{}", manifest);
+
+    // --prefix wins over install_prefix; `app` only needs core, not the plugin.
+    // Without a profile the release build is installed.
+    let out = run_drakkar(&["install", "app", "--prefix", "local"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("local/bin/app").is_file());
+    assert!(!workspace.join("local/lib/plugins").exists());
+    let status = std::process::Command::new(workspace.join("local/bin/app")).status().unwrap();
+    assert!(status.success(), "a plain install installed the debug build");
+
+    let out = run_drakkar(&["install", "debug", "app", "--prefix", "dbg"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let status = std::process::Command::new(workspace.join("dbg/bin/app")).status().unwrap();
+    assert_eq!(status.code(), Some(1));
+
+    let out = run_drakkar(&["build", "--prefix", "local"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("--prefix only works with"));
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 68: self update replaces the binary with a checksummed release asset
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_self_update() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let workspace = temp_workspace("self_update");
+    let release = workspace.join("release");
+    fs::create_dir_all(&release).unwrap();
+    // A copy, so the binary the other tests run stays as it is
+    let exe = workspace.join("drakkar");
+    fs::copy(drakkar_bin(), &exe).unwrap();
+    let asset = format!("drakkar-{}-{}", std::env::consts::ARCH, std::env::consts::OS);
+    fs::write(release.join(&asset), "#!/bin/sh\necho drakkar 2.0\n").unwrap();
+    let sha256 = |path: &PathBuf| {
+        let out = std::process::Command::new("sha256sum").arg(path).output().unwrap();
+        String::from_utf8_lossy(&out.stdout).split_whitespace().next().unwrap().to_string()
+    };
+    let self_update = || {
+        std::process::Command::new(&exe)
+            .args(["self", "update"])
+            .env("DRAKKAR_RELEASE_URL", format!("file://{}", release.display()))
+            .current_dir(&workspace)
+            .output()
+            .unwrap()
+    };
+
+    // The running binary is the release's: nothing to download
+    let sums = release.join("SHA256SUMS");
+    fs::write(&sums, format!("{}  {}\n", sha256(&exe), asset)).unwrap();
+    let out = self_update();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Up to date"));
+
+    // An asset that doesn't match its checksum replaces nothing
+    let wrong = "0".repeat(64);
+    fs::write(&sums, format!("{}  {}\n", wrong, asset)).unwrap();
+    let out = self_update();
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains(&format!("{} does not match SHA256SUMS", asset)), "{}", stderr);
+    assert_eq!(sha256(&exe), sha256(&drakkar_bin()));
+
+    fs::write(&sums, format!("{}  {}\n", sha256(&release.join(&asset)), asset)).unwrap();
+    let out = self_update();
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Updated"));
+    assert_eq!(fs::metadata(&exe).unwrap().permissions().mode() & 0o111, 0o111);
+    let out = std::process::Command::new(&exe).output().unwrap();
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "drakkar 2.0\n");
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 69: clean refuses directories that hold the project, and removes
+// preprocessed files unless preserve_temp = "true" is set
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_clean_refuses_project_dir() {
+    let workspace = temp_workspace("clean_refuses_project_dir");
+    fs::create_dir_all(workspace.join("src")).unwrap();
+    fs::write(workspace.join("src/main.c"), "int main(void) { return 0; }\n").unwrap();
+    let config_with = |output_dir: &str, temp_dir: &str, extra: &str| {
+        let config = format!(
+            "app_name = \"app\"\nsource_dir = \"src/\"\noutput_dir = \"{}\"\ntemp_dir = \"{}\"\n{}",
+            output_dir, temp_dir, extra
+        );
+        fs::write(workspace.join("config.txt"), config).unwrap();
+    };
+    let config = |output_dir: &str, temp_dir: &str| config_with(output_dir, temp_dir, "");
+
+    config("./", "target/");
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let out = run_drakkar(&["clean", "--all"], &workspace);
+    assert!(!out.status.success());
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("refusing to clean output_dir"), "{}", stderr);
+    assert!(workspace.join("src/main.c").exists() && workspace.join("config.txt").exists());
+    // Nothing was cleaned, not even temp_dir
+    assert!(workspace.join("target/main.o").exists());
+
+    config("out/", ".");
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(!out.status.success());
+    assert!(String::from_utf8_lossy(&out.stderr).contains("refusing to clean temp_dir"));
+    config("out/", "src/");
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(!out.status.success());
+    assert!(workspace.join("src/main.c").exists());
+
+    // The tests dir and a target's own sources are the project's too
+    fs::create_dir_all(workspace.join("tests")).unwrap();
+    fs::write(workspace.join("tests/check.c"), "int main(void) { return 0; }\n").unwrap();
+    config("out/", "tests/");
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(String::from_utf8_lossy(&out.stderr).contains("contains tests_dir"));
+    assert!(workspace.join("tests/check.c").exists());
+    fs::create_dir_all(workspace.join("tools/gen")).unwrap();
+    fs::write(workspace.join("tools/gen/gen.c"), "int main(void) { return 0; }\n").unwrap();
+    config_with("out/", "tools/", "[target.gen]\nsource_dir = \"tools/gen\"\n");
+    let out = run_drakkar(&["clean"], &workspace);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(stderr.contains("contains source_dir \"tools/gen\""), "{}", stderr);
+    assert!(workspace.join("tools/gen/gen.c").exists());
+
+    // Preprocessed files only survive when asked for
+    fs::write(workspace.join("target/main.i"), "").unwrap();
+    config_with("out/", "target/", "preserve_temp = \"true\"\n");
+    let out = run_drakkar(&["clean"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(workspace.join("target/main.i").exists());
+    assert!(!workspace.join("target/main.o").exists());
+
+    config("out/", "target/");
+    let out = run_drakkar(&["clean", "--all"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    assert!(!workspace.join("target/main.i").exists());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 70: test objects don't collide with the project's src/tests/
+// ─────────────────────────────────────────────
+
+#[test]
+fn test_test_objects_separate_from_project() {
+    let workspace = temp_workspace("test_objects_separate");
+    fs::create_dir_all(workspace.join("src/tests")).unwrap();
+    fs::create_dir_all(workspace.join("tests")).unwrap();
+    fs::write(workspace.join("src/tests/check.cpp"), "int check() { return 0; }\n").unwrap();
+    fs::write(
+        workspace.join("src/main.cpp"),
+        "int check();\nint main() { return check(); }\n",
+    ).unwrap();
+    fs::write(
+        workspace.join("tests/check.cpp"),
+        "int check();\nint main() { return check(); }\n",
+    ).unwrap();
+    fs::write(workspace.join("config.txt"), "app_name = \"app\"\n").unwrap();
+
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let out = run_drakkar(&["test"], &workspace);
+    let stdout = String::from_utf8_lossy(&out.stdout);
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "stdout:\n{}\nstderr:\n{}", stdout, stderr);
+    assert!(stdout.contains("1 passed; 0 failed"), "stdout:\n{}", stdout);
+
+    // The project's object is still its own
+    let out = run_drakkar(&["build"], &workspace);
+    assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+    let run_out = Command::new(workspace.join("out/app")).output().unwrap();
+    assert!(run_out.status.success());
+
+    let _ = fs::remove_dir_all(&workspace);
+}
+
+// ─────────────────────────────────────────────
+// Test 71: a build with generators is up to date the second time round
+// ─────────────────────────────────────────────
+
+#[cfg(unix)]
+#[test]
+fn test_generator_second_build_up_to_date() {
+    let workspace = temp_workspace("generator_second_build");
+    for dir in ["core", "src", "defs"] {
+        fs::create_dir_all(workspace.join(dir)).unwrap();
+    }
+    fs::write(workspace.join("core/core.c"), "int core(void) { return 0; }\n").unwrap();
+    fs::write(
+        workspace.join("src/main.c"),
+        "#include \"answer.h\"\nint core(void);\nint main(void) { return core() + answer(); }\n",
+    )
+    .unwrap();
+    fs::write(workspace.join("defs/answer.def"), "0\n").unwrap();
+    fs::write(
+        workspace.join("gen.sh"),
+        "echo \"int $3(void) { return $(cat \"$1\"); }\" > \"$2/$3.c\"\n\
+         echo \"int $3(void);\" > \"$2/$3.h\"\n",
+    )
+    .unwrap();
+    // Each target compiles a generated source next to its own, so the
+    // objects of the first build finish in no particular order
+    fs::write(
+        workspace.join("config.txt"),
+        "app_name = \"app\"\n\
+         [generator.defs]\ninputs = \"defs/*.def\"\n\
+         command = \"sh gen.sh {input} {out_dir} {stem}\"\noutputs = \"{stem}.c {stem}.h\"\n\
+         [target.core]\ntarget_type = \"static_lib\"\nsource_dir = \"core\"\n\
+         [target.app]\nsource_dir = \"src\"\ndeps = \"core\"\n",
+    )
+    .unwrap();
+
+    let build = || {
+        let out = run_drakkar(&["build"], &workspace);
+        assert!(out.status.success(), "stderr:\n{}", String::from_utf8_lossy(&out.stderr));
+        String::from_utf8_lossy(&out.stdout).into_owned()
+    };
+    let first = build();
+    assert!(first.contains("Linking\x1b[0m out/app"), "{}", first);
+    for _ in 0..2 {
+        let again = build();
+        assert!(again.contains("Up-to-date\x1b[0m out/libcore.a"), "{}", again);
+        assert!(again.contains("Up-to-date\x1b[0m out/app"), "{}", again);
+    }
+    let status = Command::new(workspace.join("out/app")).status().unwrap();
+    assert!(status.success());
+
+    let _ = fs::remove_dir_all(&workspace);
+}